@@ -0,0 +1,259 @@
+//! Status patterns that can be told apart without colour or brightness.
+//!
+//! Each [`SoilCondition`] has its own rhythm of short and long pulses, played
+//! on the status LED and echoed on a buzzer when one is fitted, so the status
+//! can be read by eye or by ear.
+
+use crate::alert::{Alert, AlertOutput, Severity};
+use crate::clock::Clock;
+use crate::led::{LedStep, LED_FULL, LED_OFF};
+use crate::moisture::SoilCondition;
+use anyhow::Result;
+use log::warn;
+use std::time::Duration;
+
+pub const SHORT_PULSE: Duration = Duration::from_millis(150);
+pub const LONG_PULSE: Duration = Duration::from_millis(600);
+/// Silence after every pulse
+pub const PULSE_GAP: Duration = Duration::from_millis(200);
+
+/// How the status LED shows the soil condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndicatorMode {
+    /// Lit outside the comfort band, dark inside it
+    #[default]
+    Brightness,
+    /// [`status_code`] pulses every reading, and on the buzzer when the
+    /// condition changes
+    Accessible,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pulse {
+    Short,
+    Long,
+}
+
+impl Pulse {
+    pub fn duration(self) -> Duration {
+        match self {
+            Pulse::Short => SHORT_PULSE,
+            Pulse::Long => LONG_PULSE,
+        }
+    }
+}
+
+/// Dry is three short pulses, optimal one long, wet two long
+pub fn status_code(condition: SoilCondition) -> &'static [Pulse] {
+    match condition {
+        SoilCondition::Dry => &[Pulse::Short, Pulse::Short, Pulse::Short],
+        SoilCondition::Optimal => &[Pulse::Long],
+        SoilCondition::Wet => &[Pulse::Long, Pulse::Long],
+    }
+}
+
+/// `(on, hold)` steps: each pulse followed by a gap
+pub fn pulse_timeline(pulses: &[Pulse]) -> Vec<(bool, Duration)> {
+    pulses
+        .iter()
+        .flat_map(|pulse| [(true, pulse.duration()), (false, PULSE_GAP)])
+        .collect()
+}
+
+/// [`status_code`] of `condition` as a full-brightness LED pattern
+pub fn led_pattern(condition: SoilCondition) -> Vec<LedStep> {
+    pulse_timeline(status_code(condition))
+        .into_iter()
+        .map(|(on, hold)| LedStep {
+            brightness: if on { LED_FULL } else { LED_OFF },
+            hold,
+        })
+        .collect()
+}
+
+/// Fixed-pitch buzzer
+pub trait Buzzer {
+    fn set_tone(&mut self, on: bool) -> Result<()>;
+}
+
+impl<B: Buzzer + ?Sized> Buzzer for Box<B> {
+    fn set_tone(&mut self, on: bool) -> Result<()> {
+        (**self).set_tone(on)
+    }
+}
+
+/// Sound `pulses` on `buzzer`, sleeping on `clock` between steps
+pub fn play_tones(buzzer: &mut dyn Buzzer, pulses: &[Pulse], clock: &dyn Clock) -> Result<()> {
+    for (on, hold) in pulse_timeline(pulses) {
+        buzzer.set_tone(on)?;
+        clock.sleep(hold);
+    }
+    Ok(())
+}
+
+/// Sounded for every critical alert: three long pulses, unlike any status code
+pub const ALARM: &[Pulse] = &[Pulse::Long, Pulse::Long, Pulse::Long];
+
+/// Sounds [`ALARM`] on a buzzer for critical alerts, including escalations
+pub struct AlarmBuzzer<B, C> {
+    buzzer: B,
+    clock: C,
+}
+
+impl<B: Buzzer, C: Clock> AlarmBuzzer<B, C> {
+    pub fn new(buzzer: B, clock: C) -> Self {
+        Self { buzzer, clock }
+    }
+}
+
+impl<B: Buzzer, C: Clock> AlertOutput for AlarmBuzzer<B, C> {
+    fn deliver(&mut self, alert: &Alert) {
+        if alert.severity() < Severity::Critical {
+            return;
+        }
+        if let Err(e) = play_tones(&mut self.buzzer, ALARM, &self.clock) {
+            warn!("Buzzer failed: {:?}", e);
+        }
+    }
+}
+
+/// Test double recording every tone change against a clock
+pub struct RecordingBuzzer<C: Clock> {
+    clock: C,
+    timeline: Vec<(Duration, bool)>,
+}
+
+impl<C: Clock> RecordingBuzzer<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            timeline: Vec::new(),
+        }
+    }
+
+    /// `(time, on)` for every change, in order
+    pub fn timeline(&self) -> &[(Duration, bool)] {
+        &self.timeline
+    }
+}
+
+impl<C: Clock> Buzzer for RecordingBuzzer<C> {
+    fn set_tone(&mut self, on: bool) -> Result<()> {
+        self.timeline.push((self.clock.now(), on));
+        Ok(())
+    }
+}
+
+/// Active buzzer on a GPIO: driving the pin high sounds it
+#[cfg(target_os = "espidf")]
+pub struct GpioBuzzer<'d> {
+    pin: esp_idf_hal::gpio::PinDriver<
+        'd,
+        esp_idf_hal::gpio::AnyOutputPin,
+        esp_idf_hal::gpio::Output,
+    >,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> GpioBuzzer<'d> {
+    pub fn new(
+        pin: esp_idf_hal::gpio::PinDriver<
+            'd,
+            esp_idf_hal::gpio::AnyOutputPin,
+            esp_idf_hal::gpio::Output,
+        >,
+    ) -> Self {
+        Self { pin }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl Buzzer for GpioBuzzer<'_> {
+    fn set_tone(&mut self, on: bool) -> Result<()> {
+        if on {
+            self.pin.set_high()?;
+        } else {
+            self.pin.set_low()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{led_pattern, play_tones, status_code, AlarmBuzzer, RecordingBuzzer};
+    use crate::alert::{Alert, AlertOutput};
+    use crate::clock::{Clock, MockClock};
+    use crate::led::{play, RecordingLed, LED_FULL, LED_OFF};
+    use crate::moisture::SoilCondition;
+    use std::time::Duration;
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn each_condition_has_its_own_led_timeline() {
+        let timeline = |condition| {
+            let clock = MockClock::new();
+            let mut led = RecordingLed::new(clock.clone());
+            play(&mut led, &led_pattern(condition), &clock).unwrap();
+            led.timeline().to_vec()
+        };
+        assert_eq!(
+            timeline(SoilCondition::Dry),
+            vec![
+                (ms(0), LED_FULL),
+                (ms(150), LED_OFF),
+                (ms(350), LED_FULL),
+                (ms(500), LED_OFF),
+                (ms(700), LED_FULL),
+                (ms(850), LED_OFF),
+            ]
+        );
+        assert_eq!(
+            timeline(SoilCondition::Optimal),
+            vec![(ms(0), LED_FULL), (ms(600), LED_OFF)]
+        );
+        assert_eq!(
+            timeline(SoilCondition::Wet),
+            vec![
+                (ms(0), LED_FULL),
+                (ms(600), LED_OFF),
+                (ms(800), LED_FULL),
+                (ms(1400), LED_OFF),
+            ]
+        );
+    }
+
+    #[test]
+    fn buzzer_sounds_the_same_rhythm() {
+        let clock = MockClock::new();
+        let mut buzzer = RecordingBuzzer::new(clock.clone());
+        play_tones(&mut buzzer, status_code(SoilCondition::Wet), &clock).unwrap();
+        assert_eq!(
+            buzzer.timeline(),
+            &[
+                (ms(0), true),
+                (ms(600), false),
+                (ms(800), true),
+                (ms(1400), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn alarm_sounds_only_for_critical_alerts() {
+        let clock = MockClock::new();
+        let mut alarm = AlarmBuzzer::new(RecordingBuzzer::new(clock.clone()), clock.clone());
+        alarm.deliver(&Alert::LowBattery);
+        assert!(alarm.buzzer.timeline().is_empty());
+
+        alarm.deliver(&Alert::Escalated {
+            alert: Box::new(Alert::LowBattery),
+            after_s: 3600,
+        });
+        assert_eq!(alarm.buzzer.timeline().len(), 6);
+        assert_eq!(clock.now(), ms(3 * 800));
+    }
+}
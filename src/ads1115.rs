@@ -0,0 +1,315 @@
+//! ADS1115 16-bit I2C ADC, for builds that bypass the noisy internal ADC.
+//!
+//! Each conversion is a single shot on one single-ended input. Results are
+//! reported in millivolts so a calibration survives a change of gain.
+
+use crate::clock::{Clock, SystemClock};
+use crate::sensor::{sample_spread, SoilSensor};
+use anyhow::{bail, Result};
+use std::time::Duration;
+
+/// Address with ADDR tied to ground
+pub const DEFAULT_ADDRESS: u8 = 0x48;
+const CONVERSION_REGISTER: u8 = 0x00;
+const CONFIG_REGISTER: u8 = 0x01;
+/// Config bit 15: write 1 to start a conversion, reads 1 once it is done
+const OS_BIT: u16 = 1 << 15;
+/// Single-shot mode, comparator disabled
+const SINGLE_SHOT: u16 = 1 << 8 | 0b11;
+/// Extra status polls once the nominal conversion time has passed
+const READY_POLLS: u32 = 3;
+
+/// Register-level access to the I2C bus
+pub trait I2cTransport {
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<()>;
+
+    /// Write `bytes` then read into `buffer` with a repeated start
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<()>;
+}
+
+/// Programmable gain, named by full-scale range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gain {
+    Fsr6144mV,
+    /// Covers a 3.3 V probe output
+    #[default]
+    Fsr4096mV,
+    Fsr2048mV,
+    Fsr1024mV,
+    Fsr512mV,
+    Fsr256mV,
+}
+
+impl Gain {
+    pub fn full_scale_mv(self) -> u32 {
+        match self {
+            Gain::Fsr6144mV => 6144,
+            Gain::Fsr4096mV => 4096,
+            Gain::Fsr2048mV => 2048,
+            Gain::Fsr1024mV => 1024,
+            Gain::Fsr512mV => 512,
+            Gain::Fsr256mV => 256,
+        }
+    }
+
+    /// PGA field, config bits 11..9
+    fn bits(self) -> u16 {
+        (self as u16) << 9
+    }
+}
+
+/// Samples per second
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DataRate {
+    Sps8,
+    Sps16,
+    Sps32,
+    Sps64,
+    #[default]
+    Sps128,
+    Sps250,
+    Sps475,
+    Sps860,
+}
+
+impl DataRate {
+    pub fn samples_per_second(self) -> u32 {
+        match self {
+            DataRate::Sps8 => 8,
+            DataRate::Sps16 => 16,
+            DataRate::Sps32 => 32,
+            DataRate::Sps64 => 64,
+            DataRate::Sps128 => 128,
+            DataRate::Sps250 => 250,
+            DataRate::Sps475 => 475,
+            DataRate::Sps860 => 860,
+        }
+    }
+
+    /// One conversion period, rounded up to whole microseconds
+    pub fn conversion_time(self) -> Duration {
+        Duration::from_micros(1_000_000u64.div_ceil(self.samples_per_second() as u64))
+    }
+
+    /// DR field, config bits 7..5
+    fn bits(self) -> u16 {
+        (self as u16) << 5
+    }
+}
+
+/// Config register value starting a single-shot conversion of input
+/// `channel` (0-3) against ground
+pub fn config_word(channel: u8, gain: Gain, rate: DataRate) -> u16 {
+    let mux = (0b100 | (channel as u16 & 0b11)) << 12;
+    OS_BIT | mux | gain.bits() | rate.bits() | SINGLE_SHOT
+}
+
+/// Millivolts for a conversion register value (big endian, two's complement);
+/// below-ground readings clamp to zero
+pub fn decode_conversion(bytes: [u8; 2], gain: Gain) -> u16 {
+    let code = i16::from_be_bytes(bytes).max(0) as u32;
+    ((code * gain.full_scale_mv() + (1 << 14)) >> 15) as u16
+}
+
+/// Soil probe on one ADS1115 input, reading millivolts as the raw value
+pub struct I2cAdcSoilSensor<T, C: Clock = SystemClock> {
+    transport: T,
+    clock: C,
+    address: u8,
+    channel: u8,
+    gain: Gain,
+    rate: DataRate,
+}
+
+impl<T: I2cTransport, C: Clock> I2cAdcSoilSensor<T, C> {
+    /// Input 0 at the default address, gain and data rate
+    pub fn new(transport: T, clock: C) -> Self {
+        Self {
+            transport,
+            clock,
+            address: DEFAULT_ADDRESS,
+            channel: 0,
+            gain: Gain::default(),
+            rate: DataRate::default(),
+        }
+    }
+
+    /// 0x48-0x4b, set by where ADDR is strapped
+    pub fn with_address(mut self, address: u8) -> Self {
+        self.address = address;
+        self
+    }
+
+    /// Single-ended input 0-3
+    pub fn with_channel(mut self, channel: u8) -> Self {
+        self.channel = channel.min(3);
+        self
+    }
+
+    pub fn with_gain(mut self, gain: Gain) -> Self {
+        self.gain = gain;
+        self
+    }
+
+    pub fn with_data_rate(mut self, rate: DataRate) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<[u8; 2]> {
+        let mut value = [0u8; 2];
+        self.transport
+            .write_read(self.address, &[register], &mut value)?;
+        Ok(value)
+    }
+
+    /// One single-shot conversion, in millivolts
+    pub fn convert(&mut self) -> Result<u16> {
+        let [hi, lo] = config_word(self.channel, self.gain, self.rate).to_be_bytes();
+        self.transport
+            .write(self.address, &[CONFIG_REGISTER, hi, lo])?;
+        self.clock.sleep(self.rate.conversion_time());
+        for _ in 0..READY_POLLS {
+            if u16::from_be_bytes(self.read_register(CONFIG_REGISTER)?) & OS_BIT != 0 {
+                let value = self.read_register(CONVERSION_REGISTER)?;
+                return Ok(decode_conversion(value, self.gain));
+            }
+            self.clock.sleep(self.rate.conversion_time() / 4);
+        }
+        bail!("ADS1115 at {:#04x} never finished converting", self.address)
+    }
+
+    fn conversions(&mut self, samples: usize) -> Result<Vec<u16>> {
+        (0..samples.max(1)).map(|_| self.convert()).collect()
+    }
+}
+
+impl<T: I2cTransport, C: Clock> SoilSensor for I2cAdcSoilSensor<T, C> {
+    fn read_averaged(&mut self, samples: usize) -> Result<u16> {
+        Ok(self.read_with_spread(samples)?.0)
+    }
+
+    fn read_with_spread(&mut self, samples: usize) -> Result<(u16, Option<u16>)> {
+        let conversions = self.conversions(samples)?;
+        // At least one conversion was taken
+        let (mean, spread) = sample_spread(&conversions).unwrap_or_default();
+        Ok((mean, Some(spread)))
+    }
+}
+
+/// Timeout for one I2C transaction
+#[cfg(target_os = "espidf")]
+const I2C_TIMEOUT_MS: u64 = 20;
+
+/// ESP-IDF I2C master transport
+#[cfg(target_os = "espidf")]
+pub struct EspI2c<'d> {
+    i2c: esp_idf_hal::i2c::I2cDriver<'d>,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> EspI2c<'d> {
+    pub fn new(i2c: esp_idf_hal::i2c::I2cDriver<'d>) -> Self {
+        Self { i2c }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl I2cTransport for EspI2c<'_> {
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<()> {
+        let timeout = esp_idf_hal::delay::TickType::new_millis(I2C_TIMEOUT_MS).ticks();
+        self.i2c.write(address, bytes, timeout)?;
+        Ok(())
+    }
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<()> {
+        let timeout = esp_idf_hal::delay::TickType::new_millis(I2C_TIMEOUT_MS).ticks();
+        self.i2c.write_read(address, bytes, buffer, timeout)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        config_word, decode_conversion, DataRate, Gain, I2cAdcSoilSensor, I2cTransport,
+        DEFAULT_ADDRESS,
+    };
+    use crate::clock::{Clock, MockClock};
+    use crate::sensor::SoilSensor;
+    use anyhow::Result;
+    use std::time::Duration;
+
+    /// ADS1115 register file: conversions finish as soon as they start
+    struct FakeAds {
+        conversion: [u8; 2],
+        config_writes: Vec<(u8, u16)>,
+        busy: bool,
+    }
+
+    impl I2cTransport for FakeAds {
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<()> {
+            assert_eq!(bytes[0], 0x01, "only the config register is written");
+            self.config_writes
+                .push((address, u16::from_be_bytes([bytes[1], bytes[2]])));
+            Ok(())
+        }
+
+        fn write_read(&mut self, _address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<()> {
+            let value = match bytes[0] {
+                0x00 => self.conversion,
+                _ if self.busy => [0x05, 0x83],
+                _ => [0x85, 0x83],
+            };
+            buffer.copy_from_slice(&value);
+            Ok(())
+        }
+    }
+
+    fn fake(conversion: [u8; 2]) -> FakeAds {
+        FakeAds {
+            conversion,
+            config_writes: Vec::new(),
+            busy: false,
+        }
+    }
+
+    #[test]
+    fn decodes_conversion_register_for_gain() {
+        // 0x5000 is 20480/32768 of full scale
+        assert_eq!(decode_conversion([0x50, 0x00], Gain::Fsr4096mV), 2560);
+        assert_eq!(decode_conversion([0x50, 0x00], Gain::Fsr2048mV), 1280);
+        assert_eq!(decode_conversion([0x7f, 0xff], Gain::Fsr6144mV), 6144);
+        // Slightly below ground on a single-ended input
+        assert_eq!(decode_conversion([0xff, 0xf0], Gain::Fsr4096mV), 0);
+    }
+
+    #[test]
+    fn config_word_selects_channel_gain_and_rate() {
+        // Datasheet default is 0x8583 with MUX AIN0/AIN1; single-ended AIN0 is 0xc583
+        assert_eq!(config_word(0, Gain::Fsr2048mV, DataRate::Sps128), 0xc583);
+        assert_eq!(config_word(3, Gain::Fsr4096mV, DataRate::Sps860), 0xf3e3);
+        assert_eq!(DataRate::Sps8.conversion_time(), Duration::from_millis(125));
+    }
+
+    #[test]
+    fn sensor_triggers_and_reads_configured_channel() {
+        let clock = MockClock::new();
+        let mut sensor = I2cAdcSoilSensor::new(fake([0x2b, 0x00]), clock.clone())
+            .with_channel(2)
+            .with_gain(Gain::Fsr4096mV)
+            .with_data_rate(DataRate::Sps8);
+        // 0x2b00 = 11008 counts of 32768 at 4.096 V full scale
+        assert_eq!(sensor.read_with_spread(2).unwrap(), (1376, Some(0)));
+        let expected = config_word(2, Gain::Fsr4096mV, DataRate::Sps8);
+        assert_eq!(
+            sensor.transport.config_writes,
+            vec![(DEFAULT_ADDRESS, expected), (DEFAULT_ADDRESS, expected)]
+        );
+        assert_eq!(clock.now(), Duration::from_millis(250));
+
+        // A converter that never reports done is an error, not a stale value
+        sensor.transport.busy = true;
+        assert!(sensor.read_averaged(1).is_err());
+    }
+}
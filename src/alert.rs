@@ -0,0 +1,284 @@
+//! Notable conditions raised to the user, and escalation of warnings that
+//! outstay their welcome.
+
+use crate::clock::Clock;
+use std::fmt;
+use std::time::Duration;
+
+/// How urgently an alert needs attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    /// Stable machine-readable name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Condition worth notifying someone about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alert {
+    /// Soil EC dropped below the fertilize threshold
+    Fertilize {
+        ec_us_cm: u16,
+    },
+    /// Pump was commanded on but no current was sensed
+    PumpFailure,
+    /// No valid reading for longer than the dead-probe timeout; the pump is
+    /// locked out until the probe recovers
+    ProbeDead {
+        silent_s: u64,
+    },
+    /// Raw baseline crept away from where it settled, suggesting cable or
+    /// connector degradation rather than soil change
+    CableDrift {
+        shift: i32,
+    },
+    LowBattery,
+    /// Temperature channel below the frost threshold, in tenths of °C
+    Frost {
+        tenths_c: i16,
+    },
+    /// Temperature channel above the heat threshold, in tenths of °C
+    Heat {
+        tenths_c: i16,
+    },
+    /// Moisture below the dry threshold; minor unless it persists
+    SoilDry {
+        moisture_percent: u8,
+    },
+    /// A warning left unresolved past the escalation timeout
+    Escalated {
+        alert: Box<Alert>,
+        after_s: u64,
+    },
+}
+
+impl Alert {
+    /// Stable machine-readable name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Alert::Fertilize { .. } => "fertilize",
+            Alert::PumpFailure => "pump_failure",
+            Alert::ProbeDead { .. } => "probe_dead",
+            Alert::CableDrift { .. } => "cable_drift",
+            Alert::LowBattery => "low_battery",
+            Alert::Frost { .. } => "frost",
+            Alert::Heat { .. } => "heat",
+            Alert::SoilDry { .. } => "soil_dry",
+            Alert::Escalated { alert, .. } => alert.kind(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Alert::Fertilize { .. } => Severity::Info,
+            Alert::CableDrift { .. }
+            | Alert::LowBattery
+            | Alert::Heat { .. }
+            | Alert::SoilDry { .. } => Severity::Warning,
+            // Frost kills plants within hours
+            Alert::PumpFailure
+            | Alert::ProbeDead { .. }
+            | Alert::Frost { .. }
+            | Alert::Escalated { .. } => Severity::Critical,
+        }
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alert::Fertilize { ec_us_cm } => {
+                write!(
+                    f,
+                    "low fertility (EC {ec_us_cm} uS/cm), consider fertilizing"
+                )
+            }
+            Alert::PumpFailure => write!(f, "pump failure: no current after activation"),
+            Alert::ProbeDead { silent_s } => {
+                write!(
+                    f,
+                    "probe dead: no valid reading for {silent_s}s, watering locked out"
+                )
+            }
+            Alert::CableDrift { shift } => {
+                write!(
+                    f,
+                    "sensor baseline shifted {shift:+} counts, check cable and connector"
+                )
+            }
+            Alert::LowBattery => write!(f, "battery low"),
+            Alert::Frost { tenths_c } => write!(f, "frost risk at {}°C", Tenths(*tenths_c)),
+            Alert::Heat { tenths_c } => write!(f, "heat stress at {}°C", Tenths(*tenths_c)),
+            Alert::SoilDry { moisture_percent } => {
+                write!(f, "soil dry at {moisture_percent}%")
+            }
+            Alert::Escalated { alert, after_s } => {
+                write!(f, "{alert} (unresolved for {after_s}s)")
+            }
+        }
+    }
+}
+
+/// Tenths of a unit shown with one decimal, e.g. -5 as "-0.5"
+pub(crate) struct Tenths(pub i16);
+
+impl fmt::Display for Tenths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{sign}{}.{}", abs / 10, abs % 10)
+    }
+}
+
+/// Destination for alerts beyond the log, e.g. a webhook or a buzzer
+pub trait AlertOutput {
+    /// Hand over a raised or escalated alert; failures are the output's to log
+    fn deliver(&mut self, alert: &Alert);
+
+    /// Called once per cycle, e.g. to flush held or queued alerts
+    fn tick(&mut self) {}
+}
+
+impl<O: AlertOutput + ?Sized> AlertOutput for Box<O> {
+    fn deliver(&mut self, alert: &Alert) {
+        (**self).deliver(alert)
+    }
+
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+}
+
+/// Promotes warnings that stay active past a timeout to critical.
+///
+/// Warnings are matched by [`Alert::kind`], so a dry spell whose moisture
+/// value changes from reading to reading is still one warning.
+#[derive(Debug, Clone)]
+pub struct AlertEscalator<C> {
+    clock: C,
+    timeout: Duration,
+    /// Kind, first time seen, and whether it has been escalated already
+    active: Vec<(&'static str, Duration, bool)>,
+}
+
+impl<C: Clock> AlertEscalator<C> {
+    pub fn new(clock: C, timeout: Duration) -> Self {
+        Self {
+            clock,
+            timeout,
+            active: Vec::new(),
+        }
+    }
+
+    /// Feed every alert currently active; warnings missing from `alerts` are
+    /// resolved. Returns warnings that crossed the timeout on this call, each
+    /// escalated once per occurrence.
+    pub fn update(&mut self, alerts: &[Alert]) -> Vec<Alert> {
+        let now = self.clock.now();
+        let warnings: Vec<&Alert> = alerts
+            .iter()
+            .filter(|a| a.severity() == Severity::Warning)
+            .collect();
+        self.active
+            .retain(|(kind, _, _)| warnings.iter().any(|a| a.kind() == *kind));
+
+        let mut escalated = Vec::new();
+        for alert in warnings {
+            let kind = alert.kind();
+            let Some(entry) = self.active.iter_mut().find(|(k, _, _)| *k == kind) else {
+                self.active.push((kind, now, false));
+                continue;
+            };
+            let elapsed = now.saturating_sub(entry.1);
+            if !entry.2 && elapsed >= self.timeout {
+                entry.2 = true;
+                escalated.push(Alert::Escalated {
+                    alert: Box::new(alert.clone()),
+                    after_s: elapsed.as_secs(),
+                });
+            }
+        }
+        escalated
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Alert, AlertEscalator, Severity};
+    use crate::clock::MockClock;
+    use std::time::Duration;
+
+    fn hours(h: u64) -> Duration {
+        Duration::from_secs(h * 60 * 60)
+    }
+
+    fn dry(moisture_percent: u8) -> Alert {
+        Alert::SoilDry { moisture_percent }
+    }
+
+    #[test]
+    fn persistent_warning_escalates_after_timeout() {
+        let clock = MockClock::new();
+        let mut escalator = AlertEscalator::new(clock.clone(), hours(12));
+        assert!(escalator.update(&[dry(20)]).is_empty());
+        clock.advance(hours(11));
+        assert!(escalator.update(&[dry(18)]).is_empty());
+
+        clock.advance(hours(1));
+        let escalated = escalator.update(&[dry(17)]);
+        assert_eq!(
+            escalated,
+            vec![Alert::Escalated {
+                alert: Box::new(dry(17)),
+                after_s: 12 * 60 * 60,
+            }]
+        );
+        assert_eq!(escalated[0].severity(), Severity::Critical);
+        assert_eq!(escalated[0].kind(), "soil_dry");
+        assert_eq!(
+            escalated[0].to_string(),
+            "soil dry at 17% (unresolved for 43200s)"
+        );
+
+        // Only once per occurrence
+        clock.advance(hours(1));
+        assert!(escalator.update(&[dry(16)]).is_empty());
+    }
+
+    #[test]
+    fn resolved_warning_does_not_escalate() {
+        let clock = MockClock::new();
+        let mut escalator = AlertEscalator::new(clock.clone(), hours(12));
+        escalator.update(&[dry(20)]);
+        clock.advance(hours(6));
+        // Watering brought moisture back up
+        assert!(escalator.update(&[]).is_empty());
+
+        clock.advance(hours(6));
+        assert!(escalator.update(&[dry(22)]).is_empty());
+        clock.advance(hours(11));
+        assert!(escalator.update(&[dry(21)]).is_empty());
+
+        // Critical and info alerts are never escalated
+        clock.advance(hours(24));
+        let other = [Alert::PumpFailure, Alert::Fertilize { ec_us_cm: 200 }];
+        assert!(escalator.update(&other).is_empty());
+    }
+}
@@ -0,0 +1,2932 @@
+//! Measurement and control cycle shared by the demo and firmware binaries.
+//!
+//! The binaries only do platform setup (logging, NVS, hardware RNG, serial
+//! console) and then hand an [`App`] to [`run_demo`] or [`run_firmware`].
+
+use crate::accessibility::{led_pattern, play_tones, status_code, Buzzer, IndicatorMode};
+use crate::alert::{Alert, AlertEscalator, AlertOutput};
+use crate::boot::BootReason;
+use crate::calibrate::{AutoCalibrationConfig, AutoCalibrator, Validation};
+use crate::checkpoint::Checkpointer;
+use crate::clock::Clock;
+use crate::command::{Command, SimulatedFault};
+use crate::config::{dump_config, ConfigFormat, EffectiveConfig, NetworkConfig};
+use crate::daily::DailyRollup;
+use crate::drift::{BaselineConfig, BaselineTracker};
+use crate::ec::EcChannel;
+use crate::fault::{
+    DeadProbeMonitor, FaultDetector, ProbeTransition, SaturationCounter, StuckDetector,
+    DEAD_PROBE_TIMEOUT,
+};
+use crate::filter::{DualFilter, Event, FilteredPair};
+use crate::frontend::{reference_check, FrontEndCorrection};
+use crate::history::{History, HistoryEntry};
+use crate::interval::ReadingInterval;
+use crate::invariant::{self, Decision};
+use crate::led::{alert_pattern, play, safe_mode_pattern, DrynessBlink, Led, NullLed};
+use crate::maintenance::{MaintenanceConfig, MaintenanceDue, MaintenanceReminder};
+use crate::moisture::{
+    Calibration, CalibrationTransition, ClampPolicy, ComfortBand, ConditionTracker,
+    ConversionCache, FieldCapacityScale, MoistureConverter, ProbeKind, SoilCondition, MOISTURE_LOW,
+};
+use crate::nvs::KvStore;
+use crate::power::{LowBatteryDetector, SagThreshold, SupplyMonitor, LOW_BATTERY_MV};
+use crate::profile::Profile;
+use crate::provision::{
+    ensure_initialized, load_calibration, load_profile, load_pump_lifetime, load_pump_state,
+    load_service_record, save_calibration, save_pump_lifetime, save_pump_state,
+    save_service_record,
+};
+use crate::pump::{
+    DepthScaling, GateMode, Guardrail, Guardrails, PumpAction, PumpAudit, PumpConfig,
+    PumpController, PumpDrive, PumpLifetime, PumpOutput, PumpReadingGate, RuntimeMeter,
+};
+use crate::rate::{check_drying_rate, SoilType};
+use crate::reading::{Annotation, Reading, ReadingFlags};
+use crate::rewet::{RewetCause, RewetConfig, RewetDetector};
+use crate::rng::Rng;
+use crate::rule::{Condition, RuleContext};
+use crate::sampling::{SamplingConfig, SamplingTuner, TunerConfig};
+use crate::schedule::Schedule;
+use crate::sensor::{MockSoilSensor, SoilSensor};
+use crate::sink::ReadingSink;
+use crate::stats::Stats;
+use crate::status::Status;
+use crate::storage::FlashStore;
+use crate::summary::write_session_summary;
+use crate::temperature::TemperatureChannel;
+use crate::timestamp::TimestampConfig;
+use crate::timing::{CycleStats, CycleTimer};
+use crate::zone::{Lockout, ZoneController};
+use anyhow::{anyhow, Result};
+use log::{error, info, warn};
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+/// Readings kept in RAM and checkpointed
+pub const HISTORY_CAPACITY: usize = 64;
+/// Batch checkpoint writes to limit flash wear
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Conversions averaged into each reading
+pub const SAMPLES_PER_READING: usize = 5;
+/// Readings taken by the scripted demo
+pub const DEMO_READINGS: usize = 20;
+/// How long detected rain counts as recent for activation rules
+pub const RAIN_LOOKBACK: Duration = Duration::from_secs(6 * 60 * 60);
+/// How long a warning may stay active before it is escalated to critical
+pub const ALERT_ESCALATION_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long a `simulate ...` fault lasts before clearing itself
+pub const SIMULATION_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// Supply reading fed to the low-battery detector by `simulate lowbattery`
+const SIMULATED_BATTERY_MV: u16 = LOW_BATTERY_MV - 200;
+
+/// Provision NVS on first boot and assemble the configuration, logging any
+/// problems and starting on the default calibration and profile instead of
+/// refusing to start
+pub fn load_config(kv: &mut dyn KvStore, reading_interval: Duration) -> Result<EffectiveConfig> {
+    ensure_initialized(kv)?;
+    let mut config = EffectiveConfig {
+        calibration: load_calibration(kv),
+        profile: load_profile(kv),
+        reading_interval,
+        network: NetworkConfig::default(),
+        schedule: Schedule::new(Vec::new(), Duration::ZERO),
+    };
+    if let Err(problems) = config.validate() {
+        for problem in problems {
+            warn!("Config problem: {}", problem);
+        }
+        warn!("Stored settings rejected, using the default calibration and profile");
+        config.calibration = Calibration::default();
+        config.profile = Profile::default();
+    }
+    info!("Effective configuration:");
+    for line in dump_config(&config, ConfigFormat::Toml).lines() {
+        info!("  {}", line);
+    }
+    Ok(config)
+}
+
+/// Parse console lines from `input` on a background thread so the control
+/// loop never blocks; unknown commands are logged and skipped
+pub fn spawn_command_reader(input: impl BufRead + Send + 'static) -> Receiver<Command> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in input.lines().map_while(Result::ok) {
+            match line.parse::<Command>() {
+                Ok(command) => {
+                    if tx.send(command).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("{}", e),
+            }
+        }
+    });
+    rx
+}
+
+/// What one pass of [`App::run_cycle`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    /// `None` when the sensor read failed
+    pub reading: Option<Reading>,
+    pub pump_action: Option<PumpAction>,
+    /// Alerts raised since the previous cycle, for the webhook or buzzer
+    pub alerts: Vec<Alert>,
+    /// Warnings escalated to critical this cycle, for the webhook or buzzer
+    pub escalated: Vec<Alert>,
+    /// Delay until the next reading is due
+    pub wait: Duration,
+}
+
+/// Sensor, detectors, pump control and persistence for one probe
+pub struct App<S, C> {
+    sensor: S,
+    clock: C,
+    calibration: Calibration,
+    /// Cycles over which a calibration change is blended in; 0 switches at once
+    calibration_blend: u32,
+    transition: Option<CalibrationTransition>,
+    auto_calibration: Option<AutoCalibrator>,
+    probe_kind: ProbeKind,
+    conversion: ConversionCache,
+    /// Range handling of the reported value
+    clamp: MoistureConverter,
+    /// Optional agronomic scale reported alongside the sensor percent
+    field_capacity: Option<FieldCapacityScale>,
+    interval: ReadingInterval,
+    sampling: SamplingConfig,
+    tuner: Option<SamplingTuner>,
+    rng: Rng,
+    schedule: Schedule,
+    guardrails: Option<Guardrails>,
+    pump: PumpController<C>,
+    pump_audit: PumpAudit,
+    /// Restored run to switch the pump back on for at the first cycle
+    resumed: Option<PumpAction>,
+    /// Relay or PWM driver the pump actions are applied to, if fitted
+    pump_output: Option<PumpOutput<Box<dyn Led + Send>, C>>,
+    runtime: RuntimeMeter,
+    rewet: RewetDetector,
+    last_rain_at: Option<Duration>,
+    /// Extra predicate that must hold before the pump may start
+    activation_rule: Option<Condition>,
+    /// Where lifetime pump totals are persisted, if anywhere
+    settings: Option<Box<dyn KvStore + Send>>,
+    maintenance: Option<MaintenanceReminder<C>>,
+    /// Last reminder logged, so it is announced once rather than every cycle
+    maintenance_due: Option<MaintenanceDue>,
+    faults: FaultDetector,
+    stuck: StuckDetector,
+    /// Flags drops too fast for this soil to be real drying, if set
+    soil_type: Option<SoilType>,
+    /// Time and moisture of the previous reading, for the drying-rate check
+    last_moisture: Option<(Duration, u8)>,
+    saturation: SaturationCounter,
+    /// Watches the raw baseline for cable or connector degradation
+    cable: Option<BaselineTracker>,
+    front_end: FrontEndCorrection,
+    /// Smooths what is reported apart from what drives decisions, if set
+    filters: Option<DualFilter>,
+    /// Readings still to be flagged as warming up
+    warm_up: u32,
+    /// Readings scoring below this are reported but drive no decisions
+    min_quality: u8,
+    /// Keeps readings during and just after a run out of control, if set
+    pump_gate: Option<PumpReadingGate>,
+    /// Probe insertion depth, reported with every reading
+    depth_cm: Option<u8>,
+    timestamps: TimestampConfig,
+    /// Optional self-profiling of each cycle
+    cycle_timer: Option<CycleTimer<C>>,
+    probe: DeadProbeMonitor<C>,
+    escalator: AlertEscalator<C>,
+    /// Heat warning as of the last temperature sample
+    heat_warning: Option<Alert>,
+    /// Dry warning as of the last trusted reading
+    dry_warning: Option<Alert>,
+    /// Raised since the last cycle and not yet handed out
+    raised: Vec<Alert>,
+    /// Entered since the last cycle, waiting to reach the sink
+    pending_annotations: Vec<Annotation>,
+    /// Injected faults and when each clears
+    simulated: Vec<(SimulatedFault, Duration)>,
+    simulation_timeout: Duration,
+    led: Box<dyn Led + Send>,
+    indicator: IndicatorMode,
+    condition: ConditionTracker,
+    last_condition: Option<SoilCondition>,
+    buzzer: Option<Box<dyn Buzzer + Send>>,
+    /// Quiet when optimal: no relay and a dark LED while moisture is inside
+    comfort: Option<ComfortBand>,
+    /// Blink faster the drier the soil below the threshold, if enabled
+    dryness_blink: Option<DrynessBlink>,
+    /// Dryness blink period for the wait after the last reading, if blinking
+    blink_period: Option<Duration>,
+    stats: Stats,
+    /// Per-day rollup written to flash as each day closes, if enabled
+    daily: Option<DailyRollup>,
+    history: History,
+    checkpointer: Checkpointer<C>,
+    boot_reason: Option<BootReason>,
+    /// Loaded configuration, for `dump-config`
+    config: Option<EffectiveConfig>,
+    /// Optional rail sampled alongside each soil reading
+    supply: Option<(Box<dyn SupplyMonitor + Send>, SagThreshold)>,
+    battery: LowBatteryDetector,
+    /// Optional temperature probe read alongside each soil reading
+    temperature: Option<(Box<dyn SoilSensor + Send>, TemperatureChannel)>,
+    /// Optional EC probe read alongside each soil reading
+    ec: Option<(Box<dyn SoilSensor + Send>, EcChannel)>,
+    /// Pumps of further watering zones fed by their own probes, sharing
+    /// this probe's safe mode
+    zones: Option<ZoneController<C>>,
+    last_read_at: Duration,
+    last_wait: Duration,
+}
+
+impl<S: SoilSensor, C: Clock + Clone> App<S, C> {
+    /// No watering windows and the default pump limits until configured
+    pub fn new(
+        sensor: S,
+        clock: C,
+        calibration: Calibration,
+        interval: ReadingInterval,
+        rng: Rng,
+    ) -> Self {
+        let schedule = Schedule::new(Vec::new(), Duration::ZERO);
+        Self {
+            pump: PumpController::new(PumpConfig::default(), clock.clone())
+                .with_schedule(schedule.clone()),
+            checkpointer: Checkpointer::new(clock.clone(), CHECKPOINT_INTERVAL),
+            probe: DeadProbeMonitor::new(clock.clone(), DEAD_PROBE_TIMEOUT),
+            escalator: AlertEscalator::new(clock.clone(), ALERT_ESCALATION_TIMEOUT),
+            heat_warning: None,
+            dry_warning: None,
+            raised: Vec::new(),
+            pending_annotations: Vec::new(),
+            simulated: Vec::new(),
+            simulation_timeout: SIMULATION_TIMEOUT,
+            last_read_at: clock.now(),
+            sensor,
+            clock,
+            calibration,
+            calibration_blend: 0,
+            transition: None,
+            auto_calibration: None,
+            probe_kind: ProbeKind::default(),
+            conversion: ConversionCache::new(),
+            clamp: MoistureConverter::default(),
+            field_capacity: None,
+            interval,
+            sampling: SamplingConfig {
+                samples: SAMPLES_PER_READING,
+            },
+            tuner: None,
+            rng,
+            schedule,
+            guardrails: None,
+            pump_audit: PumpAudit::new(32),
+            resumed: None,
+            pump_output: None,
+            runtime: RuntimeMeter::default(),
+            rewet: RewetDetector::default(),
+            last_rain_at: None,
+            activation_rule: None,
+            settings: None,
+            maintenance: None,
+            maintenance_due: None,
+            faults: FaultDetector::new(),
+            stuck: StuckDetector::default(),
+            soil_type: None,
+            last_moisture: None,
+            saturation: SaturationCounter::default(),
+            cable: None,
+            front_end: FrontEndCorrection::default(),
+            filters: None,
+            warm_up: 0,
+            min_quality: 0,
+            pump_gate: None,
+            depth_cm: None,
+            timestamps: TimestampConfig::default(),
+            cycle_timer: None,
+            led: Box::new(NullLed),
+            indicator: IndicatorMode::default(),
+            condition: ConditionTracker::default(),
+            last_condition: None,
+            buzzer: None,
+            comfort: None,
+            dryness_blink: None,
+            blink_period: None,
+            stats: Stats::new(),
+            daily: None,
+            history: History::new(HISTORY_CAPACITY),
+            boot_reason: None,
+            config: None,
+            supply: None,
+            battery: LowBatteryDetector::default(),
+            temperature: None,
+            ec: None,
+            zones: None,
+            last_wait: Duration::ZERO,
+        }
+    }
+
+    /// Replace the pump thresholds and timing limits
+    pub fn with_pump_config(mut self, config: PumpConfig) -> Self {
+        self.pump =
+            PumpController::new(config, self.clock.clone()).with_schedule(self.schedule.clone());
+        if let Some(guardrails) = self.guardrails {
+            self.pump = self.pump.with_guardrails(guardrails);
+        }
+        self
+    }
+
+    /// Record the probe's insertion depth with every reading and, given a
+    /// `scaling`, tighten the pump thresholds for it. Call after
+    /// [`with_pump_config`](Self::with_pump_config), whose thresholds are
+    /// the ones scaled.
+    pub fn with_probe_depth(mut self, depth_cm: u8, scaling: Option<DepthScaling>) -> Self {
+        self.depth_cm = Some(depth_cm);
+        if let Some(scaling) = scaling {
+            let config = self.pump.config().scaled_for_depth(depth_cm, &scaling);
+            info!(
+                "Probe at {}cm: watering below {}%, stopping at {}%",
+                depth_cm, config.start_below, config.stop_at
+            );
+            self = self.with_pump_config(config);
+        }
+        self
+    }
+
+    /// Hard flood and emergency moisture limits, overriding the thresholds,
+    /// schedule and activation rule
+    pub fn with_guardrails(mut self, guardrails: Guardrails) -> Self {
+        self.pump = self.pump.with_guardrails(guardrails);
+        self.guardrails = Some(guardrails);
+        self
+    }
+
+    /// Drive the pump through `output`, ramping it if `drive` is a soft start
+    pub fn with_pump_output(mut self, output: impl Led + Send + 'static, drive: PumpDrive) -> Self {
+        self.pump_output = Some(PumpOutput::new(Box::new(output), self.clock.clone(), drive));
+        self
+    }
+
+    /// Conversion model and fault bounds for the fitted probe type
+    pub fn with_probe_kind(mut self, kind: ProbeKind) -> Self {
+        self.probe_kind = kind;
+        self.faults = FaultDetector::new().with_probe_kind(kind);
+        self
+    }
+
+    /// Count or show readings past the calibration range instead of clamping
+    /// them silently
+    pub fn with_clamp_policy(mut self, policy: ClampPolicy) -> Self {
+        self.clamp = MoistureConverter::new(policy);
+        self
+    }
+
+    /// Flag readings whose drop since the last one is faster than `soil` can dry
+    pub fn with_soil_type(mut self, soil: SoilType) -> Self {
+        self.soil_type = Some(soil);
+        self
+    }
+
+    /// Adapt the per-reading sample count to the observed noise within `config`'s limits
+    pub fn with_sampling_tuner(mut self, config: TunerConfig) -> Self {
+        self.tuner = Some(SamplingTuner::new(config));
+        self
+    }
+
+    /// Restrict watering to `schedule`'s windows
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.pump = self.pump.with_schedule(schedule.clone());
+        self.schedule = schedule;
+        self
+    }
+
+    /// Enter safe mode after `timeout` without a valid reading
+    pub fn with_dead_probe_timeout(mut self, timeout: Duration) -> Self {
+        self.probe = DeadProbeMonitor::new(self.clock.clone(), timeout);
+        self
+    }
+
+    /// Blend reported moisture into a new calibration over `cycles` readings
+    /// instead of jumping
+    pub fn with_calibration_blend(mut self, cycles: u32) -> Self {
+        self.calibration_blend = cycles;
+        self
+    }
+
+    /// Switch to `calibration` at runtime; control and fault checks use it at
+    /// once while the reported moisture follows the configured blend from
+    /// the value last reported
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        let last = self.history.iter().next_back();
+        if let Some(last) = last.filter(|_| self.calibration_blend > 0) {
+            if calibration != self.calibration {
+                self.transition = Some(CalibrationTransition::new(
+                    last.moisture_percent,
+                    last.raw,
+                    calibration,
+                    self.probe_kind,
+                    self.calibration_blend,
+                ));
+            }
+        }
+        self.calibration = calibration;
+    }
+
+    /// Propose wider calibrations from readings past the calibrated range and
+    /// switch to each, persisting it in the settings, once it validates
+    pub fn with_auto_calibration(mut self, config: AutoCalibrationConfig) -> Self {
+        self.auto_calibration = Some(AutoCalibrator::new(config));
+        self
+    }
+
+    /// Also report each reading as percent of field capacity
+    pub fn with_field_capacity_scale(mut self, scale: FieldCapacityScale) -> Self {
+        self.field_capacity = Some(scale);
+        self
+    }
+
+    /// Alert when the slow raw baseline shifts, as a degrading cable makes it do
+    pub fn with_cable_monitor(mut self, config: BaselineConfig) -> Self {
+        self.cable = Some(BaselineTracker::new(config));
+        self
+    }
+
+    /// Filter each raw reading twice: `display` for the reading and status,
+    /// `control` for the pump, alerts and escalation
+    pub fn with_filters(mut self, filters: DualFilter) -> Self {
+        self.filters = Some(filters);
+        self
+    }
+
+    /// Correct subsequent raw readings for front-end drift, measured by reading
+    /// a reference resistor as `reading` counts where `expected` was due.
+    /// Implausible results are logged and leave the current correction in place
+    pub fn apply_reference_check(&mut self, reading: u16, expected: u16) -> FrontEndCorrection {
+        let correction = reference_check(reading, expected);
+        if correction.is_plausible() {
+            info!(
+                "Front-end gain drift {} ppm, correcting",
+                correction.gain_drift_ppm()
+            );
+            self.front_end = correction;
+        } else {
+            warn!(
+                "Reference read {} counts, expected {}; check the front-end",
+                reading, expected
+            );
+        }
+        self.front_end
+    }
+
+    /// Emit reading timestamps in this unit and epoch instead of seconds since boot
+    pub fn with_timestamps(mut self, config: TimestampConfig) -> Self {
+        self.timestamps = config;
+        self
+    }
+
+    /// Tie the device clock to Unix time `unix_now`, e.g. after an SNTP sync,
+    /// so Unix and custom epochs take effect
+    pub fn anchor_unix_time(&mut self, unix_now: Duration) {
+        self.timestamps.anchor(self.clock.now(), unix_now);
+    }
+
+    /// Roll readings, watering and alerts up per day, saving each closed day
+    /// to flash
+    pub fn with_daily_summaries(mut self, daily: DailyRollup) -> Self {
+        self.daily = Some(daily);
+        self
+    }
+
+    /// Time every cycle and log min/max/mean every `report_every` cycles
+    pub fn with_cycle_timer(mut self, report_every: u32) -> Self {
+        self.cycle_timer = Some(CycleTimer::new(self.clock.clone(), report_every));
+        self
+    }
+
+    /// Cycle durations in the current reporting window, if timing is enabled
+    pub fn cycle_stats(&self) -> Option<CycleStats> {
+        self.cycle_timer.as_ref().and_then(CycleTimer::stats)
+    }
+
+    /// Flag the first `readings` after boot as taken while the probe settles
+    pub fn with_warm_up(mut self, readings: u32) -> Self {
+        self.warm_up = readings;
+        self
+    }
+
+    /// Only let readings of at least `quality` (see [`Reading::quality`]) drive
+    /// the pump and dry alert; poorer ones are logged and the previous
+    /// decisions held, apart from the pump's run limit
+    pub fn with_min_quality(mut self, quality: u8) -> Self {
+        self.min_quality = quality;
+        self
+    }
+
+    /// Leave readings taken while the pump runs, and until `gate`'s settle
+    /// delay has passed after it stops, out of pump start and dry alert
+    /// decisions. A run still stops on its thresholds and the flood limit.
+    pub fn with_pump_reading_gate(mut self, gate: PumpReadingGate) -> Self {
+        self.pump_gate = Some(gate);
+        self
+    }
+
+    /// Escalate warnings still active after `timeout` instead of [`ALERT_ESCALATION_TIMEOUT`]
+    pub fn with_escalation_timeout(mut self, timeout: Duration) -> Self {
+        self.escalator = AlertEscalator::new(self.clock.clone(), timeout);
+        self
+    }
+
+    /// Clear `simulate ...` faults after `timeout` instead of [`SIMULATION_TIMEOUT`]
+    pub fn with_simulation_timeout(mut self, timeout: Duration) -> Self {
+        self.simulation_timeout = timeout;
+        self
+    }
+
+    /// Status LED for the safe-mode error pattern
+    pub fn with_led(mut self, led: impl Led + Send + 'static) -> Self {
+        self.led = Box::new(led);
+        self
+    }
+
+    /// How the status LED shows the soil condition
+    pub fn with_indicator_mode(mut self, mode: IndicatorMode) -> Self {
+        self.indicator = mode;
+        self
+    }
+
+    /// Sound the condition's pulse code when it changes, in
+    /// [`IndicatorMode::Accessible`]
+    pub fn with_buzzer(mut self, buzzer: impl Buzzer + Send + 'static) -> Self {
+        self.buzzer = Some(Box::new(buzzer));
+        self
+    }
+
+    /// Rise size and pump window used to tell rain from watering
+    pub fn with_rewet_config(mut self, config: RewetConfig) -> Self {
+        self.rewet = RewetDetector::new(config);
+        self
+    }
+
+    /// Only let the pump start while `rule` holds; a run already in
+    /// progress is still ended by the pump thresholds
+    pub fn with_activation_rule(mut self, rule: Condition) -> Self {
+        self.activation_rule = Some(rule);
+        self
+    }
+
+    /// Keep the pump off and the LED dark inside `band`; outside it the LED
+    /// lights to show action is needed
+    pub fn with_comfort_band(mut self, band: ComfortBand) -> Self {
+        self.comfort = Some(band);
+        self
+    }
+
+    /// Blink the LED at a rate set by how dry the soil is while below
+    /// `blink`'s threshold; above it the LED behaves as without
+    pub fn with_dryness_blink(mut self, blink: DrynessBlink) -> Self {
+        self.dryness_blink = Some(blink);
+        self
+    }
+
+    /// Continue from a restored checkpoint instead of an empty history
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Sample the supply rail with each reading and flag readings taken
+    /// while it sags past `threshold`
+    pub fn with_supply_monitor(
+        mut self,
+        monitor: impl SupplyMonitor + Send + 'static,
+        threshold: SagThreshold,
+    ) -> Self {
+        self.supply = Some((Box::new(monitor), threshold));
+        self
+    }
+
+    /// Keep lifetime pump totals and the controller's run and cooldown
+    /// position in `settings`, continuing from what is already stored there.
+    /// Call after [`with_pump_config`](Self::with_pump_config), which starts
+    /// the controller afresh.
+    pub fn with_settings(mut self, settings: impl KvStore + Send + 'static) -> Self {
+        self.runtime = RuntimeMeter::new(load_pump_lifetime(&settings));
+        self.resumed = self.pump.restore(load_pump_state(&settings));
+        self.settings = Some(Box::new(settings));
+        self
+    }
+
+    /// Remind when the pump is due for service. Call after
+    /// [`with_settings`](Self::with_settings) so the last service is loaded.
+    pub fn with_maintenance(mut self, config: MaintenanceConfig) -> Self {
+        let record = self
+            .settings
+            .as_deref()
+            .map(|kv| load_service_record(kv))
+            .unwrap_or_default();
+        self.maintenance = Some(MaintenanceReminder::new(config, self.clock.clone(), record));
+        self
+    }
+
+    /// Read `probe` through `channel` each cycle: the temperature goes out
+    /// with the reading and frost or heat raises an alert
+    pub fn with_temperature_channel(
+        mut self,
+        probe: impl SoilSensor + Send + 'static,
+        channel: TemperatureChannel,
+    ) -> Self {
+        self.temperature = Some((Box::new(probe), channel));
+        self
+    }
+
+    /// Read `probe` through `channel` each cycle: the EC goes out with the
+    /// reading and low fertility raises an alert
+    pub fn with_ec_channel(
+        mut self,
+        probe: impl SoilSensor + Send + 'static,
+        channel: EcChannel,
+    ) -> Self {
+        self.ec = Some((Box::new(probe), channel));
+        self
+    }
+
+    /// Drive further zones through [`update_zone`](Self::update_zone); they
+    /// and this probe's pump are locked out together, while this probe is in
+    /// safe mode or after any of the pumps fails
+    pub fn with_zones(mut self, mut zones: ZoneController<C>) -> Self {
+        if self.is_safe_mode() {
+            zones.lock_out(Lockout::SafeMode);
+        }
+        if self.pump.has_failed() {
+            zones.lock_out(Lockout::PumpFailure);
+        }
+        if zones.has_failed() {
+            zones.lock_out(Lockout::PumpFailure);
+            self.pump.lock_out();
+        }
+        self.zones = Some(zones);
+        self
+    }
+
+    /// Feed another zone's moisture, e.g. from a probe of a
+    /// [`SensorArray`](crate::array::SensorArray); returns the switches to
+    /// apply, for any zone. Pump alerts are raised with the next cycle's.
+    pub fn update_zone(&mut self, zone: u8, moisture_percent: u8) -> Vec<(u8, PumpAction)> {
+        let Some(zones) = &mut self.zones else {
+            warn!("Reading for zone {} but no zones are configured", zone);
+            return Vec::new();
+        };
+        let failed_before = zones.is_locked_out_for(Lockout::PumpFailure);
+        let actions = zones.update(zone, moisture_percent);
+        let failed = !failed_before && zones.is_locked_out_for(Lockout::PumpFailure);
+        for alert in zones.take_alerts() {
+            self.raise(alert);
+        }
+        for (zone, action) in &actions {
+            info!("Zone {} pump: {:?}", zone, action);
+        }
+        if failed {
+            if let Some(action) = self.lock_out(Lockout::PumpFailure) {
+                self.record_pump_action(self.clock.now(), action);
+            }
+        }
+        actions
+    }
+
+    /// Hold this probe's pump and every zone off for `reason`; returns the
+    /// switch-off for this probe's pump when it was running
+    fn lock_out(&mut self, reason: Lockout) -> Option<PumpAction> {
+        if let Some(zones) = &mut self.zones {
+            for (zone, action) in zones.lock_out(reason) {
+                info!("Zone {} pump: {:?}", zone, action);
+            }
+        }
+        self.pump.lock_out()
+    }
+
+    /// Drop `reason`; the pumps run again once no other lockout remains
+    fn release_lockout(&mut self, reason: Lockout) {
+        let held = match &mut self.zones {
+            Some(zones) => {
+                zones.release_lockout(reason);
+                zones.is_locked_out()
+            }
+            None => false,
+        };
+        if !held {
+            self.pump.release_lockout();
+        }
+    }
+
+    pub fn zones(&self) -> Option<&ZoneController<C>> {
+        self.zones.as_ref()
+    }
+
+    /// Keep the loaded configuration for the `dump-config` command
+    pub fn with_config(mut self, config: EffectiveConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// The loaded configuration with the live calibration and schedule,
+    /// rendered in `format`; `None` when no configuration was attached
+    pub fn config_dump(&self, format: ConfigFormat) -> Option<String> {
+        let config = EffectiveConfig {
+            calibration: self.calibration,
+            schedule: self.schedule.clone(),
+            ..self.config.clone()?
+        };
+        Some(dump_config(&config, format))
+    }
+
+    /// Attach `reason` to the first reading
+    pub fn with_boot_reason(mut self, reason: BootReason) -> Self {
+        self.boot_reason = Some(reason);
+        self
+    }
+
+    pub fn sensor_mut(&mut self) -> &mut S {
+        &mut self.sensor
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn pump_audit(&self) -> &PumpAudit {
+        &self.pump_audit
+    }
+
+    pub fn pump_lifetime(&self) -> PumpLifetime {
+        self.runtime.lifetime()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pump.is_paused()
+    }
+
+    pub fn is_safe_mode(&self) -> bool {
+        self.probe.is_safe_mode()
+    }
+
+    /// Alert held for as long as safe mode lasts
+    pub fn active_alert(&self) -> Option<Alert> {
+        self.probe.active_alert()
+    }
+
+    /// Health line as of the most recent reading
+    pub fn status(&self) -> Status {
+        Status::new(
+            &self.clock,
+            self.last_read_at,
+            self.last_wait,
+            &self.schedule,
+        )
+        .with_paused(self.is_paused())
+        .with_safe_mode(self.is_safe_mode())
+        .with_adc_saturations(self.saturation.count())
+        .with_clipped_readings(self.clamp.clip_count())
+        .with_pump_lifetime(self.pump_lifetime())
+        .with_maintenance_due(self.maintenance_due().is_some())
+    }
+
+    /// Why the pump needs servicing, if a reminder is configured and due
+    pub fn maintenance_due(&self) -> Option<MaintenanceDue> {
+        self.maintenance.as_ref()?.check(&self.pump_lifetime())
+    }
+
+    /// Validate the pending calibration candidate against `raw`, committing
+    /// it when its window passes
+    fn auto_calibrate(&mut self, raw: u16) {
+        let Some(auto) = &mut self.auto_calibration else {
+            return;
+        };
+        let Some(Validation::Committed(calibration)) = auto.observe(raw, &self.calibration) else {
+            return;
+        };
+        self.set_calibration(calibration);
+        if let Some(settings) = self.settings.as_deref_mut() {
+            if let Err(e) = save_calibration(settings, &calibration) {
+                warn!("Failed to persist calibration: {:?}", e);
+            }
+        }
+    }
+
+    /// Persist the lifetime totals and service baseline; failures are logged
+    fn save_counters(&mut self) {
+        let Some(settings) = self.settings.as_deref_mut() else {
+            return;
+        };
+        if let Err(e) = save_pump_lifetime(settings, &self.runtime.lifetime()) {
+            warn!("Failed to persist pump lifetime: {:?}", e);
+        }
+        if let Some(maintenance) = &self.maintenance {
+            if let Err(e) = save_service_record(settings, &maintenance.record()) {
+                warn!("Failed to persist service record: {:?}", e);
+            }
+        }
+    }
+
+    /// Pulse the condition's code on the LED, and on the buzzer when it changed
+    fn announce(&mut self, moisture_percent: u8) {
+        let condition = self.condition.update(moisture_percent);
+        if let Err(e) = play(self.led.as_mut(), &led_pattern(condition), &self.clock) {
+            warn!("Status LED failed: {:?}", e);
+        }
+        if self.last_condition.replace(condition) == Some(condition) {
+            return;
+        }
+        if let Some(buzzer) = &mut self.buzzer {
+            if let Err(e) = play_tones(buzzer.as_mut(), status_code(condition), &self.clock) {
+                warn!("Buzzer failed: {:?}", e);
+            }
+        }
+    }
+
+    /// Persist where the controller is in its run and cooldown; failures are logged
+    fn save_pump_state(&mut self) {
+        let Some(settings) = self.settings.as_deref_mut() else {
+            return;
+        };
+        if let Err(e) = save_pump_state(settings, &self.pump.state()) {
+            warn!("Failed to persist pump state: {:?}", e);
+        }
+    }
+
+    /// Apply a pump action to the output and log it, persisting the totals
+    /// when a run ends
+    fn record_pump_action(&mut self, at: Duration, action: PumpAction) {
+        if let Some(output) = &mut self.pump_output {
+            if let Err(e) = output.apply(action) {
+                error!("Failed to switch pump output: {:?}", e);
+            }
+        }
+        self.pump_audit.record(at, action);
+        if let Some(gate) = &mut self.pump_gate {
+            gate.record(at, action);
+        }
+        if let Some(filters) = &mut self.filters {
+            filters.notify_event(match action {
+                PumpAction::Activate => Event::PumpActivated,
+                PumpAction::Deactivate => Event::PumpDeactivated,
+            });
+        }
+        if action == PumpAction::Activate {
+            self.rewet.record_pump_start(at);
+            if let Some(daily) = &mut self.daily {
+                daily.record_watering();
+            }
+        }
+        let before = self.runtime.lifetime().runtime;
+        if self.runtime.record(at, action) {
+            if let Some(daily) = &mut self.daily {
+                daily.record_pump_runtime(self.runtime.lifetime().runtime - before);
+            }
+            self.save_counters();
+        }
+    }
+
+    pub fn handle_command(&mut self, command: Command) {
+        match command {
+            Command::Pause => {
+                info!("Pump control paused for maintenance");
+                if let Some(action) = self.pump.pause() {
+                    self.record_pump_action(self.clock.now(), action);
+                }
+            }
+            Command::Resume => {
+                info!("Pump control resumed");
+                self.pump.resume();
+            }
+            Command::Status => info!("{}", self.status()),
+            Command::Daily => match &self.daily {
+                Some(daily) => info!("Daily summaries:\n{}", daily.export_csv()),
+                None => info!("Daily summaries are not enabled"),
+            },
+            Command::DumpConfig => match self.config_dump(ConfigFormat::Toml) {
+                Some(dump) => info!("Effective configuration:\n{}", dump),
+                None => info!("No configuration loaded"),
+            },
+            Command::Serviced => {
+                let lifetime = self.pump_lifetime();
+                let Some(maintenance) = &mut self.maintenance else {
+                    info!("No maintenance reminder configured");
+                    return;
+                };
+                maintenance.clear(&lifetime);
+                self.maintenance_due = None;
+                info!("Service recorded, maintenance reminder cleared");
+                self.save_counters();
+            }
+            Command::Annotate(text) => {
+                let now = self.clock.now();
+                let annotation =
+                    Annotation::new(now, &text).with_emitted_at(self.timestamps.stamp(now));
+                info!("Note recorded: {}", annotation.text());
+                self.history.annotate(annotation.clone());
+                self.pending_annotations.push(annotation);
+            }
+            Command::Simulate(fault) if self.simulating(fault) => {
+                warn!("Already simulating {}, ignored", fault);
+            }
+            Command::Simulate(fault) => {
+                warn!(
+                    "Simulating {} for {}s",
+                    fault,
+                    self.simulation_timeout.as_secs()
+                );
+                self.simulated
+                    .push((fault, self.clock.now() + self.simulation_timeout));
+                match fault {
+                    // Both take effect through their detectors' inputs
+                    SimulatedFault::Disconnected | SimulatedFault::LowBattery => {}
+                    SimulatedFault::PumpFailure => {
+                        if let Some(action) = self.pump.simulate_failure() {
+                            self.record_pump_action(self.clock.now(), action);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn simulating(&self, fault: SimulatedFault) -> bool {
+        self.simulated.iter().any(|&(active, _)| active == fault)
+    }
+
+    /// End injected faults whose timeout has passed, undoing only what the
+    /// simulation itself caused
+    fn expire_simulation(&mut self) {
+        let now = self.clock.now();
+        let (expired, active): (Vec<_>, Vec<_>) = std::mem::take(&mut self.simulated)
+            .into_iter()
+            .partition(|&(_, until)| now >= until);
+        self.simulated = active;
+        for (fault, _) in expired {
+            info!("Simulated {} cleared", fault);
+            match fault {
+                SimulatedFault::Disconnected => {}
+                // A monitored supply takes over on the next reading
+                SimulatedFault::LowBattery if self.supply.is_none() => self.battery.reset(),
+                SimulatedFault::LowBattery => {}
+                SimulatedFault::PumpFailure => {
+                    self.pump.clear_simulated_failure();
+                    let zone_failed = self.zones.as_ref().is_some_and(ZoneController::has_failed);
+                    if !self.pump.has_failed() && !zone_failed {
+                        self.release_lockout(Lockout::PumpFailure);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Log `alert` and hold it for the next [`Cycle`]
+    fn raise(&mut self, alert: Alert) {
+        warn!("Alert: {}", alert);
+        self.raised.push(alert);
+    }
+
+    /// Apply every command that has arrived since the last cycle
+    pub fn drain_commands(&mut self, commands: &Receiver<Command>) {
+        while let Ok(command) = commands.try_recv() {
+            self.handle_command(command);
+        }
+    }
+
+    /// Take one reading, emit it, drive the pump and checkpoint if due.
+    /// Sensor and checkpoint failures are logged; only sink errors propagate.
+    pub fn run_cycle(
+        &mut self,
+        sink: &mut dyn ReadingSink,
+        flash: &mut dyn FlashStore,
+    ) -> Result<Cycle> {
+        self.last_read_at = self.clock.now();
+        self.last_wait = self.interval.next(&mut self.rng);
+        self.expire_simulation();
+        self.blink_period = None;
+        let mut cycle = Cycle {
+            reading: None,
+            pump_action: None,
+            alerts: Vec::new(),
+            escalated: Vec::new(),
+            wait: self.last_wait,
+        };
+
+        for annotation in std::mem::take(&mut self.pending_annotations) {
+            sink.annotate(&annotation)?;
+        }
+        if let Some(action) = self.resumed.take() {
+            match self.boot_reason.filter(BootReason::is_abnormal) {
+                // The run itself may have caused it, e.g. pump inrush browning
+                // out the supply, so wait for a fresh dry reading instead
+                Some(reason) => {
+                    warn!(
+                        "     -> Pump: run before reboot abandoned after {} reset",
+                        reason.as_str()
+                    );
+                    if let Some(action) = self.pump.stop() {
+                        self.record_pump_action(self.last_read_at, action);
+                    }
+                    self.save_pump_state();
+                }
+                None => {
+                    info!("     -> Pump: RESUMED (run in progress before reboot)");
+                    self.record_pump_action(self.last_read_at, action);
+                }
+            }
+        }
+        let was_running = self.pump.is_running();
+
+        let gate = self
+            .pump_gate
+            .as_ref()
+            .filter(|gate| gate.is_gated(self.last_read_at))
+            .map(PumpReadingGate::mode);
+        let read = if gate == Some(GateMode::Pause) && !self.pump.is_running() {
+            None
+        } else if self.simulating(SimulatedFault::Disconnected) {
+            Some(Err(anyhow!("simulated fault: probe disconnected")))
+        } else {
+            Some(self.sensor.read_with_spread(self.sampling.samples))
+        };
+        // Moisture on the current calibration, before any display blend
+        let mut control_percent = None;
+        match read {
+            None => info!("     -> Pump settling, reading skipped"),
+            Some(Ok((raw, spread))) => {
+                let raw = self.front_end.apply(raw);
+                // Implausible readings are still shown, but flagged
+                let mut suspect = false;
+                if let Err(fault) = self.faults.check(raw, &self.calibration) {
+                    warn!("Sensor fault: {}", fault);
+                    suspect = true;
+                }
+                if let Err(fault) = self.stuck.check(raw) {
+                    warn!("Sensor fault: {}", fault);
+                    suspect = true;
+                }
+                // Faults and baselines are judged on the unfiltered value
+                let FilteredPair { display, control } = match &mut self.filters {
+                    Some(filters) => filters.update(raw),
+                    None => FilteredPair {
+                        display: raw,
+                        control: raw,
+                    },
+                };
+                // Control uses the current calibration; only the reported
+                // value is blended. A calibration committed by this reading
+                // applies from the next one.
+                let moisture_percent = self
+                    .conversion
+                    .convert(control, &self.calibration, self.probe_kind)
+                    .moisture_percent;
+                if let Some(soil) = self.soil_type {
+                    if let Some((at, previous)) = self.last_moisture {
+                        let elapsed = self.last_read_at.saturating_sub(at);
+                        if let Err(fault) =
+                            check_drying_rate(previous, moisture_percent, elapsed, soil)
+                        {
+                            warn!("Sensor fault: {}", fault);
+                            suspect = true;
+                        }
+                    }
+                    self.last_moisture = Some((self.last_read_at, moisture_percent));
+                }
+                if let Some(tuner) = &mut self.tuner {
+                    tuner.observe(raw, spread, &mut self.sampling);
+                }
+                if let Some(alert) = self.cable.as_mut().and_then(|cable| cable.update(raw)) {
+                    self.raise(alert);
+                }
+                if self.saturation.record(raw) {
+                    warn!("ADC saturated at full scale; check wiring and attenuation");
+                }
+                cycle.pump_action = self.update_probe_health(!suspect);
+                let reported = match &mut self.transition {
+                    Some(transition) => {
+                        let percent = transition.convert(display, self.probe_kind);
+                        if transition.is_done() {
+                            self.transition = None;
+                        }
+                        percent
+                    }
+                    None if display == control => moisture_percent,
+                    None => self.probe_kind.moisture_percent(display, &self.calibration),
+                };
+                invariant::moisture_in_range(moisture_percent);
+                self.stats.record(reported);
+                self.roll_up_day(reported, flash);
+                control_percent = Some(moisture_percent);
+                let unclamped = self
+                    .clamp
+                    .convert(display, &self.calibration, self.probe_kind);
+                if !(0..=100).contains(&unclamped) {
+                    info!(
+                        "     -> Moisture {}%, past the calibration range",
+                        unclamped
+                    );
+                }
+
+                let mut reading = Reading::new(self.last_read_at, display, reported)
+                    .with_emitted_at(self.timestamps.stamp(self.last_read_at))
+                    .with_control_paused(self.is_paused())
+                    .with_pump_on(self.pump.is_running())
+                    .with_fault(suspect)
+                    .with_safe_mode(self.is_safe_mode())
+                    .with_flag(ReadingFlags::CLIPPED, self.clamp.clamped())
+                    .with_flag(
+                        ReadingFlags::RECALIBRATION_RECOMMENDED,
+                        self.cable.as_ref().is_some_and(BaselineTracker::is_shifted),
+                    )
+                    .with_flag(ReadingFlags::WARMING_UP, self.warm_up > 0);
+                self.warm_up = self.warm_up.saturating_sub(1);
+                if let Some(reason) = self.boot_reason.take() {
+                    reading = reading.with_boot_reason(reason);
+                }
+                if let Some(depth_cm) = self.depth_cm {
+                    reading = reading.with_depth(depth_cm);
+                }
+                if let Some(spread) = spread {
+                    reading = reading.with_uncertainty(self.probe_kind.uncertainty_tenths(
+                        display,
+                        spread,
+                        &self.calibration,
+                    ));
+                }
+                if let Some(scale) = &self.field_capacity {
+                    reading = reading.with_field_capacity(scale.percent_of_capacity(reported));
+                }
+                if let Some(cause) = self.rewet.update(self.last_read_at, moisture_percent) {
+                    info!("     -> Moisture rise: {}", cause);
+                    if cause == RewetCause::RainDetected {
+                        self.last_rain_at = Some(self.last_read_at);
+                    }
+                    reading = reading.with_rewet(cause);
+                }
+                let mut supply_mv = None;
+                if let Some((monitor, threshold)) = &mut self.supply {
+                    match monitor.read_millivolts() {
+                        Ok(mv) => {
+                            let sagging = threshold.is_sagging(mv);
+                            if sagging {
+                                warn!("Supply sagging to {} mV; reading may be biased", mv);
+                            }
+                            reading = reading.with_supply(mv, sagging);
+                            supply_mv = Some(mv);
+                        }
+                        Err(e) => warn!("Failed to read supply rail: {:?}", e),
+                    }
+                }
+                let battery_mv = if self.simulating(SimulatedFault::LowBattery) {
+                    Some(SIMULATED_BATTERY_MV)
+                } else {
+                    // Pump inrush would read as a flat battery
+                    supply_mv.filter(|_| !self.pump.is_running())
+                };
+                if let Some(alert) = self.battery.update(battery_mv) {
+                    self.raise(alert);
+                }
+                reading = reading.with_low_battery(self.battery.is_low());
+                let mut channel_alerts = Vec::new();
+                if let Some((probe, channel)) = &mut self.temperature {
+                    match probe.read_averaged(self.sampling.samples) {
+                        Ok(raw) => {
+                            let sample = channel.update(raw);
+                            reading = reading.with_temperature(sample.tenths_c);
+                            self.heat_warning = channel.is_hot().then_some(Alert::Heat {
+                                tenths_c: sample.tenths_c,
+                            });
+                            channel_alerts.extend(sample.alert);
+                        }
+                        Err(e) => warn!("Failed to read temperature: {:?}", e),
+                    }
+                }
+                if let Some((probe, channel)) = &mut self.ec {
+                    match probe.read_averaged(self.sampling.samples) {
+                        Ok(raw) => {
+                            let sample = channel.update(raw);
+                            reading = reading.with_ec(sample.us_cm);
+                            channel_alerts.extend(sample.alert);
+                        }
+                        Err(e) => warn!("Failed to read EC: {:?}", e),
+                    }
+                }
+                for alert in channel_alerts {
+                    self.raise(alert);
+                }
+                sink.emit(&reading)?;
+                self.history.push(HistoryEntry::from(&reading));
+                info!("     -> {}", self.status());
+
+                let quality = reading.quality();
+                let trusted = self.is_trusted(&reading);
+                if reading.fault() {
+                    info!("     -> Suspect reading, holding pump and alerts");
+                } else if !trusted {
+                    info!(
+                        "     -> Quality {} below {}, holding pump and alerts",
+                        quality, self.min_quality
+                    );
+                }
+                if !trusted && cycle.pump_action.is_none() {
+                    cycle.pump_action = self.pump.enforce_max_run();
+                }
+                let quiet = self.comfort.map(|band| band.contains(moisture_percent));
+                // Readings continue while paused
+                if cycle.pump_action.is_none() && trusted {
+                    cycle.pump_action = if quiet == Some(true) {
+                        self.pump.stop()
+                    } else if self.pump.is_running() {
+                        // Stopping never waits for the reading gate
+                        self.pump.update(moisture_percent)
+                    } else if gate.is_some() {
+                        info!("     -> Pump settling, holding start and alerts");
+                        None
+                    } else if self.pump.guardrail(moisture_percent) == Some(Guardrail::Emergency)
+                        || self.may_activate(moisture_percent)
+                    {
+                        self.pump.update(moisture_percent)
+                    } else {
+                        None
+                    };
+                }
+                match self.indicator {
+                    IndicatorMode::Brightness => {
+                        // Blinked through the wait by `idle` rather than here
+                        self.blink_period =
+                            self.dryness_blink.and_then(|blink| blink.period(reported));
+                        if let (None, Some(quiet)) = (self.blink_period, quiet) {
+                            if let Err(e) = self.led.set_on(!quiet) {
+                                warn!("Status LED failed: {:?}", e);
+                            }
+                        }
+                    }
+                    IndicatorMode::Accessible => self.announce(reported),
+                }
+                if !suspect {
+                    self.auto_calibrate(raw);
+                }
+                cycle.reading = Some(reading);
+            }
+            Some(Err(e)) => {
+                error!("Failed to read sensor: {:?}", e);
+                cycle.pump_action = self.update_probe_health(false);
+            }
+        }
+
+        match cycle.pump_action {
+            Some(PumpAction::Activate) => info!("     -> Pump: ACTIVATE (soil too dry)"),
+            Some(PumpAction::Deactivate) => info!("     -> Pump: DEACTIVATE"),
+            None if self.is_safe_mode() => info!("     -> Pump: LOCKED OUT (safe mode)"),
+            None if self.is_paused() => info!("     -> Pump: PAUSED (no actuation)"),
+            None => {}
+        }
+        if cfg!(debug_assertions) {
+            invariant::pump_decision(&Decision {
+                action: cycle.pump_action,
+                was_running,
+                paused: self.is_paused(),
+                safe_mode: self.is_safe_mode(),
+                // No reservoir level sensor is fitted yet
+                reservoir_empty: false,
+                in_window: self.in_window(),
+                guardrail: control_percent.and_then(|m| self.pump.guardrail(m)),
+            });
+        }
+        if let Some(action) = cycle.pump_action {
+            self.record_pump_action(self.last_read_at, action);
+        }
+        // Runs are saved every cycle so a reboot resumes them with the time already spent
+        if cycle.pump_action.is_some() || self.pump.is_running() {
+            self.save_pump_state();
+        }
+        if let Some(alert) = self.pump.take_alert() {
+            if alert == Alert::PumpFailure {
+                warn!("Pump failed, locking out every zone");
+                self.lock_out(Lockout::PumpFailure);
+            }
+            self.raise(alert);
+        }
+        let due = self.maintenance_due();
+        if due != self.maintenance_due {
+            if let Some(due) = due {
+                warn!("Alert: {}", due);
+            }
+            self.maintenance_due = due;
+        }
+        if let Some(moisture_percent) = cycle
+            .reading
+            .as_ref()
+            .filter(|r| self.is_trusted(r) && gate.is_none())
+            .and(control_percent)
+        {
+            // Failed, untrusted and gated reads leave the dry warning as it was
+            self.dry_warning =
+                (moisture_percent < MOISTURE_LOW).then_some(Alert::SoilDry { moisture_percent });
+        }
+        cycle.escalated = self.escalator.update(&self.active_warnings());
+        for alert in &cycle.escalated {
+            error!("Alert: {}", alert);
+            if let Err(e) = play(self.led.as_mut(), &alert_pattern(), &self.clock) {
+                warn!("Status LED failed: {:?}", e);
+            }
+        }
+        if self.is_safe_mode() {
+            if let Err(e) = play(self.led.as_mut(), &safe_mode_pattern(), &self.clock) {
+                warn!("Status LED failed: {:?}", e);
+            }
+        }
+
+        cycle.alerts = std::mem::take(&mut self.raised);
+        if let Some(daily) = &mut self.daily {
+            daily.record_alerts((cycle.alerts.len() + cycle.escalated.len()) as u32);
+        }
+
+        match self
+            .checkpointer
+            .maybe_checkpoint(&self.history, &self.stats, flash)
+        {
+            // Calendar age and the open day would otherwise only be saved when
+            // a run or the day ends
+            Ok(true) => {
+                self.save_counters();
+                if let Some(Err(e)) = self.daily.as_ref().map(|daily| daily.checkpoint(flash)) {
+                    warn!("Failed to checkpoint the open day: {:?}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Checkpoint failed: {:?}", e),
+        }
+        if let Some(timer) = &mut self.cycle_timer {
+            timer.record(self.clock.now().saturating_sub(self.last_read_at));
+        }
+        Ok(cycle)
+    }
+
+    /// Count a reading in the daily rollup, saving to flash when it closes a day
+    fn roll_up_day(&mut self, moisture_percent: u8, flash: &mut dyn FlashStore) {
+        let Some(daily) = &mut self.daily else {
+            return;
+        };
+        // Days follow the calendar once synced; boot counts as midnight before
+        let (at, synced) = match self.clock.local_time() {
+            Some(local) => (local, true),
+            None => (self.last_read_at, false),
+        };
+        if let Some(closed) = daily.record_reading(at, synced, moisture_percent) {
+            info!("Daily summary: {}", closed);
+            if let Err(e) = daily.save(flash) {
+                warn!("Failed to save daily summaries: {:?}", e);
+            }
+        }
+    }
+
+    /// Whether the schedule's windows allow watering at local time
+    fn in_window(&self) -> bool {
+        let local = self.clock.local_time();
+        self.schedule
+            .in_window(local.unwrap_or_default(), local.is_some())
+    }
+
+    /// Whether the activation rule, if any, allows starting the pump now
+    fn may_activate(&self, moisture_percent: u8) -> bool {
+        let Some(rule) = &self.activation_rule else {
+            return true;
+        };
+        let now = self.last_read_at;
+        let ctx = RuleContext {
+            moisture_percent,
+            in_window: self.in_window(),
+            // No reservoir level sensor is fitted yet
+            reservoir_empty: false,
+            recent_rain: self
+                .last_rain_at
+                .is_some_and(|at| now.saturating_sub(at) < RAIN_LOOKBACK),
+        };
+        rule.evaluate(&ctx)
+    }
+
+    /// Enter or leave safe mode; returns the pump switch-off when entering mid-run
+    fn update_probe_health(&mut self, valid: bool) -> Option<PumpAction> {
+        match self.probe.record(valid)? {
+            ProbeTransition::EnteredSafeMode(alert) => {
+                error!("Entering safe mode: {}", alert);
+                self.raised.push(alert);
+                self.lock_out(Lockout::SafeMode)
+            }
+            ProbeTransition::Recovered => {
+                info!("Valid reading received, leaving safe mode");
+                self.release_lockout(Lockout::SafeMode);
+                None
+            }
+        }
+    }
+
+    /// Wait out `wait` until the next reading, blinking the LED at the
+    /// dryness period from the last reading if it called for one
+    pub fn idle(&mut self, wait: Duration) {
+        let Some(period) = self.blink_period else {
+            self.clock.sleep(wait);
+            return;
+        };
+        // Whole milliseconds, the resolution the clocks sleep at
+        let half = Duration::from_millis((period.as_millis() as u64 / 2).max(1));
+        let (mut left, mut lit) = (wait, true);
+        while !left.is_zero() {
+            if let Err(e) = self.led.set_on(lit) {
+                warn!("Status LED failed: {:?}", e);
+            }
+            let step = half.min(left);
+            self.clock.sleep(step);
+            left -= step;
+            lit = !lit;
+        }
+        if let Err(e) = self.led.set_on(false) {
+            warn!("Status LED failed: {:?}", e);
+        }
+    }
+
+    /// Every warning still standing, for the escalator
+    fn active_warnings(&self) -> Vec<Alert> {
+        let cable = self
+            .cable
+            .as_ref()
+            .filter(|cable| cable.is_shifted())
+            .and_then(BaselineTracker::shift)
+            .map(|shift| Alert::CableDrift { shift });
+        [
+            self.battery.is_low().then_some(Alert::LowBattery),
+            cable,
+            self.heat_warning.clone(),
+            self.dry_warning.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// Faulty readings never drive decisions, whatever the quality threshold
+    fn is_trusted(&self, reading: &Reading) -> bool {
+        !reading.fault() && reading.quality() >= self.min_quality
+    }
+
+    /// Graceful shutdown: final checkpoint plus a session summary for later
+    /// review; the day in progress is saved as partial
+    pub fn shutdown(&mut self, flash: &mut dyn FlashStore) {
+        self.save_counters();
+        if let Some(daily) = &mut self.daily {
+            if daily.finish().is_some() {
+                if let Err(e) = daily.save(flash) {
+                    error!("Failed to save daily summaries: {:?}", e);
+                }
+            }
+        }
+        if let Err(e) = self.checkpointer.force(&self.history, &self.stats, flash) {
+            error!("Failed to write final checkpoint: {:?}", e);
+        }
+        if let Err(e) = write_session_summary(
+            &self.stats,
+            &self.pump_audit,
+            self.clock.now(),
+            self.faults.fault_count(),
+            flash,
+        ) {
+            error!("Failed to write session summary: {:?}", e);
+        }
+    }
+}
+
+/// Scripted showcase: cycle the simulated soil through dry, optimal and wet,
+/// five readings each, then shut down
+pub fn run_demo<C: Clock + Clone>(
+    app: &mut App<MockSoilSensor<C>, C>,
+    sink: &mut dyn ReadingSink,
+    flash: &mut dyn FlashStore,
+    commands: &Receiver<Command>,
+    alerts: &mut [Box<dyn AlertOutput>],
+    readings: usize,
+) -> Result<()> {
+    let conditions = ["dry", "optimal", "wet", "optimal"];
+    for i in 0..readings {
+        if i % 5 == 0 {
+            app.sensor_mut()
+                .set_soil_condition(conditions[(i / 5) % conditions.len()]);
+        }
+        app.drain_commands(commands);
+        let cycle = app.run_cycle(sink, flash)?;
+        dispatch_alerts(&cycle, alerts);
+        app.idle(cycle.wait);
+    }
+    app.shutdown(flash);
+    Ok(())
+}
+
+/// Hand a cycle's raised and escalated alerts to every output
+fn dispatch_alerts(cycle: &Cycle, outputs: &mut [Box<dyn AlertOutput>]) {
+    for output in outputs.iter_mut() {
+        for alert in cycle.alerts.iter().chain(&cycle.escalated) {
+            output.deliver(alert);
+        }
+        output.tick();
+    }
+}
+
+/// Production control loop; runs forever unless `max_cycles` is given
+pub fn run_firmware<S: SoilSensor, C: Clock + Clone>(
+    app: &mut App<S, C>,
+    sink: &mut dyn ReadingSink,
+    flash: &mut dyn FlashStore,
+    commands: &Receiver<Command>,
+    alerts: &mut [Box<dyn AlertOutput>],
+    max_cycles: Option<usize>,
+) -> Result<()> {
+    let mut cycles = 0;
+    while max_cycles.map_or(true, |max| cycles < max) {
+        app.drain_commands(commands);
+        let cycle = app.run_cycle(sink, flash)?;
+        dispatch_alerts(&cycle, alerts);
+        app.idle(cycle.wait);
+        cycles += 1;
+    }
+    app.shutdown(flash);
+    Ok(())
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        load_config, run_demo, run_firmware, spawn_command_reader, App, CHECKPOINT_INTERVAL,
+    };
+    use crate::accessibility::{Buzzer, IndicatorMode};
+    use crate::alert::{Alert, AlertOutput};
+    use crate::boot::BootReason;
+    use crate::calibrate::AutoCalibrationConfig;
+    use crate::clock::{Clock, MockClock};
+    use crate::command::{Command, SimulatedFault};
+    use crate::config::ConfigFormat;
+    use crate::daily::{load_daily_summaries, load_open_day, DailyRollup};
+    use crate::ec::EcChannel;
+    use crate::export::{export_csv, ExportOptions};
+    use crate::filter::{DualFilter, FilterChain, FixedEma, WateringResponse};
+    use crate::interval::ReadingInterval;
+    use crate::led::{DrynessBlink, Led, LED_FULL};
+    use crate::maintenance::MaintenanceConfig;
+    use crate::moisture::{Calibration, ClampPolicy, ComfortBand};
+    use crate::nvs::{KvStore, MemoryKv};
+    use crate::power::{SagThreshold, SupplyMonitor};
+    use crate::profile::Profile;
+    use crate::provision::{
+        load_pump_state, load_service_record, save_pump_lifetime, save_pump_state, CALIBRATION_KEY,
+        PROFILE_KEY,
+    };
+    use crate::pump::{
+        DepthScaling, GateMode, Guardrails, PumpAction, PumpConfig, PumpController, PumpDrive,
+        PumpFeedback, PumpLifetime, PumpReadingGate, PumpState,
+    };
+    use crate::rate::SoilType;
+    use crate::reading::ReadingFlags;
+    use crate::rng::Rng;
+    use crate::rule::Condition;
+    use crate::schedule::{Schedule, Window};
+    use crate::sensor::{sample_spread, MockSoilSensor, SoilSensor};
+    use crate::sink::MemorySink;
+    use crate::storage::{FlashStore, MemoryFlash};
+    use crate::summary::SESSION_SUMMARY_FILE;
+    use crate::temperature::TemperatureChannel;
+    use crate::timestamp::{Epoch, TimestampConfig, TimestampPrecision};
+    use crate::zone::ZoneController;
+    use anyhow::{anyhow, Result};
+    use std::cell::Cell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+    use std::sync::{mpsc, Arc, Mutex};
+    use std::time::Duration;
+
+    fn app(clock: &MockClock) -> App<MockSoilSensor<MockClock>, MockClock> {
+        App::new(
+            MockSoilSensor::with_clock(clock.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(7),
+        )
+    }
+
+    #[test]
+    fn demo_runs_one_cycle_against_mocks() {
+        let clock = MockClock::new();
+        let mut app = app(&clock);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let (_tx, commands) = mpsc::channel();
+
+        run_demo(&mut app, &mut sink, &mut flash, &commands, &mut [], 1).unwrap();
+        assert_eq!(sink.readings.len(), 1);
+        assert_eq!(clock.now(), Duration::from_secs(2));
+        // Dry soil on the first scripted reading starts the pump
+        assert_eq!(app.pump_audit().activations(), 1);
+        assert!(flash.read_file(SESSION_SUMMARY_FILE).unwrap().is_some());
+    }
+
+    #[test]
+    fn firmware_runs_one_cycle_against_mocks() {
+        let clock = MockClock::new();
+        let mut app = app(&clock);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let (tx, commands) = mpsc::channel();
+        tx.send(Command::Pause).unwrap();
+
+        run_firmware(&mut app, &mut sink, &mut flash, &commands, &mut [], Some(1)).unwrap();
+        assert_eq!(sink.readings.len(), 1);
+        assert!(sink.readings[0].control_paused());
+        assert_eq!(app.history().len(), 1);
+        assert!(flash.read_file(SESSION_SUMMARY_FILE).unwrap().is_some());
+    }
+
+    /// Alert output whose deliveries stay readable after moving into the loop
+    #[derive(Clone, Default)]
+    struct SharedAlerts(Arc<Mutex<Vec<Alert>>>);
+
+    impl AlertOutput for SharedAlerts {
+        fn deliver(&mut self, alert: &Alert) {
+            self.0.lock().unwrap().push(alert.clone());
+        }
+    }
+
+    #[test]
+    fn run_loop_routes_raised_and_escalated_alerts_to_outputs() {
+        let clock = MockClock::new();
+        // Short enough to escalate before the steady mock reads as stuck
+        let mut app = app(&clock).with_escalation_timeout(Duration::from_secs(10));
+        app.sensor_mut().set_soil_condition("dry");
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let (tx, commands) = mpsc::channel();
+        tx.send(Command::Simulate(SimulatedFault::PumpFailure))
+            .unwrap();
+        let output = SharedAlerts::default();
+        let mut outputs: Vec<Box<dyn AlertOutput>> = vec![Box::new(output.clone())];
+
+        run_firmware(
+            &mut app,
+            &mut sink,
+            &mut flash,
+            &commands,
+            &mut outputs,
+            Some(40),
+        )
+        .unwrap();
+        let delivered = output.0.lock().unwrap();
+        assert_eq!(delivered[0], Alert::PumpFailure);
+        assert!(delivered
+            .iter()
+            .any(|alert| matches!(alert, Alert::Escalated { .. })));
+    }
+
+    #[test]
+    fn completed_runs_add_to_stored_pump_lifetime() {
+        let clock = MockClock::new();
+        let mut kv = MemoryKv::new();
+        let before = PumpLifetime {
+            activations: 3,
+            runtime: Duration::from_secs(60),
+        };
+        save_pump_lifetime(&mut kv, &before).unwrap();
+        let mut app = app(&clock).with_settings(kv);
+        assert_eq!(app.pump_lifetime(), before);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let (_tx, commands) = mpsc::channel();
+
+        // Dry readings start the pump, the later wet ones stop it
+        run_demo(&mut app, &mut sink, &mut flash, &commands, &mut [], 15).unwrap();
+        let after = app.pump_lifetime();
+        assert_eq!(after.activations, 4);
+        assert!(after.runtime > before.runtime);
+        assert!(app.status().to_string().contains("over 4 runs"));
+    }
+
+    #[test]
+    fn run_in_progress_resumes_after_reboot() {
+        let clock = MockClock::new();
+        let mut kv = MemoryKv::new();
+        let state = PumpState {
+            running_for: Some(Duration::from_secs(2)),
+            stopped_for: None,
+        };
+        save_pump_state(&mut kv, &state).unwrap();
+        let mut app = app(&clock).with_settings(kv);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        let actions: Vec<_> = app.pump_audit().entries().map(|(_, a)| *a).collect();
+        assert_eq!(actions[0], PumpAction::Activate);
+        assert!(app.pump.is_running());
+        let stored = load_pump_state(app.settings.as_deref().unwrap());
+        assert!(stored.running_for.unwrap() >= state.running_for.unwrap());
+    }
+
+    #[test]
+    fn run_is_not_resumed_after_a_brownout() {
+        let clock = MockClock::new();
+        let mut kv = MemoryKv::new();
+        let state = PumpState {
+            running_for: Some(Duration::from_secs(2)),
+            stopped_for: None,
+        };
+        save_pump_state(&mut kv, &state).unwrap();
+        let mut app = app(&clock)
+            .with_settings(kv)
+            .with_boot_reason(BootReason::Brownout);
+        app.sensor_mut().set_soil_condition("dry");
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, None);
+        assert!(!app.pump.is_running());
+        assert_eq!(app.pump_audit().activations(), 0);
+        let stored = load_pump_state(app.settings.as_deref().unwrap());
+        assert_eq!(stored.running_for, None);
+    }
+
+    /// Buzzer whose tone history stays readable after moving into the app
+    #[derive(Clone, Default)]
+    struct SharedBuzzer(Arc<Mutex<Vec<bool>>>);
+
+    impl Buzzer for SharedBuzzer {
+        fn set_tone(&mut self, on: bool) -> Result<()> {
+            self.0.lock().unwrap().push(on);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn accessible_mode_pulses_condition_codes() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(2900)));
+        let (led, buzzer) = (SharedLed::default(), SharedBuzzer::default());
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        )
+        .with_led(led.clone())
+        .with_buzzer(buzzer.clone())
+        .with_indicator_mode(IndicatorMode::Accessible);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let mut cycle = || {
+            led.0.lock().unwrap().clear();
+            buzzer.0.lock().unwrap().clear();
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            let lit = led
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|&&b| b == LED_FULL)
+                .count();
+            (lit, buzzer.0.lock().unwrap().len())
+        };
+
+        // Dry: three pulses on the LED and, being new, on the buzzer
+        assert_eq!(cycle(), (3, 6));
+        // Same condition again: LED only
+        assert_eq!(cycle(), (3, 0));
+        probe.set(Some(1200));
+        assert_eq!(cycle(), (2, 4));
+    }
+
+    /// LED whose brightness history stays readable after moving into the app
+    #[derive(Clone, Default)]
+    struct SharedLed(Arc<Mutex<Vec<u8>>>);
+
+    impl Led for SharedLed {
+        fn set_brightness(&mut self, level: u8) -> Result<()> {
+            self.0.lock().unwrap().push(level);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn dryness_blink_runs_faster_for_drier_soil() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(2700)));
+        let led = SharedLed::default();
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        )
+        .with_led(led.clone())
+        .with_dryness_blink(DrynessBlink::default());
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        // Time the cycle itself takes, and how many times the LED lit while
+        // idling until the next reading
+        let mut cycle = |raw| {
+            probe.set(Some(raw));
+            led.0.lock().unwrap().clear();
+            let start = clock.now();
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            let spent = clock.now() - start;
+            let idle_start = clock.now();
+            app.idle(cycle.wait);
+            assert_eq!(clock.now() - idle_start, cycle.wait);
+            let lit = led.0.lock().unwrap().iter().filter(|&&l| l > 0).count();
+            (spent, lit, led.0.lock().unwrap().last().copied())
+        };
+
+        let (dry_spent, dry, dry_last) = cycle(2700);
+        let (drier_spent, drier, _) = cycle(2950);
+        // Blinking never holds up the cycle, and ends with the LED dark
+        assert_eq!((dry_spent, drier_spent), (Duration::ZERO, Duration::ZERO));
+        assert_eq!(dry_last, Some(0));
+        assert!(
+            drier > dry,
+            "{} blinks at ~3% is no faster than {} at ~16%",
+            drier,
+            dry
+        );
+
+        // At or above the threshold the LED is left to its normal behaviour
+        assert_eq!(cycle(2100).1, 0);
+    }
+
+    #[test]
+    fn note_command_reaches_sink_and_export() {
+        let clock = MockClock::new();
+        let mut app = app(&clock);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        clock.advance(Duration::from_secs(30));
+
+        app.handle_command("note added fertilizer".parse().unwrap());
+        clock.advance(Duration::from_secs(30));
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(sink.annotations.len(), 1);
+        assert_eq!(sink.annotations[0].text(), "added fertilizer");
+        assert_eq!(sink.annotations[0].timestamp_s, 30);
+
+        let csv = export_csv(app.history(), ExportOptions::default());
+        let rows: Vec<&str> = csv.lines().skip(1).collect();
+        let first = cycle.reading.unwrap().moisture_percent;
+        assert_eq!(rows[0], format!("0,{first},0,"));
+        assert_eq!(rows[1], "30,,,\"added fertilizer\"");
+        assert!(rows[2].starts_with("60,"));
+    }
+
+    #[test]
+    fn day_boundary_closes_daily_summary_to_flash() {
+        let clock = MockClock::new();
+        clock.set(Duration::from_secs(10 * 86_400 + 23 * 3600));
+        clock.set_synced(true);
+        let mut app = app(&clock).with_daily_summaries(DailyRollup::new(Duration::ZERO));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        for _ in 0..3 {
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            clock.advance(Duration::from_secs(20 * 60));
+        }
+        assert!(load_daily_summaries(&flash).is_empty());
+
+        // 00:00 passed during the last wait
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        let days = load_daily_summaries(&flash);
+        assert_eq!(days.len(), 1);
+        assert_eq!((days[0].day, days[0].moisture.count()), (11, 3));
+        assert!(days[0].partial);
+
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        app.shutdown(&mut flash);
+        let days = load_daily_summaries(&flash);
+        assert_eq!(days.len(), 2);
+        assert_eq!((days[1].day, days[1].moisture.count()), (12, 2));
+        assert!(days[1].partial, "shut down before the day ended");
+    }
+
+    #[test]
+    fn open_day_is_checkpointed() {
+        let clock = MockClock::new();
+        clock.set_synced(true);
+        let mut app = app(&clock).with_daily_summaries(DailyRollup::new(Duration::ZERO));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        clock.advance(CHECKPOINT_INTERVAL);
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        let open = load_open_day(&flash).unwrap();
+        assert_eq!((open.day, open.moisture.count()), (1, 2));
+    }
+
+    #[test]
+    fn probe_depth_is_reported_and_tightens_thresholds() {
+        let clock = MockClock::new();
+        let config = PumpConfig {
+            start_below: 30,
+            stop_at: 50,
+            ..PumpConfig::default()
+        };
+        let at_depth = |depth| {
+            app(&clock)
+                .with_pump_config(config)
+                .with_probe_depth(depth, Some(DepthScaling::default()))
+        };
+        let (shallow, deep) = (at_depth(5), at_depth(30));
+        assert_eq!(shallow.pump.config(), config);
+        let deep_config = deep.pump.config();
+        assert!(deep_config.start_below > 30 && deep_config.stop_at < 50);
+
+        let mut unscaled = app(&clock)
+            .with_pump_config(config)
+            .with_probe_depth(30, None);
+        assert_eq!(unscaled.pump.config(), config);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        unscaled.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(sink.readings[0].depth_cm, Some(30));
+    }
+
+    #[test]
+    fn serviced_command_clears_maintenance_reminder() {
+        let clock = MockClock::new();
+        let mut app =
+            app(&clock)
+                .with_settings(MemoryKv::new())
+                .with_maintenance(MaintenanceConfig {
+                    max_runtime: Duration::from_secs(3600),
+                    max_age: Duration::from_secs(60),
+                });
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        assert_eq!(app.maintenance_due(), None);
+
+        clock.advance(Duration::from_secs(60));
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(app.maintenance_due().is_some());
+        assert!(app.status().to_string().contains("[maintenance due]"));
+
+        app.handle_command(Command::Serviced);
+        assert_eq!(app.maintenance_due(), None);
+    }
+
+    #[test]
+    fn service_age_is_saved_with_each_checkpoint() {
+        let clock = MockClock::new();
+        let mut app = app(&clock)
+            .with_settings(MemoryKv::new())
+            .with_maintenance(MaintenanceConfig::default());
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+
+        // The pump never runs, but the age still survives a reboot
+        clock.advance(CHECKPOINT_INTERVAL);
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        let stored = load_service_record(app.settings.as_deref().unwrap());
+        assert_eq!(stored.age, CHECKPOINT_INTERVAL);
+    }
+
+    #[test]
+    fn activation_rule_gates_pump_start() {
+        let clock = MockClock::new();
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        // Dry mock soil reads around 10%
+        let strict = Condition::InWindow.and(Condition::MoistureBelow(5));
+        let mut blocked = app(&clock).with_activation_rule(strict);
+        blocked.sensor_mut().set_soil_condition("dry");
+        assert_eq!(
+            blocked
+                .run_cycle(&mut sink, &mut flash)
+                .unwrap()
+                .pump_action,
+            None
+        );
+
+        // The emergency guardrail overrides the rule
+        let mut emergency = app(&clock)
+            .with_activation_rule(Condition::InWindow.and(Condition::MoistureBelow(5)))
+            .with_guardrails(Guardrails {
+                flood_above: 95,
+                emergency_below: 20,
+            });
+        emergency.sensor_mut().set_soil_condition("dry");
+        assert_eq!(
+            emergency
+                .run_cycle(&mut sink, &mut flash)
+                .unwrap()
+                .pump_action,
+            Some(PumpAction::Activate)
+        );
+
+        let relaxed = Condition::InWindow.and(Condition::MoistureBelow(20));
+        let mut allowed = app(&clock).with_activation_rule(relaxed);
+        allowed.sensor_mut().set_soil_condition("dry");
+        assert_eq!(
+            allowed
+                .run_cycle(&mut sink, &mut flash)
+                .unwrap()
+                .pump_action,
+            Some(PumpAction::Activate)
+        );
+    }
+
+    #[test]
+    fn comfort_band_keeps_pump_and_led_quiet() {
+        let clock = MockClock::new();
+        let led = SharedLed::default();
+        // Pump thresholds alone would water at ~55%; the band overrides them
+        let mut app = app(&clock)
+            .with_pump_config(PumpConfig {
+                start_below: 60,
+                stop_at: 70,
+                ..PumpConfig::default()
+            })
+            .with_comfort_band(ComfortBand::new(40, 65))
+            .with_led(led.clone());
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+
+        app.sensor_mut().set_soil_condition("optimal");
+        for _ in 0..3 {
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            assert_eq!(cycle.pump_action, None);
+            clock.sleep(cycle.wait);
+        }
+        assert!(led.0.lock().unwrap().iter().all(|&level| level == 0));
+
+        app.sensor_mut().set_soil_condition("dry");
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+        assert_eq!(led.0.lock().unwrap().last(), Some(&255));
+        clock.sleep(cycle.wait);
+
+        // Back inside the band the run is cut short and the LED goes dark
+        app.sensor_mut().set_soil_condition("optimal");
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Deactivate));
+        assert_eq!(led.0.lock().unwrap().last(), Some(&0));
+    }
+
+    #[test]
+    fn pump_actions_drive_the_soft_start_output() {
+        let clock = MockClock::new();
+        let output = SharedLed::default();
+        let drive = PumpDrive::SoftStart {
+            ramp: Duration::from_secs(1),
+            steps: 4,
+        };
+        let mut app = app(&clock).with_pump_output(output.clone(), drive);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+
+        app.sensor_mut().set_soil_condition("dry");
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+        assert_eq!(*output.0.lock().unwrap(), vec![63, 127, 191, 255]);
+    }
+
+    #[test]
+    fn cycle_timer_measures_time_spent_in_the_cycle() {
+        let clock = MockClock::new();
+        let drive = PumpDrive::SoftStart {
+            ramp: Duration::from_secs(1),
+            steps: 4,
+        };
+        let mut app = app(&clock)
+            .with_pump_output(SharedLed::default(), drive)
+            .with_cycle_timer(10);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        assert_eq!(app.cycle_stats(), None);
+
+        // Ramping the pump up is the only thing taking simulated time
+        app.sensor_mut().set_soil_condition("optimal");
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        clock.advance(Duration::from_secs(60));
+        app.sensor_mut().set_soil_condition("dry");
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        let stats = app.cycle_stats().unwrap();
+        assert_eq!(stats.cycles, 2);
+        assert_eq!(stats.min, Duration::ZERO);
+        assert_eq!(stats.max, Duration::from_secs(1));
+        assert_eq!(stats.mean, Duration::from_millis(500));
+    }
+
+    /// Rail returning a fixed sequence of voltages
+    struct ScriptedRail(Vec<u16>);
+
+    impl SupplyMonitor for ScriptedRail {
+        fn read_millivolts(&mut self) -> Result<u16> {
+            Ok(self.0.remove(0))
+        }
+    }
+
+    #[test]
+    fn readings_during_rail_sag_are_flagged() {
+        let clock = MockClock::new();
+        let mut app = app(&clock)
+            .with_supply_monitor(ScriptedRail(vec![3290, 2950]), SagThreshold::default());
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+
+        let stable = app
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .reading
+            .unwrap();
+        assert_eq!(stable.supply_mv, Some(3290));
+        assert!(!stable.supply_sag());
+
+        // Pump inrush pulls the rail down on the next reading
+        let sagging = app
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .reading
+            .unwrap();
+        assert_eq!(sagging.supply_mv, Some(2950));
+        assert!(sagging.supply_sag());
+    }
+
+    /// Auxiliary probe that always reads the same raw value
+    struct Fixed(u16);
+
+    impl SoilSensor for Fixed {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            Ok(self.0)
+        }
+    }
+
+    #[test]
+    fn temperature_is_emitted_kept_and_alerted() {
+        let clock = MockClock::new();
+        // 0 °C on the default TMP36 calibration
+        let mut app =
+            app(&clock).with_temperature_channel(Fixed(620), TemperatureChannel::default());
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::Frost { tenths_c: 0 }]);
+        assert_eq!(sink.readings[0].temperature_tenths_c, Some(0));
+        let kept = app.history().iter().next_back().unwrap();
+        assert_eq!(kept.temperature_tenths_c, Some(0));
+
+        // Once per excursion
+        clock.advance(Duration::from_secs(60));
+        assert!(app
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .alerts
+            .is_empty());
+    }
+
+    #[test]
+    fn ec_is_emitted_and_alerted() {
+        let clock = MockClock::new();
+        // 141 uS/cm on the default calibration, below the fertilize threshold
+        let mut app = app(&clock).with_ec_channel(Fixed(200), EcChannel::default());
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::Fertilize { ec_us_cm: 141 }]);
+        assert_eq!(sink.readings[0].ec_us_cm, Some(141));
+
+        clock.advance(Duration::from_secs(60));
+        assert!(app
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .alerts
+            .is_empty());
+    }
+
+    #[test]
+    fn safe_mode_locks_out_every_zone() {
+        let clock = MockClock::new();
+        let zones = ZoneController::new()
+            .with_zone(1, PumpController::new(PumpConfig::default(), clock.clone()));
+        let mut app = app(&clock)
+            .with_dead_probe_timeout(Duration::from_secs(60))
+            .with_zones(zones);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        assert_eq!(app.update_zone(1, 10), vec![(1, PumpAction::Activate)]);
+
+        app.handle_command(Command::Simulate(SimulatedFault::Disconnected));
+        for _ in 0..3 {
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            clock.advance(Duration::from_secs(60));
+        }
+        assert!(app.is_safe_mode());
+        assert!(app.zones().unwrap().is_locked_out());
+        assert!(!app.zones().unwrap().is_running(1));
+        assert_eq!(app.update_zone(1, 10), vec![]);
+    }
+
+    #[test]
+    fn zone_pump_failure_outlasts_probe_recovery() {
+        /// Current sense on a pump that never starts
+        struct NoCurrent;
+
+        impl PumpFeedback for NoCurrent {
+            fn current_detected(&mut self) -> Result<bool> {
+                Ok(false)
+            }
+        }
+
+        let clock = MockClock::new();
+        let unpowered = PumpController::new(PumpConfig::default(), clock.clone())
+            .with_feedback(NoCurrent, Duration::from_secs(5));
+        let zones = ZoneController::new()
+            .with_zone(1, unpowered)
+            .with_zone(2, PumpController::new(PumpConfig::default(), clock.clone()));
+        let mut app = app(&clock)
+            .with_dead_probe_timeout(Duration::from_secs(60))
+            .with_simulation_timeout(Duration::from_secs(300))
+            .with_zones(zones);
+        app.sensor_mut().set_soil_condition("dry");
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        assert_eq!(app.update_zone(1, 10), vec![(1, PumpAction::Activate)]);
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(app.update_zone(1, 10), vec![(1, PumpAction::Deactivate)]);
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::PumpFailure]);
+        assert_eq!(cycle.pump_action, None);
+
+        app.handle_command(Command::Simulate(SimulatedFault::Disconnected));
+        for _ in 0..3 {
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            clock.advance(Duration::from_secs(60));
+        }
+        assert!(app.is_safe_mode());
+        clock.advance(Duration::from_secs(180));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(cycle.reading.is_some() && !app.is_safe_mode());
+
+        // Recovery only releases the safe mode lockout
+        assert_eq!(cycle.pump_action, None);
+        assert!(app.zones().unwrap().is_locked_out());
+        assert_eq!(app.update_zone(2, 10), vec![]);
+    }
+
+    #[test]
+    fn primary_pump_failure_locks_out_every_zone() {
+        let clock = MockClock::new();
+        let zones = ZoneController::new()
+            .with_zone(1, PumpController::new(PumpConfig::default(), clock.clone()));
+        let mut app = app(&clock)
+            .with_simulation_timeout(Duration::from_secs(300))
+            .with_zones(zones);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        assert_eq!(app.update_zone(1, 10), vec![(1, PumpAction::Activate)]);
+
+        app.handle_command(Command::Simulate(SimulatedFault::PumpFailure));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::PumpFailure]);
+        assert!(!app.zones().unwrap().is_running(1));
+        assert_eq!(app.update_zone(1, 10), vec![]);
+
+        // Clearing the simulation releases the zones again
+        clock.advance(Duration::from_secs(300));
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(!app.zones().unwrap().is_locked_out());
+    }
+
+    #[test]
+    fn simulated_faults_raise_alerts_and_clear() {
+        let clock = MockClock::new();
+        let mut app = app(&clock)
+            .with_dead_probe_timeout(Duration::from_secs(60))
+            .with_simulation_timeout(Duration::from_secs(300));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let minute = Duration::from_secs(60);
+
+        app.handle_command(Command::Simulate(SimulatedFault::Disconnected));
+        let mut alerts = Vec::new();
+        for _ in 0..3 {
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            assert_eq!(cycle.reading, None);
+            alerts.extend(cycle.alerts);
+            clock.advance(minute);
+        }
+        assert!(matches!(alerts[..], [Alert::ProbeDead { .. }]));
+        assert!(app.is_safe_mode());
+        clock.advance(3 * minute);
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(cycle.reading.is_some() && !app.is_safe_mode());
+
+        app.handle_command(Command::Simulate(SimulatedFault::LowBattery));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::LowBattery]);
+        assert!(cycle.reading.unwrap().low_battery());
+        clock.advance(5 * minute);
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(cycle.alerts.is_empty());
+        assert!(!cycle.reading.unwrap().low_battery());
+
+        app.handle_command(Command::Simulate(SimulatedFault::PumpFailure));
+        app.sensor_mut().set_soil_condition("dry");
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::PumpFailure]);
+        assert_eq!(cycle.pump_action, None);
+        // Stacked on the pump failure; repeating an active one is ignored
+        clock.advance(minute);
+        app.handle_command(Command::Simulate(SimulatedFault::LowBattery));
+        app.handle_command(Command::Simulate(SimulatedFault::PumpFailure));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.alerts, vec![Alert::LowBattery]);
+        clock.advance(4 * minute);
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+        assert!(cycle.reading.unwrap().low_battery());
+        clock.advance(minute);
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(!cycle.reading.unwrap().low_battery());
+    }
+
+    #[test]
+    fn persistent_low_battery_escalates() {
+        let clock = MockClock::new();
+        let mut app = app(&clock)
+            .with_escalation_timeout(Duration::from_secs(120))
+            .with_simulation_timeout(Duration::from_secs(600));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let minute = Duration::from_secs(60);
+
+        app.handle_command(Command::Simulate(SimulatedFault::LowBattery));
+        let mut escalated = Vec::new();
+        for _ in 0..4 {
+            escalated.extend(app.run_cycle(&mut sink, &mut flash).unwrap().escalated);
+            clock.advance(minute);
+        }
+        assert_eq!(
+            escalated,
+            vec![Alert::Escalated {
+                alert: Box::new(Alert::LowBattery),
+                after_s: 120,
+            }]
+        );
+    }
+
+    #[test]
+    fn runtime_calibration_change_is_blended_in() {
+        let clock = MockClock::new();
+        let raw = Rc::new(Cell::new(Some(2100)));
+        let mut app = App::new(
+            SwitchedProbe(raw.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(7),
+        )
+        .with_calibration_blend(4);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let mut reported = |app: &mut App<SwitchedProbe, MockClock>| {
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            cycle.reading.unwrap().moisture_percent
+        };
+        assert_eq!(reported(&mut app), 50);
+
+        app.set_calibration(Calibration::new(3000, 1500));
+        let blended: Vec<u8> = (0..6).map(|_| reported(&mut app)).collect();
+        assert_eq!(blended, vec![52, 55, 57, 60, 60, 60]);
+    }
+
+    #[test]
+    fn control_uses_a_new_calibration_at_once() {
+        let clock = MockClock::new();
+        let mut app = App::new(
+            SwitchedProbe(Rc::new(Cell::new(Some(2100)))),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(7),
+        )
+        .with_pump_config(PumpConfig {
+            start_below: 40,
+            stop_at: 60,
+            ..PumpConfig::default()
+        })
+        .with_calibration_blend(4);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.reading.unwrap().moisture_percent, 50);
+
+        // The same raw value is 30% on the new calibration
+        app.set_calibration(Calibration::new(2400, 1400));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(
+            cycle.reading.unwrap().moisture_percent,
+            45,
+            "still blending"
+        );
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+    }
+
+    /// Probe answering with whatever the test last set; `None` fails the read
+    struct SwitchedProbe(Rc<Cell<Option<u16>>>);
+
+    impl SoilSensor for SwitchedProbe {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            self.0.get().ok_or_else(|| anyhow!("no response"))
+        }
+    }
+
+    #[test]
+    fn smoothed_display_does_not_hold_back_the_pump() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(2100)));
+        let display = FilterChain::new().then(FixedEma::new(32));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(7),
+        )
+        .with_filters(DualFilter::new(display, FilterChain::new()));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        for _ in 0..3 {
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            clock.advance(Duration::from_secs(2));
+        }
+
+        // A step to dry soil: the reading only eases toward it, the pump reacts at once
+        probe.set(Some(2900));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        let reading = cycle.reading.unwrap();
+        assert_eq!((reading.raw, reading.moisture_percent), (2200, 44));
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn pump_start_resets_a_filter_that_asks_for_it() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(2900)));
+        let display = FilterChain::new()
+            .then(FixedEma::new(32).with_watering_response(WateringResponse::Reset));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(7),
+        )
+        .with_filters(DualFilter::new(display, FilterChain::new()));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+
+        // The smoothed history is dropped, so the wetter reading shows as-is
+        clock.advance(Duration::from_secs(2));
+        probe.set(Some(2100));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.reading.unwrap().raw, 2100);
+    }
+
+    #[test]
+    fn clipped_readings_are_counted_in_the_status() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(1000)));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(7),
+        )
+        .with_clamp_policy(ClampPolicy::Count);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        for raw in [1000, 2100, 1100] {
+            probe.set(Some(raw));
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            clock.advance(Duration::from_secs(2));
+        }
+        assert_eq!(sink.readings[0].moisture_percent, 100);
+        assert_eq!(app.status().clipped_readings, 2);
+        assert!(app.status().to_string().contains("[2 readings clipped]"));
+    }
+
+    #[test]
+    fn out_of_range_reading_never_starts_the_pump() {
+        let clock = MockClock::new();
+        // Past the fault bound: a floating input that converts to 0%
+        let probe = Rc::new(Cell::new(Some(4095)));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        );
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        for _ in 0..3 {
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            let reading = cycle.reading.unwrap();
+            assert!(reading.fault() && reading.moisture_percent == 0);
+            assert_eq!(cycle.pump_action, None);
+            assert!(cycle.escalated.is_empty());
+            clock.sleep(cycle.wait);
+        }
+
+        // The same dry soil read in range does
+        probe.set(Some(2990));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn drying_rate_is_checked_against_the_soil_type() {
+        // 50% then 47% an hour later
+        let flagged = |soil| {
+            let clock = MockClock::new();
+            let probe = Rc::new(Cell::new(Some(2100)));
+            let mut app = App::new(
+                SwitchedProbe(probe.clone()),
+                clock.clone(),
+                Calibration::default(),
+                ReadingInterval::new(Duration::from_secs(60 * 60)),
+                Rng::new(1),
+            )
+            .with_soil_type(soil);
+            let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+            let first = app.run_cycle(&mut sink, &mut flash).unwrap();
+            clock.sleep(first.wait);
+            probe.set(Some(2154));
+            let second = app.run_cycle(&mut sink, &mut flash).unwrap();
+            let (first, second) = (first.reading.unwrap(), second.reading.unwrap());
+            assert_eq!((first.moisture_percent, first.fault()), (50, false));
+            assert_eq!(second.moisture_percent, 47);
+            second.fault()
+        };
+        assert!(!flagged(SoilType::Sand));
+        assert!(flagged(SoilType::Clay));
+    }
+
+    #[test]
+    fn dead_probe_enters_safe_mode_until_a_valid_reading() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(2900)));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        )
+        .with_dead_probe_timeout(Duration::from_secs(300));
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+
+        // Dry soil starts the pump, then the probe dies mid-run
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+        probe.set(None);
+        for _ in 0..4 {
+            clock.advance(Duration::from_secs(60));
+            app.run_cycle(&mut sink, &mut flash).unwrap();
+            assert!(!app.is_safe_mode());
+        }
+        clock.advance(Duration::from_secs(60));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(cycle.pump_action, Some(PumpAction::Deactivate));
+        assert!(app.is_safe_mode());
+        assert!(app.status().to_string().contains("SAFE MODE"));
+
+        // Implausible readings keep it there, marked in telemetry, pump locked out
+        probe.set(Some(4090));
+        clock.advance(Duration::from_secs(60));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        let reading = cycle.reading.unwrap();
+        assert!(reading.safe_mode() && reading.fault());
+        assert_eq!(cycle.pump_action, None);
+        assert_eq!(app.active_alert().unwrap().kind(), "probe_dead");
+
+        // One valid dry reading resumes normal control
+        probe.set(Some(2900));
+        clock.advance(Duration::from_secs(600));
+        let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert!(!app.is_safe_mode());
+        assert!(!cycle.reading.unwrap().safe_mode());
+        assert_eq!(cycle.pump_action, Some(PumpAction::Activate));
+        assert_eq!(app.active_alert(), None);
+    }
+
+    /// Probe whose four conversions alternate `spread` counts either side of 2250
+    struct NoisyProbe(u16);
+
+    impl SoilSensor for NoisyProbe {
+        fn read_averaged(&mut self, samples: usize) -> Result<u16> {
+            Ok(self.read_with_spread(samples)?.0)
+        }
+
+        fn read_with_spread(&mut self, _samples: usize) -> Result<(u16, Option<u16>)> {
+            let (low, high) = (2250 - self.0, 2250 + self.0);
+            let (mean, spread) = sample_spread(&[low, high, low, high]).unwrap();
+            Ok((mean, Some(spread)))
+        }
+    }
+
+    #[test]
+    fn readings_carry_uncertainty_from_conversion_spread() {
+        let reported = |spread: u16| {
+            let clock = MockClock::new();
+            let mut app = App::new(
+                NoisyProbe(spread),
+                clock.clone(),
+                Calibration::new(3000, 1500),
+                ReadingInterval::new(Duration::from_secs(60)),
+                Rng::new(1),
+            );
+            let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+            app.run_cycle(&mut sink, &mut flash)
+                .unwrap()
+                .reading
+                .unwrap()
+        };
+        let steady = reported(3);
+        let noisy = reported(60);
+        // 1500 counts span the calibration: 3 counts are 0.2%, 60 are 4.0%
+        assert_eq!(steady.moisture_percent, noisy.moisture_percent);
+        assert_eq!(steady.uncertainty_tenths, Some(2));
+        assert_eq!(noisy.uncertainty_tenths, Some(40));
+
+        // Sources without sub-sample access report no uncertainty
+        let clock = MockClock::new();
+        let mut cycle = app(&clock);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let reading = cycle
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .reading
+            .unwrap();
+        assert_eq!(reading.uncertainty_tenths, None);
+    }
+
+    #[test]
+    fn auto_calibration_commits_and_persists_validated_candidate() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(1300)));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::new(3000, 1500),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        )
+        .with_settings(MemoryKv::new())
+        .with_auto_calibration(AutoCalibrationConfig {
+            window: 2,
+            margin: 50,
+        });
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let mut flags = || {
+            clock.advance(Duration::from_secs(60));
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            cycle.reading.unwrap().flags
+        };
+
+        // Wetter than the wet point: clipped, and a wider candidate is proposed
+        assert!(flags().contains(ReadingFlags::CLIPPED));
+        // Held back while it validates
+        assert!(flags().contains(ReadingFlags::CLIPPED));
+        probe.set(Some(2000));
+        flags();
+        probe.set(Some(1300));
+        assert!(!flags().contains(ReadingFlags::CLIPPED));
+
+        let stored = app.settings.as_deref().unwrap().get(CALIBRATION_KEY);
+        assert_eq!(
+            Calibration::from_bytes(&stored.unwrap().unwrap()).unwrap(),
+            Calibration::new(3000, 1300)
+        );
+    }
+
+    #[test]
+    fn low_quality_readings_do_not_drive_the_pump() {
+        let clock = MockClock::new();
+        let mut app = App::new(
+            SwitchedProbe(Rc::new(Cell::new(Some(2900)))),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        )
+        .with_warm_up(2)
+        .with_min_quality(70);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let mut cycle = || {
+            clock.advance(Duration::from_secs(60));
+            app.run_cycle(&mut sink, &mut flash).unwrap()
+        };
+
+        // Dry, but taken while the probe settles
+        for _ in 0..2 {
+            let held = cycle();
+            assert_eq!(held.reading.unwrap().quality(), 60);
+            assert_eq!(held.pump_action, None);
+        }
+        let acted = cycle();
+        assert_eq!(acted.reading.unwrap().quality(), 100);
+        assert_eq!(acted.pump_action, Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn readings_during_and_after_a_run_are_kept_out_of_control() {
+        for mode in [GateMode::Discard, GateMode::Pause] {
+            let clock = MockClock::new();
+            let probe = Rc::new(Cell::new(Some(2900)));
+            let mut app = App::new(
+                SwitchedProbe(probe.clone()),
+                clock.clone(),
+                Calibration::default(),
+                ReadingInterval::new(Duration::from_secs(20)),
+                Rng::new(1),
+            )
+            .with_pump_config(PumpConfig {
+                cooldown: Duration::ZERO,
+                ..PumpConfig::default()
+            })
+            .with_pump_reading_gate(PumpReadingGate::new(mode, Duration::from_secs(60)));
+            let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+            let mut cycle = || {
+                clock.advance(Duration::from_secs(20));
+                app.run_cycle(&mut sink, &mut flash).unwrap()
+            };
+
+            assert_eq!(cycle().pump_action, Some(PumpAction::Activate));
+            // Runs are still read and stop once wet, long before the run limit
+            probe.set(Some(1500));
+            let stopped = cycle();
+            assert!(stopped.reading.is_some());
+            assert_eq!(stopped.pump_action, Some(PumpAction::Deactivate));
+
+            // Dry again, but still inside the settle delay
+            probe.set(Some(2900));
+            for _ in 0..2 {
+                let held = cycle();
+                assert_eq!(held.pump_action, None);
+                assert_eq!(held.reading.is_some(), mode == GateMode::Discard);
+                assert!(held.escalated.is_empty());
+            }
+            assert_eq!(cycle().pump_action, Some(PumpAction::Activate));
+        }
+    }
+
+    #[test]
+    fn reference_check_corrects_later_readings() {
+        let clock = MockClock::new();
+        // Front-end reads 2% high
+        let probe = Rc::new(Cell::new(Some(2142)));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        );
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let mut raw = || {
+            clock.advance(Duration::from_secs(60));
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            cycle.reading.unwrap().raw
+        };
+        assert_eq!(raw(), 2142);
+
+        let correction = app.apply_reference_check(2089, 2048);
+        assert_eq!(correction.gain_drift_ppm(), 19_627);
+        let mut raw = || {
+            clock.advance(Duration::from_secs(60));
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            cycle.reading.unwrap().raw
+        };
+        assert_eq!(raw(), 2100);
+
+        // A reference far off is a fault, not drift: the correction stays
+        assert_eq!(app.apply_reference_check(900, 2048), correction);
+    }
+
+    #[test]
+    fn reading_flags_follow_app_state() {
+        let clock = MockClock::new();
+        let probe = Rc::new(Cell::new(Some(2100)));
+        let mut app = App::new(
+            SwitchedProbe(probe.clone()),
+            clock.clone(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(60)),
+            Rng::new(1),
+        )
+        .with_warm_up(2);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        let mut flags = || {
+            clock.advance(Duration::from_secs(60));
+            let cycle = app.run_cycle(&mut sink, &mut flash).unwrap();
+            cycle.reading.unwrap().flags
+        };
+
+        assert_eq!(flags(), ReadingFlags::WARMING_UP);
+        // Wetter than the wet calibration point, and still settling
+        probe.set(Some(1100));
+        assert_eq!(flags(), ReadingFlags::WARMING_UP | ReadingFlags::CLIPPED);
+        assert_eq!(flags(), ReadingFlags::CLIPPED);
+        // Dry enough to start the pump, which shows from the next reading
+        probe.set(Some(2900));
+        assert_eq!(flags(), ReadingFlags::empty());
+        assert_eq!(flags(), ReadingFlags::PUMP_ON);
+    }
+
+    #[test]
+    fn readings_are_stamped_in_the_configured_epoch() {
+        let clock = MockClock::new();
+        let timestamps = TimestampConfig::new(TimestampPrecision::Millis, Epoch::Unix);
+        let mut app = app(&clock).with_timestamps(timestamps);
+        let (mut sink, mut flash) = (MemorySink::default(), MemoryFlash::new());
+        clock.advance(Duration::from_millis(2_500));
+        let reading = app
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .reading
+            .unwrap();
+        assert_eq!(
+            reading.emitted_at.value, 2_500,
+            "device time until anchored"
+        );
+
+        app.anchor_unix_time(Duration::from_secs(1_700_000_000));
+        clock.advance(Duration::from_millis(2_250));
+        let reading = app
+            .run_cycle(&mut sink, &mut flash)
+            .unwrap()
+            .reading
+            .unwrap();
+        assert_eq!(reading.emitted_at.value, 1_700_000_002_250);
+        assert_eq!(reading.timestamp, Duration::from_millis(4_750));
+
+        // Notes land on the same timeline as the readings
+        app.handle_command("note repotted".parse().unwrap());
+        app.run_cycle(&mut sink, &mut flash).unwrap();
+        assert_eq!(sink.annotations[0].emitted_at.value, 1_700_000_002_250);
+    }
+
+    #[test]
+    fn config_is_provisioned_and_loaded() {
+        let mut kv = MemoryKv::new();
+        let config = load_config(&mut kv, Duration::from_secs(2)).unwrap();
+        assert_eq!(config.calibration, Calibration::default());
+    }
+
+    #[test]
+    fn invalid_stored_config_falls_back_to_defaults() {
+        let mut kv = MemoryKv::new();
+        load_config(&mut kv, Duration::from_secs(2)).unwrap();
+        // Dry below wet on a dry-high probe
+        kv.set(CALIBRATION_KEY, &Calibration::new(1300, 2800).to_bytes())
+            .unwrap();
+        let config = load_config(&mut kv, Duration::from_secs(2)).unwrap();
+        assert_eq!(config.calibration, Calibration::default());
+        assert!(config.validate().is_ok());
+
+        let inverted = Profile {
+            moisture_low: 70,
+            moisture_high: 30,
+            ..Profile::default()
+        };
+        assert!(Profile::from_bytes(&inverted.to_bytes()).is_err());
+        kv.set(PROFILE_KEY, &inverted.to_bytes()).unwrap();
+        let config = load_config(&mut kv, Duration::from_secs(2)).unwrap();
+        assert_eq!(config.profile, Profile::default());
+    }
+
+    #[test]
+    fn config_dump_shows_the_live_calibration_and_schedule() {
+        let mut kv = MemoryKv::new();
+        let config = load_config(&mut kv, Duration::from_secs(2)).unwrap();
+        let mut app = App::new(
+            MockSoilSensor::with_clock(MockClock::new()),
+            MockClock::new(),
+            Calibration::default(),
+            ReadingInterval::new(Duration::from_secs(2)),
+            Rng::new(1),
+        );
+        assert_eq!(app.config_dump(ConfigFormat::Toml), None);
+
+        app = app.with_config(config).with_schedule(Schedule::new(
+            vec![Window::new(
+                Duration::from_secs(18 * 3600),
+                Duration::from_secs(20 * 3600),
+            )],
+            Duration::ZERO,
+        ));
+        app.set_calibration(Calibration::new(2800, 1300));
+        let dump = app.config_dump(ConfigFormat::Toml).unwrap();
+        assert!(
+            dump.contains("[calibration]\ndry = 2800\nwet = 1300\n"),
+            "{dump}"
+        );
+        assert!(dump.contains("windows = \"18:00-20:00\""), "{dump}");
+        app.handle_command(Command::DumpConfig);
+    }
+
+    #[test]
+    fn command_reader_skips_unknown_lines() {
+        let commands = spawn_command_reader(Cursor::new("pause\nwater\nstatus\n"));
+        let received: Vec<_> = commands.iter().collect();
+        assert_eq!(received, vec![Command::Pause, Command::Status]);
+    }
+}
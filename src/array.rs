@@ -0,0 +1,228 @@
+//! Several probes in one bed, each with its own calibration.
+
+use crate::moisture::{raw_to_moisture_percent, Calibration};
+use crate::sensor::SoilSensor;
+use anyhow::{bail, Result};
+use log::warn;
+
+/// How per-channel moisture percentages combine into one value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    #[default]
+    Mean,
+    /// Middle value (lower middle for an even count); robust to one bad probe
+    Median,
+    /// Driest channel, so watering follows the worst spot
+    Min,
+}
+
+impl Aggregation {
+    fn combine(self, percents: &mut [u8]) -> u8 {
+        match self {
+            Aggregation::Mean => {
+                let sum: u32 = percents.iter().map(|&p| p as u32).sum();
+                (sum / percents.len() as u32) as u8
+            }
+            Aggregation::Median => {
+                percents.sort_unstable();
+                percents[(percents.len() - 1) / 2]
+            }
+            Aggregation::Min => percents.iter().copied().min().unwrap_or(0),
+        }
+    }
+}
+
+/// Per-probe settings of one array channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelConfig {
+    pub calibration: Calibration,
+    /// Insertion depth below the surface, reported with each reading
+    pub depth_cm: Option<u8>,
+}
+
+impl ChannelConfig {
+    pub fn new(calibration: Calibration) -> Self {
+        Self {
+            calibration,
+            depth_cm: None,
+        }
+    }
+
+    pub fn with_depth(mut self, depth_cm: u8) -> Self {
+        self.depth_cm = Some(depth_cm);
+        self
+    }
+}
+
+struct Channel {
+    sensor: Box<dyn SoilSensor + Send>,
+    config: ChannelConfig,
+}
+
+/// One pass over all channels
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayReading {
+    /// Per-channel `(raw, percent)`, `None` where the read failed
+    pub channels: Vec<Option<(u16, u8)>>,
+    /// Per-channel configured depth, in the same order
+    pub depths_cm: Vec<Option<u8>>,
+    /// Aggregate of the channels that read successfully
+    pub moisture_percent: u8,
+}
+
+/// Probes read together; each raw value is converted with its own
+/// calibration before aggregation, since probes are never identical
+pub struct SensorArray {
+    channels: Vec<Channel>,
+    aggregation: Aggregation,
+}
+
+impl SensorArray {
+    pub fn new(aggregation: Aggregation) -> Self {
+        Self {
+            channels: Vec::new(),
+            aggregation,
+        }
+    }
+
+    /// Add a probe with its own calibration
+    pub fn with_channel(
+        self,
+        sensor: impl SoilSensor + Send + 'static,
+        calibration: Calibration,
+    ) -> Self {
+        self.with_configured_channel(sensor, ChannelConfig::new(calibration))
+    }
+
+    /// Add a probe with its calibration and depth
+    pub fn with_configured_channel(
+        mut self,
+        sensor: impl SoilSensor + Send + 'static,
+        config: ChannelConfig,
+    ) -> Self {
+        self.channels.push(Channel {
+            sensor: Box::new(sensor),
+            config,
+        });
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Read every channel; fails only if no channel could be read
+    pub fn read(&mut self, samples: usize) -> Result<ArrayReading> {
+        let channels: Vec<_> = self
+            .channels
+            .iter_mut()
+            .enumerate()
+            .map(|(i, ch)| match ch.sensor.read_averaged(samples) {
+                Ok(raw) => Some((raw, raw_to_moisture_percent(raw, &ch.config.calibration))),
+                Err(e) => {
+                    warn!("Channel {} read failed: {:?}", i, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut percents: Vec<u8> = channels.iter().flatten().map(|&(_, p)| p).collect();
+        if percents.is_empty() {
+            bail!("no channel of the sensor array could be read");
+        }
+        let moisture_percent = self.aggregation.combine(&mut percents);
+        Ok(ArrayReading {
+            channels,
+            depths_cm: self.channels.iter().map(|ch| ch.config.depth_cm).collect(),
+            moisture_percent,
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Aggregation, ChannelConfig, SensorArray};
+    use crate::moisture::Calibration;
+    use crate::sensor::SoilSensor;
+    use anyhow::{bail, Result};
+
+    struct Fixed(u16);
+
+    impl SoilSensor for Fixed {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            Ok(self.0)
+        }
+    }
+
+    struct Broken;
+
+    impl SoilSensor for Broken {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            bail!("no response")
+        }
+    }
+
+    #[test]
+    fn differently_calibrated_probes_agree_on_true_moisture() {
+        // Both probes sit in 50% soil but read very different raw values
+        let mut array = SensorArray::new(Aggregation::Mean)
+            .with_channel(Fixed(2100), Calibration::new(3000, 1200))
+            .with_channel(Fixed(1600), Calibration::new(2400, 800));
+        let reading = array.read(5).unwrap();
+        assert_eq!(reading.channels, vec![Some((2100, 50)), Some((1600, 50))]);
+        assert_eq!(reading.moisture_percent, 50);
+        assert_eq!(reading.depths_cm, vec![None, None]);
+
+        // Averaging raw values under one shared calibration would not give 50%
+        let shared = crate::moisture::raw_to_moisture_percent(1850, &Calibration::new(3000, 1200));
+        assert_ne!(shared, 50);
+    }
+
+    #[test]
+    fn aggregations_skip_failed_channels() {
+        let build = |aggregation| {
+            SensorArray::new(aggregation)
+                .with_channel(Fixed(2100), Calibration::default()) // 50%
+                .with_channel(Broken, Calibration::default())
+                .with_channel(Fixed(2820), Calibration::default()) // 10%
+                .with_channel(Fixed(1560), Calibration::default()) // 80%
+        };
+        assert_eq!(
+            build(Aggregation::Mean).read(1).unwrap().moisture_percent,
+            46
+        );
+        assert_eq!(
+            build(Aggregation::Median).read(1).unwrap().moisture_percent,
+            50
+        );
+        assert_eq!(
+            build(Aggregation::Min).read(1).unwrap().moisture_percent,
+            10
+        );
+
+        let mut dead =
+            SensorArray::new(Aggregation::Mean).with_channel(Broken, Calibration::default());
+        assert!(dead.read(1).is_err());
+    }
+
+    #[test]
+    fn channel_depths_accompany_the_reading() {
+        let mut array = SensorArray::new(Aggregation::Mean)
+            .with_configured_channel(
+                Fixed(2100),
+                ChannelConfig::new(Calibration::default()).with_depth(5),
+            )
+            .with_configured_channel(
+                Broken,
+                ChannelConfig::new(Calibration::default()).with_depth(20),
+            );
+        let reading = array.read(1).unwrap();
+        // Depths stay aligned with channels even where a read failed
+        assert_eq!(reading.depths_cm, vec![Some(5), Some(20)]);
+        assert_eq!(reading.channels[1], None);
+    }
+}
@@ -0,0 +1,151 @@
+//! ESP32 Soil Humidity Sensor - Rust Reference Implementation (demo)
+//!
+//! Scripted 20-reading walkthrough of the shared control cycle against the
+//! simulated sensor, cycling through dry, optimal and wet soil.
+//! For the production-ready C++ version, see: ../soil-sensor-cpp/
+
+use anyhow::Result;
+use esp_idf_svc::log::EspLogger;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use log::{info, warn};
+use soil_sensor_rust::alert::AlertOutput;
+use soil_sensor_rust::app::{load_config, run_demo, spawn_command_reader, App, DEMO_READINGS};
+use soil_sensor_rust::boot::{log_boot_reason, EspResetReason};
+use soil_sensor_rust::calibrate::{run_calibration, CALIBRATION_SAMPLES};
+use soil_sensor_rust::checkpoint::restore_checkpoint;
+use soil_sensor_rust::clock::SystemClock;
+use soil_sensor_rust::histogram::{render_histogram, sample_histogram};
+use soil_sensor_rust::interval::ReadingInterval;
+use soil_sensor_rust::led::NullLed;
+use soil_sensor_rust::moisture::Polarity;
+use soil_sensor_rust::nvs::EspKv;
+use soil_sensor_rust::rng::Rng;
+use soil_sensor_rust::sensor::MockSoilSensor;
+use soil_sensor_rust::sink::ConsoleSink;
+use soil_sensor_rust::startup::startup_sequence;
+use soil_sensor_rust::storage::FsFlash;
+use soil_sensor_rust::webhook::{AlertSink, EspHttpClient};
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+// Demo loop configuration
+const READING_INTERVAL_MS: u64 = 2000; // Read every 2 seconds
+const READING_JITTER_MS: u64 = 50; // +/- jitter so reads don't beat against mains hum
+const CALIBRATION_MODE: bool = false; // Set to true for calibration
+const POLARITY_OVERRIDE: Option<Polarity> = None; // Set if the inferred polarity is wrong
+const FLASH_ROOT: &str = "/spiffs"; // VFS mount point of the data partition
+const NVS_NAMESPACE: &str = "soil"; // NVS namespace for persisted settings
+const ALERT_WEBHOOK_URL: &str = ""; // Webhook alerts are posted to; empty to only log them
+const ALERT_DEDUP_S: u64 = 10 * 60; // Identical alerts within this are posted once
+const ALERT_QUEUE_FILE: &str = "alerts.q"; // Failed posts kept on flash for retry
+
+fn main() -> Result<()> {
+    // Ensure the ESP-IDF patches and logging are set up before anything else
+    esp_idf_sys::link_patches();
+    EspLogger::initialize_default();
+
+    info!("========================================");
+    info!("ESP32 Soil Humidity Sensor (Rust Reference)");
+    info!("Board: AITRIP ESP-WROOM-32 (Simulated)");
+    info!("========================================");
+    info!("");
+    info!("Sensor Pin: GPIO 36 (ADC1_CH0) - Simulated");
+    info!("LED Pin: GPIO 2 - Simulated");
+    info!("Pump Relay Pin: GPIO 4 - Simulated");
+    info!("");
+
+    // Recorded before anything else can fail, and attached to the first reading
+    let boot_reason = log_boot_reason(&EspResetReason);
+
+    // Load persisted settings, writing defaults on a brand-new device
+    let mut settings = EspKv::new(EspDefaultNvsPartition::take()?, NVS_NAMESPACE)?;
+    let config = load_config(&mut settings, Duration::from_millis(READING_INTERVAL_MS))?;
+
+    let clock = SystemClock::new();
+    let mut sensor = MockSoilSensor::with_clock(clock.clone());
+    let mut flash = FsFlash::new(FLASH_ROOT);
+    // History survives resets via checkpoints; stats cover this session only
+    let (history, _) = restore_checkpoint(&flash, soil_sensor_rust::app::HISTORY_CAPACITY);
+    info!("Restored {} readings from checkpoint", history.len());
+
+    // Startup sequence simulation
+    startup_sequence(&mut NullLed, &clock)?;
+
+    if CALIBRATION_MODE {
+        info!("=== CALIBRATION MODE ACTIVE ===");
+        let stdin = std::io::stdin();
+        let wizard = run_calibration(
+            &mut sensor,
+            CALIBRATION_SAMPLES,
+            POLARITY_OVERRIDE,
+            |step| {
+                info!("Calibration: {}, then press Enter", step);
+                let _ = stdin.lock().read_line(&mut String::new());
+            },
+        );
+        match wizard {
+            Ok(cal) => info!("Update DRY_SOIL={} and WET_SOIL={}", cal.dry, cal.wet),
+            Err(e) => warn!("Calibration failed: {:?}", e),
+        }
+        info!("");
+
+        // Raw noise distribution, to judge whether the averaging count is enough
+        match sample_histogram(&mut sensor, 200, 10) {
+            Ok(hist) => {
+                for line in render_histogram(&hist).lines() {
+                    info!("  {}", line);
+                }
+            }
+            Err(e) => warn!("Raw histogram failed: {:?}", e),
+        }
+        info!("");
+    }
+
+    let interval = ReadingInterval::new(config.reading_interval)
+        .with_jitter(Duration::from_millis(READING_JITTER_MS));
+    // SAFETY: esp_random only reads the hardware RNG
+    let rng = Rng::new(unsafe { esp_idf_sys::esp_random() } as u64);
+    // No watering windows in the demo: the pump may run at any time
+    let mut app = App::new(sensor, clock.clone(), config.calibration, interval, rng)
+        .with_config(config.clone())
+        .with_history(history)
+        .with_settings(settings)
+        .with_boot_reason(boot_reason);
+
+    // Serial console commands are read on their own thread so the loop never blocks
+    let commands = spawn_command_reader(BufReader::new(std::io::stdin()));
+
+    let mut console = ConsoleSink::default();
+    console.header();
+    let mut alerts = alert_outputs(&clock)?;
+    run_demo(
+        &mut app,
+        &mut console,
+        &mut flash,
+        &commands,
+        &mut alerts,
+        DEMO_READINGS,
+    )?;
+
+    info!("========================================");
+    info!("Demonstration complete!");
+    info!("For real ESP32 hardware, use: ../soil-sensor-cpp/");
+    info!("========================================");
+
+    Ok(())
+}
+
+/// Alert destinations beyond the log: the webhook, when one is configured
+fn alert_outputs(clock: &SystemClock) -> Result<Vec<Box<dyn AlertOutput>>> {
+    if ALERT_WEBHOOK_URL.is_empty() {
+        return Ok(Vec::new());
+    }
+    let webhook = AlertSink::new(
+        EspHttpClient::new()?,
+        clock.clone(),
+        ALERT_WEBHOOK_URL,
+        Duration::from_secs(ALERT_DEDUP_S),
+    )
+    .with_retry_queue(FsFlash::new(FLASH_ROOT), ALERT_QUEUE_FILE);
+    Ok(vec![Box::new(webhook)])
+}
@@ -0,0 +1,164 @@
+//! ESP32 Soil Humidity Sensor - Rust Reference Implementation (firmware)
+//!
+//! Runs the shared control cycle indefinitely with the configured profile
+//! driving the pump thresholds.
+
+use anyhow::Result;
+#[cfg(feature = "ble")]
+use esp_idf_svc::hal::peripherals::Peripherals;
+use esp_idf_svc::log::EspLogger;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sntp::EspSntp;
+use log::info;
+use soil_sensor_rust::alert::AlertOutput;
+use soil_sensor_rust::app::{
+    load_config, run_firmware, spawn_command_reader, App, HISTORY_CAPACITY,
+};
+#[cfg(feature = "ble")]
+use soil_sensor_rust::ble::{BleExporter, EspGatt};
+use soil_sensor_rust::boot::{log_boot_reason, EspResetReason};
+use soil_sensor_rust::checkpoint::restore_checkpoint;
+use soil_sensor_rust::clock::{SyncedClock, SystemClock};
+use soil_sensor_rust::daily::{load_daily_summaries, load_open_day, DailyRollup};
+use soil_sensor_rust::history::Decimation;
+use soil_sensor_rust::interval::ReadingInterval;
+use soil_sensor_rust::led::NullLed;
+use soil_sensor_rust::maintenance::MaintenanceConfig;
+use soil_sensor_rust::nvs::EspKv;
+use soil_sensor_rust::pump::PumpConfig;
+use soil_sensor_rust::rng::Rng;
+use soil_sensor_rust::sensor::MockSoilSensor;
+#[cfg(feature = "ble")]
+use soil_sensor_rust::sink::Tee;
+use soil_sensor_rust::sink::{Coalescing, ConsoleSink};
+use soil_sensor_rust::startup::startup_sequence;
+use soil_sensor_rust::storage::FsFlash;
+use soil_sensor_rust::uplink::{spawn_uplink, uplink_channel};
+use soil_sensor_rust::webhook::{AlertSink, EspHttpClient};
+use std::io::BufReader;
+use std::time::Duration;
+
+const READING_INTERVAL_MS: u64 = 60_000; // Read once a minute
+const READING_JITTER_MS: u64 = 500; // +/- jitter so reads don't beat against mains hum
+const FLASH_ROOT: &str = "/spiffs"; // VFS mount point of the data partition
+const NVS_NAMESPACE: &str = "soil"; // NVS namespace for persisted settings
+const UPLINK_QUEUE_LEN: usize = 16; // Readings buffered while the uplink is slow
+const CONSOLE_SUMMARY_EVERY: u32 = 60; // Unchanged rows between "still ..." lines (an hour)
+const HISTORY_RECENT: usize = 32; // Newest readings kept at full resolution
+const HISTORY_KEEP_EVERY: usize = 4; // Older readings kept one in this many
+const WARM_UP_READINGS: u32 = 3; // Readings flagged while the probe settles after power-on
+const CYCLE_REPORT_EVERY: u32 = 60; // Cycles between cycle-time log lines (an hour)
+const DAY_BOUNDARY_HOUR: u64 = 6; // Daily summaries run from 06:00 local time
+const UTC_OFFSET_S: i32 = 0; // Local time zone of the watering windows
+const ALERT_WEBHOOK_URL: &str = ""; // Webhook alerts are posted to; empty to only log them
+const ALERT_DEDUP_S: u64 = 10 * 60; // Identical alerts within this are posted once
+const ALERT_GRACE_S: u64 = 5 * 60; // Alerts held after boot while filters settle
+const ALERT_QUEUE_FILE: &str = "alerts.q"; // Failed posts kept on flash for retry
+const TIME_SYNC_POLL: Duration = Duration::from_secs(30); // Checks for SNTP having set the clock
+#[cfg(feature = "ble")]
+const BLE_DEVICE_NAME: &str = "soil-sensor"; // Name the soil service is advertised under
+
+fn main() -> Result<()> {
+    esp_idf_sys::link_patches();
+    EspLogger::initialize_default();
+    info!("ESP32 Soil Humidity Sensor (Rust firmware)");
+
+    let boot_reason = log_boot_reason(&EspResetReason);
+
+    let nvs = EspDefaultNvsPartition::take()?;
+    let mut settings = EspKv::new(nvs.clone(), NVS_NAMESPACE)?;
+    let config = load_config(&mut settings, Duration::from_millis(READING_INTERVAL_MS))?;
+    let profile = &config.profile;
+    let pump = PumpConfig {
+        start_below: profile.moisture_low,
+        // Widened so the sum cannot wrap
+        stop_at: ((u16::from(profile.moisture_low) + u16::from(profile.moisture_high)) / 2) as u8,
+        ..PumpConfig::default()
+    };
+
+    // Watering windows and calendar days wait for SNTP; timers run from boot
+    let clock = SyncedClock::new(SystemClock::new());
+    let _sntp = EspSntp::new_default()?;
+    let sync = clock.clone();
+    std::thread::spawn(move || {
+        while !sync.synchronize_from_system(UTC_OFFSET_S) {
+            std::thread::sleep(TIME_SYNC_POLL);
+        }
+        info!("Clock synchronized, watering windows active");
+    });
+    let mut flash = FsFlash::new(FLASH_ROOT);
+    let (history, _) = restore_checkpoint(&flash, HISTORY_CAPACITY);
+    let history = history.with_decimation(Decimation {
+        recent: HISTORY_RECENT,
+        keep_every: HISTORY_KEEP_EVERY,
+    });
+    info!("Restored {} readings from checkpoint", history.len());
+
+    startup_sequence(&mut NullLed, &clock)?;
+
+    let interval = ReadingInterval::new(config.reading_interval)
+        .with_jitter(Duration::from_millis(READING_JITTER_MS));
+    // SAFETY: esp_random only reads the hardware RNG
+    let rng = Rng::new(unsafe { esp_idf_sys::esp_random() } as u64);
+    // The board has no real probe wired yet, so the simulated sensor stands in
+    let sensor = MockSoilSensor::with_clock(clock.clone());
+    let mut app = App::new(sensor, clock.clone(), config.calibration, interval, rng)
+        .with_config(config.clone())
+        .with_pump_config(pump)
+        .with_history(history)
+        .with_settings(settings)
+        .with_maintenance(MaintenanceConfig::default())
+        .with_warm_up(WARM_UP_READINGS)
+        .with_cycle_timer(CYCLE_REPORT_EVERY)
+        .with_daily_summaries(
+            DailyRollup::new(Duration::from_secs(DAY_BOUNDARY_HOUR * 60 * 60))
+                .with_history(load_daily_summaries(&flash))
+                .with_open_day(load_open_day(&flash)),
+        )
+        .with_boot_reason(boot_reason);
+
+    let commands = spawn_command_reader(BufReader::new(std::io::stdin()));
+    let console = ConsoleSink::default().with_coalescing(Coalescing {
+        summary_every: CONSOLE_SUMMARY_EVERY,
+        ..Coalescing::default()
+    });
+    console.header();
+    // Phones read the same stream over BLE; the controller keeps its bonding keys in NVS
+    #[cfg(feature = "ble")]
+    let console = Tee(
+        console,
+        BleExporter::new(EspGatt::start(
+            Peripherals::take()?.modem,
+            nvs,
+            BLE_DEVICE_NAME,
+        )?),
+    );
+    // Output runs on its own task so a slow link never delays sensing
+    let (mut uplink, readings) = uplink_channel(UPLINK_QUEUE_LEN);
+    spawn_uplink(readings, console)?;
+    let mut alerts = alert_outputs(&clock)?;
+    run_firmware(
+        &mut app,
+        &mut uplink,
+        &mut flash,
+        &commands,
+        &mut alerts,
+        None,
+    )
+}
+
+/// Alert destinations beyond the log: the webhook, when one is configured
+fn alert_outputs(clock: &SyncedClock<SystemClock>) -> Result<Vec<Box<dyn AlertOutput>>> {
+    if ALERT_WEBHOOK_URL.is_empty() {
+        return Ok(Vec::new());
+    }
+    let webhook = AlertSink::new(
+        EspHttpClient::new()?,
+        clock.clone(),
+        ALERT_WEBHOOK_URL,
+        Duration::from_secs(ALERT_DEDUP_S),
+    )
+    .with_retry_queue(FsFlash::new(FLASH_ROOT), ALERT_QUEUE_FILE)
+    .with_startup_grace(Duration::from_secs(ALERT_GRACE_S));
+    Ok(vec![Box::new(webhook)])
+}
@@ -0,0 +1,455 @@
+//! Reading export over a BLE GATT service, for phone checks in the field
+//! without WiFi.
+//!
+//! Moisture, soil status and pump state are notifiable characteristics;
+//! recent history is a read-only characteristic. The value encoding is
+//! plain Rust behind [`GattServer`]; the ESP-IDF backend registers the
+//! service with Bluedroid, advertises it and pushes bytes to the connected
+//! phone.
+
+use crate::moisture::{ConditionTracker, SoilCondition};
+use crate::reading::Reading;
+use crate::sink::ReadingSink;
+#[cfg(target_os = "espidf")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::bt::ble::gap::{AdvConfiguration, BleGapEvent, EspBleGap};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::bt::ble::gatt::server::{ConnectionId, EspGatts, GattsEvent};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::bt::ble::gatt::{
+    AutoResponse, GattCharacteristic, GattDescriptor, GattId, GattInterface, GattServiceId, Handle,
+    Permission, Property,
+};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::bt::{Ble, BtDriver, BtUuid};
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::hal::modem::Modem;
+#[cfg(target_os = "espidf")]
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+#[cfg(target_os = "espidf")]
+use log::{info, warn};
+use std::collections::VecDeque;
+#[cfg(target_os = "espidf")]
+use std::sync::{Arc, Mutex};
+
+/// Primary service UUID (128-bit, little endian on the wire)
+pub const SERVICE_UUID: u128 = 0x5e1f_0000_8c3a_4b7e_9f21_6d2a_b0c4_e901;
+/// Readings kept in the history characteristic; 16 * 5 bytes fits one long read
+pub const BLE_HISTORY_LEN: usize = 16;
+
+/// Characteristics of the soil service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Characteristic {
+    /// Percent (u8) then raw counts (u16 LE); notifiable
+    Moisture,
+    /// [`SoilCondition`] code: 0 dry, 1 optimal, 2 wet; notifiable
+    Status,
+    /// 1 while the pump runs; notifiable
+    Pump,
+    /// Oldest first, `timestamp_s` (u32 LE) then percent (u8) per reading; read only
+    History,
+}
+
+impl Characteristic {
+    pub const ALL: [Characteristic; 4] = [
+        Characteristic::Moisture,
+        Characteristic::Status,
+        Characteristic::Pump,
+        Characteristic::History,
+    ];
+
+    /// Characteristic UUID: the service UUID with the characteristic number in bits 96..112
+    pub fn uuid(self) -> u128 {
+        let n: u128 = match self {
+            Characteristic::Moisture => 1,
+            Characteristic::Status => 2,
+            Characteristic::Pump => 3,
+            Characteristic::History => 4,
+        };
+        SERVICE_UUID | n << 96
+    }
+
+    /// Index into [`ALL`](Self::ALL)
+    fn slot(self) -> usize {
+        self as usize
+    }
+
+    pub fn notifiable(self) -> bool {
+        self != Characteristic::History
+    }
+}
+
+pub fn encode_moisture(reading: &Reading) -> Vec<u8> {
+    let mut value = vec![reading.moisture_percent];
+    value.extend_from_slice(&reading.raw.to_le_bytes());
+    value
+}
+
+pub fn encode_status(condition: SoilCondition) -> Vec<u8> {
+    vec![match condition {
+        SoilCondition::Dry => 0,
+        SoilCondition::Optimal => 1,
+        SoilCondition::Wet => 2,
+    }]
+}
+
+pub fn encode_pump(on: bool) -> Vec<u8> {
+    vec![on as u8]
+}
+
+pub fn encode_history<'a>(readings: impl IntoIterator<Item = &'a Reading>) -> Vec<u8> {
+    let mut value = Vec::new();
+    for reading in readings {
+        let ts = reading.emitted_at.as_secs().min(u32::MAX as u64) as u32;
+        value.extend_from_slice(&ts.to_le_bytes());
+        value.push(reading.moisture_percent);
+    }
+    value
+}
+
+/// Attribute table of the soil service, as seen by the exporter
+pub trait GattServer {
+    /// Replace the stored value read by clients
+    fn set_value(&mut self, characteristic: Characteristic, value: &[u8]) -> Result<()>;
+
+    /// Push the stored value to subscribed clients; a no-op without a connection
+    fn notify(&mut self, characteristic: Characteristic) -> Result<()>;
+}
+
+/// Keeps the soil service up to date from the reading stream; clients are
+/// only notified of values that changed
+pub struct BleExporter<G> {
+    gatt: G,
+    condition: ConditionTracker,
+    recent: VecDeque<Reading>,
+    /// Last value written per characteristic, in [`Characteristic::ALL`] order
+    last: [Option<Vec<u8>>; 4],
+}
+
+impl<G: GattServer> BleExporter<G> {
+    pub fn new(gatt: G) -> Self {
+        Self {
+            gatt,
+            condition: ConditionTracker::default(),
+            recent: VecDeque::with_capacity(BLE_HISTORY_LEN),
+            last: Default::default(),
+        }
+    }
+
+    pub fn gatt(&self) -> &G {
+        &self.gatt
+    }
+
+    fn publish(&mut self, characteristic: Characteristic, value: Vec<u8>) -> Result<()> {
+        let slot = characteristic.slot();
+        if self.last[slot].as_ref() == Some(&value) {
+            return Ok(());
+        }
+        self.gatt.set_value(characteristic, &value)?;
+        if characteristic.notifiable() {
+            self.gatt.notify(characteristic)?;
+        }
+        self.last[slot] = Some(value);
+        Ok(())
+    }
+}
+
+impl<G: GattServer> ReadingSink for BleExporter<G> {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        if self.recent.len() == BLE_HISTORY_LEN {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(reading.clone());
+        let condition = self.condition.update(reading.moisture_percent);
+
+        self.publish(Characteristic::Moisture, encode_moisture(reading))?;
+        self.publish(Characteristic::Status, encode_status(condition))?;
+        self.publish(Characteristic::Pump, encode_pump(reading.pump_on()))?;
+        let history = encode_history(&self.recent);
+        self.publish(Characteristic::History, history)
+    }
+}
+
+/// GATT application id of the soil service
+#[cfg(target_os = "espidf")]
+const APP_ID: u16 = 0;
+/// Service declaration, a declaration and value per characteristic, one CCCD per notifiable one
+#[cfg(target_os = "espidf")]
+const SERVICE_HANDLES: u16 = 1 + 4 * 2 + 3;
+/// Client Characteristic Configuration descriptor, written by phones to subscribe
+#[cfg(target_os = "espidf")]
+const CCCD_UUID: u16 = 0x2902;
+/// Longest value: a full history characteristic
+#[cfg(target_os = "espidf")]
+const MAX_VALUE_LEN: usize = BLE_HISTORY_LEN * 5;
+
+#[cfg(target_os = "espidf")]
+type Gap = EspBleGap<'static, Ble, Arc<BtDriver<'static, Ble>>>;
+#[cfg(target_os = "espidf")]
+type Gatts = EspGatts<'static, Ble, Arc<BtDriver<'static, Ble>>>;
+
+/// State shared between [`EspGatt`] and the Bluedroid event callbacks
+#[cfg(target_os = "espidf")]
+#[derive(Default)]
+struct Link {
+    gatts_if: Option<GattInterface>,
+    service: Option<Handle>,
+    /// Characteristics are added one at a time; index of the next one in [`Characteristic::ALL`]
+    next: usize,
+    /// Attribute handles in [`Characteristic::ALL`] order, once registered
+    handles: [Option<Handle>; 4],
+    /// Latest value per characteristic: the initial value at registration
+    /// and the notification payload
+    values: [Vec<u8>; 4],
+    conn_id: Option<ConnectionId>,
+}
+
+/// Bluedroid backend: registers the soil service, advertises it and tracks
+/// the connected phone
+#[cfg(target_os = "espidf")]
+pub struct EspGatt {
+    gatts: Arc<Gatts>,
+    link: Arc<Mutex<Link>>,
+}
+
+#[cfg(target_os = "espidf")]
+impl EspGatt {
+    /// Bring up the BLE controller and Bluedroid, then register the service
+    /// and advertise as `name`; values set before registration completes are
+    /// used as the initial values
+    pub fn start(modem: Modem, nvs: EspDefaultNvsPartition, name: &'static str) -> Result<Self> {
+        let bt = Arc::new(BtDriver::new(modem, Some(nvs))?);
+        let gap = Arc::new(Gap::new(bt.clone())?);
+        let gatts = Arc::new(Gatts::new(bt)?);
+        let link = Arc::new(Mutex::new(Link::default()));
+
+        let advertiser = gap.clone();
+        gap.subscribe(move |event| {
+            if let BleGapEvent::AdvertisingConfigured(_) = event {
+                if let Err(e) = advertiser.start_advertising() {
+                    warn!("BLE advertising failed: {:?}", e);
+                }
+            }
+        })?;
+        let (server, shared) = (gatts.clone(), link.clone());
+        gatts.subscribe(move |(gatts_if, event)| {
+            let mut link = shared.lock().unwrap();
+            if let Err(e) = on_gatts_event(&gap, &server, &mut link, name, gatts_if, event) {
+                warn!("BLE service setup failed: {:?}", e);
+            }
+        })?;
+        gatts.register_app(APP_ID)?;
+        Ok(Self { gatts, link })
+    }
+}
+
+/// Service registration chain and connection tracking
+#[cfg(target_os = "espidf")]
+fn on_gatts_event(
+    gap: &Gap,
+    gatts: &Gatts,
+    link: &mut Link,
+    name: &str,
+    gatts_if: GattInterface,
+    event: GattsEvent,
+) -> Result<()> {
+    match event {
+        GattsEvent::ServiceRegistered { app_id: APP_ID, .. } => {
+            link.gatts_if = Some(gatts_if);
+            gap.set_device_name(name)?;
+            gap.set_adv_conf(&AdvConfiguration {
+                include_name: true,
+                // General discoverable, BR/EDR not supported
+                flag: 0x06,
+                service_uuid: Some(BtUuid::uuid128(SERVICE_UUID)),
+                ..Default::default()
+            })?;
+            let service = GattServiceId {
+                id: GattId {
+                    uuid: BtUuid::uuid128(SERVICE_UUID),
+                    inst_id: 0,
+                },
+                is_primary: true,
+            };
+            gatts.create_service(gatts_if, &service, SERVICE_HANDLES)?;
+        }
+        GattsEvent::ServiceCreated { service_handle, .. } => {
+            link.service = Some(service_handle);
+            gatts.start_service(service_handle)?;
+            add_next_characteristic(gatts, link)?;
+        }
+        GattsEvent::CharacteristicAdded { attr_handle, .. } => {
+            let characteristic = Characteristic::ALL[link.next - 1];
+            link.handles[characteristic.slot()] = Some(attr_handle);
+            match link.service {
+                Some(service) if characteristic.notifiable() => {
+                    let cccd = GattDescriptor {
+                        uuid: BtUuid::uuid16(CCCD_UUID),
+                        permissions: Permission::Read | Permission::Write,
+                    };
+                    gatts.add_descriptor(service, &cccd)?;
+                }
+                _ => add_next_characteristic(gatts, link)?,
+            }
+        }
+        GattsEvent::DescriptorAdded { .. } => add_next_characteristic(gatts, link)?,
+        GattsEvent::PeerConnected { conn_id, .. } => {
+            info!("BLE client connected");
+            link.conn_id = Some(conn_id);
+        }
+        GattsEvent::PeerDisconnected { .. } => {
+            info!("BLE client disconnected");
+            link.conn_id = None;
+            // Advertising stops on connect
+            gap.start_advertising()?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "espidf")]
+fn add_next_characteristic(gatts: &Gatts, link: &mut Link) -> Result<()> {
+    let (Some(service), Some(&characteristic)) = (link.service, Characteristic::ALL.get(link.next))
+    else {
+        return Ok(());
+    };
+    link.next += 1;
+    let properties = if characteristic.notifiable() {
+        Property::Read | Property::Notify
+    } else {
+        Property::Read.into()
+    };
+    let gatt_characteristic = GattCharacteristic {
+        uuid: BtUuid::uuid128(characteristic.uuid()),
+        permissions: Permission::Read.into(),
+        properties,
+        max_len: MAX_VALUE_LEN,
+        // Reads are answered from the stored value
+        auto_rsp: AutoResponse::ByGatt,
+    };
+    gatts.add_characteristic(
+        service,
+        &gatt_characteristic,
+        &link.values[characteristic.slot()],
+    )?;
+    Ok(())
+}
+
+#[cfg(target_os = "espidf")]
+impl GattServer for EspGatt {
+    fn set_value(&mut self, characteristic: Characteristic, value: &[u8]) -> Result<()> {
+        let slot = characteristic.slot();
+        let handle = {
+            let mut link = self.link.lock().unwrap();
+            link.values[slot] = value.to_vec();
+            link.handles[slot]
+        };
+        if let Some(handle) = handle {
+            self.gatts
+                .set_attr(handle, value)
+                .with_context(|| format!("setting {:?}", characteristic))?;
+        }
+        Ok(())
+    }
+
+    fn notify(&mut self, characteristic: Characteristic) -> Result<()> {
+        let slot = characteristic.slot();
+        let (target, value) = {
+            let link = self.link.lock().unwrap();
+            let target = link.gatts_if.zip(link.conn_id).zip(link.handles[slot]);
+            (target, link.values[slot].clone())
+        };
+        let Some(((gatts_if, conn_id), handle)) = target else {
+            return Ok(());
+        };
+        self.gatts
+            .notify(gatts_if, conn_id, handle, &value)
+            .with_context(|| format!("notifying {:?}", characteristic))?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        encode_history, encode_moisture, encode_pump, encode_status, BleExporter, Characteristic,
+        GattServer, BLE_HISTORY_LEN, SERVICE_UUID,
+    };
+    use crate::moisture::SoilCondition;
+    use crate::reading::Reading;
+    use crate::sink::ReadingSink;
+    use anyhow::Result;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct RecordingGatt {
+        values: Vec<(Characteristic, Vec<u8>)>,
+        notified: Vec<Characteristic>,
+    }
+
+    impl GattServer for RecordingGatt {
+        fn set_value(&mut self, characteristic: Characteristic, value: &[u8]) -> Result<()> {
+            self.values.push((characteristic, value.to_vec()));
+            Ok(())
+        }
+
+        fn notify(&mut self, characteristic: Characteristic) -> Result<()> {
+            self.notified.push(characteristic);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn encodes_characteristic_values() {
+        let reading = Reading::new(Duration::from_secs(300), 2100, 50).with_pump_on(true);
+        assert_eq!(encode_moisture(&reading), vec![50, 0x34, 0x08]);
+        assert_eq!(encode_status(SoilCondition::Dry), vec![0]);
+        assert_eq!(encode_status(SoilCondition::Wet), vec![2]);
+        assert_eq!(encode_pump(reading.pump_on()), vec![1]);
+
+        let older = Reading::new(Duration::from_secs(0x0102_0304), 2900, 5);
+        assert_eq!(
+            encode_history([&older, &reading]),
+            vec![0x04, 0x03, 0x02, 0x01, 5, 0x2c, 0x01, 0, 0, 50]
+        );
+        assert_eq!(
+            Characteristic::Pump.uuid(),
+            SERVICE_UUID | 3 << 96,
+            "characteristics share the service base"
+        );
+    }
+
+    #[test]
+    fn exporter_notifies_only_changed_values() {
+        let mut ble = BleExporter::new(RecordingGatt::default());
+        ble.emit(&Reading::new(Duration::from_secs(1), 2100, 50))
+            .unwrap();
+        assert_eq!(
+            ble.gatt().notified,
+            vec![
+                Characteristic::Moisture,
+                Characteristic::Status,
+                Characteristic::Pump
+            ]
+        );
+
+        // Same moisture and pump state: only the history grows
+        ble.emit(&Reading::new(Duration::from_secs(2), 2100, 50))
+            .unwrap();
+        assert_eq!(ble.gatt().notified.len(), 3);
+        let (last, history) = ble.gatt().values.last().unwrap();
+        assert_eq!(*last, Characteristic::History);
+        assert_eq!(history.len(), 10);
+
+        for i in 0..BLE_HISTORY_LEN as u64 {
+            ble.emit(&Reading::new(Duration::from_secs(10 + i), 2100, 50))
+                .unwrap();
+        }
+        let (_, history) = ble.gatt().values.last().unwrap();
+        assert_eq!(history.len(), BLE_HISTORY_LEN * 5);
+        assert_eq!(history[0], 10, "oldest readings fall out");
+    }
+}
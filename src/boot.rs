@@ -0,0 +1,189 @@
+//! Reset reason reported at startup, for diagnosing field units.
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Why the chip last came out of reset, mapped from `esp_reset_reason_t`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BootReason {
+    Unknown,
+    PowerOn,
+    /// External reset pin
+    External,
+    /// `esp_restart()`
+    Software,
+    /// Exception or panic
+    Panic,
+    InterruptWatchdog,
+    TaskWatchdog,
+    /// Other watchdogs (RTC, MWDT)
+    Watchdog,
+    DeepSleepWake,
+    Brownout,
+    /// Reset over SDIO
+    Sdio,
+    /// Reset by the USB peripheral
+    Usb,
+    /// Reset by JTAG
+    Jtag,
+    /// eFuse error
+    Efuse,
+    /// Power glitch detected
+    PowerGlitch,
+    /// CPU lock up (double exception)
+    CpuLockup,
+    /// Code not known to this firmware
+    Other(u32),
+}
+
+impl BootReason {
+    /// Map a raw `esp_reset_reason_t` value
+    pub fn from_raw(code: u32) -> Self {
+        match code {
+            0 => BootReason::Unknown,
+            1 => BootReason::PowerOn,
+            2 => BootReason::External,
+            3 => BootReason::Software,
+            4 => BootReason::Panic,
+            5 => BootReason::InterruptWatchdog,
+            6 => BootReason::TaskWatchdog,
+            7 => BootReason::Watchdog,
+            8 => BootReason::DeepSleepWake,
+            9 => BootReason::Brownout,
+            10 => BootReason::Sdio,
+            11 => BootReason::Usb,
+            12 => BootReason::Jtag,
+            13 => BootReason::Efuse,
+            14 => BootReason::PowerGlitch,
+            15 => BootReason::CpuLockup,
+            other => BootReason::Other(other),
+        }
+    }
+
+    /// Stable identifier for logs and telemetry
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BootReason::Unknown => "unknown",
+            BootReason::PowerOn => "power_on",
+            BootReason::External => "external",
+            BootReason::Software => "software",
+            BootReason::Panic => "panic",
+            BootReason::InterruptWatchdog => "int_watchdog",
+            BootReason::TaskWatchdog => "task_watchdog",
+            BootReason::Watchdog => "watchdog",
+            BootReason::DeepSleepWake => "deep_sleep_wake",
+            BootReason::Brownout => "brownout",
+            BootReason::Sdio => "sdio",
+            BootReason::Usb => "usb",
+            BootReason::Jtag => "jtag",
+            BootReason::Efuse => "efuse",
+            BootReason::PowerGlitch => "power_glitch",
+            BootReason::CpuLockup => "cpu_lockup",
+            BootReason::Other(_) => "other",
+        }
+    }
+
+    /// Resets that point at a crash, hang or power problem
+    pub fn is_abnormal(&self) -> bool {
+        matches!(
+            self,
+            BootReason::Panic
+                | BootReason::InterruptWatchdog
+                | BootReason::TaskWatchdog
+                | BootReason::Watchdog
+                | BootReason::Brownout
+                | BootReason::PowerGlitch
+                | BootReason::CpuLockup
+        )
+    }
+}
+
+impl fmt::Display for BootReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootReason::Other(code) => write!(f, "other({code})"),
+            reason => f.write_str(reason.as_str()),
+        }
+    }
+}
+
+/// Where the raw reset reason comes from
+pub trait ResetReasonSource {
+    fn raw_reset_reason(&self) -> u32;
+}
+
+/// Fixed reason, for host tests
+#[derive(Debug, Clone, Copy)]
+pub struct FixedResetReason(pub u32);
+
+impl ResetReasonSource for FixedResetReason {
+    fn raw_reset_reason(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Reads and logs the reset reason; abnormal resets are logged as warnings
+pub fn log_boot_reason(source: &dyn ResetReasonSource) -> BootReason {
+    let code = source.raw_reset_reason();
+    let reason = BootReason::from_raw(code);
+    if reason.is_abnormal() {
+        warn!("boot_reason={} code={}", reason, code);
+    } else {
+        info!("boot_reason={} code={}", reason, code);
+    }
+    reason
+}
+
+/// Reset reason from `esp_reset_reason()`
+#[cfg(target_os = "espidf")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EspResetReason;
+
+#[cfg(target_os = "espidf")]
+impl ResetReasonSource for EspResetReason {
+    fn raw_reset_reason(&self) -> u32 {
+        // SAFETY: plain query of a value latched at boot
+        unsafe { esp_idf_sys::esp_reset_reason() as u32 }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{log_boot_reason, BootReason, FixedResetReason};
+
+    #[test]
+    fn maps_each_raw_code() {
+        let expected = [
+            BootReason::Unknown,
+            BootReason::PowerOn,
+            BootReason::External,
+            BootReason::Software,
+            BootReason::Panic,
+            BootReason::InterruptWatchdog,
+            BootReason::TaskWatchdog,
+            BootReason::Watchdog,
+            BootReason::DeepSleepWake,
+            BootReason::Brownout,
+            BootReason::Sdio,
+            BootReason::Usb,
+            BootReason::Jtag,
+            BootReason::Efuse,
+            BootReason::PowerGlitch,
+            BootReason::CpuLockup,
+        ];
+        for (code, reason) in expected.iter().enumerate() {
+            assert_eq!(BootReason::from_raw(code as u32), *reason, "code {code}");
+        }
+        assert_eq!(BootReason::from_raw(42), BootReason::Other(42));
+        assert_eq!(BootReason::from_raw(42).to_string(), "other(42)");
+    }
+
+    #[test]
+    fn injected_source_is_reported() {
+        let reason = log_boot_reason(&FixedResetReason(6));
+        assert_eq!(reason, BootReason::TaskWatchdog);
+        assert!(reason.is_abnormal());
+        assert!(!log_boot_reason(&FixedResetReason(1)).is_abnormal());
+    }
+}
@@ -0,0 +1,238 @@
+//! Per-hour cap on transmitted readings for metered links (cellular, LoRa).
+//!
+//! Part of every hour's budget is held back for readings that matter most:
+//! threshold crossings and readings carrying a fault, safe-mode, rewet or
+//! boot flag. Routine readings share the rest, spaced evenly over the hour
+//! unless moisture moved enough since the last one sent to be worth the slot.
+
+use crate::moisture::{MOISTURE_HIGH, MOISTURE_LOW};
+use crate::reading::{Annotation, Reading};
+use crate::sink::ReadingSink;
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Decides which readings of a stream to transmit under a per-hour cap
+#[derive(Debug, Clone)]
+pub struct TelemetryBudget {
+    per_hour: u32,
+    /// Slots only priority readings may use
+    reserved: u32,
+    /// Change in percentage points that lets a routine reading skip the spacing
+    significant_change: u8,
+    low: u8,
+    high: u8,
+    /// Send times within the last hour, oldest first
+    sent: VecDeque<Duration>,
+    last_sent_moisture: Option<u8>,
+    last_band: Option<i8>,
+}
+
+impl TelemetryBudget {
+    /// At most `per_hour` readings in any rolling hour; a quarter (at least
+    /// one) is reserved for crossings and flagged readings
+    pub fn new(per_hour: u32) -> Self {
+        Self {
+            per_hour,
+            reserved: (per_hour / 4).max(1).min(per_hour),
+            significant_change: 5,
+            low: MOISTURE_LOW,
+            high: MOISTURE_HIGH,
+            sent: VecDeque::new(),
+            last_sent_moisture: None,
+            last_band: None,
+        }
+    }
+
+    /// Thresholds whose crossings are always transmitted
+    pub fn with_thresholds(mut self, low: u8, high: u8) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// Routine readings that moved this far since the last one sent go out
+    /// without waiting for their evenly spaced slot
+    pub fn with_significant_change(mut self, points: u8) -> Self {
+        self.significant_change = points;
+        self
+    }
+
+    /// Readings transmitted in the hour up to the last admitted reading
+    pub fn sent_this_hour(&self) -> u32 {
+        self.sent.len() as u32
+    }
+
+    /// Whether `reading` should be transmitted; admitted readings count
+    /// against the budget
+    pub fn admit(&mut self, reading: &Reading) -> bool {
+        let now = reading.timestamp;
+        while self
+            .sent
+            .front()
+            .is_some_and(|&at| now.saturating_sub(at) >= HOUR)
+        {
+            self.sent.pop_front();
+        }
+
+        let band = self.band(reading.moisture_percent);
+        let crossed = self.last_band.is_some_and(|last| last != band);
+        self.last_band = Some(band);
+        let flagged = reading.fault()
+            || reading.safe_mode()
+            || reading.rewet.is_some()
+            || reading.boot_reason.is_some();
+
+        let used = self.sent_this_hour();
+        let send = if crossed || flagged {
+            used < self.per_hour
+        } else {
+            used < self.per_hour - self.reserved && (self.slot_due(now) || self.moved(reading))
+        };
+        if send {
+            self.sent.push_back(now);
+            self.last_sent_moisture = Some(reading.moisture_percent);
+        }
+        send
+    }
+
+    /// -1 dry, 0 optimal, 1 wet
+    fn band(&self, moisture_percent: u8) -> i8 {
+        if moisture_percent < self.low {
+            -1
+        } else if moisture_percent > self.high {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Enough time passed since the last send for an evenly spaced routine reading
+    fn slot_due(&self, now: Duration) -> bool {
+        let routine = self.per_hour - self.reserved;
+        self.sent.back().map_or(true, |&last| {
+            now.saturating_sub(last) >= HOUR / routine.max(1)
+        })
+    }
+
+    fn moved(&self, reading: &Reading) -> bool {
+        self.last_sent_moisture.map_or(true, |last| {
+            last.abs_diff(reading.moisture_percent) >= self.significant_change
+        })
+    }
+}
+
+/// Sink stage forwarding only the readings a [`TelemetryBudget`] admits
+pub struct BudgetedSink<S> {
+    inner: S,
+    budget: TelemetryBudget,
+    dropped: u32,
+}
+
+impl<S: ReadingSink> BudgetedSink<S> {
+    pub fn new(inner: S, budget: TelemetryBudget) -> Self {
+        Self {
+            inner,
+            budget,
+            dropped: 0,
+        }
+    }
+
+    /// Readings held back to stay within budget
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: ReadingSink> ReadingSink for BudgetedSink<S> {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        if self.budget.admit(reading) {
+            self.inner.emit(reading)
+        } else {
+            self.dropped = self.dropped.saturating_add(1);
+            Ok(())
+        }
+    }
+
+    /// Notes are rare and always forwarded
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        self.inner.annotate(annotation)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{BudgetedSink, TelemetryBudget};
+    use crate::reading::Reading;
+    use crate::sink::{MemorySink, ReadingSink};
+    use std::time::Duration;
+
+    /// One reading every 10s for two hours, dipping below 25% three times an hour
+    fn dense_stream() -> Vec<Reading> {
+        (0..720u64)
+            .map(|i| {
+                let moisture = match i % 120 {
+                    0..=29 => 24,
+                    _ => 30 + (i % 7) as u8,
+                };
+                Reading::new(Duration::from_secs(i * 10), 2000, moisture)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dense_stream_stays_within_budget_and_keeps_crossings() {
+        let mut sink = BudgetedSink::new(
+            MemorySink::default(),
+            TelemetryBudget::new(24).with_significant_change(10),
+        );
+        let stream = dense_stream();
+        for reading in &stream {
+            sink.emit(reading).unwrap();
+        }
+        let sent = &sink.inner().readings;
+        assert_eq!(sent.len() as u32 + sink.dropped(), 720);
+
+        // No rolling hour exceeds the cap
+        for (i, first) in sent.iter().enumerate() {
+            let in_hour = sent[i..]
+                .iter()
+                .take_while(|r| r.timestamp - first.timestamp < Duration::from_secs(3600))
+                .count();
+            assert!(
+                in_hour <= 24,
+                "{in_hour} readings in the hour from {first:?}"
+            );
+        }
+
+        // Every reading where moisture crossed the dry threshold was sent
+        for pair in stream.windows(2) {
+            if (pair[0].moisture_percent < 25) != (pair[1].moisture_percent < 25) {
+                assert!(sent.contains(&pair[1]), "crossing at {:?} dropped", pair[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn flagged_readings_use_the_reserve() {
+        let mut budget = TelemetryBudget::new(4);
+        let at = |s: u64| Reading::new(Duration::from_secs(s), 2000, 50);
+        assert!(budget.admit(&at(0)));
+        assert!(budget.admit(&Reading::new(Duration::from_secs(1), 2000, 60)));
+        assert!(budget.admit(&Reading::new(Duration::from_secs(2), 2000, 70)));
+        // Routine share (3 of 4) is spent
+        assert!(!budget.admit(&Reading::new(Duration::from_secs(3), 2000, 40)));
+        assert!(budget.admit(&at(4).with_fault(true)));
+        assert!(!budget.admit(&at(5).with_fault(true)));
+        assert_eq!(budget.sent_this_hour(), 4);
+
+        // An hour after the first send its slot frees up again
+        assert!(budget.admit(&at(3600).with_fault(true)));
+    }
+}
@@ -0,0 +1,384 @@
+//! Two-point calibration wizard: one raw capture in dry soil, one in wet.
+//!
+//! The probe's [`Polarity`] is inferred from which capture is larger (dry
+//! reads high on typical probes). An override wins when the inference is
+//! wrong, e.g. because the probe went into the wet pot first; the captures
+//! are then treated as swapped.
+//!
+//! [`AutoCalibrator`] adjusts the points in the field instead: readings past
+//! the calibrated range propose a widened calibration, which only replaces
+//! the active one after a validation window.
+
+use crate::moisture::{raw_to_moisture_unclamped, Calibration, Polarity};
+use crate::sensor::SoilSensor;
+use anyhow::{ensure, Context, Result};
+use log::{info, warn};
+use std::fmt;
+
+/// Conversions averaged into each capture
+pub const CALIBRATION_SAMPLES: usize = 64;
+/// Captures closer than this cannot tell dry from wet reliably
+pub const MIN_CALIBRATION_SPAN: u16 = 200;
+
+/// Readings a candidate calibration is validated over by default
+pub const VALIDATION_WINDOW: u32 = 30;
+/// Counts past the calibrated range before a wider calibration is proposed
+pub const PROPOSAL_MARGIN: u16 = 50;
+
+/// Point the wizard is about to capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationStep {
+    Dry,
+    Wet,
+}
+
+impl fmt::Display for CalibrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CalibrationStep::Dry => write!(f, "place the probe in DRY soil"),
+            CalibrationStep::Wet => write!(f, "place the probe in WET soil"),
+        }
+    }
+}
+
+/// Capture the dry then the wet point; `ready` is called before each
+/// capture and returns once the probe is in place
+pub fn run_calibration(
+    sensor: &mut dyn SoilSensor,
+    samples: usize,
+    polarity: Option<Polarity>,
+    mut ready: impl FnMut(CalibrationStep),
+) -> Result<Calibration> {
+    let mut capture = |step: CalibrationStep| {
+        ready(step);
+        let raw = sensor
+            .read_averaged(samples)
+            .with_context(|| format!("capturing {step:?} point"))?;
+        info!("Captured {:?} point: raw {}", step, raw);
+        Ok::<u16, anyhow::Error>(raw)
+    };
+    let dry = capture(CalibrationStep::Dry)?;
+    let wet = capture(CalibrationStep::Wet)?;
+    ensure!(
+        dry.abs_diff(wet) >= MIN_CALIBRATION_SPAN,
+        "dry capture {} and wet capture {} are less than {} apart",
+        dry,
+        wet,
+        MIN_CALIBRATION_SPAN
+    );
+
+    let inferred = Polarity::infer(dry, wet);
+    info!(
+        "Inferred polarity: {} (dry capture {}, wet capture {})",
+        inferred, dry, wet
+    );
+    let cal = match polarity {
+        Some(forced) if forced != inferred => {
+            warn!(
+                "Polarity overridden to {}; treating the captures as swapped",
+                forced
+            );
+            Calibration::new(wet, dry).with_polarity(forced)
+        }
+        _ => Calibration::new(dry, wet).with_polarity(inferred),
+    };
+    info!(
+        "Calibration: dry {} wet {} ({})",
+        cal.dry, cal.wet, cal.polarity
+    );
+    Ok(cal)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoCalibrationConfig {
+    /// Readings a candidate must convert cleanly before it is committed
+    pub window: u32,
+    /// Counts past the active dry or wet point that trigger a proposal
+    pub margin: u16,
+}
+
+impl Default for AutoCalibrationConfig {
+    fn default() -> Self {
+        Self {
+            window: VALIDATION_WINDOW,
+            margin: PROPOSAL_MARGIN,
+        }
+    }
+}
+
+/// Why a candidate calibration was discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// `raw` converted outside 0..=100%
+    Clipped { raw: u16 },
+    /// Moving to `raw` changed moisture the opposite way to the active calibration
+    NotMonotonic { raw: u16 },
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::Clipped { raw } => write!(f, "raw {} falls outside 0-100%", raw),
+            Rejection::NotMonotonic { raw } => {
+                write!(f, "raw {} moves moisture the wrong way", raw)
+            }
+        }
+    }
+}
+
+/// End of a validation window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validation {
+    /// Every reading converted cleanly; the caller switches to and persists it
+    Committed(Calibration),
+    Discarded(Calibration, Rejection),
+}
+
+/// Candidate held back while it is validated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Candidate {
+    calibration: Calibration,
+    validated: u32,
+    last_raw: Option<u16>,
+}
+
+/// Proposes calibrations from readings past the calibrated range and
+/// validates each over a window of readings before committing it
+#[derive(Debug, Clone, Default)]
+pub struct AutoCalibrator {
+    config: AutoCalibrationConfig,
+    candidate: Option<Candidate>,
+}
+
+impl AutoCalibrator {
+    pub fn new(config: AutoCalibrationConfig) -> Self {
+        Self {
+            config,
+            candidate: None,
+        }
+    }
+
+    /// Calibration under validation, if any
+    pub fn candidate(&self) -> Option<&Calibration> {
+        self.candidate.as_ref().map(|c| &c.calibration)
+    }
+
+    /// Hold `calibration` as the candidate, replacing any under validation
+    pub fn propose(&mut self, calibration: Calibration) {
+        info!(
+            "Calibration candidate: dry {} wet {}, validating over {} readings",
+            calibration.dry, calibration.wet, self.config.window
+        );
+        self.candidate = Some(Candidate {
+            calibration,
+            validated: 0,
+            last_raw: None,
+        });
+    }
+
+    /// Feed one raw reading taken under `active`. Without a candidate,
+    /// readings past the active points propose a widened calibration; with
+    /// one, the reading is checked against it and the window's verdict is
+    /// returned once known
+    pub fn observe(&mut self, raw: u16, active: &Calibration) -> Option<Validation> {
+        let Some(candidate) = &mut self.candidate else {
+            if let Some(widened) = widen(active, raw, self.config.margin) {
+                self.propose(widened);
+            }
+            return None;
+        };
+        let calibration = candidate.calibration;
+        let rejection = if !(0..=100).contains(&raw_to_moisture_unclamped(raw, &calibration)) {
+            Some(Rejection::Clipped { raw })
+        } else if candidate
+            .last_raw
+            .is_some_and(|last| !same_direction(last, raw, active, &calibration))
+        {
+            Some(Rejection::NotMonotonic { raw })
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            warn!("Calibration candidate discarded: {}", rejection);
+            self.candidate = None;
+            return Some(Validation::Discarded(calibration, rejection));
+        }
+        candidate.last_raw = Some(raw);
+        candidate.validated += 1;
+        if candidate.validated < self.config.window {
+            return None;
+        }
+        info!(
+            "Calibration committed: dry {} wet {}",
+            calibration.dry, calibration.wet
+        );
+        self.candidate = None;
+        Some(Validation::Committed(calibration))
+    }
+}
+
+/// `active` stretched to reach `raw`, if `raw` lies more than `margin` past
+/// its dry or wet point
+fn widen(active: &Calibration, raw: u16, margin: u16) -> Option<Calibration> {
+    let mut widened = *active;
+    let (low, high) = match active.polarity {
+        Polarity::DryHigh => (&mut widened.wet, &mut widened.dry),
+        Polarity::WetHigh => (&mut widened.dry, &mut widened.wet),
+    };
+    if raw < low.saturating_sub(margin) {
+        *low = raw;
+    } else if raw > high.saturating_add(margin) {
+        *high = raw;
+    } else {
+        return None;
+    }
+    Some(widened)
+}
+
+/// Both calibrations move moisture the same way (or not at all) from `from` to `to`
+fn same_direction(from: u16, to: u16, active: &Calibration, candidate: &Calibration) -> bool {
+    let step = |cal: &Calibration| {
+        (raw_to_moisture_unclamped(to, cal) - raw_to_moisture_unclamped(from, cal)).signum()
+    };
+    step(active) * step(candidate) >= 0
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        run_calibration, AutoCalibrationConfig, AutoCalibrator, CalibrationStep, Rejection,
+        Validation,
+    };
+    use crate::moisture::{raw_to_moisture_percent, Calibration, Polarity};
+    use crate::sensor::SoilSensor;
+    use anyhow::Result;
+    use std::cell::Cell;
+
+    /// Reads the dry or wet value depending on the step last announced
+    struct Scripted<'a> {
+        dry: u16,
+        wet: u16,
+        step: &'a Cell<CalibrationStep>,
+    }
+
+    impl SoilSensor for Scripted<'_> {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            Ok(match self.step.get() {
+                CalibrationStep::Dry => self.dry,
+                CalibrationStep::Wet => self.wet,
+            })
+        }
+    }
+
+    fn calibrate(dry: u16, wet: u16, polarity: Option<Polarity>) -> Result<Calibration> {
+        let step = Cell::new(CalibrationStep::Dry);
+        let mut sensor = Scripted {
+            dry,
+            wet,
+            step: &step,
+        };
+        let mut announced = Vec::new();
+        let cal = run_calibration(&mut sensor, 8, polarity, |s| {
+            announced.push(s);
+            step.set(s);
+        });
+        assert_eq!(announced, vec![CalibrationStep::Dry, CalibrationStep::Wet]);
+        cal
+    }
+
+    #[test]
+    fn higher_dry_capture_infers_dry_high() {
+        let cal = calibrate(3000, 1200, None).unwrap();
+        assert_eq!(cal.polarity, Polarity::DryHigh);
+        assert_eq!((cal.dry, cal.wet), (3000, 1200));
+        assert_eq!(raw_to_moisture_percent(1200, &cal), 100);
+    }
+
+    #[test]
+    fn higher_wet_capture_infers_wet_high() {
+        let cal = calibrate(900, 2700, None).unwrap();
+        assert_eq!(cal.polarity, Polarity::WetHigh);
+        assert_eq!((cal.dry, cal.wet), (900, 2700));
+        assert_eq!(raw_to_moisture_percent(900, &cal), 0);
+        assert_eq!(raw_to_moisture_percent(2700, &cal), 100);
+    }
+
+    #[test]
+    fn override_swaps_captures_taken_in_the_wrong_pots() {
+        // Probe went into the wet pot first but is known to read high when dry
+        let cal = calibrate(1200, 3000, Some(Polarity::DryHigh)).unwrap();
+        assert_eq!(cal.polarity, Polarity::DryHigh);
+        assert_eq!((cal.dry, cal.wet), (3000, 1200));
+
+        // An override agreeing with the captures changes nothing
+        let cal = calibrate(900, 2700, Some(Polarity::WetHigh)).unwrap();
+        assert_eq!(
+            (cal.dry, cal.wet, cal.polarity),
+            (900, 2700, Polarity::WetHigh)
+        );
+    }
+
+    #[test]
+    fn captures_too_close_together_are_rejected() {
+        assert!(calibrate(2000, 1900, None).is_err());
+    }
+
+    fn validating(window: u32) -> AutoCalibrator {
+        AutoCalibrator::new(AutoCalibrationConfig { window, margin: 50 })
+    }
+
+    #[test]
+    fn clean_candidate_commits_after_the_window() {
+        let active = Calibration::new(3000, 1500);
+        let mut auto = validating(3);
+        // Within the margin nothing is proposed
+        assert_eq!(auto.observe(1460, &active), None);
+        assert_eq!(auto.candidate(), None);
+
+        // Wetter than the wet point by more than the margin
+        assert_eq!(auto.observe(1300, &active), None);
+        let candidate = *auto.candidate().unwrap();
+        assert_eq!((candidate.dry, candidate.wet), (3000, 1300));
+
+        assert_eq!(auto.observe(1350, &active), None);
+        assert_eq!(auto.observe(2000, &active), None);
+        assert_eq!(
+            auto.observe(2900, &active),
+            Some(Validation::Committed(candidate))
+        );
+        assert_eq!(auto.candidate(), None);
+    }
+
+    #[test]
+    fn clipping_candidate_is_discarded() {
+        let active = Calibration::new(3000, 1500);
+        let mut auto = validating(5);
+        let narrow = Calibration::new(2600, 1800);
+        auto.propose(narrow);
+        assert_eq!(auto.observe(2200, &active), None);
+        assert_eq!(
+            auto.observe(1600, &active),
+            Some(Validation::Discarded(
+                narrow,
+                Rejection::Clipped { raw: 1600 }
+            ))
+        );
+        assert_eq!(auto.candidate(), None);
+    }
+
+    #[test]
+    fn reversed_candidate_is_discarded() {
+        let active = Calibration::new(3000, 1500);
+        let mut auto = validating(5);
+        let reversed = Calibration::new(1500, 3000).with_polarity(Polarity::WetHigh);
+        auto.propose(reversed);
+        assert_eq!(auto.observe(2000, &active), None);
+        assert_eq!(
+            auto.observe(2500, &active),
+            Some(Validation::Discarded(
+                reversed,
+                Rejection::NotMonotonic { raw: 2500 }
+            ))
+        );
+    }
+}
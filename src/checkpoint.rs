@@ -0,0 +1,186 @@
+//! Periodic persistence of in-RAM history and stats to limit flash wear.
+
+use crate::clock::Clock;
+use crate::history::History;
+use crate::stats::Stats;
+use crate::storage::FlashStore;
+use anyhow::{Context, Result};
+use log::warn;
+use std::time::Duration;
+
+/// Flash file holding the checkpointed history region
+pub const HISTORY_CHECKPOINT_FILE: &str = "history.bin";
+/// Flash file holding the checkpointed session stats
+pub const STATS_CHECKPOINT_FILE: &str = "stats.bin";
+
+/// Writes history and stats to flash at most once per `interval`, plus on demand
+pub struct Checkpointer<C> {
+    clock: C,
+    interval: Duration,
+    last: Duration,
+    checkpoints: u32,
+}
+
+impl<C: Clock> Checkpointer<C> {
+    /// First periodic checkpoint is due `interval` from now
+    pub fn new(clock: C, interval: Duration) -> Self {
+        let last = clock.now();
+        Self {
+            clock,
+            interval,
+            last,
+            checkpoints: 0,
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.clock.now().saturating_sub(self.last) >= self.interval
+    }
+
+    /// Checkpoint if the interval has elapsed; returns whether anything was written
+    pub fn maybe_checkpoint(
+        &mut self,
+        history: &History,
+        stats: &Stats,
+        flash: &mut dyn FlashStore,
+    ) -> Result<bool> {
+        if !self.is_due() {
+            return Ok(false);
+        }
+        self.force(history, stats, flash)?;
+        Ok(true)
+    }
+
+    /// Checkpoint now regardless of the interval, e.g. on graceful shutdown.
+    /// A failed write leaves the checkpoint due so the next call retries.
+    pub fn force(
+        &mut self,
+        history: &History,
+        stats: &Stats,
+        flash: &mut dyn FlashStore,
+    ) -> Result<()> {
+        let mut region = vec![0u8; History::region_len(history.len())];
+        history
+            .to_region(&mut region)
+            .context("serializing history")?;
+        flash.write_file(HISTORY_CHECKPOINT_FILE, &region)?;
+        flash.write_file(STATS_CHECKPOINT_FILE, &stats.to_bytes())?;
+        self.last = self.clock.now();
+        self.checkpoints = self.checkpoints.saturating_add(1);
+        Ok(())
+    }
+
+    /// Successful checkpoints so far
+    pub fn checkpoint_count(&self) -> u32 {
+        self.checkpoints
+    }
+}
+
+/// Load the last checkpoint, starting fresh for anything missing or damaged
+pub fn restore_checkpoint(flash: &dyn FlashStore, capacity: usize) -> (History, Stats) {
+    let history = match flash.read_file(HISTORY_CHECKPOINT_FILE) {
+        Ok(Some(region)) => History::from_region(&region, capacity).unwrap_or_else(|e| {
+            warn!("Discarding history checkpoint: {}", e);
+            History::new(capacity)
+        }),
+        Ok(None) => History::new(capacity),
+        Err(e) => {
+            warn!("Could not read history checkpoint: {:?}", e);
+            History::new(capacity)
+        }
+    };
+    let stats = match flash.read_file(STATS_CHECKPOINT_FILE) {
+        Ok(Some(bytes)) => Stats::from_bytes(&bytes).unwrap_or_else(|e| {
+            warn!("Discarding stats checkpoint: {:?}", e);
+            Stats::new()
+        }),
+        Ok(None) => Stats::new(),
+        Err(e) => {
+            warn!("Could not read stats checkpoint: {:?}", e);
+            Stats::new()
+        }
+    };
+    (history, stats)
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{restore_checkpoint, Checkpointer};
+    use crate::clock::MockClock;
+    use crate::history::{History, HistoryEntry};
+    use crate::stats::Stats;
+    use crate::storage::MemoryFlash;
+    use std::time::Duration;
+
+    fn minutes(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    #[test]
+    fn checkpoints_at_configured_cadence() {
+        let clock = MockClock::new();
+        let mut flash = MemoryFlash::new();
+        let mut checkpointer = Checkpointer::new(clock.clone(), minutes(10));
+        let mut history = History::new(16);
+        let mut stats = Stats::new();
+
+        // One reading a minute for 35 minutes
+        let mut written_at = Vec::new();
+        for minute in 1..=35 {
+            clock.advance(minutes(1));
+            history.push(HistoryEntry {
+                timestamp_s: minute * 60,
+                raw: 2000,
+                moisture_percent: 50,
+                temperature_tenths_c: None,
+            });
+            stats.record(50);
+            if checkpointer
+                .maybe_checkpoint(&history, &stats, &mut flash)
+                .unwrap()
+            {
+                written_at.push(minute);
+            }
+        }
+        assert_eq!(written_at, vec![10, 20, 30]);
+
+        let (restored_history, restored_stats) = restore_checkpoint(&flash, 16);
+        assert_eq!(restored_stats.count(), 30);
+        assert_eq!(restored_history.len(), 16);
+    }
+
+    #[test]
+    fn forced_checkpoint_writes_immediately_and_restarts_interval() {
+        let clock = MockClock::new();
+        let mut flash = MemoryFlash::new();
+        let mut checkpointer = Checkpointer::new(clock.clone(), minutes(10));
+        let mut stats = Stats::new();
+        stats.record(42);
+
+        clock.advance(minutes(3));
+        checkpointer
+            .force(&History::new(4), &stats, &mut flash)
+            .unwrap();
+        assert_eq!(checkpointer.checkpoint_count(), 1);
+        assert_eq!(restore_checkpoint(&flash, 4).1, stats);
+
+        clock.advance(minutes(9));
+        assert!(!checkpointer.is_due());
+        clock.advance(minutes(1));
+        assert!(checkpointer.is_due());
+    }
+
+    #[test]
+    fn failed_write_stays_due() {
+        let clock = MockClock::new();
+        let mut flash = MemoryFlash::new();
+        flash.fail_writes = true;
+        let mut checkpointer = Checkpointer::new(clock.clone(), minutes(10));
+        clock.advance(minutes(10));
+        assert!(checkpointer
+            .maybe_checkpoint(&History::new(4), &Stats::new(), &mut flash)
+            .is_err());
+        assert!(checkpointer.is_due());
+        assert_eq!(checkpointer.checkpoint_count(), 0);
+    }
+}
@@ -0,0 +1,267 @@
+//! User-configurable moisture bands mapping a percentage to a soil status.
+
+use crate::moisture::{MOISTURE_HIGH, MOISTURE_LOW};
+use std::fmt;
+
+/// Inclusive span of moisture percentages sharing one status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Band {
+    pub min: u8,
+    pub max: u8,
+    pub label: String,
+    /// Whether the status LED is lit in this band
+    pub led: bool,
+}
+
+impl Band {
+    fn contains(&self, percent: u8) -> bool {
+        (self.min..=self.max).contains(&percent)
+    }
+}
+
+/// One way a set of bands fails to tile 0–100%
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BandError {
+    /// `min` is above `max`, so the band matches nothing
+    Inverted { label: String, min: u8, max: u8 },
+    /// Two bands both claim `from..=to`
+    Overlap {
+        first: String,
+        second: String,
+        from: u8,
+        to: u8,
+    },
+    /// No band covers `from..=to`
+    Gap { from: u8, to: u8 },
+    /// `max` is above 100%
+    AboveHundred { label: String, max: u8 },
+}
+
+impl fmt::Display for BandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BandError::Inverted { label, min, max } => {
+                write!(f, "band '{label}' is inverted ({min}%..={max}%)")
+            }
+            BandError::Overlap {
+                first,
+                second,
+                from,
+                to,
+            } => write!(
+                f,
+                "bands '{first}' and '{second}' overlap at {from}%..={to}%"
+            ),
+            BandError::Gap { from, to } => write!(f, "no band covers {from}%..={to}%"),
+            BandError::AboveHundred { label, max } => {
+                write!(f, "band '{label}' extends to {max}%, past 100%")
+            }
+        }
+    }
+}
+
+/// Every problem found while building a [`Classifier`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassifierError {
+    pub problems: Vec<BandError>,
+}
+
+impl fmt::Display for ClassifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid moisture bands: ")?;
+        for (i, problem) in self.problems.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ClassifierError {}
+
+/// Bands collected before validation
+#[derive(Debug, Clone, Default)]
+pub struct ClassifierBuilder {
+    bands: Vec<Band>,
+}
+
+impl ClassifierBuilder {
+    /// Add a band covering `min..=max` percent
+    pub fn band(mut self, min: u8, max: u8, label: impl Into<String>, led: bool) -> Self {
+        self.bands.push(Band {
+            min,
+            max,
+            label: label.into(),
+            led,
+        });
+        self
+    }
+
+    /// Check the bands cover 0–100% exactly once; all problems are reported
+    pub fn build(self) -> Result<Classifier, ClassifierError> {
+        let mut problems = Vec::new();
+        for band in &self.bands {
+            if band.min > band.max {
+                problems.push(BandError::Inverted {
+                    label: band.label.clone(),
+                    min: band.min,
+                    max: band.max,
+                });
+            }
+            if band.max > 100 {
+                problems.push(BandError::AboveHundred {
+                    label: band.label.clone(),
+                    max: band.max,
+                });
+            }
+        }
+
+        for (i, first) in self.bands.iter().enumerate() {
+            for second in &self.bands[i + 1..] {
+                let from = first.min.max(second.min);
+                let to = first.max.min(second.max);
+                if from <= to {
+                    problems.push(BandError::Overlap {
+                        first: first.label.clone(),
+                        second: second.label.clone(),
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+
+        let mut gap_start = None;
+        for percent in 0..=100u8 {
+            let covered = self.bands.iter().any(|b| b.contains(percent));
+            match (covered, gap_start) {
+                (false, None) => gap_start = Some(percent),
+                (true, Some(from)) => {
+                    problems.push(BandError::Gap {
+                        from,
+                        to: percent - 1,
+                    });
+                    gap_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(from) = gap_start {
+            problems.push(BandError::Gap { from, to: 100 });
+        }
+
+        if problems.is_empty() {
+            Ok(Classifier { bands: self.bands })
+        } else {
+            Err(ClassifierError { problems })
+        }
+    }
+}
+
+/// Validated bands covering every percentage exactly once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Classifier {
+    bands: Vec<Band>,
+}
+
+impl Classifier {
+    pub fn builder() -> ClassifierBuilder {
+        ClassifierBuilder::default()
+    }
+
+    /// Band for `moisture_percent`; values above 100 use the top band
+    pub fn classify(&self, moisture_percent: u8) -> &Band {
+        let percent = moisture_percent.min(100);
+        self.bands
+            .iter()
+            .find(|b| b.contains(percent))
+            .expect("validated bands cover 0-100")
+    }
+
+    pub fn bands(&self) -> &[Band] {
+        &self.bands
+    }
+}
+
+impl Default for Classifier {
+    /// Dry / optimal / wet zones matching [`crate::moisture::get_soil_condition`]
+    fn default() -> Self {
+        Classifier::builder()
+            .band(0, MOISTURE_LOW - 1, "DRY - Need Water!", true)
+            .band(MOISTURE_LOW, MOISTURE_HIGH, "OPTIMAL", false)
+            .band(MOISTURE_HIGH + 1, 100, "WET - Too Much Water!", false)
+            .build()
+            .expect("default bands are valid")
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{BandError, Classifier};
+    use crate::moisture::get_soil_condition;
+
+    #[test]
+    fn default_zones_validate_and_match_soil_condition() {
+        let classifier = Classifier::default();
+        assert_eq!(classifier.bands().len(), 3);
+        for percent in 0..=100 {
+            let band = classifier.classify(percent);
+            assert_eq!(
+                (band.label.as_str(), band.led),
+                get_soil_condition(percent),
+                "{percent}%"
+            );
+        }
+    }
+
+    #[test]
+    fn overlapping_bands_are_listed() {
+        let err = Classifier::builder()
+            .band(0, 30, "dry", true)
+            .band(25, 75, "ok", false)
+            .band(70, 100, "wet", false)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.problems,
+            vec![
+                BandError::Overlap {
+                    first: "dry".into(),
+                    second: "ok".into(),
+                    from: 25,
+                    to: 30
+                },
+                BandError::Overlap {
+                    first: "ok".into(),
+                    second: "wet".into(),
+                    from: 70,
+                    to: 75
+                },
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid moisture bands: bands 'dry' and 'ok' overlap at 25%..=30%; \
+             bands 'ok' and 'wet' overlap at 70%..=75%"
+        );
+    }
+
+    #[test]
+    fn gaps_are_listed_including_the_ends() {
+        let err = Classifier::builder()
+            .band(5, 24, "dry", true)
+            .band(30, 90, "ok", false)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.problems,
+            vec![
+                BandError::Gap { from: 0, to: 4 },
+                BandError::Gap { from: 25, to: 29 },
+                BandError::Gap { from: 91, to: 100 },
+            ]
+        );
+    }
+}
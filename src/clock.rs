@@ -0,0 +1,221 @@
+//! Injectable time source so time-dependent logic can run against simulated time.
+
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// System times before this (2024-01-01) mean nothing has set the clock yet
+const MIN_PLAUSIBLE_UNIX: Duration = Duration::from_secs(1_704_067_200);
+
+/// Monotonic time source, optionally anchored to local time
+pub trait Clock {
+    /// Time elapsed since the clock's epoch (boot for the system clock);
+    /// never jumps, so timers can rely on it
+    fn now(&self) -> Duration;
+
+    /// Block for `duration`; simulated clocks just advance
+    fn sleep(&self, duration: Duration);
+
+    /// Time since local midnight of day zero, once a time source has set it;
+    /// drives watering windows and calendar days, never timers
+    fn local_time(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether [`local_time`](Self::local_time) is known, so watering windows
+    /// mean something
+    fn is_synced(&self) -> bool {
+        self.local_time().is_some()
+    }
+}
+
+/// Current Unix time from the system clock, once something (SNTP, an RTC)
+/// has set it
+pub fn system_unix_time() -> Option<Duration> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .filter(|&t| t >= MIN_PLAUSIBLE_UNIX)
+}
+
+/// Real monotonic clock backed by `Instant`; clones share the same epoch
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Manually advanced clock; clones share the same time so a test can keep a
+/// handle while the code under test owns another
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now_ms: Arc<AtomicU64>,
+    unsynced: Arc<AtomicBool>,
+}
+
+impl MockClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move simulated time forward
+    pub fn advance(&self, by: Duration) {
+        self.now_ms
+            .fetch_add(by.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Jump to an absolute simulated time
+    pub fn set(&self, now: Duration) {
+        self.now_ms.store(now.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Simulate a clock that has (or hasn't) been set from a time source
+    pub fn set_synced(&self, synced: bool) {
+        self.unsynced.store(!synced, Ordering::Relaxed);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Duration {
+        Duration::from_millis(self.now_ms.load(Ordering::Relaxed))
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    /// Simulated time doubles as local time once synced
+    fn local_time(&self) -> Option<Duration> {
+        (!self.unsynced.load(Ordering::Relaxed)).then(|| self.now())
+    }
+}
+
+/// Wraps a monotonic clock and anchors it to local time once an external
+/// source (SNTP, RTC) reports it; unsynced until then. Clones share the
+/// anchor. Only [`local_time`](Clock::local_time) moves on a sync; `now`
+/// keeps counting from boot so running timers never see the jump.
+#[derive(Debug, Clone)]
+pub struct SyncedClock<C> {
+    inner: C,
+    /// Local time minus `inner.now()`, in milliseconds
+    offset_ms: Arc<AtomicI64>,
+    synced: Arc<AtomicBool>,
+}
+
+impl<C: Clock> SyncedClock<C> {
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            offset_ms: Arc::new(AtomicI64::new(0)),
+            synced: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Anchor to `local_now`, the time since local midnight of the schedule's day zero
+    pub fn synchronize(&self, local_now: Duration) {
+        let offset = local_now.as_millis() as i64 - self.inner.now().as_millis() as i64;
+        self.offset_ms.store(offset, Ordering::Relaxed);
+        self.synced.store(true, Ordering::Relaxed);
+    }
+
+    /// Anchor to the system clock if it has been set, shifting UTC by
+    /// `utc_offset_s` for local time; true once synced
+    pub fn synchronize_from_system(&self, utc_offset_s: i32) -> bool {
+        let Some(unix) = system_unix_time() else {
+            return false;
+        };
+        let local_s = (unix.as_secs() as i64 + utc_offset_s as i64).max(0) as u64;
+        self.synchronize(
+            Duration::from_secs(local_s) + Duration::from_nanos(unix.subsec_nanos() as u64),
+        );
+        true
+    }
+}
+
+impl<C: Clock> Clock for SyncedClock<C> {
+    fn now(&self) -> Duration {
+        self.inner.now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.inner.sleep(duration);
+    }
+
+    fn local_time(&self) -> Option<Duration> {
+        if !self.synced.load(Ordering::Relaxed) {
+            return None;
+        }
+        let local = self.inner.now().as_millis() as i64 + self.offset_ms.load(Ordering::Relaxed);
+        Some(Duration::from_millis(local.max(0) as u64))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Clock, MockClock, SyncedClock, SystemClock};
+    use std::time::Duration;
+
+    #[test]
+    fn mock_clock_clones_share_time() {
+        let clock = MockClock::new();
+        let handle = clock.clone();
+        handle.advance(Duration::from_secs(90));
+        assert_eq!(clock.now(), Duration::from_secs(90));
+
+        clock.set(Duration::from_millis(5));
+        assert_eq!(handle.now(), Duration::from_millis(5));
+
+        clock.sleep(Duration::from_millis(20));
+        assert_eq!(handle.now(), Duration::from_millis(25));
+    }
+
+    #[test]
+    fn synced_clock_anchors_to_local_time() {
+        let boot = MockClock::new();
+        let clock = SyncedClock::new(boot.clone());
+        boot.advance(Duration::from_secs(30));
+        assert!(!clock.is_synced());
+        assert_eq!(clock.local_time(), None);
+        assert_eq!(clock.now(), Duration::from_secs(30));
+
+        clock.clone().synchronize(Duration::from_secs(6 * 3600));
+        assert!(clock.is_synced());
+        boot.advance(Duration::from_secs(10));
+        assert_eq!(clock.local_time(), Some(Duration::from_secs(6 * 3600 + 10)));
+        // Timers keep counting from boot
+        assert_eq!(clock.now(), Duration::from_secs(40));
+
+        // Local time may be behind uptime, e.g. shortly after midnight
+        clock.synchronize(Duration::from_secs(5));
+        boot.advance(Duration::from_secs(1));
+        assert_eq!(clock.local_time(), Some(Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn system_clock_is_unsynced() {
+        assert!(!SystemClock::new().is_synced());
+    }
+}
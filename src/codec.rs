@@ -0,0 +1,96 @@
+//! Wire encodings for readings, selectable per sink.
+
+use crate::reading::Reading;
+use anyhow::{Context, Result};
+
+/// Encoding used to turn a [`Reading`] into bytes and back
+pub trait Codec {
+    /// MIME-style name, e.g. for a content-type header or MQTT property
+    fn content_type(&self) -> &'static str;
+
+    fn encode(&self, reading: &Reading) -> Result<Vec<u8>>;
+
+    fn decode(&self, bytes: &[u8]) -> Result<Reading>;
+}
+
+/// Human-readable JSON, the default for debugging and HTTP backends
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonCodec;
+
+impl Codec for JsonCodec {
+    fn content_type(&self) -> &'static str {
+        "application/json"
+    }
+
+    fn encode(&self, reading: &Reading) -> Result<Vec<u8>> {
+        serde_json::to_vec(reading).context("encoding reading as JSON")
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Reading> {
+        serde_json::from_slice(bytes).context("decoding JSON reading")
+    }
+}
+
+/// Compact binary CBOR for bandwidth-limited links
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CborCodec;
+
+impl Codec for CborCodec {
+    fn content_type(&self) -> &'static str {
+        "application/cbor"
+    }
+
+    fn encode(&self, reading: &Reading) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        ciborium::into_writer(reading, &mut out).context("encoding reading as CBOR")?;
+        Ok(out)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<Reading> {
+        ciborium::from_reader(bytes).context("decoding CBOR reading")
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{CborCodec, Codec, JsonCodec};
+    use crate::boot::BootReason;
+    use crate::reading::Reading;
+    use std::time::Duration;
+
+    fn readings() -> [Reading; 2] {
+        [
+            Reading::new(Duration::from_millis(2500), 2100, 50),
+            Reading::new(Duration::from_secs(60), 1400, 88)
+                .with_ec(640)
+                .with_boot_reason(BootReason::Other(42)),
+        ]
+    }
+
+    fn round_trips(codec: &dyn Codec) {
+        for reading in readings() {
+            let bytes = codec.encode(&reading).unwrap();
+            assert_eq!(codec.decode(&bytes).unwrap(), reading);
+        }
+        assert!(codec.decode(&[0xff, 0x00]).is_err());
+    }
+
+    #[test]
+    fn json_round_trips() {
+        round_trips(&JsonCodec);
+        let json = JsonCodec.encode(&readings()[0]).unwrap();
+        assert!(String::from_utf8(json)
+            .unwrap()
+            .contains("\"moisture_percent\":50"));
+    }
+
+    #[test]
+    fn cbor_round_trips_and_is_smaller_than_json() {
+        round_trips(&CborCodec);
+        for reading in readings() {
+            let cbor = CborCodec.encode(&reading).unwrap();
+            let json = JsonCodec.encode(&reading).unwrap();
+            assert!(cbor.len() < json.len());
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Line-oriented serial console commands.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Operator command typed on the serial console
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Suspend automatic pump control; readings continue
+    Pause,
+    /// Re-enable automatic pump control
+    Resume,
+    /// Print the health/status line
+    Status,
+    /// Pump has been serviced; restart the maintenance reminder
+    Serviced,
+    /// Inject a fault for field testing of the alert chain; clears itself
+    Simulate(SimulatedFault),
+    /// `note <text>`: place a manual observation on the timeline
+    Annotate(String),
+    /// Print the daily summaries as CSV
+    Daily,
+    /// Print the effective configuration, secrets redacted
+    DumpConfig,
+}
+
+/// Fault injected by `simulate ...`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulatedFault {
+    /// Sensor reads fail as if the probe were unplugged
+    Disconnected,
+    LowBattery,
+    /// Pump reports no current after activation
+    PumpFailure,
+}
+
+impl fmt::Display for SimulatedFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulatedFault::Disconnected => write!(f, "probe disconnected"),
+            SimulatedFault::LowBattery => write!(f, "low battery"),
+            SimulatedFault::PumpFailure => write!(f, "pump failure"),
+        }
+    }
+}
+
+/// Line that is not a known command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownCommand(pub String);
+
+impl fmt::Display for UnknownCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown command {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownCommand {}
+
+impl FromStr for Command {
+    type Err = UnknownCommand;
+
+    /// Case-insensitive, surrounding whitespace ignored; `simulate` takes
+    /// an optional `fault` before the fault name
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let trimmed = line.trim();
+        if let Some((word, text)) = trimmed.split_once(char::is_whitespace) {
+            if word.eq_ignore_ascii_case("note") && !text.trim().is_empty() {
+                return Ok(Command::Annotate(text.trim().to_string()));
+            }
+        }
+        let lower = trimmed.to_ascii_lowercase();
+        let words: Vec<&str> = lower.split_whitespace().collect();
+        let simulated = match words.as_slice() {
+            ["simulate", "fault", name] | ["simulate", name] => match *name {
+                "disconnected" => Some(SimulatedFault::Disconnected),
+                "lowbattery" => Some(SimulatedFault::LowBattery),
+                "pumpfail" => Some(SimulatedFault::PumpFailure),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(fault) = simulated {
+            return Ok(Command::Simulate(fault));
+        }
+        match lower.as_str() {
+            "pause" => Ok(Command::Pause),
+            "resume" => Ok(Command::Resume),
+            "status" => Ok(Command::Status),
+            "serviced" => Ok(Command::Serviced),
+            "daily" => Ok(Command::Daily),
+            "dump-config" => Ok(Command::DumpConfig),
+            _ => Err(UnknownCommand(line.trim().to_string())),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Command, SimulatedFault, UnknownCommand};
+
+    #[test]
+    fn parses_known_commands() {
+        assert_eq!(" Pause\r\n".parse(), Ok(Command::Pause));
+        assert_eq!("resume".parse(), Ok(Command::Resume));
+        assert_eq!("STATUS".parse(), Ok(Command::Status));
+        assert_eq!("serviced".parse(), Ok(Command::Serviced));
+        assert_eq!("daily".parse(), Ok(Command::Daily));
+        assert_eq!("Dump-Config".parse(), Ok(Command::DumpConfig));
+        assert_eq!(
+            "simulate fault disconnected".parse(),
+            Ok(Command::Simulate(SimulatedFault::Disconnected))
+        );
+        assert_eq!(
+            "Simulate  LowBattery".parse(),
+            Ok(Command::Simulate(SimulatedFault::LowBattery))
+        );
+        assert_eq!(
+            "simulate pumpfail".parse(),
+            Ok(Command::Simulate(SimulatedFault::PumpFailure))
+        );
+        assert!("simulate fault fire".parse::<Command>().is_err());
+        assert_eq!(
+            "Note  Added fertilizer ".parse(),
+            Ok(Command::Annotate("Added fertilizer".to_string()))
+        );
+        assert!("note".parse::<Command>().is_err());
+        assert_eq!(
+            "water".parse::<Command>(),
+            Err(UnknownCommand("water".to_string()))
+        );
+    }
+}
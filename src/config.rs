@@ -0,0 +1,534 @@
+//! Effective runtime configuration, assembled for inspection.
+
+use crate::moisture::{Calibration, Polarity};
+use crate::profile::Profile;
+use crate::schedule::{Schedule, UnsyncedPolicy};
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+const REDACTED: &str = "<redacted>";
+
+/// WiFi and MQTT broker settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetworkConfig {
+    pub wifi_ssid: String,
+    pub wifi_password: String,
+    pub broker_url: Option<String>,
+    pub broker_username: Option<String>,
+    pub broker_password: Option<String>,
+}
+
+/// Everything that shapes what the firmware is doing right now
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveConfig {
+    pub calibration: Calibration,
+    pub profile: Profile,
+    pub reading_interval: Duration,
+    pub network: NetworkConfig,
+    pub schedule: Schedule,
+}
+
+/// Cross-field invariant broken by a configuration bundle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Dry reading must be above the wet reading (higher = drier)
+    CalibrationInverted {
+        dry: u16,
+        wet: u16,
+    },
+    /// Wet-high probe: wet reading must be above the dry reading
+    CalibrationInvertedWetHigh {
+        dry: u16,
+        wet: u16,
+    },
+    /// Plausible range is empty
+    ValidRangeEmpty {
+        min: u16,
+        max: u16,
+    },
+    /// A calibration point lies outside the plausible range, so it would be flagged as a fault
+    CalibrationOutsideValidRange {
+        point: u16,
+        min: u16,
+        max: u16,
+    },
+    /// Output gain of zero maps every reading to the offset
+    OutputGainZero,
+    /// Dry threshold must be below the wet threshold
+    ThresholdsInverted {
+        low: u8,
+        high: u8,
+    },
+    /// Percent thresholds cannot exceed 100
+    ThresholdAboveHundred {
+        value: u8,
+    },
+    ReadingIntervalZero,
+    /// A broker is configured but there is no WiFi network to reach it
+    BrokerWithoutWifi,
+    /// Broker password set without a username
+    BrokerPasswordWithoutUsername,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::CalibrationInverted { dry, wet } => {
+                write!(f, "dry calibration {dry} must be above wet {wet}")
+            }
+            ConfigError::CalibrationInvertedWetHigh { dry, wet } => {
+                write!(
+                    f,
+                    "wet calibration {wet} must be above dry {dry} (wet-high probe)"
+                )
+            }
+            ConfigError::ValidRangeEmpty { min, max } => {
+                write!(f, "valid range {min}..={max} is empty")
+            }
+            ConfigError::CalibrationOutsideValidRange { point, min, max } => {
+                write!(
+                    f,
+                    "calibration point {point} outside valid range {min}..={max}"
+                )
+            }
+            ConfigError::OutputGainZero => write!(f, "calibration output gain must be non-zero"),
+            ConfigError::ThresholdsInverted { low, high } => {
+                write!(f, "moisture_low {low}% must be below moisture_high {high}%")
+            }
+            ConfigError::ThresholdAboveHundred { value } => {
+                write!(f, "threshold {value}% exceeds 100%")
+            }
+            ConfigError::ReadingIntervalZero => write!(f, "reading interval must be non-zero"),
+            ConfigError::BrokerWithoutWifi => write!(f, "broker configured without a WiFi SSID"),
+            ConfigError::BrokerPasswordWithoutUsername => {
+                write!(f, "broker password set without a username")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl EffectiveConfig {
+    /// Check every cross-field invariant at once so a bundle of changes can be
+    /// rejected as a whole; all problems are reported, not just the first
+    pub fn validate(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        let cal = &self.calibration;
+        if cal.is_inverted() {
+            let (dry, wet) = (cal.dry, cal.wet);
+            errors.push(match cal.polarity {
+                Polarity::DryHigh => ConfigError::CalibrationInverted { dry, wet },
+                Polarity::WetHigh => ConfigError::CalibrationInvertedWetHigh { dry, wet },
+            });
+        }
+        if cal.output_gain == 0 {
+            errors.push(ConfigError::OutputGainZero);
+        }
+        let (min, max) = cal.valid_range();
+        if min >= max {
+            errors.push(ConfigError::ValidRangeEmpty { min, max });
+        } else {
+            for point in [cal.dry, cal.wet] {
+                if !(min..=max).contains(&point) {
+                    errors.push(ConfigError::CalibrationOutsideValidRange { point, min, max });
+                }
+            }
+        }
+
+        let (low, high) = (self.profile.moisture_low, self.profile.moisture_high);
+        if low >= high {
+            errors.push(ConfigError::ThresholdsInverted { low, high });
+        }
+        for value in [low, high] {
+            if value > 100 {
+                errors.push(ConfigError::ThresholdAboveHundred { value });
+            }
+        }
+
+        if self.reading_interval.is_zero() {
+            errors.push(ConfigError::ReadingIntervalZero);
+        }
+
+        let net = &self.network;
+        if net.broker_url.is_some() && net.wifi_ssid.is_empty() {
+            errors.push(ConfigError::BrokerWithoutWifi);
+        }
+        if net.broker_password.is_some() && net.broker_username.is_none() {
+            errors.push(ConfigError::BrokerPasswordWithoutUsername);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Output syntax for [`dump_config`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+}
+
+/// Compile-time feature flags reported alongside the runtime settings
+fn feature_flags() -> Vec<(&'static str, Value)> {
+    vec![
+        ("integer_only", Value::Bool(cfg!(feature = "integer-only"))),
+        (
+            "fault_injection",
+            Value::Bool(cfg!(feature = "fault-injection")),
+        ),
+        ("ble", Value::Bool(cfg!(feature = "ble"))),
+    ]
+}
+
+/// Offset from midnight as `HH:MM`
+fn time_of_day(offset: Duration) -> String {
+    let minutes = offset.as_secs() / 60;
+    format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+}
+
+/// Small document tree so JSON and TOML share one field list
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Str(String),
+    Null,
+    Table(Vec<(&'static str, Value)>),
+}
+
+fn opt_u16(v: Option<u16>) -> Value {
+    v.map_or(Value::Null, |v| Value::Int(v as i64))
+}
+
+fn opt_str(v: &Option<String>) -> Value {
+    v.as_ref().map_or(Value::Null, |v| Value::Str(v.clone()))
+}
+
+/// Secrets only reveal whether they are set
+fn secret(set: bool) -> Value {
+    if set {
+        Value::Str(REDACTED.to_string())
+    } else {
+        Value::Null
+    }
+}
+
+impl EffectiveConfig {
+    fn to_value(&self) -> Value {
+        let cal = &self.calibration;
+        let net = &self.network;
+        Value::Table(vec![
+            (
+                "calibration",
+                Value::Table(vec![
+                    ("dry", Value::Int(cal.dry as i64)),
+                    ("wet", Value::Int(cal.wet as i64)),
+                    ("valid_min", opt_u16(cal.valid_min)),
+                    ("valid_max", opt_u16(cal.valid_max)),
+                    (
+                        "polarity",
+                        Value::Str(
+                            match cal.polarity {
+                                Polarity::DryHigh => "dry_high",
+                                Polarity::WetHigh => "wet_high",
+                            }
+                            .to_string(),
+                        ),
+                    ),
+                    ("output_offset", Value::Int(cal.output_offset as i64)),
+                    ("output_gain", Value::Int(cal.output_gain as i64)),
+                ]),
+            ),
+            (
+                "profile",
+                Value::Table(vec![
+                    ("name", Value::Str(self.profile.name.clone())),
+                    ("moisture_low", Value::Int(self.profile.moisture_low as i64)),
+                    (
+                        "moisture_high",
+                        Value::Int(self.profile.moisture_high as i64),
+                    ),
+                ]),
+            ),
+            (
+                "sampling",
+                Value::Table(vec![(
+                    "interval_ms",
+                    Value::Int(self.reading_interval.as_millis() as i64),
+                )]),
+            ),
+            (
+                "network",
+                Value::Table(vec![
+                    ("wifi_ssid", Value::Str(net.wifi_ssid.clone())),
+                    ("wifi_password", secret(!net.wifi_password.is_empty())),
+                    ("broker_url", opt_str(&net.broker_url)),
+                    ("broker_username", secret(net.broker_username.is_some())),
+                    ("broker_password", secret(net.broker_password.is_some())),
+                ]),
+            ),
+            (
+                "schedule",
+                Value::Table(vec![
+                    (
+                        "windows",
+                        Value::Str(
+                            self.schedule
+                                .windows
+                                .iter()
+                                .map(|w| format!("{}-{}", time_of_day(w.start), time_of_day(w.end)))
+                                .collect::<Vec<_>>()
+                                .join(","),
+                        ),
+                    ),
+                    (
+                        "min_dwell_s",
+                        Value::Int(self.schedule.min_dwell.as_secs() as i64),
+                    ),
+                    (
+                        "unsynced",
+                        Value::Str(
+                            match self.schedule.unsynced {
+                                UnsyncedPolicy::Block => "block",
+                                UnsyncedPolicy::IgnoreSchedule => "ignore_schedule",
+                            }
+                            .to_string(),
+                        ),
+                    ),
+                ]),
+            ),
+            ("features", Value::Table(feature_flags())),
+        ])
+    }
+}
+
+/// Render the effective configuration with secrets redacted
+pub fn dump_config(config: &EffectiveConfig, format: ConfigFormat) -> String {
+    let mut out = String::new();
+    match format {
+        ConfigFormat::Json => write_json(&mut out, &config.to_value()),
+        ConfigFormat::Toml => {
+            if let Value::Table(fields) = config.to_value() {
+                write_toml_table(&mut out, "", &fields);
+            }
+        }
+    }
+    out
+}
+
+fn quote(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_json(out: &mut String, value: &Value) {
+    match value {
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Int(i) => {
+            let _ = write!(out, "{}", i);
+        }
+        Value::Str(s) => quote(out, s),
+        Value::Null => out.push_str("null"),
+        Value::Table(fields) => {
+            out.push('{');
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                quote(out, key);
+                out.push(':');
+                write_json(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// TOML has no null, so unset values are omitted
+fn write_toml_table(out: &mut String, path: &str, fields: &[(&'static str, Value)]) {
+    for (key, value) in fields {
+        match value {
+            Value::Table(_) | Value::Null => {}
+            Value::Bool(b) => {
+                let _ = writeln!(out, "{} = {}", key, b);
+            }
+            Value::Int(i) => {
+                let _ = writeln!(out, "{} = {}", key, i);
+            }
+            Value::Str(s) => {
+                let _ = write!(out, "{} = ", key);
+                quote(out, s);
+                out.push('\n');
+            }
+        }
+    }
+    for (key, value) in fields {
+        if let Value::Table(inner) = value {
+            let path = if path.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            let _ = writeln!(out, "\n[{}]", path);
+            write_toml_table(out, &path, inner);
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{dump_config, ConfigError, ConfigFormat, EffectiveConfig, NetworkConfig};
+    use crate::moisture::{Calibration, Polarity};
+    use crate::profile::Profile;
+    use crate::schedule::{Schedule, Window};
+    use std::time::Duration;
+
+    fn config() -> EffectiveConfig {
+        EffectiveConfig {
+            calibration: Calibration::new(2950, 1150),
+            profile: Profile::default(),
+            reading_interval: Duration::from_secs(2),
+            network: NetworkConfig {
+                wifi_ssid: "garden".to_string(),
+                wifi_password: "hunter2".to_string(),
+                broker_url: Some("mqtt://broker.local".to_string()),
+                broker_username: Some("sensor".to_string()),
+                broker_password: Some("s3cret".to_string()),
+            },
+            schedule: Schedule::new(
+                vec![Window::new(
+                    Duration::from_secs(6 * 3600),
+                    Duration::from_secs(7 * 3600 + 30 * 60),
+                )],
+                Duration::from_secs(600),
+            ),
+        }
+    }
+
+    #[test]
+    fn json_dump_includes_key_fields_and_redacts_secrets() {
+        let json = dump_config(&config(), ConfigFormat::Json);
+        assert!(json.contains(r#""calibration":{"dry":2950,"wet":1150"#));
+        assert!(json.contains(r#""moisture_low":25"#));
+        assert!(json.contains(r#""interval_ms":2000"#));
+        assert!(json.contains(r#""wifi_ssid":"garden""#));
+        assert!(json.contains(r#""wifi_password":"<redacted>""#));
+        assert!(json.contains(r#""integer_only":"#));
+        assert!(json.contains(r#""fault_injection":"#));
+        assert!(json.contains(r#""ble":"#));
+        assert!(json.contains(r#""schedule":{"windows":"06:00-07:30","min_dwell_s":600"#));
+        for secret in ["hunter2", "s3cret", "\"sensor\""] {
+            assert!(!json.contains(secret), "{secret} leaked: {json}");
+        }
+    }
+
+    #[test]
+    fn toml_dump_uses_tables_and_redacts_secrets() {
+        let toml = dump_config(&config(), ConfigFormat::Toml);
+        assert!(toml.contains("[calibration]\ndry = 2950\nwet = 1150\n"));
+        assert!(toml.contains("[profile]\nname = \"default\"\n"));
+        assert!(toml.contains("broker_password = \"<redacted>\""));
+        assert!(toml.contains("[schedule]\nwindows = \"06:00-07:30\"\n"));
+        assert!(!toml.contains("hunter2") && !toml.contains("s3cret"));
+    }
+
+    #[test]
+    fn valid_config_passes() {
+        assert_eq!(config().validate(), Ok(()));
+    }
+
+    #[test]
+    fn individually_invalid_fields_are_reported() {
+        let mut cfg = config();
+        cfg.calibration = Calibration::new(1200, 3000);
+        assert_eq!(
+            cfg.validate(),
+            Err(vec![ConfigError::CalibrationInverted {
+                dry: 1200,
+                wet: 3000
+            }])
+        );
+        cfg.calibration = cfg.calibration.with_polarity(Polarity::WetHigh);
+        assert_eq!(cfg.validate(), Ok(()));
+        cfg.calibration = Calibration::new(3000, 1200).with_polarity(Polarity::WetHigh);
+        assert_eq!(
+            cfg.validate(),
+            Err(vec![ConfigError::CalibrationInvertedWetHigh {
+                dry: 3000,
+                wet: 1200
+            }])
+        );
+
+        let mut cfg = config();
+        cfg.profile.moisture_low = 80;
+        assert_eq!(
+            cfg.validate(),
+            Err(vec![ConfigError::ThresholdsInverted { low: 80, high: 75 }])
+        );
+
+        let mut cfg = config();
+        cfg.calibration = cfg.calibration.with_valid_range(1500, 3500);
+        assert_eq!(
+            cfg.validate(),
+            Err(vec![ConfigError::CalibrationOutsideValidRange {
+                point: 1150,
+                min: 1500,
+                max: 3500
+            }])
+        );
+
+        let mut cfg = config();
+        cfg.calibration = cfg.calibration.with_output_correction(-5, 0);
+        assert_eq!(cfg.validate(), Err(vec![ConfigError::OutputGainZero]));
+
+        let mut cfg = config();
+        cfg.reading_interval = Duration::ZERO;
+        assert_eq!(cfg.validate(), Err(vec![ConfigError::ReadingIntervalZero]));
+    }
+
+    #[test]
+    fn jointly_invalid_bundle_reports_every_problem() {
+        // Each change alone is fine; together they invert the thresholds
+        let mut lower_high = config();
+        lower_high.profile.moisture_high = 30;
+        assert_eq!(lower_high.validate(), Ok(()));
+        let mut raise_low = config();
+        raise_low.profile.moisture_low = 60;
+        assert_eq!(raise_low.validate(), Ok(()));
+
+        let mut cfg = config();
+        cfg.profile.moisture_high = 30;
+        cfg.profile.moisture_low = 60;
+        cfg.network.wifi_ssid.clear();
+        cfg.network.broker_username = None;
+        let errors = cfg.validate().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                ConfigError::ThresholdsInverted { low: 60, high: 30 },
+                ConfigError::BrokerWithoutWifi,
+                ConfigError::BrokerPasswordWithoutUsername,
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "moisture_low 60% must be below moisture_high 30%"
+        );
+    }
+}
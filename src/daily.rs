@@ -0,0 +1,475 @@
+//! One summary per day of moisture, watering and alerts, kept on flash.
+//!
+//! Days run from a configurable boundary (e.g. 06:00) on the clock's local
+//! time, so they only line up with the calendar once the clock is synced;
+//! until then boot counts as midnight. The first day after boot, the day
+//! still open at shutdown, and the days either side of the clock syncing
+//! cover only part of the day and are marked partial.
+//!
+//! The open day is checkpointed alongside the closed ones, so a reboot
+//! continues it (as partial) once the clock is synced again instead of
+//! losing it.
+
+use crate::stats::Stats;
+use crate::storage::FlashStore;
+use anyhow::{bail, ensure, Result};
+use log::warn;
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::time::Duration;
+
+/// Flash file holding the closed days
+pub const DAILY_SUMMARY_FILE: &str = "daily.bin";
+/// Flash file holding the synced day in progress, in the same format
+pub const DAILY_OPEN_FILE: &str = "dayopen.bin";
+/// Closed days kept on flash, oldest dropped first
+pub const DAILY_SUMMARY_DAYS: usize = 31;
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const DAILY_FORMAT_VERSION: u8 = 1;
+/// day, partial, stats blob, pump runtime, waterings, alerts
+const RECORD_LEN: usize = 4 + 1 + 17 + 4 + 2 + 2;
+const CSV_HEADER: &str = "day,partial,readings,moisture_min,moisture_max,moisture_mean,\
+                          pump_runtime_s,waterings,alerts";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DailySummary {
+    /// Day boundaries passed since local time zero
+    pub day: u32,
+    /// Covers only part of the day: the device started or stopped during it
+    pub partial: bool,
+    pub moisture: Stats,
+    pub pump_runtime: Duration,
+    pub waterings: u32,
+    pub alerts: u32,
+}
+
+impl fmt::Display for DailySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |v: Option<u8>| v.map_or_else(|| "n/a".to_string(), |v| format!("{v}%"));
+        write!(
+            f,
+            "day {}{}: moisture {}..{} (mean {}), {} waterings, pump {}s, {} alerts",
+            self.day,
+            if self.partial { " (partial)" } else { "" },
+            percent(self.moisture.min()),
+            percent(self.moisture.max()),
+            percent(self.moisture.mean()),
+            self.waterings,
+            self.pump_runtime.as_secs(),
+            self.alerts
+        )
+    }
+}
+
+impl DailySummary {
+    fn csv_row(&self) -> String {
+        let opt = |v: Option<u8>| v.map_or_else(String::new, |v| v.to_string());
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.day,
+            self.partial as u8,
+            self.moisture.count(),
+            opt(self.moisture.min()),
+            opt(self.moisture.max()),
+            opt(self.moisture.mean()),
+            self.pump_runtime.as_secs(),
+            self.waterings,
+            self.alerts
+        )
+    }
+}
+
+/// Accumulates the current day and keeps the most recent closed ones
+#[derive(Debug, Clone)]
+pub struct DailyRollup {
+    /// Offset of the day boundary from local midnight
+    boundary: Duration,
+    current: Option<DailySummary>,
+    /// Whether a day has been opened since boot; the first one is partial
+    opened: bool,
+    /// Clock sync state the current day was counted under
+    synced: bool,
+    /// Day open before the reboot, held until the clock is synced again
+    restored: Option<DailySummary>,
+    days: VecDeque<DailySummary>,
+}
+
+impl DailyRollup {
+    /// Days starting `boundary` after local midnight
+    pub fn new(boundary: Duration) -> Self {
+        Self {
+            boundary: Duration::from_secs(boundary.as_secs() % DAY.as_secs()),
+            current: None,
+            opened: false,
+            synced: false,
+            restored: None,
+            days: VecDeque::new(),
+        }
+    }
+
+    /// Continue from days closed before this boot
+    pub fn with_history(mut self, days: Vec<DailySummary>) -> Self {
+        self.days = days.into();
+        while self.days.len() > DAILY_SUMMARY_DAYS {
+            self.days.pop_front();
+        }
+        self
+    }
+
+    /// Continue the day open at the last checkpoint, unless it was closed
+    /// since; call after [`with_history`](Self::with_history)
+    pub fn with_open_day(mut self, day: Option<DailySummary>) -> Self {
+        let last_closed = self.days.back().map(|d| d.day);
+        self.restored = day.filter(|d| last_closed < Some(d.day));
+        self
+    }
+
+    /// Day index of local time `at`
+    pub fn day_of(&self, at: Duration) -> u32 {
+        ((at + DAY).saturating_sub(self.boundary).as_secs() / DAY.as_secs()) as u32
+    }
+
+    /// Count a reading at `at`, first closing the previous day if `at` is
+    /// past its boundary; returns the day closed. A change in `synced` means
+    /// the clock jumped, so the day so far is closed as partial. `at` is the
+    /// clock's local time once synced, time since boot before that.
+    pub fn record_reading(
+        &mut self,
+        at: Duration,
+        synced: bool,
+        moisture_percent: u8,
+    ) -> Option<DailySummary> {
+        let day = self.day_of(at);
+        let resynced = self.current.is_some() && synced != self.synced;
+        let rolled_over = self.current.as_ref().is_some_and(|c| c.day != day);
+        let mut closed = if resynced {
+            self.finish()
+        } else if rolled_over {
+            self.close()
+        } else {
+            None
+        };
+        if synced {
+            if let Some(restored) = self.restored.take() {
+                // Readings were missed while the device was down
+                let restored = DailySummary {
+                    partial: true,
+                    ..restored
+                };
+                if restored.day == day && self.current.is_none() {
+                    self.current = Some(restored);
+                } else {
+                    closed = closed.or(Some(self.push_closed(restored)));
+                }
+            }
+        }
+        let partial = !self.opened || resynced;
+        self.synced = synced;
+        let current = self.current.get_or_insert_with(|| DailySummary {
+            day,
+            partial,
+            ..DailySummary::default()
+        });
+        self.opened = true;
+        current.moisture.record(moisture_percent);
+        closed
+    }
+
+    /// Count a pump activation in the current day
+    pub fn record_watering(&mut self) {
+        if let Some(current) = &mut self.current {
+            current.waterings = current.waterings.saturating_add(1);
+        }
+    }
+
+    /// Add a finished run's time to the current day
+    pub fn record_pump_runtime(&mut self, runtime: Duration) {
+        if let Some(current) = &mut self.current {
+            current.pump_runtime += runtime;
+        }
+    }
+
+    pub fn record_alerts(&mut self, count: u32) {
+        if let Some(current) = &mut self.current {
+            current.alerts = current.alerts.saturating_add(count);
+        }
+    }
+
+    /// Close the current day early, e.g. at shutdown, marking it partial
+    pub fn finish(&mut self) -> Option<DailySummary> {
+        if let Some(current) = &mut self.current {
+            current.partial = true;
+        }
+        self.close()
+    }
+
+    fn close(&mut self) -> Option<DailySummary> {
+        let closed = self.current.take()?;
+        Some(self.push_closed(closed))
+    }
+
+    fn push_closed(&mut self, closed: DailySummary) -> DailySummary {
+        if self.days.len() == DAILY_SUMMARY_DAYS {
+            self.days.pop_front();
+        }
+        self.days.push_back(closed.clone());
+        closed
+    }
+
+    /// Day in progress worth continuing after a reboot: only a synced day
+    /// is on the calendar, and a restored one not yet resumed is kept
+    pub fn open_day(&self) -> Option<&DailySummary> {
+        match &self.current {
+            Some(current) if self.synced => Some(current),
+            _ => self.restored.as_ref(),
+        }
+    }
+
+    /// Closed days, oldest first
+    pub fn days(&self) -> impl Iterator<Item = &DailySummary> {
+        self.days.iter()
+    }
+
+    /// CSV of the closed days plus the day in progress, which is partial so far
+    pub fn export_csv(&self) -> String {
+        let mut out = String::from(CSV_HEADER);
+        out.push('\n');
+        let in_progress = self.current.clone().map(|day| DailySummary {
+            partial: true,
+            ..day
+        });
+        for day in self.days.iter().chain(in_progress.as_ref()) {
+            // Writing to a String cannot fail
+            let _ = writeln!(out, "{}", day.csv_row());
+        }
+        out
+    }
+
+    /// Persisted form: version, day count, then per day the index (u32),
+    /// partial flag, [`Stats`] blob, pump runtime in s (u32), waterings and
+    /// alerts (u16 each, saturating), little endian
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_days(&self.days)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Vec<DailySummary>> {
+        ensure!(bytes.len() >= 2, "daily summary blob is truncated");
+        if bytes[0] != DAILY_FORMAT_VERSION {
+            bail!("unsupported daily summary format version {}", bytes[0]);
+        }
+        let count = bytes[1] as usize;
+        ensure!(
+            bytes.len() == 2 + count * RECORD_LEN,
+            "daily summary blob is {} bytes, expected {} for {} days",
+            bytes.len(),
+            2 + count * RECORD_LEN,
+            count
+        );
+        bytes[2..]
+            .chunks_exact(RECORD_LEN)
+            .map(|r| {
+                let u16_at = |i: usize| u16::from_le_bytes([r[i], r[i + 1]]) as u32;
+                Ok(DailySummary {
+                    day: u32::from_le_bytes([r[0], r[1], r[2], r[3]]),
+                    partial: r[4] != 0,
+                    moisture: Stats::from_bytes(&r[5..22])?,
+                    pump_runtime: Duration::from_secs(u32::from_le_bytes([
+                        r[22], r[23], r[24], r[25],
+                    ]) as u64),
+                    waterings: u16_at(26),
+                    alerts: u16_at(28),
+                })
+            })
+            .collect()
+    }
+
+    /// Write the closed days to [`DAILY_SUMMARY_FILE`], and the open day
+    /// with them so a reboot cannot count it twice
+    pub fn save(&self, flash: &mut dyn FlashStore) -> Result<()> {
+        flash.write_file(DAILY_SUMMARY_FILE, &self.to_bytes())?;
+        self.checkpoint(flash)
+    }
+
+    /// Write the open day to [`DAILY_OPEN_FILE`]
+    pub fn checkpoint(&self, flash: &mut dyn FlashStore) -> Result<()> {
+        flash.write_file(DAILY_OPEN_FILE, &encode_days(self.open_day()))
+    }
+}
+
+fn encode_days<'a>(days: impl IntoIterator<Item = &'a DailySummary>) -> Vec<u8> {
+    let mut out = vec![DAILY_FORMAT_VERSION, 0];
+    for day in days {
+        out[1] += 1;
+        out.extend_from_slice(&day.day.to_le_bytes());
+        out.push(day.partial as u8);
+        out.extend_from_slice(&day.moisture.to_bytes());
+        let runtime = day.pump_runtime.as_secs().min(u32::MAX as u64) as u32;
+        out.extend_from_slice(&runtime.to_le_bytes());
+        for count in [day.waterings, day.alerts] {
+            out.extend_from_slice(&(count.min(u16::MAX as u32) as u16).to_le_bytes());
+        }
+    }
+    out
+}
+
+fn load_days(flash: &dyn FlashStore, file: &str) -> Vec<DailySummary> {
+    match flash.read_file(file) {
+        Ok(Some(bytes)) => DailyRollup::from_bytes(&bytes).unwrap_or_else(|e| {
+            warn!("Discarding daily summaries in {}: {}", file, e);
+            Vec::new()
+        }),
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            warn!("Could not read daily summaries from {}: {:?}", file, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Days saved by a previous boot, or none if missing or unreadable
+pub fn load_daily_summaries(flash: &dyn FlashStore) -> Vec<DailySummary> {
+    load_days(flash, DAILY_SUMMARY_FILE)
+}
+
+/// Day open at the previous boot's last checkpoint, for
+/// [`DailyRollup::with_open_day`]
+pub fn load_open_day(flash: &dyn FlashStore) -> Option<DailySummary> {
+    load_days(flash, DAILY_OPEN_FILE).pop()
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{load_daily_summaries, load_open_day, DailyRollup, DAY};
+    use crate::storage::MemoryFlash;
+    use std::time::Duration;
+
+    fn hours(h: u64) -> Duration {
+        Duration::from_secs(h * 60 * 60)
+    }
+
+    #[test]
+    fn readings_across_the_boundary_close_two_days() {
+        // Days start at 06:00; boot at 20:00 on day zero
+        let mut daily = DailyRollup::new(hours(6));
+        assert_eq!(daily.day_of(hours(5)), 0);
+        assert_eq!(daily.day_of(hours(6)), 1);
+
+        assert_eq!(daily.record_reading(hours(20), true, 40), None);
+        daily.record_watering();
+        daily.record_pump_runtime(Duration::from_secs(45));
+        assert_eq!(daily.record_reading(hours(29), true, 60), None);
+        daily.record_alerts(1);
+
+        // 06:00 the next morning starts day 2
+        let first = daily.record_reading(hours(30), true, 35).unwrap();
+        assert_eq!(first.day, 1);
+        assert!(first.partial, "booted part way through the day");
+        assert_eq!(first.moisture.count(), 2);
+        assert_eq!(
+            (
+                first.moisture.min(),
+                first.moisture.max(),
+                first.moisture.mean()
+            ),
+            (Some(40), Some(60), Some(50))
+        );
+        assert_eq!(first.pump_runtime, Duration::from_secs(45));
+        assert_eq!((first.waterings, first.alerts), (1, 1));
+
+        daily.record_reading(hours(40), true, 25);
+        daily.record_watering();
+        let second = daily.record_reading(hours(30) + DAY, true, 50).unwrap();
+        assert_eq!(second.day, 2);
+        assert!(!second.partial);
+        assert_eq!(second.moisture.count(), 2);
+        assert_eq!(second.moisture.mean(), Some(30));
+        assert_eq!((second.waterings, second.alerts), (1, 0));
+
+        // Shutting down part way through day 3
+        let last = daily.finish().unwrap();
+        assert!(last.partial);
+        assert_eq!(daily.days().count(), 3);
+    }
+
+    #[test]
+    fn closed_days_survive_flash_and_export_as_csv() {
+        let mut daily = DailyRollup::new(Duration::ZERO);
+        daily.record_reading(hours(12), true, 40);
+        daily.record_watering();
+        daily.record_reading(hours(36), true, 70);
+        let mut flash = MemoryFlash::new();
+        daily.save(&mut flash).unwrap();
+
+        let restored = DailyRollup::new(Duration::ZERO).with_history(load_daily_summaries(&flash));
+        assert_eq!(
+            restored.days().collect::<Vec<_>>(),
+            daily.days().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            daily.export_csv(),
+            "day,partial,readings,moisture_min,moisture_max,moisture_mean,pump_runtime_s,\
+             waterings,alerts\n\
+             1,1,1,40,40,40,0,1,0\n\
+             2,1,1,70,70,70,0,0,0\n"
+        );
+        assert!(load_daily_summaries(&MemoryFlash::new()).is_empty());
+    }
+
+    #[test]
+    fn clock_sync_closes_the_unsynced_stretch_as_partial() {
+        let mut daily = DailyRollup::new(Duration::ZERO);
+        // Unsynced: boot counts as midnight of day zero
+        daily.record_reading(hours(1), false, 40);
+        daily.record_reading(hours(2), false, 42);
+        // Synced to noon on day 300; the same day index must not merge
+        let before_sync = daily
+            .record_reading(hours(300 * 24 + 12), true, 44)
+            .unwrap();
+        assert!(before_sync.partial);
+        assert_eq!((before_sync.day, before_sync.moisture.count()), (1, 2));
+        let after_sync = daily.record_reading(hours(301 * 24 + 1), true, 46).unwrap();
+        assert!(after_sync.partial, "started at the sync, not the boundary");
+        assert_eq!(after_sync.day, 301);
+    }
+
+    #[test]
+    fn open_day_continues_after_a_reboot() {
+        let mut daily = DailyRollup::new(Duration::ZERO);
+        daily.record_reading(hours(300 * 24 + 8), true, 40);
+        daily.record_watering();
+        let mut flash = MemoryFlash::new();
+        daily.checkpoint(&mut flash).unwrap();
+        let restore = |flash: &MemoryFlash| {
+            DailyRollup::new(Duration::ZERO)
+                .with_history(load_daily_summaries(flash))
+                .with_open_day(load_open_day(flash))
+        };
+
+        // Until the clock syncs again readings count from boot
+        let mut rebooted = restore(&flash);
+        rebooted.record_reading(hours(0), false, 42);
+        let unsynced = rebooted.record_reading(hours(300 * 24 + 10), true, 44);
+        assert_eq!(unsynced.map(|d| d.day), Some(1));
+        let resumed = rebooted.finish().unwrap();
+        assert_eq!((resumed.day, resumed.moisture.count()), (301, 2));
+        assert_eq!(resumed.waterings, 1);
+        assert!(resumed.partial, "the reboot lost part of the day");
+
+        // Back up on a later day: the restored day is closed as it was
+        let mut rebooted = restore(&flash);
+        let closed = rebooted
+            .record_reading(hours(302 * 24 + 1), true, 50)
+            .unwrap();
+        assert_eq!((closed.day, closed.moisture.count()), (301, 1));
+        assert_eq!(rebooted.days().count(), 1);
+
+        // Closed before the reboot: not counted twice
+        rebooted.save(&mut flash).unwrap();
+        let mut rebooted = DailyRollup::new(Duration::ZERO)
+            .with_history(load_daily_summaries(&flash))
+            .with_open_day(Some(closed));
+        assert!(rebooted.open_day().is_none());
+        rebooted.record_reading(hours(302 * 24 + 2), true, 50);
+        assert_eq!(rebooted.days().count(), 1);
+    }
+}
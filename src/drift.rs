@@ -0,0 +1,239 @@
+//! Long-term drift detection for the raw sensor baseline.
+
+use crate::alert::Alert;
+use std::collections::VecDeque;
+
+/// Outcome of feeding a reading to the drift detector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftStatus {
+    /// Not enough readings yet to judge the baseline
+    Warming,
+    /// Baseline is within tolerance of the calibration reference
+    Stable,
+    /// Baseline has moved far enough that the calibration is no longer trustworthy
+    RecalibrationRecommended {
+        /// Windowed mean minus reference, in raw counts
+        deviation: i32,
+    },
+}
+
+/// Compares a rolling mean of raw readings against the reference baseline
+/// captured at calibration time
+pub struct DriftDetector {
+    reference: u16,
+    threshold: u16,
+    window: usize,
+    readings: VecDeque<u16>,
+}
+
+impl DriftDetector {
+    /// `threshold` is the allowed deviation in raw counts; `window` is how many
+    /// readings are averaged so ordinary noise does not trigger it
+    pub fn new(reference: u16, threshold: u16, window: usize) -> Self {
+        let window = window.max(1);
+        Self {
+            reference,
+            threshold,
+            window,
+            readings: VecDeque::with_capacity(window),
+        }
+    }
+
+    /// Feed one raw reading and get the current drift assessment
+    pub fn update(&mut self, raw: u16) -> DriftStatus {
+        if self.readings.len() == self.window {
+            self.readings.pop_front();
+        }
+        self.readings.push_back(raw);
+
+        if self.readings.len() < self.window {
+            return DriftStatus::Warming;
+        }
+
+        let deviation = self.mean() as i32 - self.reference as i32;
+        if deviation.unsigned_abs() > self.threshold as u32 {
+            DriftStatus::RecalibrationRecommended { deviation }
+        } else {
+            DriftStatus::Stable
+        }
+    }
+
+    /// Start over against a new reference, e.g. after recalibrating
+    pub fn reset(&mut self, reference: u16) {
+        self.reference = reference;
+        self.readings.clear();
+    }
+
+    fn mean(&self) -> u16 {
+        let sum: u32 = self.readings.iter().map(|&r| r as u32).sum();
+        (sum / self.readings.len() as u32) as u16
+    }
+}
+
+/// Time constant, settling period and alert threshold for [`BaselineTracker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaselineConfig {
+    /// Low-pass time constant in readings; long enough that wetting and
+    /// drying cycles average out
+    pub time_constant: u32,
+    /// Readings before the long-term reference is captured
+    pub settle: u32,
+    /// Baseline shift from the reference, in raw counts, that raises an alert
+    pub threshold: u16,
+}
+
+impl Default for BaselineConfig {
+    fn default() -> Self {
+        // At one reading a minute: ~3 day time constant, reference after ~2 weeks
+        Self {
+            time_constant: 4096,
+            settle: 20_000,
+            threshold: 150,
+        }
+    }
+}
+
+/// Separates a slow baseline from the soil signal by low-passing the raw
+/// value, and flags the baseline creeping away from where it settled, as
+/// cable or connector capacitance changes do.
+///
+/// Integer only: the filter state is the raw value scaled by 2^16.
+#[derive(Debug, Clone)]
+pub struct BaselineTracker {
+    config: BaselineConfig,
+    state: Option<i64>,
+    seen: u32,
+    reference: Option<u16>,
+    alerted: bool,
+}
+
+impl BaselineTracker {
+    pub fn new(config: BaselineConfig) -> Self {
+        Self {
+            config,
+            state: None,
+            seen: 0,
+            reference: None,
+            alerted: false,
+        }
+    }
+
+    /// Feed one raw reading; returns an alert when the baseline first moves
+    /// past the threshold, re-arming once it is back within half of it
+    pub fn update(&mut self, raw: u16) -> Option<Alert> {
+        let target = i64::from(raw) << 16;
+        let tau = i64::from(self.config.time_constant.max(1));
+        let state = self.state.map_or(target, |s| s + (target - s) / tau);
+        self.state = Some(state);
+        self.seen = self.seen.saturating_add(1);
+        if self.reference.is_none() && self.seen >= self.config.settle {
+            self.reference = self.baseline();
+        }
+
+        let shift = self.shift()?;
+        let magnitude = shift.unsigned_abs();
+        let threshold = u32::from(self.config.threshold);
+        if self.alerted {
+            self.alerted = magnitude > threshold / 2;
+            return None;
+        }
+        self.alerted = magnitude > threshold;
+        self.alerted.then_some(Alert::CableDrift { shift })
+    }
+
+    /// Baseline is past the threshold and has not yet come back within half of it
+    pub fn is_shifted(&self) -> bool {
+        self.alerted
+    }
+
+    /// Slow baseline in raw counts
+    pub fn baseline(&self) -> Option<u16> {
+        self.state.map(|s| (s >> 16) as u16)
+    }
+
+    /// Baseline minus the reference, once the reference is captured
+    pub fn shift(&self) -> Option<i32> {
+        Some(i32::from(self.baseline()?) - i32::from(self.reference?))
+    }
+
+    /// Fast component of `raw`: its distance from the slow baseline
+    pub fn soil_signal(&self, raw: u16) -> Option<i32> {
+        Some(i32::from(raw) - i32::from(self.baseline()?))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{BaselineConfig, BaselineTracker, DriftDetector, DriftStatus};
+    use crate::alert::Alert;
+
+    #[test]
+    fn needs_a_full_window_before_judging() {
+        let mut detector = DriftDetector::new(2000, 50, 3);
+        assert_eq!(detector.update(2500), DriftStatus::Warming);
+        assert_eq!(detector.update(2500), DriftStatus::Warming);
+        assert_eq!(
+            detector.update(2500),
+            DriftStatus::RecalibrationRecommended { deviation: 500 }
+        );
+    }
+
+    #[test]
+    fn noise_around_reference_stays_stable() {
+        let mut detector = DriftDetector::new(2000, 50, 4);
+        for raw in [1960, 2040, 1980, 2020, 1990, 2010] {
+            assert!(!matches!(
+                detector.update(raw),
+                DriftStatus::RecalibrationRecommended { .. }
+            ));
+        }
+    }
+
+    /// Triangle wave of +/-300 counts with a 48-reading period: the soil
+    /// wetting and drying far faster than the baseline filter follows
+    fn soil(i: u32) -> i32 {
+        let phase = (i % 48) as i32;
+        let tri = if phase < 24 { phase } else { 48 - phase };
+        tri * 25 - 300
+    }
+
+    fn config() -> BaselineConfig {
+        BaselineConfig {
+            time_constant: 256,
+            settle: 2000,
+            threshold: 150,
+        }
+    }
+
+    #[test]
+    fn slow_baseline_shift_raises_one_alert() {
+        let mut tracker = BaselineTracker::new(config());
+        let mut alerts = Vec::new();
+        for i in 0..20_000u32 {
+            // Cable degradation adds one count every 25 readings after settling
+            let creep = i.saturating_sub(2000) as i32 / 25;
+            let raw = (2000 + soil(i) + creep) as u16;
+            if let Some(alert) = tracker.update(raw) {
+                alerts.push((i, alert));
+            }
+        }
+        assert_eq!(alerts.len(), 1, "{alerts:?}");
+        let (at, alert) = &alerts[0];
+        // Creep passes 150 counts near reading 5750; the filter lags a little
+        assert!((5700..6200).contains(at), "alerted at {at}");
+        assert!(matches!(alert, Alert::CableDrift { shift } if *shift > 150));
+        assert!(tracker.shift().unwrap() > 600);
+        // The soil signal is still seen on top of the raised baseline
+        let raw = tracker.baseline().unwrap() + 250;
+        assert!((230..=270).contains(&tracker.soil_signal(raw).unwrap()));
+    }
+
+    #[test]
+    fn fast_soil_changes_do_not_move_the_baseline() {
+        let mut tracker = BaselineTracker::new(config());
+        for i in 0..20_000u32 {
+            assert_eq!(tracker.update((2000 + soil(i)) as u16), None);
+        }
+        assert!(tracker.shift().unwrap().abs() < 30);
+    }
+}
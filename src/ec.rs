@@ -0,0 +1,124 @@
+//! Electrical conductivity (fertility) channel.
+
+use crate::alert::Alert;
+
+/// Two-point linear calibration from raw counts to uS/cm, typically taken
+/// in two reference solutions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcCalibration {
+    pub raw_low: u16,
+    pub us_cm_low: u16,
+    pub raw_high: u16,
+    pub us_cm_high: u16,
+}
+
+impl Default for EcCalibration {
+    /// Dry probe reads 0 uS/cm; the 1413 uS/cm standard solution reads ~2000 counts
+    fn default() -> Self {
+        Self {
+            raw_low: 0,
+            us_cm_low: 0,
+            raw_high: 2000,
+            us_cm_high: 1413,
+        }
+    }
+}
+
+impl EcCalibration {
+    /// Convert a raw reading to uS/cm, never below zero
+    pub fn to_us_cm(&self, raw: u16) -> u16 {
+        let dx = self.raw_high as i64 - self.raw_low as i64;
+        if dx == 0 {
+            return self.us_cm_low;
+        }
+        let dy = self.us_cm_high as i64 - self.us_cm_low as i64;
+        let ec = self.us_cm_low as i64 + (raw as i64 - self.raw_low as i64) * dy / dx;
+        ec.clamp(0, u16::MAX as i64) as u16
+    }
+}
+
+/// EC levels that trigger alerts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EcThresholds {
+    /// EC below this suggests the soil needs fertilizer
+    pub fertilize_below_us_cm: u16,
+}
+
+impl Default for EcThresholds {
+    fn default() -> Self {
+        Self {
+            fertilize_below_us_cm: 200,
+        }
+    }
+}
+
+/// Converted EC value plus any alert raised by it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EcSample {
+    pub us_cm: u16,
+    pub alert: Option<Alert>,
+}
+
+/// EC conversion and fertilize alerting, independent of the moisture path
+#[derive(Debug, Clone, Default)]
+pub struct EcChannel {
+    calibration: EcCalibration,
+    thresholds: EcThresholds,
+    low_fertility: bool,
+}
+
+impl EcChannel {
+    pub fn new(calibration: EcCalibration, thresholds: EcThresholds) -> Self {
+        Self {
+            calibration,
+            thresholds,
+            low_fertility: false,
+        }
+    }
+
+    /// Convert `raw`; the fertilize alert fires once per drop below the threshold
+    pub fn update(&mut self, raw: u16) -> EcSample {
+        let us_cm = self.calibration.to_us_cm(raw);
+        let below = us_cm < self.thresholds.fertilize_below_us_cm;
+        let alert = (below && !self.low_fertility).then_some(Alert::Fertilize { ec_us_cm: us_cm });
+        self.low_fertility = below;
+        EcSample { us_cm, alert }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{EcCalibration, EcChannel, EcThresholds};
+    use crate::alert::Alert;
+
+    #[test]
+    fn converts_with_two_point_calibration() {
+        let cal = EcCalibration {
+            raw_low: 100,
+            us_cm_low: 0,
+            raw_high: 2100,
+            us_cm_high: 1000,
+        };
+        assert_eq!(cal.to_us_cm(100), 0);
+        assert_eq!(cal.to_us_cm(1100), 500);
+        assert_eq!(cal.to_us_cm(2100), 1000);
+        assert_eq!(cal.to_us_cm(50), 0); // below the low point clamps to zero
+    }
+
+    #[test]
+    fn fertilize_alert_fires_once_on_crossing() {
+        let mut ec = EcChannel::new(
+            EcCalibration::default(),
+            EcThresholds {
+                fertilize_below_us_cm: 300,
+            },
+        );
+        // ~706 uS/cm, then ~282 uS/cm, then still low, then recovered
+        assert_eq!(ec.update(1000).alert, None);
+        let sample = ec.update(400);
+        assert_eq!(sample.alert, Some(Alert::Fertilize { ec_us_cm: 282 }));
+        assert_eq!(ec.update(380).alert, None);
+        assert_eq!(ec.update(1000).alert, None);
+        assert!(ec.update(400).alert.is_some());
+    }
+}
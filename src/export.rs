@@ -0,0 +1,275 @@
+//! Time-series CSV export of the reading history for graphing.
+//!
+//! [`resample`] puts readings on an even grid so plots line up across
+//! devices. Dropped readings (faults, sleeps) leave empty slots; short gaps
+//! are bridged by linear interpolation, long ones stay explicit nulls so a
+//! graph shows the outage instead of inventing data. Uptime restarts at
+//! each reboot, so the readings of each boot are resampled as a series of
+//! their own, never bridged to the previous boot's.
+//!
+//! Annotations are interleaved as their own rows, with only the timestamp
+//! and annotation columns filled.
+
+use crate::history::{History, HistoryEntry};
+use crate::reading::Annotation;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// CSV column header written by [`render_csv`]
+pub const CSV_HEADER: &str = "timestamp_s,moisture_percent,interpolated,annotation";
+
+/// How [`resample`] lays out the series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Grid spacing; several readings in one slot are averaged
+    pub step: Duration,
+    /// Longest gap between real readings that is interpolated across;
+    /// zero disables gap filling
+    pub max_gap: Duration,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            step: Duration::from_secs(60),
+            max_gap: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// One grid slot of the exported series
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeriesPoint {
+    /// Start of the slot, seconds since the boot it was read in
+    pub timestamp_s: u32,
+    /// `None` when the slot sits in a gap longer than the cutoff
+    pub moisture_percent: Option<u8>,
+    /// Value was interpolated rather than measured
+    pub interpolated: bool,
+}
+
+/// Resample `history` onto a grid of `options.step`, starting afresh at the
+/// oldest entry of each boot
+pub fn resample(history: &History, options: ExportOptions) -> Vec<SeriesPoint> {
+    let entries: Vec<HistoryEntry> = history.iter().copied().collect();
+    // A timestamp going backwards is a reboot restarting uptime
+    entries
+        .chunk_by(|a, b| b.timestamp_s >= a.timestamp_s)
+        .flat_map(|boot| resample_boot(boot, options))
+        .collect()
+}
+
+/// Resample one boot's entries, whose timestamps never go backwards
+fn resample_boot(entries: &[HistoryEntry], options: ExportOptions) -> Vec<SeriesPoint> {
+    let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+        return Vec::new();
+    };
+    let step = (options.step.as_secs() as u32).max(1);
+    let max_gap = options.max_gap.as_secs() as u32;
+    let start = first.timestamp_s;
+
+    let mut points = Vec::new();
+    let mut next = 0;
+    let mut slot = start;
+    while slot <= last.timestamp_s {
+        let slot_end = slot.saturating_add(step);
+        let in_slot = entries[next..]
+            .iter()
+            .take_while(|e| e.timestamp_s < slot_end)
+            .count();
+
+        let point = if in_slot > 0 {
+            let slot_entries = &entries[next..next + in_slot];
+            let sum: u32 = slot_entries.iter().map(|e| e.moisture_percent as u32).sum();
+            next += in_slot;
+            SeriesPoint {
+                timestamp_s: slot,
+                moisture_percent: Some((sum / in_slot as u32) as u8),
+                interpolated: false,
+            }
+        } else {
+            // An empty slot always has real readings on both sides
+            let before = &entries[next - 1];
+            let after = &entries[next];
+            let gap = after.timestamp_s - before.timestamp_s;
+            SeriesPoint {
+                timestamp_s: slot,
+                moisture_percent: (gap <= max_gap).then(|| interpolate(before, after, slot)),
+                interpolated: true,
+            }
+        };
+        points.push(point);
+        slot = slot_end;
+    }
+    points
+}
+
+/// Moisture on the straight line between two readings at `at`, rounded
+fn interpolate(before: &HistoryEntry, after: &HistoryEntry, at: u32) -> u8 {
+    let span = (after.timestamp_s - before.timestamp_s) as i32;
+    let elapsed = (at - before.timestamp_s) as i32;
+    let delta = after.moisture_percent as i32 - before.moisture_percent as i32;
+    let offset = (2 * delta * elapsed + delta.signum() * span) / (2 * span);
+    (before.moisture_percent as i32 + offset) as u8
+}
+
+/// Resampled history plus its annotations as CSV
+pub fn export_csv(history: &History, options: ExportOptions) -> String {
+    let annotations: Vec<Annotation> = history.annotations().cloned().collect();
+    render_csv(&resample(history, options), &annotations)
+}
+
+/// Render points as CSV with a header; nulls are empty fields. Annotations
+/// are placed in timestamp order among the points.
+pub fn render_csv(points: &[SeriesPoint], annotations: &[Annotation]) -> String {
+    let mut out = String::new();
+    let mut notes = annotations.iter().peekable();
+    let write_note = |out: &mut String, note: &Annotation| {
+        let _ = writeln!(out, "{},,,{}", note.timestamp_s, csv_field(note.text()));
+    };
+    // Writing to a String cannot fail
+    let _ = writeln!(out, "{CSV_HEADER}");
+    for point in points {
+        while let Some(note) = notes.next_if(|n| n.timestamp_s < point.timestamp_s) {
+            write_note(&mut out, note);
+        }
+        match point.moisture_percent {
+            Some(m) => {
+                let _ = writeln!(
+                    out,
+                    "{},{},{},",
+                    point.timestamp_s, m, point.interpolated as u8
+                );
+            }
+            None => {
+                let _ = writeln!(out, "{},,,", point.timestamp_s);
+            }
+        }
+    }
+    for note in notes {
+        write_note(&mut out, note);
+    }
+    out
+}
+
+/// Quote a free-text field, doubling embedded quotes
+fn csv_field(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{export_csv, render_csv, resample, ExportOptions, SeriesPoint};
+    use crate::history::{History, HistoryEntry};
+    use crate::reading::Annotation;
+    use std::time::Duration;
+
+    fn history(points: &[(u32, u8)]) -> History {
+        let mut history = History::new(32);
+        for &(timestamp_s, moisture_percent) in points {
+            history.push(HistoryEntry {
+                timestamp_s,
+                raw: 2000,
+                moisture_percent,
+                temperature_tenths_c: None,
+            });
+        }
+        history
+    }
+
+    fn options(step: u64, max_gap: u64) -> ExportOptions {
+        ExportOptions {
+            step: Duration::from_secs(step),
+            max_gap: Duration::from_secs(max_gap),
+        }
+    }
+
+    fn values(points: &[SeriesPoint]) -> Vec<Option<u8>> {
+        points.iter().map(|p| p.moisture_percent).collect()
+    }
+
+    #[test]
+    fn short_gap_is_interpolated() {
+        // Two readings dropped between 20s and 50s
+        let points = resample(
+            &history(&[(0, 40), (10, 40), (20, 40), (50, 70)]),
+            options(10, 30),
+        );
+        assert_eq!(
+            values(&points),
+            vec![Some(40), Some(40), Some(40), Some(50), Some(60), Some(70)]
+        );
+        let filled: Vec<bool> = points.iter().map(|p| p.interpolated).collect();
+        assert_eq!(filled, vec![false, false, false, true, true, false]);
+    }
+
+    #[test]
+    fn long_gap_is_left_null() {
+        let points = resample(&history(&[(0, 40), (40, 60)]), options(10, 30));
+        assert_eq!(values(&points), vec![Some(40), None, None, None, Some(60)]);
+        assert_eq!(
+            render_csv(&points[..2], &[]),
+            "timestamp_s,moisture_percent,interpolated,annotation\n0,40,0,\n10,,,\n"
+        );
+
+        // A zero cutoff never fills
+        let points = resample(&history(&[(0, 40), (20, 60)]), options(10, 0));
+        assert_eq!(values(&points), vec![Some(40), None, Some(60)]);
+    }
+
+    #[test]
+    fn readings_sharing_a_slot_are_averaged() {
+        let points = resample(&history(&[(0, 40), (30, 50), (45, 60)]), options(60, 0));
+        assert_eq!(values(&points), vec![Some(50)]);
+        assert!(resample(&History::new(4), ExportOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn each_boot_is_resampled_on_its_own() {
+        // Restored from before a reboot, then fresh uptime
+        let history = history(&[
+            (5000, 40),
+            (5060, 41),
+            (5120, 42),
+            (10, 45),
+            (70, 46),
+            (130, 47),
+        ]);
+        let points = resample(&history, options(60, 300));
+        let stamps: Vec<u32> = points.iter().map(|p| p.timestamp_s).collect();
+        assert_eq!(stamps, vec![5000, 5060, 5120, 10, 70, 130]);
+        assert_eq!(
+            values(&points),
+            vec![Some(40), Some(41), Some(42), Some(45), Some(46), Some(47)]
+        );
+        assert!(points.iter().all(|p| !p.interpolated));
+    }
+
+    #[test]
+    fn annotations_appear_as_their_own_rows() {
+        let mut history = history(&[(0, 40), (60, 42), (120, 55)]);
+        history.annotate(Annotation::new(
+            Duration::from_secs(75),
+            "added \"fertilizer\"",
+        ));
+        history.annotate(Annotation::new(Duration::from_secs(500), "repotted\nbed 2"));
+        assert_eq!(
+            export_csv(&history, options(60, 0)),
+            "timestamp_s,moisture_percent,interpolated,annotation\n\
+             0,40,0,\n\
+             60,42,0,\n\
+             75,,,\"added \"\"fertilizer\"\"\"\n\
+             120,55,0,\n\
+             500,,,\"repotted bed 2\"\n"
+        );
+    }
+
+    #[test]
+    fn annotation_text_is_capped() {
+        let long = "x".repeat(100);
+        assert_eq!(Annotation::new(Duration::ZERO, &long).text().len(), 64);
+        // Never splits a multi-byte character
+        let accented = "é".repeat(40);
+        assert_eq!(Annotation::new(Duration::ZERO, &accented).text().len(), 64);
+    }
+}
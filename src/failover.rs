@@ -0,0 +1,348 @@
+//! Primary and backup probes, with control following the backup while the
+//! primary is faulty.
+//!
+//! A primary read is faulty when it errors or falls outside the valid raw
+//! range. Failing over takes several faults in a row and failing back several
+//! good reads in a row, and any fault restarts the recovery count, so a
+//! flapping primary stays on the backup instead of dragging control back and
+//! forth. The primary is still read while on the backup, to spot recovery.
+//!
+//! The backup is usually a different probe with its own calibration, so its
+//! readings are rescaled onto the primary's raw scale before being returned;
+//! downstream conversion keeps using the primary's calibration throughout.
+
+use crate::moisture::{rescale_raw, Calibration, ProbeKind};
+use crate::sensor::SoilSensor;
+use anyhow::{anyhow, bail, Result};
+use log::{info, warn};
+
+/// When to switch between the probes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailoverPolicy {
+    /// Consecutive primary faults before switching to the backup
+    pub fail_after: u32,
+    /// Consecutive good primary reads before switching back
+    pub recover_after: u32,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            fail_after: 3,
+            recover_after: 5,
+        }
+    }
+}
+
+/// Probe control is following
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeSource {
+    Primary,
+    Backup,
+}
+
+/// Reads the primary probe, or the backup while the primary is failed over
+pub struct FailoverSensor<P, B> {
+    primary: P,
+    backup: B,
+    policy: FailoverPolicy,
+    /// Raw values outside this are primary faults
+    valid_range: (u16, u16),
+    /// Primary and backup calibrations and curves; `None` returns backup raw
+    /// values as read
+    calibrations: Option<((Calibration, ProbeKind), (Calibration, ProbeKind))>,
+    active: ProbeSource,
+    /// Consecutive faults while on the primary, good reads while on the backup
+    streak: u32,
+    switches: u32,
+}
+
+impl<P: SoilSensor, B: SoilSensor> FailoverSensor<P, B> {
+    pub fn new(primary: P, backup: B, policy: FailoverPolicy) -> Self {
+        Self {
+            primary,
+            backup,
+            policy,
+            valid_range: (0, u16::MAX),
+            calibrations: None,
+            active: ProbeSource::Primary,
+            streak: 0,
+            switches: 0,
+        }
+    }
+
+    /// Treat primary readings outside `min..=max` as faults
+    pub fn with_valid_range(mut self, min: u16, max: u16) -> Self {
+        self.valid_range = (min, max);
+        self
+    }
+
+    /// Convert backup readings from the backup probe's calibration and curve
+    /// to the primary's
+    pub fn with_calibrations(
+        mut self,
+        primary: (Calibration, ProbeKind),
+        backup: (Calibration, ProbeKind),
+    ) -> Self {
+        self.calibrations = Some((primary, backup));
+        self
+    }
+
+    pub fn active(&self) -> ProbeSource {
+        self.active
+    }
+
+    /// Failovers plus failbacks since startup
+    pub fn switches(&self) -> u32 {
+        self.switches
+    }
+
+    /// Read the primary, then the backup if the primary is failed over or
+    /// this read of it was faulty
+    fn read_from(
+        &mut self,
+        primary: impl FnOnce(&mut P) -> Result<(u16, Option<u16>)>,
+        backup: impl FnOnce(&mut B) -> Result<(u16, Option<u16>)>,
+    ) -> Result<(u16, Option<u16>)> {
+        let (min, max) = self.valid_range;
+        let read = match primary(&mut self.primary) {
+            Ok(reading) if (min..=max).contains(&reading.0) => Ok(reading),
+            Ok((raw, _)) => Err(anyhow!("raw {raw} outside {min}..={max}")),
+            Err(e) => Err(e),
+        };
+        self.update(read.is_ok());
+        match (self.active, read) {
+            (ProbeSource::Primary, Ok(reading)) => Ok(reading),
+            (_, primary_read) => match backup(&mut self.backup) {
+                Ok(reading) => Ok(self.to_primary_scale(reading)),
+                Err(e) => match primary_read {
+                    // Better a primary reading that is failed over for now
+                    // than none at all
+                    Ok(reading) => Ok(reading),
+                    Err(primary_err) => {
+                        bail!("primary ({primary_err}) and backup ({e}) probes both failed")
+                    }
+                },
+            },
+        }
+    }
+
+    /// Rescale a backup reading and its spread onto the primary's raw scale
+    fn to_primary_scale(&self, (raw, spread): (u16, Option<u16>)) -> (u16, Option<u16>) {
+        let Some(((primary, primary_kind), (backup, backup_kind))) = &self.calibrations else {
+            return (raw, spread);
+        };
+        let rescale = |raw| rescale_raw(raw, backup, *backup_kind, primary, *primary_kind);
+        // Both ends of the spread, since the curves' slopes differ along the scale
+        let spread = spread.map(|spread| {
+            let dry_side = rescale(raw.saturating_sub(spread));
+            let wet_side = rescale(raw.saturating_add(spread));
+            dry_side.abs_diff(wet_side).div_ceil(2)
+        });
+        (rescale(raw), spread)
+    }
+
+    /// Advance the streaks for one primary read and switch when one completes
+    fn update(&mut self, primary_ok: bool) {
+        match self.active {
+            ProbeSource::Primary if primary_ok => self.streak = 0,
+            ProbeSource::Primary => {
+                self.streak += 1;
+                if self.streak >= self.policy.fail_after {
+                    warn!(
+                        "Primary probe failed {} reads in a row, failing over to backup",
+                        self.streak
+                    );
+                    self.switch(ProbeSource::Backup);
+                }
+            }
+            ProbeSource::Backup if !primary_ok => self.streak = 0,
+            ProbeSource::Backup => {
+                self.streak += 1;
+                if self.streak >= self.policy.recover_after {
+                    info!(
+                        "Primary probe good for {} reads, failing back from backup",
+                        self.streak
+                    );
+                    self.switch(ProbeSource::Primary);
+                }
+            }
+        }
+    }
+
+    fn switch(&mut self, to: ProbeSource) {
+        self.active = to;
+        self.streak = 0;
+        self.switches = self.switches.saturating_add(1);
+    }
+}
+
+impl<P: SoilSensor, B: SoilSensor> SoilSensor for FailoverSensor<P, B> {
+    fn read_averaged(&mut self, samples: usize) -> Result<u16> {
+        self.read_from(
+            |p| Ok((p.read_averaged(samples)?, None)),
+            |b| Ok((b.read_averaged(samples)?, None)),
+        )
+        .map(|(raw, _)| raw)
+    }
+
+    fn read_with_spread(&mut self, samples: usize) -> Result<(u16, Option<u16>)> {
+        self.read_from(
+            |p| p.read_with_spread(samples),
+            |b| b.read_with_spread(samples),
+        )
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{FailoverPolicy, FailoverSensor, ProbeSource};
+    use crate::moisture::{raw_to_moisture_percent, Calibration, ProbeKind};
+    use crate::sensor::SoilSensor;
+    use anyhow::{bail, Result};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Probe whose next value the test sets; `None` fails the read
+    #[derive(Clone, Default)]
+    struct Settable(Rc<Cell<Option<u16>>>);
+
+    impl SoilSensor for Settable {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            match self.0.get() {
+                Some(raw) => Ok(raw),
+                None => bail!("no response"),
+            }
+        }
+    }
+
+    const PRIMARY: u16 = 2100;
+    const BACKUP: u16 = 2200;
+
+    fn sensors() -> (Settable, FailoverSensor<Settable, Settable>) {
+        let (primary, backup) = (Settable::default(), Settable::default());
+        primary.0.set(Some(PRIMARY));
+        backup.0.set(Some(BACKUP));
+        let sensor = FailoverSensor::new(primary.clone(), backup, FailoverPolicy::default())
+            .with_valid_range(500, 3500);
+        (primary, sensor)
+    }
+
+    #[test]
+    fn fails_over_after_consecutive_faults_and_back_after_recovery() {
+        let (primary, mut sensor) = sensors();
+        assert_eq!(sensor.read_averaged(1).unwrap(), PRIMARY);
+
+        // Out of range counts like a failed read
+        primary.0.set(Some(4095));
+        for _ in 0..2 {
+            // Each faulty read is covered by the backup before failing over
+            assert_eq!(sensor.read_averaged(1).unwrap(), BACKUP);
+            assert_eq!(sensor.active(), ProbeSource::Primary);
+        }
+        primary.0.set(None);
+        assert_eq!(sensor.read_averaged(1).unwrap(), BACKUP);
+        assert_eq!(sensor.active(), ProbeSource::Backup);
+
+        primary.0.set(Some(PRIMARY));
+        for _ in 0..4 {
+            assert_eq!(sensor.read_averaged(1).unwrap(), BACKUP);
+        }
+        // The fifth good read completes the recovery and is used directly
+        assert_eq!(sensor.read_averaged(1).unwrap(), PRIMARY);
+        assert_eq!(sensor.active(), ProbeSource::Primary);
+        assert_eq!(sensor.switches(), 2);
+    }
+
+    #[test]
+    fn flapping_primary_stays_on_backup() {
+        let (primary, mut sensor) = sensors();
+        primary.0.set(None);
+        for _ in 0..3 {
+            sensor.read_averaged(1).unwrap();
+        }
+        assert_eq!(sensor.active(), ProbeSource::Backup);
+
+        // Good for a few reads, then faulty again, over and over
+        for _ in 0..10 {
+            for good in [true, true, true, false] {
+                primary.0.set(good.then_some(PRIMARY));
+                assert_eq!(sensor.read_averaged(1).unwrap(), BACKUP);
+            }
+        }
+        assert_eq!(sensor.active(), ProbeSource::Backup);
+        assert_eq!(sensor.switches(), 1);
+
+        // Isolated glitches on a healthy primary never fail over either
+        let (primary, mut sensor) = sensors();
+        for _ in 0..10 {
+            primary.0.set(None);
+            sensor.read_averaged(1).unwrap();
+            primary.0.set(Some(PRIMARY));
+            sensor.read_averaged(1).unwrap();
+        }
+        assert_eq!(sensor.switches(), 0);
+    }
+
+    #[test]
+    fn backup_readings_convert_with_the_backup_calibration() {
+        let (primary_cal, backup_cal) =
+            (Calibration::new(3000, 1200), Calibration::new(2600, 1800));
+        let (primary, backup) = (Settable::default(), Settable::default());
+        let mut sensor =
+            FailoverSensor::new(primary.clone(), backup.clone(), FailoverPolicy::default())
+                .with_calibrations(
+                    (primary_cal, ProbeKind::Capacitive),
+                    (backup_cal, ProbeKind::Capacitive),
+                );
+        // 50% on the backup's own scale
+        backup.0.set(Some(2200));
+        primary.0.set(None);
+        let (raw, _) = sensor.read_with_spread(1).unwrap();
+        assert_eq!(raw, 2100);
+        assert_eq!(raw_to_moisture_percent(raw, &primary_cal), 50);
+
+        // Output correction on either side is honoured
+        let corrected = backup_cal.with_output_correction(-5, 1000);
+        let mut sensor = FailoverSensor::new(primary.clone(), backup, FailoverPolicy::default())
+            .with_calibrations(
+                (primary_cal, ProbeKind::Capacitive),
+                (corrected, ProbeKind::Capacitive),
+            );
+        let raw = sensor.read_averaged(1).unwrap();
+        assert_eq!(raw_to_moisture_percent(raw, &primary_cal), 45);
+
+        // The primary's own readings are returned as read
+        primary.0.set(Some(2500));
+        assert_eq!(sensor.read_averaged(1).unwrap(), 2500);
+    }
+
+    #[test]
+    fn backup_readings_follow_the_backup_curve() {
+        let cal = Calibration::new(3000, 1200);
+        let (primary, backup) = (Settable::default(), Settable::default());
+        let mut sensor =
+            FailoverSensor::new(primary.clone(), backup.clone(), FailoverPolicy::default())
+                .with_calibrations((cal, ProbeKind::Capacitive), (cal, ProbeKind::Resistive));
+        // Half way on a resistive probe is only a quarter wet
+        backup.0.set(Some(2100));
+        primary.0.set(None);
+        let (raw, spread) = sensor.read_with_spread(1).unwrap();
+        assert_eq!(raw, 2550);
+        assert_eq!(ProbeKind::Capacitive.moisture_percent(raw, &cal), 25);
+        assert_eq!(
+            ProbeKind::Resistive.moisture_percent(2100, &cal),
+            ProbeKind::Capacitive.moisture_percent(raw, &cal)
+        );
+        assert!(spread.is_none());
+    }
+
+    #[test]
+    fn errors_only_when_both_probes_fail() {
+        let (primary, mut sensor) = sensors();
+        sensor.backup.0.set(None);
+        assert_eq!(sensor.read_averaged(1).unwrap(), PRIMARY);
+        primary.0.set(None);
+        assert!(sensor.read_averaged(1).is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! Plausibility checks that tell a broken probe apart from real soil readings.
+
+use crate::alert::Alert;
+use crate::clock::Clock;
+use crate::moisture::{Calibration, ProbeKind};
+use std::fmt;
+use std::time::Duration;
+
+/// Readings below this usually mean a shorted probe or broken ground
+pub const FAULT_RAW_MIN: u16 = 200;
+/// Readings above this usually mean a disconnected probe floating near full scale
+pub const FAULT_RAW_MAX: u16 = 4000;
+/// Identical consecutive readings after which a probe is considered stuck
+pub const STUCK_READINGS: u32 = 10;
+/// Full-scale value of the ESP32's 12-bit ADC
+pub const ADC_MAX_12BIT: u16 = 4095;
+/// Time without a valid reading after which the probe is considered dead
+pub const DEAD_PROBE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Reason a raw reading was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorFault {
+    /// Raw value outside the calibration's plausible range
+    OutOfRange { raw: u16, min: u16, max: u16 },
+    /// Response frame failed its checksum
+    BadCrc { expected: u16, actual: u16 },
+    /// Response frame was short or did not match the request
+    MalformedResponse,
+    /// Same raw value `repeats` times in a row; real soil is never that quiet
+    Stuck { raw: u16, repeats: u32 },
+    /// Moisture fell faster than the soil type can dry out
+    ImplausibleDrying {
+        rate_milli_per_hour: i32,
+        limit: i32,
+    },
+}
+
+impl fmt::Display for SensorFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorFault::OutOfRange { raw, min, max } => {
+                write!(f, "raw reading {raw} outside valid range {min}..={max}")
+            }
+            SensorFault::BadCrc { expected, actual } => {
+                write!(
+                    f,
+                    "CRC mismatch (expected {expected:#06x}, got {actual:#06x})"
+                )
+            }
+            SensorFault::MalformedResponse => write!(f, "malformed sensor response"),
+            SensorFault::Stuck { raw, repeats } => {
+                write!(f, "raw reading stuck at {raw} for {repeats} readings")
+            }
+            SensorFault::ImplausibleDrying {
+                rate_milli_per_hour,
+                limit,
+            } => write!(
+                f,
+                "moisture falling {} m%/h, faster than the {limit} m%/h this soil can dry",
+                rate_milli_per_hour.saturating_neg()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SensorFault {}
+
+/// Flags implausible raw readings and keeps a running fault count
+#[derive(Debug, Default)]
+pub struct FaultDetector {
+    fault_count: u32,
+    probe: ProbeKind,
+}
+
+impl FaultDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `probe`'s fault bounds where the calibration sets none
+    pub fn with_probe_kind(mut self, probe: ProbeKind) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// Check a raw reading against the calibration's valid range
+    pub fn check(&mut self, raw: u16, cal: &Calibration) -> Result<u16, SensorFault> {
+        let (min, max) = cal.valid_range_for(self.probe);
+        if raw < min || raw > max {
+            self.fault_count = self.fault_count.saturating_add(1);
+            return Err(SensorFault::OutOfRange { raw, min, max });
+        }
+        Ok(raw)
+    }
+
+    /// Total readings rejected since startup
+    pub fn fault_count(&self) -> u32 {
+        self.fault_count
+    }
+}
+
+/// Flags a probe that keeps returning exactly the same raw value
+#[derive(Debug, Clone)]
+pub struct StuckDetector {
+    max_repeats: u32,
+    last: Option<u16>,
+    repeats: u32,
+}
+
+impl StuckDetector {
+    /// Fault once `max_repeats` consecutive readings are identical (at least 2)
+    pub fn new(max_repeats: u32) -> Self {
+        Self {
+            max_repeats: max_repeats.max(2),
+            last: None,
+            repeats: 0,
+        }
+    }
+
+    /// Feed the next raw reading; keeps failing until the value changes
+    pub fn check(&mut self, raw: u16) -> Result<u16, SensorFault> {
+        if self.last == Some(raw) {
+            self.repeats = self.repeats.saturating_add(1);
+        } else {
+            self.last = Some(raw);
+            self.repeats = 1;
+        }
+        if self.repeats >= self.max_repeats {
+            return Err(SensorFault::Stuck {
+                raw,
+                repeats: self.repeats,
+            });
+        }
+        Ok(raw)
+    }
+}
+
+impl Default for StuckDetector {
+    fn default() -> Self {
+        Self::new(STUCK_READINGS)
+    }
+}
+
+/// Counts raw readings pinned at ADC full scale, which points at wiring or
+/// attenuation problems rather than soil; separate from calibration clipping
+#[derive(Debug, Clone)]
+pub struct SaturationCounter {
+    adc_max: u16,
+    count: u32,
+}
+
+impl SaturationCounter {
+    pub fn new(adc_max: u16) -> Self {
+        Self { adc_max, count: 0 }
+    }
+
+    /// Feed a raw reading; returns whether it was saturated
+    pub fn record(&mut self, raw: u16) -> bool {
+        let saturated = raw >= self.adc_max;
+        if saturated {
+            self.count = self.count.saturating_add(1);
+        }
+        saturated
+    }
+
+    /// Saturated readings so far
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+impl Default for SaturationCounter {
+    fn default() -> Self {
+        Self::new(ADC_MAX_12BIT)
+    }
+}
+
+/// Change in probe health reported by [`DeadProbeMonitor::record`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProbeTransition {
+    /// Timeout passed without a valid reading
+    EnteredSafeMode(Alert),
+    /// A valid reading arrived while in safe mode
+    Recovered,
+}
+
+/// Enters safe mode once no valid reading has been seen for `timeout`.
+///
+/// Unlike per-reading faults this only trips when the probe is silent or
+/// implausible for a sustained period; one valid reading exits safe mode.
+pub struct DeadProbeMonitor<C> {
+    clock: C,
+    timeout: Duration,
+    last_valid: Duration,
+    safe_mode: bool,
+}
+
+impl<C: Clock> DeadProbeMonitor<C> {
+    /// The timeout starts counting now, so a probe that never answers
+    /// after boot is caught too
+    pub fn new(clock: C, timeout: Duration) -> Self {
+        let last_valid = clock.now();
+        Self {
+            clock,
+            timeout,
+            last_valid,
+            safe_mode: false,
+        }
+    }
+
+    /// Note whether the latest attempt produced a valid reading
+    pub fn record(&mut self, valid: bool) -> Option<ProbeTransition> {
+        let now = self.clock.now();
+        if valid {
+            self.last_valid = now;
+            if self.safe_mode {
+                self.safe_mode = false;
+                return Some(ProbeTransition::Recovered);
+            }
+            return None;
+        }
+        let silent = now.saturating_sub(self.last_valid);
+        if !self.safe_mode && silent >= self.timeout {
+            self.safe_mode = true;
+            return Some(ProbeTransition::EnteredSafeMode(Alert::ProbeDead {
+                silent_s: silent.as_secs(),
+            }));
+        }
+        None
+    }
+
+    pub fn is_safe_mode(&self) -> bool {
+        self.safe_mode
+    }
+
+    /// Alert that stays raised for as long as safe mode lasts
+    pub fn active_alert(&self) -> Option<Alert> {
+        self.safe_mode.then(|| Alert::ProbeDead {
+            silent_s: self.clock.now().saturating_sub(self.last_valid).as_secs(),
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        DeadProbeMonitor, FaultDetector, ProbeTransition, SaturationCounter, SensorFault,
+        StuckDetector, FAULT_RAW_MAX, FAULT_RAW_MIN,
+    };
+    use crate::alert::Alert;
+    use crate::clock::MockClock;
+    use crate::moisture::{Calibration, ProbeKind};
+    use std::time::Duration;
+
+    #[test]
+    fn global_bounds_apply_without_a_per_calibration_range() {
+        let mut detector = FaultDetector::new();
+        let cal = Calibration::default();
+        assert!(detector.check(FAULT_RAW_MIN, &cal).is_ok());
+        assert!(detector.check(FAULT_RAW_MAX, &cal).is_ok());
+        assert!(detector.check(FAULT_RAW_MAX + 1, &cal).is_err());
+        assert!(detector.check(FAULT_RAW_MIN - 1, &cal).is_err());
+        assert_eq!(detector.fault_count(), 2);
+
+        // A dry resistive probe sits near full scale; only a short is a fault
+        let mut resistive = FaultDetector::new().with_probe_kind(ProbeKind::Resistive);
+        assert!(resistive.check(FAULT_RAW_MAX + 50, &cal).is_ok());
+        assert!(resistive.check(FAULT_RAW_MIN - 1, &cal).is_err());
+    }
+
+    #[test]
+    fn tighter_calibration_range_flags_otherwise_valid_reading() {
+        let mut detector = FaultDetector::new();
+        let loose = Calibration::default();
+        let tight = Calibration::default().with_valid_range(1000, 3200);
+
+        assert_eq!(detector.check(3500, &loose), Ok(3500));
+        assert_eq!(
+            detector.check(3500, &tight),
+            Err(SensorFault::OutOfRange {
+                raw: 3500,
+                min: 1000,
+                max: 3200
+            })
+        );
+    }
+
+    #[test]
+    fn constant_series_trips_stuck_detector() {
+        let mut stuck = StuckDetector::new(5);
+        for _ in 0..4 {
+            assert_eq!(stuck.check(2048), Ok(2048));
+        }
+        assert_eq!(
+            stuck.check(2048),
+            Err(SensorFault::Stuck {
+                raw: 2048,
+                repeats: 5
+            })
+        );
+        assert!(stuck.check(2048).is_err());
+        // Any movement clears it
+        assert_eq!(stuck.check(2049), Ok(2049));
+    }
+
+    #[test]
+    fn slightly_noisy_series_does_not_trip() {
+        let mut stuck = StuckDetector::new(5);
+        for raw in [2048, 2048, 2049, 2049, 2048, 2047, 2048, 2048, 2048, 2049]
+            .iter()
+            .cycle()
+            .take(200)
+        {
+            assert!(stuck.check(*raw).is_ok());
+        }
+    }
+
+    #[test]
+    fn saturation_counter_counts_only_full_scale() {
+        let mut sat = SaturationCounter::default();
+        for raw in [4094, 4095, 2000, 4095, 0] {
+            sat.record(raw);
+        }
+        assert_eq!(sat.count(), 2);
+
+        // Attenuation or bit-width can lower full scale
+        let mut low = SaturationCounter::new(1023);
+        assert!(low.record(1023));
+        assert!(!low.record(1022));
+        assert_eq!(low.count(), 1);
+    }
+
+    #[test]
+    fn dead_probe_enters_safe_mode_after_timeout_and_recovers() {
+        let clock = MockClock::new();
+        let mut monitor = DeadProbeMonitor::new(clock.clone(), Duration::from_secs(600));
+
+        // Transient errors inside the timeout are tolerated
+        for _ in 0..9 {
+            clock.advance(Duration::from_secs(60));
+            assert_eq!(monitor.record(false), None);
+        }
+        assert!(!monitor.is_safe_mode());
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(
+            monitor.record(false),
+            Some(ProbeTransition::EnteredSafeMode(Alert::ProbeDead {
+                silent_s: 600
+            }))
+        );
+        // Entered once; the alert persists while dead
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(monitor.record(false), None);
+        assert_eq!(
+            monitor.active_alert(),
+            Some(Alert::ProbeDead { silent_s: 660 })
+        );
+
+        assert_eq!(monitor.record(true), Some(ProbeTransition::Recovered));
+        assert!(!monitor.is_safe_mode());
+        assert_eq!(monitor.active_alert(), None);
+    }
+
+    #[test]
+    fn valid_readings_keep_restarting_the_timeout() {
+        let clock = MockClock::new();
+        let mut monitor = DeadProbeMonitor::new(clock.clone(), Duration::from_secs(600));
+        for _ in 0..20 {
+            clock.advance(Duration::from_secs(500));
+            assert_eq!(monitor.record(false), None);
+            assert_eq!(monitor.record(true), None);
+        }
+    }
+}
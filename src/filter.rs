@@ -0,0 +1,473 @@
+//! Smoothing filters applied to raw readings before conversion.
+//!
+//! The firmware's paths use the fixed-point variants; the floating point
+//! ones stay available as references for them.
+
+/// Something that happened outside the filter that may invalidate its history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The pump was switched on, so moisture is about to step up
+    PumpActivated,
+    PumpDeactivated,
+}
+
+/// Stateful smoothing stage over raw readings
+pub trait Filter {
+    /// Feed one raw reading and get the filtered value
+    fn update(&mut self, raw: u16) -> u16;
+
+    /// Forget all history
+    fn reset(&mut self);
+
+    /// React to an outside event; ignored by default
+    fn notify_event(&mut self, _event: Event) {}
+}
+
+/// How an EMA responds to [`Event::PumpActivated`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WateringResponse {
+    /// Keep smoothing as usual
+    #[default]
+    Ignore,
+    /// Drop history so the next reading is taken as-is
+    Reset,
+    /// Use `alpha_q8` (out of 256) instead of the normal factor for the next `samples` readings
+    FastAdapt { alpha_q8: u16, samples: u16 },
+}
+
+/// Fractional bits used by the fixed-point filters (Q8)
+pub const FIXED_SHIFT: u32 = 8;
+/// 1.0 in Q8
+pub const FIXED_ONE: u32 = 1 << FIXED_SHIFT;
+
+/// Exponential moving average in Q8 fixed point.
+///
+/// Tracks the floating point [`Ema`] to within 1 raw count when `alpha_q8`
+/// is the float alpha scaled by 256.
+#[derive(Debug, Clone)]
+pub struct FixedEma {
+    alpha_q8: u32,
+    state_q8: Option<u32>,
+    on_watering: WateringResponse,
+    /// Readings left at the fast-adapt factor
+    fast_remaining: u16,
+}
+
+impl FixedEma {
+    /// `alpha_q8` is the smoothing factor out of 256 (256 = no smoothing)
+    pub fn new(alpha_q8: u16) -> Self {
+        Self {
+            alpha_q8: (alpha_q8 as u32).clamp(1, FIXED_ONE),
+            state_q8: None,
+            on_watering: WateringResponse::Ignore,
+            fast_remaining: 0,
+        }
+    }
+
+    /// Catch up quickly after the pump runs instead of lagging the new level
+    pub fn with_watering_response(mut self, response: WateringResponse) -> Self {
+        self.on_watering = response;
+        self
+    }
+
+    fn current_alpha_q8(&mut self) -> u32 {
+        match self.on_watering {
+            WateringResponse::FastAdapt { alpha_q8, .. } if self.fast_remaining > 0 => {
+                self.fast_remaining -= 1;
+                (alpha_q8 as u32).clamp(1, FIXED_ONE)
+            }
+            _ => self.alpha_q8,
+        }
+    }
+}
+
+impl Filter for FixedEma {
+    fn update(&mut self, raw: u16) -> u16 {
+        let sample = (raw as u32) << FIXED_SHIFT;
+        let alpha_q8 = self.current_alpha_q8();
+        let next = match self.state_q8 {
+            None => sample,
+            Some(prev) => {
+                // prev + alpha * (sample - prev), kept in signed space
+                let delta = sample as i64 - prev as i64;
+                (prev as i64 + delta * alpha_q8 as i64 / FIXED_ONE as i64) as u32
+            }
+        };
+        self.state_q8 = Some(next);
+        // Round to nearest raw count
+        ((next + FIXED_ONE / 2) >> FIXED_SHIFT) as u16
+    }
+
+    fn reset(&mut self) {
+        self.state_q8 = None;
+        self.fast_remaining = 0;
+    }
+
+    fn notify_event(&mut self, event: Event) {
+        if event != Event::PumpActivated {
+            return;
+        }
+        match self.on_watering {
+            WateringResponse::Ignore => {}
+            WateringResponse::Reset => self.state_q8 = None,
+            WateringResponse::FastAdapt { samples, .. } => self.fast_remaining = samples,
+        }
+    }
+}
+
+/// Filters applied in sequence; an empty chain passes readings through
+#[derive(Default)]
+pub struct FilterChain {
+    stages: Vec<Box<dyn Filter + Send>>,
+}
+
+impl FilterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a stage after the existing ones
+    pub fn then(mut self, filter: impl Filter + Send + 'static) -> Self {
+        self.stages.push(Box::new(filter));
+        self
+    }
+}
+
+impl Filter for FilterChain {
+    fn update(&mut self, raw: u16) -> u16 {
+        self.stages.iter_mut().fold(raw, |value, f| f.update(value))
+    }
+
+    fn reset(&mut self) {
+        self.stages.iter_mut().for_each(|f| f.reset());
+    }
+
+    fn notify_event(&mut self, event: Event) {
+        self.stages.iter_mut().for_each(|f| f.notify_event(event));
+    }
+}
+
+/// Same raw reading filtered two ways
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilteredPair {
+    /// Heavily smoothed value for the sink and status display
+    pub display: u16,
+    /// Responsive value for pump and alert decisions
+    pub control: u16,
+}
+
+/// Independent display and control filter chains fed from one raw reading
+#[derive(Default)]
+pub struct DualFilter {
+    display: FilterChain,
+    control: FilterChain,
+}
+
+impl DualFilter {
+    pub fn new(display: FilterChain, control: FilterChain) -> Self {
+        Self { display, control }
+    }
+
+    pub fn update(&mut self, raw: u16) -> FilteredPair {
+        FilteredPair {
+            display: self.display.update(raw),
+            control: self.control.update(raw),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.display.reset();
+        self.control.reset();
+    }
+
+    /// Forward `event` to both chains
+    pub fn notify_event(&mut self, event: Event) {
+        self.display.notify_event(event);
+        self.control.notify_event(event);
+    }
+}
+
+/// Exponential moving average with a floating point smoothing factor
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: f32,
+    state: Option<f32>,
+}
+
+impl Ema {
+    /// `alpha` in `(0, 1]`; smaller is smoother
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(f32::EPSILON, 1.0),
+            state: None,
+        }
+    }
+}
+
+impl Filter for Ema {
+    fn update(&mut self, raw: u16) -> u16 {
+        let sample = raw as f32;
+        let next = match self.state {
+            None => sample,
+            Some(prev) => prev + self.alpha * (sample - prev),
+        };
+        self.state = Some(next);
+        next.round() as u16
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// Innovations beyond this many standard deviations count as a genuine shift
+pub const KALMAN_SHIFT_GATE: f32 = 3.0;
+
+/// One-dimensional Kalman filter over raw counts.
+///
+/// The gain follows the estimate's uncertainty, so it settles low while the
+/// soil is stable. A reading further than the shift gate from the estimate
+/// (e.g. after watering) inflates the uncertainty and the filter jumps to
+/// the new level instead of lagging it like an EMA.
+#[derive(Debug, Clone)]
+pub struct KalmanFilter {
+    /// Expected drift of the true value per reading, as a variance in counts²
+    process_noise: f32,
+    /// Variance of the sensor noise in counts²
+    measurement_noise: f32,
+    shift_gate: f32,
+    estimate: Option<f32>,
+    variance: f32,
+}
+
+impl KalmanFilter {
+    /// Both noise parameters are variances in raw counts²
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        Self {
+            process_noise: process_noise.max(0.0),
+            measurement_noise: measurement_noise.max(f32::EPSILON),
+            shift_gate: KALMAN_SHIFT_GATE,
+            estimate: None,
+            variance: 0.0,
+        }
+    }
+
+    /// Standard deviations an innovation must exceed to count as a shift;
+    /// `f32::INFINITY` disables shift detection
+    pub fn with_shift_gate(mut self, sigmas: f32) -> Self {
+        self.shift_gate = sigmas.max(0.0);
+        self
+    }
+
+    /// Current gain applied to the innovation, for diagnostics
+    pub fn gain(&self) -> f32 {
+        let p = self.variance + self.process_noise;
+        p / (p + self.measurement_noise)
+    }
+}
+
+impl Filter for KalmanFilter {
+    fn update(&mut self, raw: u16) -> u16 {
+        let sample = raw as f32;
+        let Some(prev) = self.estimate else {
+            self.estimate = Some(sample);
+            self.variance = self.measurement_noise;
+            return raw;
+        };
+        let mut predicted = self.variance + self.process_noise;
+        let innovation = sample - prev;
+        let spread = self.shift_gate * self.shift_gate * (predicted + self.measurement_noise);
+        if innovation * innovation > spread {
+            predicted += innovation * innovation;
+        }
+        let gain = predicted / (predicted + self.measurement_noise);
+        let next = prev + gain * innovation;
+        self.estimate = Some(next);
+        self.variance = (1.0 - gain) * predicted;
+        next.round() as u16
+    }
+
+    fn reset(&mut self) {
+        self.estimate = None;
+        self.variance = 0.0;
+    }
+
+    fn notify_event(&mut self, event: Event) {
+        // Watering is a known shift: distrust the old level before it shows
+        if event == Event::PumpActivated && self.estimate.is_some() {
+            self.variance += 10.0 * self.measurement_noise;
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{DualFilter, Event, Filter, FilterChain, FixedEma, WateringResponse};
+
+    #[test]
+    fn fixed_ema_starts_at_first_sample_and_converges() {
+        let mut ema = FixedEma::new(64); // alpha = 0.25
+        assert_eq!(ema.update(2000), 2000);
+        assert_eq!(ema.update(1000), 1750);
+        for _ in 0..50 {
+            ema.update(1000);
+        }
+        assert_eq!(ema.update(1000), 1000);
+        ema.reset();
+        assert_eq!(ema.update(3000), 3000);
+    }
+
+    #[test]
+    fn display_lags_while_control_tracks_a_step() {
+        let mut dual = DualFilter::new(
+            FilterChain::new().then(FixedEma::new(26)),  // ~0.1
+            FilterChain::new().then(FixedEma::new(154)), // ~0.6
+        );
+        dual.update(2800);
+
+        // Step from dry to freshly watered
+        let after_step: Vec<_> = (0..3).map(|_| dual.update(1600)).collect();
+        for pair in &after_step {
+            assert!(pair.control < pair.display, "{pair:?}");
+        }
+        let last = after_step.last().unwrap();
+        assert!(
+            last.control < 1700,
+            "control should be near the new level: {last:?}"
+        );
+        assert!(
+            last.display > 2400,
+            "display should still be smoothing: {last:?}"
+        );
+    }
+
+    /// Readings after watering until the filter is within 20 counts of the new level
+    fn cycles_to_converge(mut filter: impl Filter) -> usize {
+        for _ in 0..10 {
+            filter.update(2800);
+        }
+        filter.notify_event(Event::PumpActivated);
+        (1..=100)
+            .find(|_| filter.update(1600).abs_diff(1600) <= 20)
+            .unwrap_or(usize::MAX)
+    }
+
+    #[test]
+    fn watering_response_speeds_up_convergence() {
+        let plain = cycles_to_converge(FixedEma::new(26));
+        let reset =
+            cycles_to_converge(FixedEma::new(26).with_watering_response(WateringResponse::Reset));
+        let fast = cycles_to_converge(FilterChain::new().then(
+            FixedEma::new(26).with_watering_response(WateringResponse::FastAdapt {
+                alpha_q8: 154,
+                samples: 5,
+            }),
+        ));
+        assert_eq!(reset, 1);
+        assert!(fast < plain / 4, "fast-adapt {fast} vs plain {plain}");
+    }
+
+    #[test]
+    fn other_events_leave_history_alone() {
+        let mut ema = FixedEma::new(64).with_watering_response(WateringResponse::Reset);
+        ema.update(2000);
+        ema.notify_event(Event::PumpDeactivated);
+        assert_eq!(ema.update(1000), 1750);
+    }
+
+    #[test]
+    fn empty_chain_passes_through() {
+        let mut chain = FilterChain::new();
+        assert_eq!(chain.update(1234), 1234);
+    }
+
+    // Documented tolerance: fixed-point output within 1 raw count of float
+    #[test]
+    fn fixed_ema_matches_float_within_one_count() {
+        use super::Ema;
+
+        let mut fixed = FixedEma::new(51); // 51 / 256 ~= 0.2
+        let mut float = Ema::new(51.0 / 256.0);
+        let inputs = [
+            2400u16, 2380, 2900, 1200, 1250, 3000, 3001, 2999, 1800, 2200,
+        ];
+        for raw in inputs.iter().cycle().take(200) {
+            let a = fixed.update(*raw) as i32;
+            let b = float.update(*raw) as i32;
+            assert!((a - b).abs() <= 1, "fixed {a} vs float {b}");
+        }
+    }
+
+    mod kalman {
+        use super::super::{Ema, Filter, KalmanFilter};
+        use crate::rng::Rng;
+
+        const DRY: u16 = 2800;
+        const WET: u16 = 1600;
+
+        /// Reproducible readings around `level`, within +/-40 counts
+        fn noisy(rng: &mut Rng, level: u16) -> u16 {
+            (level as i64 + rng.range_inclusive(-40, 40)) as u16
+        }
+
+        /// Spread of the output over stable noisy readings, after settling
+        fn steady_spread(mut filter: impl Filter) -> u16 {
+            let mut rng = Rng::new(7);
+            let out: Vec<u16> = (0..200)
+                .map(|_| filter.update(noisy(&mut rng, DRY)))
+                .skip(50)
+                .collect();
+            out.iter().max().unwrap() - out.iter().min().unwrap()
+        }
+
+        /// Readings after a step until the output stays within 20 counts
+        fn step_response(mut filter: impl Filter) -> usize {
+            let mut rng = Rng::new(11);
+            for _ in 0..50 {
+                filter.update(noisy(&mut rng, DRY));
+            }
+            let out: Vec<u16> = (0..60).map(|_| filter.update(WET)).collect();
+            out.iter()
+                .rposition(|v| v.abs_diff(WET) > 20)
+                .map_or(0, |i| i + 1)
+        }
+
+        fn tuned() -> KalmanFilter {
+            // Noise std ~23 counts, true moisture barely moves between readings
+            KalmanFilter::new(0.5, 540.0)
+        }
+
+        #[test]
+        fn smooths_noise_at_least_as_well_as_ema() {
+            let ema = steady_spread(Ema::new(0.2));
+            let kalman = steady_spread(tuned());
+            assert!(kalman < ema, "kalman spread {kalman} vs ema {ema}");
+            assert!(kalman < 30, "kalman spread {kalman}");
+        }
+
+        #[test]
+        fn follows_watering_faster_than_ema() {
+            let ema = step_response(Ema::new(0.2));
+            let kalman = step_response(tuned());
+            assert!(kalman <= 2, "kalman settled after {kalman}");
+            assert!(kalman < ema, "kalman {kalman} vs ema {ema}");
+
+            // Without shift detection it lags like a heavy EMA
+            let gated_off = step_response(tuned().with_shift_gate(f32::INFINITY));
+            assert!(gated_off > ema, "ungated {gated_off} vs ema {ema}");
+        }
+
+        #[test]
+        fn gain_settles_while_stable_and_reset_restarts() {
+            let mut filter = tuned();
+            assert_eq!(filter.update(2000), 2000);
+            let early = filter.gain();
+            for _ in 0..100 {
+                filter.update(2000);
+            }
+            assert!(filter.gain() < early / 4.0, "gain {}", filter.gain());
+            filter.reset();
+            assert_eq!(filter.update(3000), 3000);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Fixed-size binary telemetry frame for LoRa and other low-bandwidth links.
+//!
+//! Layout (little endian, [`FRAME_LEN`] bytes):
+//!
+//! | offset | size | field                                               |
+//! |--------|------|-----------------------------------------------------|
+//! | 0      | 1    | format version ([`FRAME_VERSION`])                  |
+//! | 1      | 1    | zone ID                                             |
+//! | 2      | 2    | [`ReadingFlags`] bits                               |
+//! | 4      | 1    | moisture percent, 0..=100                           |
+//! | 5      | 2    | raw ADC value                                       |
+//! | 7      | 4    | emitted timestamp in whole seconds                  |
+//! | 11     | 1    | CRC-8 (poly 0x07) over bytes 0..11                  |
+//!
+//! EC, boot reason, the rail voltage itself and sub-second timing are not carried.
+
+use crate::reading::{Reading, ReadingFlags};
+use anyhow::{bail, ensure, Result};
+use std::time::Duration;
+
+/// Bytes in one encoded frame
+pub const FRAME_LEN: usize = 12;
+/// Layout version in byte 0; version 2 added the supply-sag flag, version 3
+/// the safe-mode flag and version 4 a second flags byte
+pub const FRAME_VERSION: u8 = 4;
+const CRC_AT: usize = FRAME_LEN - 1;
+
+/// Pack `reading` into a frame; the emitted timestamp is truncated to whole
+/// seconds and saturates past `u32::MAX`
+pub fn encode_frame(reading: &Reading) -> [u8; FRAME_LEN] {
+    let seconds = u32::try_from(reading.emitted_at.as_secs()).unwrap_or(u32::MAX);
+
+    let mut frame = [0u8; FRAME_LEN];
+    frame[0] = FRAME_VERSION;
+    frame[1] = reading.zone;
+    frame[2..4].copy_from_slice(&reading.flags.bits().to_le_bytes());
+    frame[4] = reading.moisture_percent;
+    frame[5..7].copy_from_slice(&reading.raw.to_le_bytes());
+    frame[7..11].copy_from_slice(&seconds.to_le_bytes());
+    frame[CRC_AT] = crc8(&frame[..CRC_AT]);
+    frame
+}
+
+/// Unpack a frame produced by [`encode_frame`], rejecting anything malformed
+pub fn decode_frame(bytes: &[u8]) -> Result<Reading> {
+    ensure!(
+        bytes.len() == FRAME_LEN,
+        "telemetry frame is {} bytes, expected {}",
+        bytes.len(),
+        FRAME_LEN
+    );
+    if bytes[0] != FRAME_VERSION {
+        bail!("unsupported telemetry frame version {}", bytes[0]);
+    }
+    let crc = crc8(&bytes[..CRC_AT]);
+    ensure!(
+        crc == bytes[CRC_AT],
+        "telemetry frame CRC mismatch (got {:#04x}, computed {:#04x})",
+        bytes[CRC_AT],
+        crc
+    );
+    let bits = u16::from_le_bytes([bytes[2], bytes[3]]);
+    let Some(flags) = ReadingFlags::from_bits(bits) else {
+        bail!("unknown telemetry flags {:#06x}", bits);
+    };
+    let moisture_percent = bytes[4];
+    ensure!(
+        moisture_percent <= 100,
+        "moisture {}% out of range",
+        moisture_percent
+    );
+
+    let raw = u16::from_le_bytes([bytes[5], bytes[6]]);
+    let seconds = u32::from_le_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]);
+    Ok(
+        Reading::new(Duration::from_secs(seconds as u64), raw, moisture_percent)
+            .with_zone(bytes[1])
+            .with_flag(flags, true),
+    )
+}
+
+/// CRC-8, polynomial 0x07, initial value 0
+fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for &byte in bytes {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{decode_frame, encode_frame, CRC_AT, FRAME_LEN};
+    use crate::reading::{Reading, ReadingFlags};
+    use std::time::Duration;
+
+    fn samples() -> Vec<Reading> {
+        vec![
+            Reading::new(Duration::ZERO, 0, 0),
+            Reading::new(Duration::from_secs(42), 2100, 50).with_zone(3),
+            Reading::new(Duration::from_secs(86_400), 3000, 0)
+                .with_pump_on(true)
+                .with_low_battery(true),
+            Reading::new(Duration::from_secs(u32::MAX as u64), u16::MAX, 100)
+                .with_zone(255)
+                .with_pump_on(true)
+                .with_fault(true)
+                .with_low_battery(true)
+                .with_control_paused(true),
+            Reading::new(Duration::from_secs(3600), 4095, 0)
+                .with_fault(true)
+                .with_safe_mode(true),
+            Reading::new(Duration::from_secs(7), 1900, 61)
+                .with_flag(ReadingFlags::PUMP_ON | ReadingFlags::SUPPLY_SAG, true),
+            Reading::new(Duration::from_secs(8), 4000, 0)
+                .with_flag(ReadingFlags::CLIPPED | ReadingFlags::WARMING_UP, true)
+                .with_flag(ReadingFlags::RECALIBRATION_RECOMMENDED, true),
+        ]
+    }
+
+    #[test]
+    fn round_trips_readings_and_frames() {
+        for reading in samples() {
+            let frame = encode_frame(&reading);
+            let decoded = decode_frame(&frame).unwrap();
+            assert_eq!(decoded, reading);
+            assert_eq!(encode_frame(&decoded), frame);
+        }
+    }
+
+    #[test]
+    fn layout_is_stable() {
+        let reading = Reading::new(Duration::from_millis(42_900), 0x0834, 50)
+            .with_zone(3)
+            .with_pump_on(true);
+        let frame = encode_frame(&reading);
+        assert_eq!(
+            &frame[..CRC_AT],
+            &[4, 3, 0x01, 0, 50, 0x34, 0x08, 42, 0, 0, 0]
+        );
+        let clipped = reading.with_flag(ReadingFlags::CLIPPED | ReadingFlags::WARMING_UP, true);
+        assert_eq!(&encode_frame(&clipped)[2..4], &[0x41, 0x01]);
+        // Sub-second part is dropped
+        assert_eq!(
+            decode_frame(&frame).unwrap().timestamp,
+            Duration::from_secs(42)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        let good = encode_frame(&samples()[1]);
+        assert!(decode_frame(&good[..FRAME_LEN - 1]).is_err());
+        assert!(decode_frame(&[good.as_slice(), &[0]].concat()).is_err());
+
+        for (offset, value) in [(0, 1u8), (0, 2), (0, 3), (3, 0x02), (4, 101)] {
+            let mut bad = good;
+            bad[offset] = value;
+            // Fix up the CRC so the field check itself is exercised
+            bad[CRC_AT] = super::crc8(&bad[..CRC_AT]);
+            assert!(decode_frame(&bad).is_err(), "offset {offset}");
+        }
+
+        let mut flipped = good;
+        flipped[5] ^= 0x01;
+        let err = decode_frame(&flipped).unwrap_err();
+        assert!(err.to_string().contains("CRC"), "{err}");
+    }
+}
@@ -0,0 +1,120 @@
+//! Self-check of the ADC front-end against known reference resistors.
+//!
+//! Switching a reference resistor in place of a resistive probe gives a
+//! reading whose true value is known. One reference pins the front-end gain;
+//! a second (e.g. a short to ground) separates out the offset. The resulting
+//! [`FrontEndCorrection`] is applied to raw readings before the moisture
+//! conversion.
+
+/// Unity gain in parts per million
+pub const UNITY_GAIN_PPM: u32 = 1_000_000;
+/// Corrections implying more gain drift than this point at a fault, not drift
+pub const MAX_GAIN_DRIFT_PPM: u32 = 200_000;
+
+/// Gain and offset that map drifted ADC counts back to true counts:
+/// `(raw - offset) * gain_ppm / 1e6`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrontEndCorrection {
+    pub gain_ppm: u32,
+    /// Counts the front-end reads with zero input
+    pub offset: i16,
+}
+
+impl Default for FrontEndCorrection {
+    fn default() -> Self {
+        Self {
+            gain_ppm: UNITY_GAIN_PPM,
+            offset: 0,
+        }
+    }
+}
+
+impl FrontEndCorrection {
+    /// Corrected raw value, rounded and clamped to the `u16` range
+    pub fn apply(&self, raw: u16) -> u16 {
+        let shifted = raw as i64 - self.offset as i64;
+        let scaled = (shifted * self.gain_ppm as i64 + UNITY_GAIN_PPM as i64 / 2)
+            .div_euclid(UNITY_GAIN_PPM as i64);
+        scaled.clamp(0, u16::MAX as i64) as u16
+    }
+
+    /// Gain drift away from unity, in ppm
+    pub fn gain_drift_ppm(&self) -> u32 {
+        self.gain_ppm.abs_diff(UNITY_GAIN_PPM)
+    }
+
+    /// Within [`MAX_GAIN_DRIFT_PPM`] of unity
+    pub fn is_plausible(&self) -> bool {
+        self.gain_drift_ppm() <= MAX_GAIN_DRIFT_PPM
+    }
+}
+
+/// Gain correction from one reference read as `reading` counts where
+/// `expected` was due; the offset is assumed to be zero
+pub fn reference_check(reading: u16, expected: u16) -> FrontEndCorrection {
+    FrontEndCorrection {
+        gain_ppm: ratio_ppm(expected as i64, reading as i64),
+        offset: 0,
+    }
+}
+
+/// Gain and offset from two references, each `(reading, expected)`; no
+/// correction unless `high` is above `low` on both counts
+pub fn reference_check_pair(low: (u16, u16), high: (u16, u16)) -> FrontEndCorrection {
+    let read_span = high.0 as i64 - low.0 as i64;
+    let expected_span = high.1 as i64 - low.1 as i64;
+    if read_span <= 0 || expected_span <= 0 {
+        return FrontEndCorrection::default();
+    }
+    let gain_ppm = ratio_ppm(expected_span, read_span);
+    // Reading at zero input: low reading minus the low expectation in drifted counts
+    let offset = low.0 as i64 - (low.1 as i64 * read_span + expected_span / 2) / expected_span;
+    FrontEndCorrection {
+        gain_ppm,
+        offset: offset.clamp(i16::MIN as i64, i16::MAX as i64) as i16,
+    }
+}
+
+/// `num / den` in ppm, rounded; unity when the denominator is not positive
+fn ratio_ppm(num: i64, den: i64) -> u32 {
+    if den <= 0 || num < 0 {
+        return UNITY_GAIN_PPM;
+    }
+    ((num * UNITY_GAIN_PPM as i64 + den / 2) / den).min(u32::MAX as i64) as u32
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{reference_check, reference_check_pair, FrontEndCorrection, UNITY_GAIN_PPM};
+
+    #[test]
+    fn single_reference_corrects_gain_drift() {
+        // 10k reference due at 2048 counts reads 2% high
+        let correction = reference_check(2089, 2048);
+        assert_eq!(correction.offset, 0);
+        assert_eq!(correction.gain_drift_ppm(), 19_627);
+        assert_eq!(correction.apply(2089), 2048);
+        // Other readings scale by the same ratio
+        assert_eq!(correction.apply(3060), 3000);
+        assert!(correction.is_plausible());
+
+        assert_eq!(reference_check(2048, 2048), FrontEndCorrection::default());
+        assert_eq!(reference_check(0, 2048).gain_ppm, UNITY_GAIN_PPM);
+    }
+
+    #[test]
+    fn reference_pair_separates_gain_and_offset() {
+        // Front-end reads 30 counts high at zero and has 5% excess gain
+        let drifted = |true_counts: u16| (30.0 + true_counts as f64 * 1.05).round() as u16;
+        let correction = reference_check_pair((drifted(500), 500), (drifted(3500), 3500));
+        assert_eq!(correction.offset, 30);
+        for true_counts in [500, 1200, 2048, 3500] {
+            assert_eq!(correction.apply(drifted(true_counts)), true_counts);
+        }
+    }
+
+    #[test]
+    fn gross_mismatch_is_implausible() {
+        assert!(!reference_check(1000, 2048).is_plausible());
+    }
+}
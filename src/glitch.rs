@@ -0,0 +1,224 @@
+//! Power-loss injection for the flash and NVS abstractions.
+//!
+//! Only built for tests or with the `fault-injection` feature. A glitched
+//! write leaves damaged data behind and reports an error, as if the device
+//! browned out part-way through, so loaders can be checked for recovery.
+
+use crate::nvs::KvStore;
+use crate::storage::FlashStore;
+use anyhow::{anyhow, Result};
+
+/// Damage applied to the next write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Glitch {
+    /// Power lost after the first `n` bytes reached flash
+    Truncate(usize),
+    /// Byte at this offset flipped (clamped to the last byte)
+    Corrupt(usize),
+}
+
+impl Glitch {
+    fn apply(&self, data: &[u8]) -> Vec<u8> {
+        match *self {
+            Glitch::Truncate(n) => data[..n.min(data.len())].to_vec(),
+            Glitch::Corrupt(offset) => {
+                let mut damaged = data.to_vec();
+                if let Some(byte) = damaged.get_mut(offset.min(data.len().saturating_sub(1))) {
+                    *byte ^= 0xFF;
+                }
+                damaged
+            }
+        }
+    }
+}
+
+/// One-shot glitch armed for a future write
+#[derive(Debug, Default)]
+struct Trigger {
+    /// Clean writes to let through before glitching
+    skip: usize,
+    glitch: Option<Glitch>,
+}
+
+impl Trigger {
+    fn take(&mut self) -> Option<Glitch> {
+        self.glitch?;
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+        self.glitch.take()
+    }
+}
+
+/// Flash store whose writes can be cut short
+pub struct GlitchFlash<F> {
+    inner: F,
+    trigger: Trigger,
+}
+
+impl<F: FlashStore> GlitchFlash<F> {
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner,
+            trigger: Trigger::default(),
+        }
+    }
+
+    /// Glitch the write after `skip` clean ones
+    pub fn arm(&mut self, glitch: Glitch, skip: usize) {
+        self.trigger = Trigger {
+            skip,
+            glitch: Some(glitch),
+        };
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: FlashStore> FlashStore for GlitchFlash<F> {
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self.trigger.take() {
+            None => self.inner.write_file(name, data),
+            Some(glitch) => {
+                self.inner.write_file(name, &glitch.apply(data))?;
+                Err(anyhow!("simulated power loss writing {}", name))
+            }
+        }
+    }
+
+    fn read_file(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.read_file(name)
+    }
+}
+
+/// NVS store whose writes can be cut short
+pub struct GlitchKv<K> {
+    inner: K,
+    trigger: Trigger,
+}
+
+impl<K: KvStore> GlitchKv<K> {
+    pub fn new(inner: K) -> Self {
+        Self {
+            inner,
+            trigger: Trigger::default(),
+        }
+    }
+
+    /// Glitch the write after `skip` clean ones
+    pub fn arm(&mut self, glitch: Glitch, skip: usize) {
+        self.trigger = Trigger {
+            skip,
+            glitch: Some(glitch),
+        };
+    }
+
+    pub fn into_inner(self) -> K {
+        self.inner
+    }
+}
+
+impl<K: KvStore> KvStore for GlitchKv<K> {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.inner.get(key)
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        match self.trigger.take() {
+            None => self.inner.set(key, value),
+            Some(glitch) => {
+                self.inner.set(key, &glitch.apply(value))?;
+                Err(anyhow!("simulated power loss writing {}", key))
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.inner.remove(key)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Glitch, GlitchFlash, GlitchKv};
+    use crate::checkpoint::{restore_checkpoint, Checkpointer};
+    use crate::clock::MockClock;
+    use crate::history::{History, HistoryEntry};
+    use crate::moisture::Calibration;
+    use crate::nvs::{KvStore, MemoryKv};
+    use crate::provision::{
+        check_boot_state, ensure_initialized, load_calibration, load_profile, BootState,
+        CALIBRATION_KEY,
+    };
+    use crate::stats::Stats;
+    use crate::storage::MemoryFlash;
+    use std::time::Duration;
+
+    #[test]
+    fn truncated_calibration_write_loads_defaults() {
+        let mut kv = GlitchKv::new(MemoryKv::new());
+        ensure_initialized(&mut kv).unwrap();
+
+        kv.arm(Glitch::Truncate(4), 0);
+        assert!(kv
+            .set(CALIBRATION_KEY, &Calibration::new(2800, 1300).to_bytes())
+            .is_err());
+        assert_eq!(kv.get(CALIBRATION_KEY).unwrap().unwrap().len(), 4);
+        assert_eq!(load_calibration(&kv), Calibration::default());
+    }
+
+    #[test]
+    fn power_loss_during_first_boot_setup_is_retried() {
+        let mut kv = GlitchKv::new(MemoryKv::new());
+        // Profile and calibration land, the sentinel is cut short
+        kv.arm(Glitch::Truncate(2), 2);
+        assert!(ensure_initialized(&mut kv).is_err());
+        assert_eq!(check_boot_state(&kv).unwrap(), BootState::CorruptSentinel);
+
+        assert!(ensure_initialized(&mut kv).unwrap());
+        assert_eq!(check_boot_state(&kv).unwrap(), BootState::Initialized);
+        assert_eq!(load_profile(&kv), Default::default());
+    }
+
+    #[test]
+    fn corrupted_version_byte_loads_defaults() {
+        let mut kv = GlitchKv::new(MemoryKv::new());
+        kv.arm(Glitch::Corrupt(0), 0);
+        let _ = kv.set(CALIBRATION_KEY, &Calibration::new(2800, 1300).to_bytes());
+        assert_eq!(load_calibration(&kv), Calibration::default());
+    }
+
+    #[test]
+    fn truncated_checkpoint_restores_empty() {
+        let clock = MockClock::new();
+        let mut flash = GlitchFlash::new(MemoryFlash::new());
+        let mut checkpointer = Checkpointer::new(clock, Duration::from_secs(60));
+        let mut history = History::new(8);
+        let mut stats = Stats::new();
+        for i in 0..5 {
+            history.push(HistoryEntry {
+                timestamp_s: i,
+                raw: 2000,
+                moisture_percent: 50,
+                temperature_tenths_c: None,
+            });
+            stats.record(50);
+        }
+
+        // History lands, stats write is cut off mid-record
+        flash.arm(Glitch::Truncate(6), 1);
+        assert!(checkpointer.force(&history, &stats, &mut flash).is_err());
+        let (restored_history, restored_stats) = restore_checkpoint(&flash, 8);
+        assert_eq!(restored_history.len(), 5);
+        assert_eq!(restored_stats, Stats::new());
+
+        // Now the history region itself is torn
+        flash.arm(Glitch::Truncate(10), 0);
+        assert!(checkpointer.force(&history, &stats, &mut flash).is_err());
+        let (restored_history, _) = restore_checkpoint(&flash, 8);
+        assert!(restored_history.is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Golden-table check of the raw-to-percent conversion.
+//!
+//! A table is CSV with one `raw,expected_percent` pair per line; blank
+//! lines, `#` comments and a leading `raw,expected_percent` header are
+//! skipped. Every row the conversion misses by more than the tolerance is
+//! reported, so a change to the math shows exactly which points moved.
+
+use crate::moisture::{raw_to_moisture_percent, Calibration};
+use std::fmt;
+use std::path::Path;
+
+/// One problem found while checking a table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The table file could not be read
+    Unreadable(String),
+    /// Line is not a `raw,expected_percent` pair
+    Malformed { line: usize, text: String },
+    /// Conversion is further than the tolerance from the expected value
+    OutOfTolerance {
+        line: usize,
+        raw: u16,
+        expected: u8,
+        actual: u8,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Unreadable(reason) => write!(f, "cannot read table: {reason}"),
+            Mismatch::Malformed { line, text } => write!(f, "line {line}: malformed row {text:?}"),
+            Mismatch::OutOfTolerance {
+                line,
+                raw,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "line {line}: raw {raw} converts to {actual}%, expected {expected}%"
+            ),
+        }
+    }
+}
+
+/// Check the conversion under `cal` against the table at `path`
+pub fn verify_against_table(
+    path: impl AsRef<Path>,
+    cal: &Calibration,
+    tolerance: u8,
+) -> Result<(), Vec<Mismatch>> {
+    let table = std::fs::read_to_string(path.as_ref()).map_err(|e| {
+        vec![Mismatch::Unreadable(format!(
+            "{}: {e}",
+            path.as_ref().display()
+        ))]
+    })?;
+    verify_table(&table, cal, tolerance)
+}
+
+/// Check the conversion against a table already in memory
+pub fn verify_table(table: &str, cal: &Calibration, tolerance: u8) -> Result<(), Vec<Mismatch>> {
+    let mut mismatches = Vec::new();
+    for (i, text) in table.lines().enumerate() {
+        let line = i + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') || (line == 1 && text.starts_with("raw")) {
+            continue;
+        }
+        let Some((raw, expected)) = parse_row(text) else {
+            mismatches.push(Mismatch::Malformed {
+                line,
+                text: text.to_string(),
+            });
+            continue;
+        };
+        let actual = raw_to_moisture_percent(raw, cal);
+        if actual.abs_diff(expected) > tolerance {
+            mismatches.push(Mismatch::OutOfTolerance {
+                line,
+                raw,
+                expected,
+                actual,
+            });
+        }
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
+
+fn parse_row(text: &str) -> Option<(u16, u8)> {
+    let (raw, expected) = text.split_once(',')?;
+    Some((raw.trim().parse().ok()?, expected.trim().parse().ok()?))
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{verify_against_table, verify_table, Mismatch};
+    use crate::moisture::Calibration;
+
+    /// Default calibration: 3000 dry, 1200 wet
+    const REFERENCE: &str = "raw,expected_percent
+# dry end, beyond it, and wet end
+3000,0
+3200,0
+1200,100
+2100,50
+2550,25
+1650,75
+# between whole percents
+2400,33
+1300,94
+";
+
+    #[test]
+    fn reference_table_matches_conversion() {
+        assert_eq!(verify_table(REFERENCE, &Calibration::default(), 1), Ok(()));
+
+        let path = std::env::temp_dir().join("soil-golden-reference.csv");
+        std::fs::write(&path, REFERENCE).unwrap();
+        let result = verify_against_table(&path, &Calibration::default(), 1);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn wrong_expected_value_is_reported() {
+        let table = format!("{REFERENCE}2100,55\n1800,abc\n");
+        let mismatches = verify_table(&table, &Calibration::default(), 2).unwrap_err();
+        assert_eq!(
+            mismatches,
+            vec![
+                Mismatch::OutOfTolerance {
+                    line: 12,
+                    raw: 2100,
+                    expected: 55,
+                    actual: 50,
+                },
+                Mismatch::Malformed {
+                    line: 13,
+                    text: "1800,abc".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            mismatches[0].to_string(),
+            "line 12: raw 2100 converts to 50%, expected 55%"
+        );
+    }
+
+    #[test]
+    fn missing_table_is_unreadable() {
+        let result = verify_against_table("/nonexistent/table.csv", &Calibration::default(), 1);
+        assert!(matches!(result.unwrap_err()[..], [Mismatch::Unreadable(_)]));
+    }
+}
@@ -0,0 +1,147 @@
+//! Raw-sample histogram for judging sensor noise and averaging counts.
+
+use crate::sensor::SoilSensor;
+use anyhow::{ensure, Result};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Width of the longest bar in [`render_histogram`]
+const BAR_WIDTH: u32 = 40;
+
+/// Distribution of raw readings with equal-width buckets spanning `min..=max`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    pub samples: u32,
+    pub min: u16,
+    pub max: u16,
+    /// Most frequent raw value; the lowest one wins a tie
+    pub mode: u16,
+    /// Raw counts covered by each bucket; up to 65536 for one bucket over the
+    /// full raw range, so wider than the raw values themselves
+    pub bucket_width: u32,
+    pub buckets: Vec<u32>,
+}
+
+impl Histogram {
+    /// Bin `values` into `buckets` equal-width buckets
+    pub fn from_values(values: &[u16], buckets: usize) -> Result<Self> {
+        ensure!(!values.is_empty(), "histogram needs at least one sample");
+        ensure!(buckets > 0, "histogram needs at least one bucket");
+        let min = *values.iter().min().unwrap_or(&0);
+        let max = *values.iter().max().unwrap_or(&0);
+
+        let mut freq: HashMap<u16, u32> = HashMap::new();
+        for &v in values {
+            *freq.entry(v).or_default() += 1;
+        }
+        let mode = freq
+            .iter()
+            .max_by_key(|&(&value, &count)| (count, std::cmp::Reverse(value)))
+            .map_or(min, |(&value, _)| value);
+
+        let span = (max - min) as usize + 1;
+        let bucket_width = span.div_ceil(buckets).max(1);
+        let mut counts = vec![0u32; buckets];
+        for &v in values {
+            counts[(v - min) as usize / bucket_width] += 1;
+        }
+
+        Ok(Self {
+            samples: values.len() as u32,
+            min,
+            max,
+            mode,
+            bucket_width: bucket_width as u32,
+            buckets: counts,
+        })
+    }
+
+    /// Inclusive raw range covered by bucket `index`
+    pub fn bucket_range(&self, index: usize) -> (u16, u16) {
+        let lo = self.min as u32 + index as u32 * self.bucket_width;
+        let hi = lo + self.bucket_width - 1;
+        (lo as u16, hi.min(u16::MAX as u32) as u16)
+    }
+}
+
+/// Take `n` single raw conversions back to back and bin them; read-only
+pub fn sample_histogram(
+    sensor: &mut dyn SoilSensor,
+    n: usize,
+    buckets: usize,
+) -> Result<Histogram> {
+    let values = (0..n)
+        .map(|_| sensor.read_averaged(1))
+        .collect::<Result<Vec<_>>>()?;
+    Histogram::from_values(&values, buckets)
+}
+
+/// Text rendering for the serial console, one bar per bucket
+pub fn render_histogram(hist: &Histogram) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "samples={} min={} max={} mode={}",
+        hist.samples, hist.min, hist.max, hist.mode
+    );
+    let peak = hist.buckets.iter().copied().max().unwrap_or(0).max(1);
+    for (i, &count) in hist.buckets.iter().enumerate() {
+        let (lo, hi) = hist.bucket_range(i);
+        let bar = "#".repeat((count * BAR_WIDTH / peak) as usize);
+        let _ = writeln!(out, "{:5}..{:5} | {:6} {}", lo, hi, count, bar);
+    }
+    out
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{render_histogram, sample_histogram, Histogram};
+    use crate::sensor::SoilSensor;
+    use anyhow::Result;
+
+    /// Replays a fixed sequence of raw values
+    struct Sequence(Vec<u16>, usize);
+
+    impl SoilSensor for Sequence {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            let v = self.0[self.1 % self.0.len()];
+            self.1 += 1;
+            Ok(v)
+        }
+    }
+
+    #[test]
+    fn buckets_a_known_distribution() {
+        // 1000..=1009 once each, plus 1005 twice more
+        let mut values: Vec<u16> = (1000..1010).collect();
+        values.extend([1005, 1005]);
+        let mut sensor = Sequence(values, 0);
+
+        let hist = sample_histogram(&mut sensor, 12, 5).unwrap();
+        assert_eq!((hist.min, hist.max, hist.mode), (1000, 1009, 1005));
+        assert_eq!(hist.bucket_width, 2);
+        assert_eq!(hist.buckets, vec![2, 2, 4, 2, 2]);
+        assert_eq!(hist.bucket_range(2), (1004, 1005));
+
+        let text = render_histogram(&hist);
+        assert!(text.starts_with("samples=12 min=1000 max=1009 mode=1005\n"));
+        assert_eq!(text.lines().count(), 6);
+    }
+
+    #[test]
+    fn one_bucket_spans_the_full_raw_range() {
+        let hist = Histogram::from_values(&[0, u16::MAX], 1).unwrap();
+        assert_eq!(hist.bucket_width, 65_536);
+        assert_eq!(hist.buckets, vec![2]);
+        assert_eq!(hist.bucket_range(0), (0, u16::MAX));
+    }
+
+    #[test]
+    fn constant_signal_lands_in_first_bucket() {
+        let hist = Histogram::from_values(&[2048; 20], 4).unwrap();
+        assert_eq!(hist.buckets, vec![20, 0, 0, 0]);
+        assert_eq!(hist.mode, 2048);
+        assert!(Histogram::from_values(&[], 4).is_err());
+        assert!(Histogram::from_values(&[1], 0).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! In-RAM ring buffer of recent readings, recoverable after a crash.
+//!
+//! [`History::to_region`] lays the buffer out in a fixed byte region (e.g. a
+//! `.rtc_noinit` static that survives a watchdog or panic reset) behind a
+//! magic, version and CRC header so [`History::from_region`] can tell a clean
+//! power-on from a crash with salvageable data.
+//!
+//! Annotations ride along in RAM for exports but are not part of the region.
+//!
+//! With a [`Decimation`], entries older than the recency horizon are thinned
+//! out as they cross it, so the same capacity spans a longer time.
+
+use crate::reading::{Annotation, Reading};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+const REGION_MAGIC: u32 = 0x534F_494C; // "SOIL"
+const REGION_VERSION: u16 = 2;
+const HEADER_LEN: usize = 12;
+const ENTRY_LEN: usize = 10;
+/// Temperature field of an entry without one
+const NO_TEMPERATURE: i16 = i16::MIN;
+/// Most recent annotations kept alongside the readings
+pub const ANNOTATION_CAPACITY: usize = 16;
+
+/// Compact history record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Seconds since boot
+    pub timestamp_s: u32,
+    pub raw: u16,
+    pub moisture_percent: u8,
+    /// Tenths of °C, when a temperature channel is read
+    pub temperature_tenths_c: Option<i16>,
+}
+
+impl From<&Reading> for HistoryEntry {
+    fn from(reading: &Reading) -> Self {
+        Self {
+            timestamp_s: reading.timestamp.as_secs() as u32,
+            raw: reading.raw,
+            moisture_percent: reading.moisture_percent,
+            temperature_tenths_c: reading.temperature_tenths_c,
+        }
+    }
+}
+
+impl HistoryEntry {
+    pub fn timestamp(&self) -> Duration {
+        Duration::from_secs(self.timestamp_s as u64)
+    }
+}
+
+/// Tiered retention: the newest `recent` entries at full resolution, then
+/// only every `keep_every`th entry beyond them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimation {
+    pub recent: usize,
+    /// 1 keeps everything
+    pub keep_every: usize,
+}
+
+/// Why a region could not be recovered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionError {
+    /// No magic: memory was never written, i.e. a clean power-on
+    Empty,
+    /// Written by a different firmware layout
+    UnsupportedVersion(u16),
+    /// Header or payload damaged
+    Corrupt,
+    /// Destination region cannot hold the header plus all entries
+    TooSmall { needed: usize },
+}
+
+impl fmt::Display for RegionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegionError::Empty => write!(f, "no saved history"),
+            RegionError::UnsupportedVersion(v) => write!(f, "unsupported history version {v}"),
+            RegionError::Corrupt => write!(f, "saved history failed its CRC check"),
+            RegionError::TooSmall { needed } => write!(f, "region too small, need {needed} bytes"),
+        }
+    }
+}
+
+impl std::error::Error for RegionError {}
+
+/// Fixed-capacity ring buffer of readings, oldest dropped first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+    annotations: VecDeque<Annotation>,
+    decimation: Option<Decimation>,
+    /// Entries that have crossed the recency horizon so far
+    aged: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            annotations: VecDeque::new(),
+            decimation: None,
+            aged: 0,
+        }
+    }
+
+    /// Thin out entries beyond the recency horizon from now on; entries
+    /// already held, e.g. restored from a region, are kept as they are
+    pub fn with_decimation(mut self, decimation: Decimation) -> Self {
+        self.decimation = Some(decimation);
+        self
+    }
+
+    /// Keep `annotation`, dropping the oldest past [`ANNOTATION_CAPACITY`]
+    pub fn annotate(&mut self, annotation: Annotation) {
+        if self.annotations.len() == ANNOTATION_CAPACITY {
+            self.annotations.pop_front();
+        }
+        self.annotations.push_back(annotation);
+    }
+
+    /// Annotations oldest first
+    pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        self.annotations.iter()
+    }
+
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.push_back(entry);
+        if let Some(d) = self.decimation {
+            // The entry just pushed past the horizon survives only on the grid
+            if let Some(crossed) = self.entries.len().checked_sub(d.recent + 1) {
+                if self.aged % d.keep_every.max(1) != 0 {
+                    self.entries.remove(crossed);
+                }
+                self.aged = self.aged.wrapping_add(1);
+            }
+        }
+        if self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Entries oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &HistoryEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes needed to store `capacity` entries with [`to_region`](Self::to_region)
+    pub const fn region_len(capacity: usize) -> usize {
+        HEADER_LEN + capacity * ENTRY_LEN
+    }
+
+    /// Write the history into `region`, returning the bytes used.
+    ///
+    /// Layout (little endian): magic u32, version u16, count u16, CRC-32 u32
+    /// over count and entries, then `count` 10-byte entries: timestamp u32,
+    /// raw u16, moisture u8, a zero byte and temperature i16 (`i16::MIN` if none).
+    pub fn to_region(&self, region: &mut [u8]) -> Result<usize, RegionError> {
+        let needed = Self::region_len(self.entries.len());
+        if region.len() < needed {
+            return Err(RegionError::TooSmall { needed });
+        }
+
+        let count = self.entries.len() as u16;
+        let payload = &mut region[HEADER_LEN..needed];
+        for (chunk, entry) in payload.chunks_exact_mut(ENTRY_LEN).zip(&self.entries) {
+            chunk[0..4].copy_from_slice(&entry.timestamp_s.to_le_bytes());
+            chunk[4..6].copy_from_slice(&entry.raw.to_le_bytes());
+            chunk[6] = entry.moisture_percent;
+            chunk[7] = 0;
+            let temperature = entry.temperature_tenths_c.unwrap_or(NO_TEMPERATURE);
+            chunk[8..10].copy_from_slice(&temperature.to_le_bytes());
+        }
+        let crc = crc32(&count.to_le_bytes(), payload);
+
+        region[0..4].copy_from_slice(&REGION_MAGIC.to_le_bytes());
+        region[4..6].copy_from_slice(&REGION_VERSION.to_le_bytes());
+        region[6..8].copy_from_slice(&count.to_le_bytes());
+        region[8..12].copy_from_slice(&crc.to_le_bytes());
+        Ok(needed)
+    }
+
+    /// Validate and rebuild a history written by [`to_region`](Self::to_region)
+    pub fn from_region(region: &[u8], capacity: usize) -> Result<Self, RegionError> {
+        if region.len() < HEADER_LEN {
+            return Err(RegionError::Empty);
+        }
+        let word =
+            |i: usize| u32::from_le_bytes([region[i], region[i + 1], region[i + 2], region[i + 3]]);
+        let half = |i: usize| u16::from_le_bytes([region[i], region[i + 1]]);
+
+        if word(0) != REGION_MAGIC {
+            return Err(RegionError::Empty);
+        }
+        let version = half(4);
+        if version != REGION_VERSION {
+            return Err(RegionError::UnsupportedVersion(version));
+        }
+        let count = half(6);
+        let end = Self::region_len(count as usize);
+        if region.len() < end {
+            return Err(RegionError::Corrupt);
+        }
+        let payload = &region[HEADER_LEN..end];
+        if crc32(&count.to_le_bytes(), payload) != word(8) {
+            return Err(RegionError::Corrupt);
+        }
+
+        let mut history = Self::new(capacity);
+        for chunk in payload.chunks_exact(ENTRY_LEN) {
+            history.push(HistoryEntry {
+                timestamp_s: u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                raw: u16::from_le_bytes([chunk[4], chunk[5]]),
+                moisture_percent: chunk[6],
+                temperature_tenths_c: Some(i16::from_le_bytes([chunk[8], chunk[9]]))
+                    .filter(|&t| t != NO_TEMPERATURE),
+            });
+        }
+        Ok(history)
+    }
+}
+
+/// CRC-32 (IEEE, reflected) over `head` followed by `body`
+fn crc32(head: &[u8], body: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in head.iter().chain(body) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{crc32, Decimation, History, HistoryEntry, RegionError};
+
+    fn sample(capacity: usize, n: u32) -> History {
+        let mut history = History::new(capacity);
+        for i in 0..n {
+            history.push(HistoryEntry {
+                timestamp_s: i * 2,
+                raw: 2000 + i as u16,
+                moisture_percent: (40 + i) as u8,
+                temperature_tenths_c: (i % 2 == 0).then_some(-15 + i as i16),
+            });
+        }
+        history
+    }
+
+    #[test]
+    fn ring_buffer_drops_oldest() {
+        let history = sample(3, 5);
+        let stamps: Vec<u32> = history.iter().map(|e| e.timestamp_s).collect();
+        assert_eq!(stamps, vec![4, 6, 8]);
+    }
+
+    fn stamps(history: &History) -> Vec<u32> {
+        history.iter().map(|e| e.timestamp_s).collect()
+    }
+
+    #[test]
+    fn decimation_keeps_recent_entries_at_full_resolution() {
+        let decimation = Decimation {
+            recent: 4,
+            keep_every: 3,
+        };
+        let mut history = History::new(8).with_decimation(decimation);
+        for i in 0..6 {
+            history.push(HistoryEntry {
+                timestamp_s: i * 2,
+                raw: 2000,
+                moisture_percent: 40,
+                temperature_tenths_c: None,
+            });
+        }
+        // Two entries crossed the horizon; only the first is on the grid
+        assert_eq!(stamps(&history), vec![0, 4, 6, 8, 10]);
+
+        for i in 6..40 {
+            history.push(HistoryEntry {
+                timestamp_s: i * 2,
+                raw: 2000,
+                moisture_percent: 40,
+                temperature_tenths_c: None,
+            });
+        }
+        assert_eq!(history.len(), 8);
+        let kept = stamps(&history);
+        assert_eq!(kept[4..], [72, 74, 76, 78], "newest entries every reading");
+        assert_eq!(kept[..4], [48, 54, 60, 66], "older entries every third");
+        // Eight slots span 16 readings instead of 8
+        assert_eq!((kept[7] - kept[0]) / 2 + 1, 16);
+    }
+
+    #[test]
+    fn recovers_history_from_valid_region() {
+        let history = sample(16, 10);
+        let mut region = [0u8; History::region_len(16)];
+        history.to_region(&mut region).unwrap();
+
+        assert_eq!(History::from_region(&region, 16).unwrap(), history);
+    }
+
+    #[test]
+    fn rejects_corrupted_region_and_detects_clean_boot() {
+        let history = sample(16, 10);
+        let mut region = [0u8; History::region_len(16)];
+        history.to_region(&mut region).unwrap();
+
+        region[20] ^= 0x40; // flip a bit inside an entry
+        assert_eq!(History::from_region(&region, 16), Err(RegionError::Corrupt));
+
+        let blank = [0u8; History::region_len(16)];
+        assert_eq!(History::from_region(&blank, 16), Err(RegionError::Empty));
+    }
+
+    #[test]
+    fn crc32_matches_reference_value() {
+        assert_eq!(crc32(b"1234", b"56789"), 0xCBF4_3926);
+    }
+}
@@ -0,0 +1,241 @@
+//! Readings as InfluxDB line protocol, posted to the write API or logged
+//! for Telegraf to pick up.
+//!
+//! One line per reading: measurement `soil`, the zone as a tag, numeric
+//! values as typed fields (`i` suffix for integers) and the reading time as
+//! emitted, so writes need `precision=s` or `precision=ms` to match the
+//! [`TimestampConfig`](crate::timestamp::TimestampConfig).
+
+use crate::alert::Tenths;
+use crate::reading::{Annotation, Reading};
+use crate::sink::ReadingSink;
+use crate::webhook::HttpClient;
+use anyhow::Result;
+use log::info;
+use std::fmt::Write;
+
+pub const INFLUX_MEASUREMENT: &str = "soil";
+const LINE_PROTOCOL: &str = "text/plain; charset=utf-8";
+
+/// Escape a tag key or value: commas, equals signs and spaces would end it early
+pub fn escape_tag(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ',' | '=' | ' ' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            // Line protocol has no escape for newlines
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Line for `reading`, tagged with its zone number
+pub fn influx_line(reading: &Reading) -> String {
+    line_with_zone(reading, &reading.zone.to_string())
+}
+
+fn line_with_zone(reading: &Reading, zone: &str) -> String {
+    let mut out = format!("{INFLUX_MEASUREMENT},zone={} ", escape_tag(zone));
+    // Writing to a String cannot fail
+    let _ = write!(
+        out,
+        "moisture={},raw={}i,pump={}",
+        reading.moisture_percent,
+        reading.raw,
+        reading.pump_on()
+    );
+    if let Some(ec) = reading.ec_us_cm {
+        let _ = write!(out, ",ec_us_cm={ec}i");
+    }
+    if let Some(fc) = reading.field_capacity_percent {
+        let _ = write!(out, ",fc_percent={fc}i");
+    }
+    if let Some(depth) = reading.depth_cm {
+        let _ = write!(out, ",depth_cm={depth}i");
+    }
+    if let Some(u) = reading.uncertainty_tenths {
+        let _ = write!(
+            out,
+            ",uncertainty_percent={}",
+            Tenths(u.min(i16::MAX as u16) as i16)
+        );
+    }
+    if let Some(t) = reading.temperature_tenths_c {
+        let _ = write!(out, ",temp_c={}", Tenths(t));
+    }
+    if let Some(mv) = reading.supply_mv {
+        let _ = write!(out, ",supply_mv={mv}i");
+    }
+    if reading.fault() {
+        out.push_str(",fault=true");
+    }
+    let _ = write!(out, " {}", reading.emitted_at);
+    out
+}
+
+/// Emits each reading as line protocol, over HTTP or to the log
+pub struct InfluxSink {
+    /// Write API client and URL including `db`/`bucket` and `precision`
+    http: Option<(Box<dyn HttpClient + Send>, String)>,
+    zone_names: Vec<(u8, String)>,
+}
+
+impl InfluxSink {
+    /// Log lines for Telegraf's tail or serial input to scrape
+    pub fn logged() -> Self {
+        Self {
+            http: None,
+            zone_names: Vec::new(),
+        }
+    }
+
+    /// POST lines to the write API at `url`
+    pub fn http(client: impl HttpClient + Send + 'static, url: impl Into<String>) -> Self {
+        Self {
+            http: Some((Box::new(client), url.into())),
+            zone_names: Vec::new(),
+        }
+    }
+
+    /// Tag `zone` with `name` instead of its number
+    pub fn with_zone_name(mut self, zone: u8, name: impl Into<String>) -> Self {
+        self.zone_names.retain(|(z, _)| *z != zone);
+        self.zone_names.push((zone, name.into()));
+        self
+    }
+
+    /// Line for `reading` using the configured zone names
+    pub fn line(&self, reading: &Reading) -> String {
+        match self.zone_names.iter().find(|(z, _)| *z == reading.zone) {
+            Some((_, name)) => line_with_zone(reading, name),
+            None => influx_line(reading),
+        }
+    }
+
+    fn write(&mut self, line: &str) -> Result<()> {
+        match &mut self.http {
+            Some((client, url)) => client.post(url, LINE_PROTOCOL, line.as_bytes()),
+            None => {
+                info!("{}", line);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl ReadingSink for InfluxSink {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        let line = self.line(reading);
+        self.write(&line)
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        // Annotations become events on the dashboard via their own measurement
+        let text = annotation.text().replace('\\', "\\\\").replace('"', "\\\"");
+        let line = format!(
+            "{INFLUX_MEASUREMENT}_note text=\"{text}\" {}",
+            annotation.emitted_at
+        );
+        self.write(&line)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{escape_tag, influx_line, InfluxSink};
+    use crate::reading::{Annotation, Reading};
+    use crate::sink::ReadingSink;
+    use crate::timestamp::{Timestamp, TimestampPrecision};
+    use crate::webhook::HttpClient;
+    use anyhow::Result;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[test]
+    fn line_has_typed_fields_and_zone_tag() {
+        let reading = Reading::new(Duration::from_secs(1_700_000_000), 2100, 45).with_zone(1);
+        assert_eq!(
+            influx_line(&reading),
+            "soil,zone=1 moisture=45,raw=2100i,pump=false 1700000000"
+        );
+
+        let reading = reading
+            .with_pump_on(true)
+            .with_ec(180)
+            .with_depth(20)
+            .with_temperature(-15)
+            .with_fault(true);
+        assert_eq!(
+            influx_line(&reading),
+            "soil,zone=1 moisture=45,raw=2100i,pump=true,ec_us_cm=180i,depth_cm=20i,temp_c=-1.5,\
+             fault=true 1700000000"
+        );
+
+        // The configured timestamp is written as is
+        let stamped = Reading::new(Duration::from_secs(60), 2100, 45).with_emitted_at(Timestamp {
+            value: 1_700_000_000_250,
+            precision: TimestampPrecision::Millis,
+        });
+        assert!(influx_line(&stamped).ends_with(" 1700000000250"));
+    }
+
+    #[test]
+    fn zone_names_are_escaped() {
+        assert_eq!(escape_tag("bed 1,north=A"), "bed\\ 1\\,north\\=A");
+        let sink = InfluxSink::logged()
+            .with_zone_name(1, "bed1")
+            .with_zone_name(2, "raised bed, east");
+        let at = Duration::from_secs(60);
+        assert_eq!(
+            sink.line(&Reading::new(at, 2100, 45).with_zone(1)),
+            "soil,zone=bed1 moisture=45,raw=2100i,pump=false 60"
+        );
+        assert!(sink
+            .line(&Reading::new(at, 2100, 45).with_zone(2))
+            .starts_with("soil,zone=raised\\ bed\\,\\ east moisture=45,"));
+        // Unnamed zones fall back to the number
+        assert!(sink
+            .line(&Reading::new(at, 2100, 45).with_zone(3))
+            .starts_with("soil,zone=3 "));
+    }
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl HttpClient for Recorder {
+        fn post(&mut self, url: &str, _content_type: &str, body: &[u8]) -> Result<()> {
+            let body = String::from_utf8(body.to_vec())?;
+            self.0.lock().unwrap().push((url.to_string(), body));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn http_sink_posts_each_line() {
+        let posted = Recorder::default();
+        let url = "http://influx:8086/write?db=garden&precision=s";
+        let mut sink = InfluxSink::http(posted.clone(), url);
+        sink.emit(&Reading::new(Duration::from_secs(5), 2100, 45))
+            .unwrap();
+        sink.annotate(&Annotation::new(Duration::from_secs(6), "said \"hi\""))
+            .unwrap();
+        let note = Annotation::new(Duration::from_secs(7), "repotted").with_emitted_at(Timestamp {
+            value: 1_700_000_000_250,
+            precision: TimestampPrecision::Millis,
+        });
+        sink.annotate(&note).unwrap();
+        let posted = posted.0.lock().unwrap();
+        assert_eq!(posted[0].0, url);
+        assert_eq!(
+            posted[0].1,
+            "soil,zone=0 moisture=45,raw=2100i,pump=false 5"
+        );
+        assert_eq!(posted[1].1, "soil_note text=\"said \\\"hi\\\"\" 6");
+        assert_eq!(posted[2].1, "soil_note text=\"repotted\" 1700000000250");
+    }
+}
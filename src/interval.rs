@@ -0,0 +1,80 @@
+//! Reading cadence, optionally jittered to avoid beating against mains noise.
+
+use crate::rng::Rng;
+use std::time::Duration;
+
+/// Shortest interval jitter can produce
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Time between readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadingInterval {
+    pub base: Duration,
+    /// Each interval is drawn uniformly from `base ± jitter`
+    pub jitter: Duration,
+}
+
+impl ReadingInterval {
+    pub fn new(base: Duration) -> Self {
+        Self {
+            base,
+            jitter: Duration::ZERO,
+        }
+    }
+
+    /// Randomize each interval by up to `jitter` either way, decorrelating
+    /// reads from 50/60 Hz interference
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Draw the next interval; never zero, even if `jitter >= base`
+    pub fn next(&self, rng: &mut Rng) -> Duration {
+        if self.jitter.is_zero() {
+            return self.base.max(MIN_INTERVAL);
+        }
+        let jitter_ms = self.jitter.as_millis() as i64;
+        let ms = self.base.as_millis() as i64 + rng.range_inclusive(-jitter_ms, jitter_ms);
+        Duration::from_millis(ms.max(MIN_INTERVAL.as_millis() as i64) as u64)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::ReadingInterval;
+    use crate::rng::Rng;
+    use std::time::Duration;
+
+    #[test]
+    fn jittered_intervals_stay_within_bounds() {
+        let interval = ReadingInterval::new(Duration::from_millis(2000))
+            .with_jitter(Duration::from_millis(50));
+        let mut rng = Rng::new(0x5011);
+        let draws: Vec<_> = (0..10_000).map(|_| interval.next(&mut rng)).collect();
+        for d in &draws {
+            assert!(
+                (Duration::from_millis(1950)..=Duration::from_millis(2050)).contains(d),
+                "{d:?}"
+            );
+        }
+        // Actually jittered, and reproducible from the seed
+        assert!(draws.iter().any(|d| *d != draws[0]));
+        let mut again = Rng::new(0x5011);
+        assert_eq!(interval.next(&mut again), draws[0]);
+    }
+
+    #[test]
+    fn jitter_never_reaches_zero() {
+        let interval =
+            ReadingInterval::new(Duration::from_millis(20)).with_jitter(Duration::from_millis(50));
+        let mut rng = Rng::new(1);
+        for _ in 0..10_000 {
+            assert!(interval.next(&mut rng) >= Duration::from_millis(1));
+        }
+        assert_eq!(
+            ReadingInterval::new(Duration::ZERO).next(&mut rng),
+            Duration::from_millis(1)
+        );
+    }
+}
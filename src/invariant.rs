@@ -0,0 +1,114 @@
+//! Debug-build checks of the control loop's invariants, kept in one place so
+//! they are easy to audit.
+//!
+//! Each check is a `debug_assert!`; callers build their inputs under
+//! `cfg!(debug_assertions)`, so release builds carry neither the checks nor
+//! the bookkeeping behind them.
+
+use crate::pump::{Guardrail, PumpAction};
+
+/// Converted moisture stays on the 0-100 scale
+#[inline]
+pub fn moisture_in_range(percent: u8) {
+    debug_assert!(percent <= 100, "moisture {percent}% outside 0..=100");
+}
+
+/// State around one pump decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decision {
+    pub action: Option<PumpAction>,
+    /// Pump was running before the decision
+    pub was_running: bool,
+    pub paused: bool,
+    pub safe_mode: bool,
+    pub reservoir_empty: bool,
+    /// Schedule allows watering now
+    pub in_window: bool,
+    pub guardrail: Option<Guardrail>,
+}
+
+/// The pump only starts from off, never while paused, in safe mode, on an
+/// empty reservoir or past the flood limit, and outside the schedule only
+/// for an emergency; it only stops from on
+#[inline]
+pub fn pump_decision(d: &Decision) {
+    match d.action {
+        Some(PumpAction::Activate) => {
+            debug_assert!(!d.was_running, "pump started while already running");
+            debug_assert!(!d.paused, "pump started while control was paused");
+            debug_assert!(!d.safe_mode, "pump started in safe mode");
+            debug_assert!(!d.reservoir_empty, "pump started on an empty reservoir");
+            debug_assert!(
+                d.guardrail != Some(Guardrail::Flood),
+                "pump started above the flood limit"
+            );
+            debug_assert!(
+                d.in_window || d.guardrail == Some(Guardrail::Emergency),
+                "pump started outside the schedule without an emergency"
+            );
+        }
+        Some(PumpAction::Deactivate) => {
+            debug_assert!(d.was_running, "pump stopped while already off");
+        }
+        None => {}
+    }
+}
+
+#[cfg(all(test, debug_assertions, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{moisture_in_range, pump_decision, Decision};
+    use crate::pump::{Guardrail, PumpAction};
+
+    fn start() -> Decision {
+        Decision {
+            action: Some(PumpAction::Activate),
+            was_running: false,
+            paused: false,
+            safe_mode: false,
+            reservoir_empty: false,
+            in_window: true,
+            guardrail: None,
+        }
+    }
+
+    #[test]
+    fn sound_decisions_pass() {
+        moisture_in_range(100);
+        pump_decision(&start());
+        pump_decision(&Decision {
+            in_window: false,
+            guardrail: Some(Guardrail::Emergency),
+            ..start()
+        });
+        pump_decision(&Decision {
+            action: Some(PumpAction::Deactivate),
+            was_running: true,
+            paused: true,
+            ..start()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "outside 0..=100")]
+    fn out_of_scale_moisture_is_caught() {
+        moisture_in_range(101);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty reservoir")]
+    fn start_on_empty_reservoir_is_caught() {
+        pump_decision(&Decision {
+            reservoir_empty: true,
+            ..start()
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the schedule")]
+    fn start_outside_schedule_is_caught() {
+        pump_decision(&Decision {
+            in_window: false,
+            ..start()
+        });
+    }
+}
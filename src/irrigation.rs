@@ -0,0 +1,100 @@
+//! Water volume estimates for bringing a bed up to its target moisture.
+//!
+//! Moisture percent is taken as the share of the soil's water-holding
+//! capacity that is filled, so the water needed is the bed's soil volume
+//! times the capacity per litre times the gap to the target. Runoff and
+//! uneven spread are ignored; the estimate is a starting point for the run
+//! duration, not a replacement for the moisture thresholds.
+
+use std::time::Duration;
+
+/// Plant-available water held by a litre of loam at full capacity
+pub const DEFAULT_HOLDING_CAPACITY_ML_PER_L: u16 = 150;
+
+/// Size and soil of one watered bed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BedConfig {
+    /// Surface area in square centimetres
+    pub area_cm2: u32,
+    /// Depth of the root zone being wetted
+    pub depth_cm: u16,
+    /// Water a litre of this soil holds between dry (0%) and full (100%)
+    pub holding_capacity_ml_per_l: u16,
+}
+
+impl BedConfig {
+    /// Bed of loam with the default holding capacity
+    pub fn new(area_cm2: u32, depth_cm: u16) -> Self {
+        Self {
+            area_cm2,
+            depth_cm,
+            holding_capacity_ml_per_l: DEFAULT_HOLDING_CAPACITY_ML_PER_L,
+        }
+    }
+
+    pub fn with_holding_capacity(mut self, ml_per_l: u16) -> Self {
+        self.holding_capacity_ml_per_l = ml_per_l;
+        self
+    }
+
+    /// Soil volume in millilitres (cubic centimetres)
+    pub fn soil_volume_ml(&self) -> u64 {
+        self.area_cm2 as u64 * self.depth_cm as u64
+    }
+}
+
+/// Millilitres to raise `bed` from `current_percent` to `target_percent`,
+/// rounded; zero when already at or above the target
+pub fn water_needed_ml(current_percent: u8, target_percent: u8, bed: &BedConfig) -> u32 {
+    let gap = target_percent.min(100).saturating_sub(current_percent) as u64;
+    // volume (ml) / 1000 -> litres, gap / 100 -> share of capacity
+    let scaled = bed.soil_volume_ml() * bed.holding_capacity_ml_per_l as u64 * gap;
+    ((scaled + 50_000) / 100_000).min(u32::MAX as u64) as u32
+}
+
+/// Pump run time to deliver `ml` at `flow_ml_per_min`, rounded up to whole
+/// seconds; zero without a flow rate
+pub fn run_time_for(ml: u32, flow_ml_per_min: u32) -> Duration {
+    if flow_ml_per_min == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_secs((ml as u64 * 60).div_ceil(flow_ml_per_min as u64))
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{run_time_for, water_needed_ml, BedConfig};
+    use std::time::Duration;
+
+    #[test]
+    fn volume_scales_with_the_moisture_gap() {
+        // 1 m x 0.5 m bed wetted 20 cm deep: 100 L of soil at 150 ml/L
+        let bed = BedConfig::new(100 * 50, 20);
+        assert_eq!(bed.soil_volume_ml(), 100_000);
+        assert_eq!(water_needed_ml(30, 50, &bed), 3_000);
+        assert_eq!(water_needed_ml(20, 70, &bed), 7_500);
+        assert_eq!(water_needed_ml(49, 50, &bed), 150);
+
+        let sandy = bed.with_holding_capacity(60);
+        assert_eq!(water_needed_ml(30, 50, &sandy), 1_200);
+    }
+
+    #[test]
+    fn nothing_needed_at_or_above_target() {
+        let bed = BedConfig::new(100 * 50, 20);
+        assert_eq!(water_needed_ml(50, 50, &bed), 0);
+        assert_eq!(water_needed_ml(80, 50, &bed), 0);
+        // Targets past 100% are treated as full capacity
+        assert_eq!(
+            water_needed_ml(90, 150, &bed),
+            water_needed_ml(90, 100, &bed)
+        );
+    }
+
+    #[test]
+    fn run_time_follows_flow_rate() {
+        assert_eq!(run_time_for(3_000, 1_200), Duration::from_secs(150));
+        assert_eq!(run_time_for(1, 1_200), Duration::from_secs(1));
+        assert_eq!(run_time_for(3_000, 0), Duration::ZERO);
+    }
+}
@@ -0,0 +1,277 @@
+//! Status LED driven by brightness levels so patterns can dim and fade.
+
+use crate::clock::Clock;
+use crate::moisture::MOISTURE_LOW;
+use anyhow::Result;
+use std::time::Duration;
+
+pub const LED_FULL: u8 = 255;
+pub const LED_OFF: u8 = 0;
+
+/// Dimmable status LED
+pub trait Led {
+    /// Set brightness, 0 = off, 255 = full
+    fn set_brightness(&mut self, level: u8) -> Result<()>;
+
+    /// Binary control kept for callers that only need on/off
+    fn set_on(&mut self, on: bool) -> Result<()> {
+        self.set_brightness(if on { LED_FULL } else { LED_OFF })
+    }
+}
+
+/// Hold `brightness` for `hold` before moving to the next step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedStep {
+    pub brightness: u8,
+    pub hold: Duration,
+}
+
+/// `count` on/off blinks at `level`
+pub fn blink(count: usize, level: u8, on: Duration, off: Duration) -> Vec<LedStep> {
+    (0..count)
+        .flat_map(|_| {
+            [
+                LedStep {
+                    brightness: level,
+                    hold: on,
+                },
+                LedStep {
+                    brightness: LED_OFF,
+                    hold: off,
+                },
+            ]
+        })
+        .collect()
+}
+
+/// Linear fade from `from` to `to` over `duration` in `steps` equal steps
+pub fn fade(from: u8, to: u8, duration: Duration, steps: u32) -> Vec<LedStep> {
+    let steps = steps.max(1);
+    let hold = duration / steps;
+    (1..=steps)
+        .map(|i| {
+            let delta = (to as i32 - from as i32) * i as i32 / steps as i32;
+            LedStep {
+                brightness: (from as i32 + delta) as u8,
+                hold,
+            }
+        })
+        .collect()
+}
+
+/// Rapid triple blink shown every reading while in safe mode
+pub fn safe_mode_pattern() -> Vec<LedStep> {
+    blink(
+        3,
+        LED_FULL,
+        Duration::from_millis(100),
+        Duration::from_millis(100),
+    )
+}
+
+/// Long double blink played once when a warning escalates to critical
+pub fn alert_pattern() -> Vec<LedStep> {
+    blink(
+        2,
+        LED_FULL,
+        Duration::from_millis(500),
+        Duration::from_millis(250),
+    )
+}
+
+/// Blink period, held through the wait between readings, that speeds up the further moisture falls below `threshold`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrynessBlink {
+    /// Readings below this blink; at or above it the LED is left alone
+    pub threshold: u8,
+    /// Period at 0% moisture
+    pub fastest: Duration,
+    /// Period just below the threshold
+    pub slowest: Duration,
+}
+
+impl Default for DrynessBlink {
+    fn default() -> Self {
+        Self {
+            threshold: MOISTURE_LOW,
+            fastest: Duration::from_millis(150),
+            slowest: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl DrynessBlink {
+    pub fn with_threshold(mut self, threshold: u8) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Periods at 0% and just below the threshold, in either order
+    pub fn with_bounds(mut self, fastest: Duration, slowest: Duration) -> Self {
+        self.fastest = fastest.min(slowest);
+        self.slowest = fastest.max(slowest);
+        self
+    }
+
+    /// On/off period for `moisture_percent`, `None` at or above the threshold
+    pub fn period(&self, moisture_percent: u8) -> Option<Duration> {
+        if moisture_percent >= self.threshold {
+            return None;
+        }
+        let below = (self.threshold - moisture_percent) as u32;
+        let span = self.slowest.saturating_sub(self.fastest);
+        // One point below is the slowest, 0% the fastest
+        let faster = span * (below - 1) / (self.threshold as u32).max(2).saturating_sub(1);
+        Some(self.slowest.saturating_sub(faster).max(self.fastest))
+    }
+}
+
+/// Play a pattern, sleeping on `clock` between steps
+pub fn play(led: &mut dyn Led, steps: &[LedStep], clock: &dyn Clock) -> Result<()> {
+    for step in steps {
+        led.set_brightness(step.brightness)?;
+        clock.sleep(step.hold);
+    }
+    Ok(())
+}
+
+impl<L: Led + ?Sized> Led for Box<L> {
+    fn set_brightness(&mut self, level: u8) -> Result<()> {
+        (**self).set_brightness(level)
+    }
+}
+
+/// LED that ignores commands, for simulated boards
+#[derive(Debug, Default)]
+pub struct NullLed;
+
+impl Led for NullLed {
+    fn set_brightness(&mut self, _level: u8) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Test double recording every brightness change against a clock
+pub struct RecordingLed<C: Clock> {
+    clock: C,
+    timeline: Vec<(Duration, u8)>,
+}
+
+impl<C: Clock> RecordingLed<C> {
+    pub fn new(clock: C) -> Self {
+        Self {
+            clock,
+            timeline: Vec::new(),
+        }
+    }
+
+    /// `(time, brightness)` for every change, in order
+    pub fn timeline(&self) -> &[(Duration, u8)] {
+        &self.timeline
+    }
+}
+
+impl<C: Clock> Led for RecordingLed<C> {
+    fn set_brightness(&mut self, level: u8) -> Result<()> {
+        self.timeline.push((self.clock.now(), level));
+        Ok(())
+    }
+}
+
+/// LEDC (hardware PWM) backed LED
+#[cfg(target_os = "espidf")]
+pub struct LedcLed<'d> {
+    driver: esp_idf_hal::ledc::LedcDriver<'d>,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> LedcLed<'d> {
+    pub fn new(driver: esp_idf_hal::ledc::LedcDriver<'d>) -> Self {
+        Self { driver }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl Led for LedcLed<'_> {
+    fn set_brightness(&mut self, level: u8) -> Result<()> {
+        let duty = self.driver.get_max_duty() * level as u32 / LED_FULL as u32;
+        self.driver.set_duty(duty)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{blink, fade, play, DrynessBlink, Led, RecordingLed, LED_FULL, LED_OFF};
+    use crate::clock::{Clock, MockClock};
+    use std::time::Duration;
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn blink_pattern_produces_on_off_timeline() {
+        let clock = MockClock::new();
+        let mut led = RecordingLed::new(clock.clone());
+        play(&mut led, &blink(2, 128, ms(200), ms(300)), &clock).unwrap();
+
+        assert_eq!(
+            led.timeline(),
+            &[(ms(0), 128), (ms(200), 0), (ms(500), 128), (ms(700), 0)]
+        );
+        assert_eq!(clock.now(), ms(1000));
+    }
+
+    #[test]
+    fn fade_ramps_evenly_to_target() {
+        let clock = MockClock::new();
+        let mut led = RecordingLed::new(clock.clone());
+        play(&mut led, &fade(0, 200, ms(400), 4), &clock).unwrap();
+
+        assert_eq!(
+            led.timeline(),
+            &[(ms(0), 50), (ms(100), 100), (ms(200), 150), (ms(300), 200)]
+        );
+    }
+
+    #[test]
+    fn on_off_maps_to_full_and_zero_brightness() {
+        let mut led = RecordingLed::new(MockClock::new());
+        led.set_on(true).unwrap();
+        led.set_on(false).unwrap();
+        let levels: Vec<u8> = led.timeline().iter().map(|&(_, b)| b).collect();
+        assert_eq!(levels, vec![LED_FULL, LED_OFF]);
+    }
+
+    #[test]
+    fn dryness_blink_speeds_up_as_soil_dries() {
+        let rate = DrynessBlink::default();
+        assert_eq!(rate.threshold, 25);
+        // At or above the threshold the LED keeps its normal behaviour
+        assert_eq!(rate.period(25), None);
+        assert_eq!(rate.period(80), None);
+        assert_eq!(rate.period(24), Some(ms(1000)));
+        assert_eq!(rate.period(0), Some(ms(150)));
+
+        let mut last = rate.period(24).unwrap();
+        for moisture in (0..24).rev() {
+            let period = rate.period(moisture).unwrap();
+            assert!(
+                period < last,
+                "{}% blinks no faster than {}%",
+                moisture,
+                moisture + 1
+            );
+            last = period;
+        }
+
+        // Bounds given the wrong way round are swapped
+        let custom = DrynessBlink::default()
+            .with_threshold(40)
+            .with_bounds(ms(2000), ms(200));
+        assert_eq!(custom.period(39), Some(ms(2000)));
+        assert_eq!(custom.period(0), Some(ms(200)));
+        assert!(custom.period(10) < custom.period(30));
+    }
+}
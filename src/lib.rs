@@ -0,0 +1,72 @@
+//! Host-testable building blocks for the soil sensor reference firmware.
+//!
+//! Everything here is plain Rust so it can be exercised with `cargo test` on
+//! the host; ESP-IDF backed implementations are gated on `target_os = "espidf"`.
+
+pub mod accessibility;
+pub mod ads1115;
+pub mod alert;
+pub mod app;
+pub mod array;
+#[cfg(any(test, feature = "ble"))]
+pub mod ble;
+pub mod boot;
+pub mod budget;
+pub mod calibrate;
+pub mod checkpoint;
+pub mod classifier;
+pub mod clock;
+pub mod codec;
+pub mod command;
+pub mod config;
+pub mod daily;
+pub mod drift;
+pub mod ec;
+pub mod export;
+pub mod failover;
+pub mod fault;
+pub mod filter;
+pub mod frame;
+pub mod frontend;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod glitch;
+pub mod golden;
+pub mod histogram;
+pub mod history;
+pub mod influx;
+pub mod interval;
+pub mod invariant;
+pub mod irrigation;
+pub mod led;
+pub mod maintenance;
+pub mod modbus;
+pub mod moisture;
+pub mod notify;
+pub mod nvs;
+pub mod poll;
+pub mod power;
+pub mod profile;
+pub mod provision;
+pub mod pump;
+pub mod rate;
+pub mod reading;
+pub mod rewet;
+pub mod rng;
+pub mod rule;
+pub mod sampling;
+pub mod schedule;
+pub mod sdcard;
+pub mod sensor;
+pub mod sink;
+pub mod startup;
+pub mod stats;
+pub mod status;
+pub mod storage;
+pub mod summary;
+pub mod temperature;
+pub mod timestamp;
+pub mod timing;
+pub mod uplink;
+pub mod webhook;
+pub mod window;
+pub mod zone;
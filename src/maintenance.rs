@@ -0,0 +1,230 @@
+//! Service reminder driven by pump runtime and time since the last service.
+//!
+//! There is no RTC, so "calendar" time is operating time accumulated across
+//! boots and persisted alongside the pump totals; time spent powered off is
+//! not counted.
+
+use crate::clock::Clock;
+use crate::pump::PumpLifetime;
+use anyhow::{bail, ensure, Result};
+use std::fmt;
+use std::time::Duration;
+
+const SERVICE_FORMAT_VERSION: u8 = 1;
+const SERVICE_BLOB_LEN: usize = 17;
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Whichever limit is reached first makes maintenance due
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaintenanceConfig {
+    /// Pump runtime since the last service
+    pub max_runtime: Duration,
+    /// Operating time since the last service
+    pub max_age: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            max_runtime: 50 * HOUR,
+            max_age: 90 * DAY,
+        }
+    }
+}
+
+/// Baseline persisted at the last service
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServiceRecord {
+    /// Lifetime pump runtime when the service was done
+    pub runtime_at_service: Duration,
+    /// Operating time since the service, as of the last save
+    pub age: Duration,
+}
+
+impl ServiceRecord {
+    /// Persisted form: version, then both durations in ms as u64 (little endian)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![SERVICE_FORMAT_VERSION];
+        out.extend_from_slice(&(self.runtime_at_service.as_millis() as u64).to_le_bytes());
+        out.extend_from_slice(&(self.age.as_millis() as u64).to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == SERVICE_BLOB_LEN,
+            "service record is {} bytes, expected {}",
+            bytes.len(),
+            SERVICE_BLOB_LEN
+        );
+        if bytes[0] != SERVICE_FORMAT_VERSION {
+            bail!("unsupported service record version {}", bytes[0]);
+        }
+        let ms = |i: usize| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(&bytes[i..i + 8]);
+            Duration::from_millis(u64::from_le_bytes(word))
+        };
+        Ok(Self {
+            runtime_at_service: ms(1),
+            age: ms(9),
+        })
+    }
+}
+
+/// Which limit made maintenance due
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceDue {
+    Runtime { hours: u64 },
+    Calendar { days: u64 },
+}
+
+impl fmt::Display for MaintenanceDue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaintenanceDue::Runtime { hours } => {
+                write!(f, "maintenance due: pump ran {hours}h since last service")
+            }
+            MaintenanceDue::Calendar { days } => {
+                write!(f, "maintenance due: {days} days since last service")
+            }
+        }
+    }
+}
+
+/// Tracks time since the last service against a [`MaintenanceConfig`]
+#[derive(Debug, Clone)]
+pub struct MaintenanceReminder<C> {
+    config: MaintenanceConfig,
+    clock: C,
+    record: ServiceRecord,
+    /// Clock time at which `record.age` was last brought up to date
+    synced_at: Duration,
+}
+
+impl<C: Clock> MaintenanceReminder<C> {
+    /// Continue from `record` as loaded at boot
+    pub fn new(config: MaintenanceConfig, clock: C, record: ServiceRecord) -> Self {
+        Self {
+            config,
+            synced_at: clock.now(),
+            clock,
+            record,
+        }
+    }
+
+    /// Operating time since the last service
+    pub fn age(&self) -> Duration {
+        self.record.age + self.clock.now().saturating_sub(self.synced_at)
+    }
+
+    /// Why maintenance is due, if it is; runtime is checked first
+    pub fn check(&self, lifetime: &PumpLifetime) -> Option<MaintenanceDue> {
+        let ran = lifetime
+            .runtime
+            .saturating_sub(self.record.runtime_at_service);
+        if ran >= self.config.max_runtime {
+            return Some(MaintenanceDue::Runtime {
+                hours: ran.as_secs() / HOUR.as_secs(),
+            });
+        }
+        let age = self.age();
+        (age >= self.config.max_age).then(|| MaintenanceDue::Calendar {
+            days: age.as_secs() / DAY.as_secs(),
+        })
+    }
+
+    /// Record that the pump was serviced now
+    pub fn clear(&mut self, lifetime: &PumpLifetime) {
+        self.record = ServiceRecord {
+            runtime_at_service: lifetime.runtime,
+            age: Duration::ZERO,
+        };
+        self.synced_at = self.clock.now();
+    }
+
+    /// Up-to-date record for persisting
+    pub fn record(&self) -> ServiceRecord {
+        ServiceRecord {
+            age: self.age(),
+            ..self.record
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{MaintenanceConfig, MaintenanceDue, MaintenanceReminder, ServiceRecord, DAY, HOUR};
+    use crate::clock::MockClock;
+    use crate::pump::PumpLifetime;
+    use std::time::Duration;
+
+    fn config() -> MaintenanceConfig {
+        MaintenanceConfig {
+            max_runtime: 10 * HOUR,
+            max_age: 30 * DAY,
+        }
+    }
+
+    fn ran(runtime: Duration) -> PumpLifetime {
+        PumpLifetime {
+            activations: 100,
+            runtime,
+        }
+    }
+
+    #[test]
+    fn runtime_limit_makes_maintenance_due() {
+        let clock = MockClock::new();
+        let record = ServiceRecord {
+            runtime_at_service: 5 * HOUR,
+            age: Duration::ZERO,
+        };
+        let reminder = MaintenanceReminder::new(config(), clock.clone(), record);
+        assert_eq!(reminder.check(&ran(14 * HOUR)), None);
+        assert_eq!(
+            reminder.check(&ran(15 * HOUR)),
+            Some(MaintenanceDue::Runtime { hours: 10 })
+        );
+    }
+
+    #[test]
+    fn calendar_limit_counts_age_carried_over_from_earlier_boots() {
+        let clock = MockClock::new();
+        let record = ServiceRecord {
+            runtime_at_service: Duration::ZERO,
+            age: 29 * DAY,
+        };
+        let reminder = MaintenanceReminder::new(config(), clock.clone(), record);
+        assert_eq!(reminder.check(&ran(HOUR)), None);
+
+        clock.advance(DAY);
+        let due = reminder.check(&ran(HOUR)).unwrap();
+        assert_eq!(due, MaintenanceDue::Calendar { days: 30 });
+        assert_eq!(
+            due.to_string(),
+            "maintenance due: 30 days since last service"
+        );
+
+        let saved = reminder.record();
+        assert_eq!(ServiceRecord::from_bytes(&saved.to_bytes()).unwrap(), saved);
+        assert_eq!(saved.age, 30 * DAY);
+    }
+
+    #[test]
+    fn clearing_restarts_both_limits() {
+        let clock = MockClock::new();
+        let mut reminder =
+            MaintenanceReminder::new(config(), clock.clone(), ServiceRecord::default());
+        clock.advance(31 * DAY);
+        let lifetime = ran(12 * HOUR);
+        assert!(reminder.check(&lifetime).is_some());
+
+        reminder.clear(&lifetime);
+        assert_eq!(reminder.check(&lifetime), None);
+        assert_eq!(reminder.record().runtime_at_service, 12 * HOUR);
+        clock.advance(DAY);
+        assert_eq!(reminder.check(&ran(13 * HOUR)), None);
+    }
+}
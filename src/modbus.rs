@@ -0,0 +1,226 @@
+//! Modbus RTU soil probes (moisture/temperature/EC over RS-485).
+
+use crate::fault::SensorFault;
+use crate::sensor::SoilSensor;
+use anyhow::Result;
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+
+/// Byte transport to the RS-485 bus
+pub trait ModbusTransport {
+    /// Send `request` and return the raw response frame
+    fn exchange(&mut self, request: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Holding register addresses for a particular probe model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterMap {
+    pub moisture: u16,
+    pub temperature: Option<u16>,
+    pub ec: Option<u16>,
+}
+
+impl Default for RegisterMap {
+    /// Layout used by the common 3-in-1 RS-485 probes
+    fn default() -> Self {
+        Self {
+            moisture: 0x0000,
+            temperature: Some(0x0001),
+            ec: Some(0x0002),
+        }
+    }
+}
+
+/// Modbus CRC-16 (poly 0xA001, init 0xFFFF), transmitted low byte first
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xA001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Build a "read one holding register" request frame
+pub fn read_register_request(slave: u8, register: u16) -> [u8; 8] {
+    let [reg_hi, reg_lo] = register.to_be_bytes();
+    let mut frame = [
+        slave,
+        READ_HOLDING_REGISTERS,
+        reg_hi,
+        reg_lo,
+        0x00,
+        0x01,
+        0,
+        0,
+    ];
+    let [crc_lo, crc_hi] = crc16(&frame[..6]).to_le_bytes();
+    frame[6] = crc_lo;
+    frame[7] = crc_hi;
+    frame
+}
+
+/// Validate a single-register response frame and extract its value
+pub fn decode_register_response(slave: u8, frame: &[u8]) -> Result<u16, SensorFault> {
+    if frame.len() != 7 {
+        return Err(SensorFault::MalformedResponse);
+    }
+    let expected = crc16(&frame[..5]);
+    let actual = u16::from_le_bytes([frame[5], frame[6]]);
+    if expected != actual {
+        return Err(SensorFault::BadCrc { expected, actual });
+    }
+    if frame[0] != slave || frame[1] != READ_HOLDING_REGISTERS || frame[2] != 2 {
+        return Err(SensorFault::MalformedResponse);
+    }
+    Ok(u16::from_be_bytes([frame[3], frame[4]]))
+}
+
+/// RS-485 probe returning its moisture register as the raw reading
+pub struct ModbusSoilSensor<T> {
+    transport: T,
+    slave: u8,
+    registers: RegisterMap,
+}
+
+impl<T: ModbusTransport> ModbusSoilSensor<T> {
+    pub fn new(transport: T, slave: u8, registers: RegisterMap) -> Self {
+        Self {
+            transport,
+            slave,
+            registers,
+        }
+    }
+
+    fn read_register(&mut self, register: u16) -> Result<u16> {
+        let response = self
+            .transport
+            .exchange(&read_register_request(self.slave, register))?;
+        Ok(decode_register_response(self.slave, &response)?)
+    }
+
+    /// Probe temperature register, if the map has one
+    pub fn read_temperature_raw(&mut self) -> Result<Option<u16>> {
+        self.registers
+            .temperature
+            .map(|reg| self.read_register(reg))
+            .transpose()
+    }
+
+    /// Probe EC register, if the map has one
+    pub fn read_ec_raw(&mut self) -> Result<Option<u16>> {
+        self.registers
+            .ec
+            .map(|reg| self.read_register(reg))
+            .transpose()
+    }
+}
+
+impl<T: ModbusTransport> SoilSensor for ModbusSoilSensor<T> {
+    fn read_averaged(&mut self, samples: usize) -> Result<u16> {
+        let samples = samples.max(1);
+        let mut sum = 0u32;
+        for _ in 0..samples {
+            sum += self.read_register(self.registers.moisture)? as u32;
+        }
+        Ok((sum / samples as u32) as u16)
+    }
+}
+
+/// Response timeout for one Modbus transaction
+#[cfg(target_os = "espidf")]
+const RESPONSE_TIMEOUT_MS: u64 = 200;
+
+/// UART transport; RS-485 direction switching is left to the UART's RS485 mode
+#[cfg(target_os = "espidf")]
+pub struct UartTransport<'d> {
+    uart: esp_idf_hal::uart::UartDriver<'d>,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> UartTransport<'d> {
+    pub fn new(uart: esp_idf_hal::uart::UartDriver<'d>) -> Self {
+        Self { uart }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl ModbusTransport for UartTransport<'_> {
+    fn exchange(&mut self, request: &[u8]) -> Result<Vec<u8>> {
+        let timeout = esp_idf_hal::delay::TickType::new_millis(RESPONSE_TIMEOUT_MS).ticks();
+        self.uart.write(request)?;
+        let mut buf = [0u8; 16];
+        let len = self.uart.read(&mut buf, timeout)?;
+        Ok(buf[..len].to_vec())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{crc16, read_register_request, ModbusSoilSensor, ModbusTransport, RegisterMap};
+    use crate::fault::SensorFault;
+    use crate::sensor::SoilSensor;
+    use anyhow::Result;
+
+    /// Replays one canned response and remembers the last request
+    struct Canned {
+        response: Vec<u8>,
+        last_request: Vec<u8>,
+    }
+
+    impl ModbusTransport for Canned {
+        fn exchange(&mut self, request: &[u8]) -> Result<Vec<u8>> {
+            self.last_request = request.to_vec();
+            Ok(self.response.clone())
+        }
+    }
+
+    #[test]
+    fn request_frame_matches_reference() {
+        // Reference frame for slave 1, read 1 register at 0x0000
+        assert_eq!(
+            read_register_request(0x01, 0x0000),
+            [0x01, 0x03, 0x00, 0x00, 0x00, 0x01, 0x84, 0x0A]
+        );
+        assert_eq!(crc16(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x01]), 0x0A84);
+    }
+
+    #[test]
+    fn decodes_known_good_response() {
+        // Slave 1, 2 data bytes, value 0x0292 (65.8% in tenths)
+        let mut response = vec![0x01, 0x03, 0x02, 0x02, 0x92];
+        response.extend_from_slice(&crc16(&response).to_le_bytes());
+        let transport = Canned {
+            response,
+            last_request: Vec::new(),
+        };
+        let mut sensor = ModbusSoilSensor::new(transport, 0x01, RegisterMap::default());
+
+        assert_eq!(sensor.read_averaged(1).unwrap(), 658);
+        assert_eq!(
+            sensor.transport.last_request,
+            read_register_request(0x01, 0x0000)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_crc_as_sensor_fault() {
+        let transport = Canned {
+            response: vec![0x01, 0x03, 0x02, 0x02, 0x92, 0x00, 0x00],
+            last_request: Vec::new(),
+        };
+        let mut sensor = ModbusSoilSensor::new(transport, 0x01, RegisterMap::default());
+
+        let err = sensor.read_averaged(1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SensorFault>(),
+            Some(SensorFault::BadCrc { actual: 0, .. })
+        ));
+    }
+}
@@ -0,0 +1,1145 @@
+//! Raw ADC to moisture percentage conversion and soil condition classification.
+
+use crate::fault::{ADC_MAX_12BIT, FAULT_RAW_MAX, FAULT_RAW_MIN};
+use crate::sensor::isqrt;
+use anyhow::{bail, ensure, Result};
+use std::fmt;
+
+const CALIBRATION_FORMAT_VERSION: u8 = 3;
+/// Output gain of 1.0, in thousandths
+pub const UNITY_GAIN: u16 = 1000;
+
+// Sensor configuration constants
+pub const DRY_SOIL: u16 = 3000; // Sensor reading in completely dry soil (higher = drier)
+pub const WET_SOIL: u16 = 1200; // Sensor reading in very wet soil (lower = wetter)
+pub const MOISTURE_LOW: u8 = 25; // Below 25% - very dry
+pub const MOISTURE_HIGH: u8 = 75; // Above 75% - very wet
+pub const MOISTURE_HYSTERESIS: u8 = 3; // Points past a threshold before DRY/WET clears
+pub const RESISTIVE_DRY_SOIL: u16 = 3900; // Resistive probe in dry soil, close to open circuit
+pub const RESISTIVE_WET_SOIL: u16 = 1500; // Resistive probe in saturated soil
+
+/// Sensing principle of the probe, which decides the conversion model and
+/// the fault bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProbeKind {
+    /// Roughly linear response; the model everything else assumes
+    #[default]
+    Capacitive,
+    /// Raw value falls steeply with the first bit of moisture and flattens
+    /// towards saturation; electrodes corrode, so expect recalibration
+    Resistive,
+    /// Linear on each side of a bend in the response: dry to
+    /// `breakpoint_raw` covers 0 to `breakpoint_percent`, `breakpoint_raw` to
+    /// wet the rest, so the segments meet at the breakpoint. Falls back to
+    /// the single line when the breakpoint is not between dry and wet.
+    DualLinear {
+        breakpoint_raw: u16,
+        breakpoint_percent: u8,
+    },
+}
+
+impl ProbeKind {
+    /// Typical calibration points for a new probe of this kind
+    pub fn default_calibration(&self) -> Calibration {
+        match self {
+            ProbeKind::Capacitive | ProbeKind::DualLinear { .. } => Calibration::default(),
+            ProbeKind::Resistive => Calibration::new(RESISTIVE_DRY_SOIL, RESISTIVE_WET_SOIL),
+        }
+    }
+
+    /// Plausible raw range when the calibration sets none. Dry resistive
+    /// probes legitimately read near full scale, so only a short is a fault.
+    pub fn fault_bounds(&self) -> (u16, u16) {
+        match self {
+            ProbeKind::Capacitive | ProbeKind::DualLinear { .. } => (FAULT_RAW_MIN, FAULT_RAW_MAX),
+            ProbeKind::Resistive => (FAULT_RAW_MIN, ADC_MAX_12BIT),
+        }
+    }
+
+    /// Convert raw ADC reading to moisture percentage with this kind's model
+    pub fn moisture_percent(&self, raw_value: u16, cal: &Calibration) -> u8 {
+        match self {
+            ProbeKind::Capacitive => raw_to_moisture_percent(raw_value, cal),
+            ProbeKind::Resistive => {
+                // Quadratic in the linear wetness fraction: half way between
+                // the raw calibration points is only a quarter wet
+                let linear = map_raw(raw_value, cal, 1000).clamp(0, 1000);
+                cal.correct(linear * linear / 10_000, 100).clamp(0, 100) as u8
+            }
+            ProbeKind::DualLinear {
+                breakpoint_raw,
+                breakpoint_percent,
+            } => {
+                let mapped =
+                    map_dual_linear(raw_value, cal, *breakpoint_raw, *breakpoint_percent, 100);
+                cal.correct(mapped, 100).clamp(0, 100) as u8
+            }
+        }
+    }
+
+    /// Moisture in tenths of a percent on this kind's curve, without
+    /// clamping, so readings past the calibration points land outside 0..=1000
+    pub fn moisture_tenths_unclamped(&self, raw_value: u16, cal: &Calibration) -> i32 {
+        let mapped = match self {
+            ProbeKind::Capacitive => map_raw(raw_value, cal, 1000),
+            ProbeKind::Resistive => {
+                // Signed square rounded away from zero, so anything past
+                // either calibration point stays outside 0..=1000
+                let linear = map_raw(raw_value, cal, 1000);
+                let squared = (linear * linear + 999) / 1000;
+                if linear < 0 {
+                    -squared
+                } else {
+                    squared
+                }
+            }
+            ProbeKind::DualLinear {
+                breakpoint_raw,
+                breakpoint_percent,
+            } => map_dual_linear(raw_value, cal, *breakpoint_raw, *breakpoint_percent, 1000),
+        };
+        cal.correct(mapped, 1000)
+    }
+
+    /// Raw value this kind's curve maps to `mapped` (tenths before the output
+    /// correction), unclamped; the inverse of the mapping in
+    /// [`moisture_tenths_unclamped`](Self::moisture_tenths_unclamped)
+    fn raw_for_mapped(&self, mapped: i32, cal: &Calibration) -> i32 {
+        let (dry, wet) = (cal.dry as i32, cal.wet as i32);
+        // Rounded away from the dry point, undoing the truncation towards it
+        let away = |offset: i32, scale: i32| (offset + offset.signum() * (scale - 1)) / scale;
+        let linear = |mapped: i32| dry - away(mapped * (dry - wet), 1000);
+        match self {
+            ProbeKind::Capacitive => linear(mapped),
+            ProbeKind::Resistive => {
+                let root = isqrt(mapped.unsigned_abs() as u64 * 1000) as i32;
+                linear(if mapped < 0 { -root } else { root })
+            }
+            ProbeKind::DualLinear {
+                breakpoint_raw,
+                breakpoint_percent,
+            } => {
+                let bp = *breakpoint_raw as i32;
+                if !(dry.min(wet) + 1..dry.max(wet)).contains(&bp) {
+                    return linear(mapped);
+                }
+                let bp_mapped = (*breakpoint_percent).min(100) as i32 * 10;
+                let dry_segment = match bp_mapped {
+                    0 => false,
+                    1000 => true,
+                    _ => mapped <= bp_mapped,
+                };
+                if dry_segment {
+                    dry - away(mapped * (dry - bp), bp_mapped)
+                } else {
+                    bp - away((mapped - bp_mapped) * (bp - wet), 1000 - bp_mapped)
+                }
+            }
+        }
+    }
+
+    /// [`uncertainty_tenths`] on this kind's curve: the spread scaled by the
+    /// slope around `raw_value`, which varies along non-linear curves
+    pub fn uncertainty_tenths(&self, raw_value: u16, spread: u16, cal: &Calibration) -> u16 {
+        if *self == ProbeKind::Capacitive || cal.is_inverted() {
+            return uncertainty_tenths(spread, cal);
+        }
+        let dry_side = self.moisture_tenths_unclamped(raw_value.saturating_sub(spread), cal);
+        let wet_side = self.moisture_tenths_unclamped(raw_value.saturating_add(spread), cal);
+        dry_side.abs_diff(wet_side).div_ceil(2).min(1000) as u16
+    }
+}
+
+/// Which end of the raw scale is dry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Polarity {
+    /// Raw value falls as soil gets wetter (typical capacitive and resistive modules)
+    #[default]
+    DryHigh,
+    /// Raw value rises as soil gets wetter
+    WetHigh,
+}
+
+impl Polarity {
+    /// Polarity implied by raw readings captured in dry and in wet soil
+    pub fn infer(dry: u16, wet: u16) -> Polarity {
+        if dry >= wet {
+            Polarity::DryHigh
+        } else {
+            Polarity::WetHigh
+        }
+    }
+}
+
+impl fmt::Display for Polarity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Polarity::DryHigh => write!(f, "dry reads high"),
+            Polarity::WetHigh => write!(f, "wet reads high"),
+        }
+    }
+}
+
+/// Per-probe calibration points and plausibility range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    /// Raw reading in completely dry soil
+    pub dry: u16,
+    /// Raw reading in saturated soil
+    pub wet: u16,
+    /// Lowest plausible raw reading for this probe; `None` uses the global fault bound
+    pub valid_min: Option<u16>,
+    /// Highest plausible raw reading for this probe; `None` uses the global fault bound
+    pub valid_max: Option<u16>,
+    pub polarity: Polarity,
+    /// Percentage points added after the raw-to-percent mapping, e.g. -5 for
+    /// a probe reading 5% high against a gravimetric reference
+    pub output_offset: i8,
+    /// Scale applied to the mapped percentage before the offset, in
+    /// thousandths ([`UNITY_GAIN`] leaves it unchanged)
+    pub output_gain: u16,
+}
+
+impl Calibration {
+    /// Dry-high calibration
+    pub fn new(dry: u16, wet: u16) -> Self {
+        Self {
+            dry,
+            wet,
+            valid_min: None,
+            valid_max: None,
+            polarity: Polarity::DryHigh,
+            output_offset: 0,
+            output_gain: UNITY_GAIN,
+        }
+    }
+
+    pub fn with_polarity(mut self, polarity: Polarity) -> Self {
+        self.polarity = polarity;
+        self
+    }
+
+    /// Correct the mapped percentage: `percent * gain / 1000 + offset`
+    pub fn with_output_correction(mut self, offset: i8, gain: u16) -> Self {
+        self.output_offset = offset;
+        self.output_gain = gain;
+        self
+    }
+
+    /// Apply the output correction to a mapped value where `full_scale` is 100%
+    fn correct(&self, mapped: i32, full_scale: i32) -> i32 {
+        mapped * self.output_gain as i32 / UNITY_GAIN as i32
+            + self.output_offset as i32 * full_scale / 100
+    }
+
+    /// The points are the wrong way round (or equal) for the polarity
+    pub fn is_inverted(&self) -> bool {
+        match self.polarity {
+            Polarity::DryHigh => self.dry <= self.wet,
+            Polarity::WetHigh => self.wet <= self.dry,
+        }
+    }
+
+    /// Restrict plausible raw readings to `min..=max` for this deployment
+    pub fn with_valid_range(mut self, min: u16, max: u16) -> Self {
+        self.valid_min = Some(min);
+        self.valid_max = Some(max);
+        self
+    }
+
+    /// Inclusive range of raw readings considered plausible
+    pub fn valid_range(&self) -> (u16, u16) {
+        self.valid_range_for(ProbeKind::Capacitive)
+    }
+
+    /// Like [`valid_range`](Self::valid_range), falling back to `kind`'s bounds
+    pub fn valid_range_for(&self, kind: ProbeKind) -> (u16, u16) {
+        let (min, max) = kind.fault_bounds();
+        (self.valid_min.unwrap_or(min), self.valid_max.unwrap_or(max))
+    }
+
+    /// Compact persisted form: version, dry, wet, then each optional bound
+    /// as a presence byte followed by the value (little endian), then the
+    /// polarity (0 dry-high, 1 wet-high), output offset (i8) and output gain.
+    /// Version 1 blobs lack the polarity and load as dry-high; versions 1 and
+    /// 2 load without output correction.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![CALIBRATION_FORMAT_VERSION];
+        out.extend_from_slice(&self.dry.to_le_bytes());
+        out.extend_from_slice(&self.wet.to_le_bytes());
+        for bound in [self.valid_min, self.valid_max] {
+            out.push(bound.is_some() as u8);
+            out.extend_from_slice(&bound.unwrap_or(0).to_le_bytes());
+        }
+        out.push((self.polarity == Polarity::WetHigh) as u8);
+        out.push(self.output_offset as u8);
+        out.extend_from_slice(&self.output_gain.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let expected = match bytes.first() {
+            Some(1) => 11,
+            Some(2) => 12,
+            Some(&CALIBRATION_FORMAT_VERSION) => 15,
+            Some(v) => bail!("unsupported calibration format version {}", v),
+            None => bail!("calibration blob is empty"),
+        };
+        ensure!(
+            bytes.len() == expected,
+            "calibration blob is {} bytes, expected {}",
+            bytes.len(),
+            expected
+        );
+        let word = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let bound = |i: usize| (bytes[i] != 0).then(|| word(i + 1));
+        let polarity = match bytes.get(11) {
+            Some(1) => Polarity::WetHigh,
+            _ => Polarity::DryHigh,
+        };
+        let (output_offset, output_gain) = match bytes.len() {
+            15 => (bytes[12] as i8, word(13)),
+            _ => (0, UNITY_GAIN),
+        };
+        Ok(Self {
+            dry: word(1),
+            wet: word(3),
+            valid_min: bound(5),
+            valid_max: bound(8),
+            polarity,
+            output_offset,
+            output_gain,
+        })
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::new(DRY_SOIL, WET_SOIL)
+    }
+}
+
+/// Linear raw-to-percent mapping without clamping; readings beyond the
+/// calibration points extrapolate below 0 or above 100
+pub fn raw_to_moisture_unclamped(raw_value: u16, cal: &Calibration) -> i32 {
+    cal.correct(map_raw(raw_value, cal, 100), 100)
+}
+
+/// Core mapping shared by all resolutions: `full_scale` at the wet point, 0 at dry
+fn map_raw(raw_value: u16, cal: &Calibration, full_scale: i32) -> i32 {
+    if cal.is_inverted() {
+        // Degenerate calibration, fall back to a hard threshold
+        let dry_side = match cal.polarity {
+            Polarity::DryHigh => raw_value >= cal.dry,
+            Polarity::WetHigh => raw_value <= cal.dry,
+        };
+        return if dry_side { 0 } else { full_scale };
+    }
+    // Linear mapping: map(raw_value, cal.dry, cal.wet, 0, full_scale); range
+    // and offset are both negative for wet-high probes
+    let range = cal.dry as i32 - cal.wet as i32;
+    let offset = cal.dry as i32 - raw_value as i32;
+    offset * full_scale / range
+}
+
+/// Piecewise linear mapping through (`breakpoint_raw`, `breakpoint_percent`),
+/// `full_scale` at the wet point; either segment extends past its
+/// calibration point
+fn map_dual_linear(
+    raw_value: u16,
+    cal: &Calibration,
+    breakpoint_raw: u16,
+    breakpoint_percent: u8,
+    full_scale: i32,
+) -> i32 {
+    let (dry, wet, bp) = (cal.dry as i32, cal.wet as i32, breakpoint_raw as i32);
+    let between = (dry.min(wet) + 1..dry.max(wet)).contains(&bp);
+    if cal.is_inverted() || !between {
+        return map_raw(raw_value, cal, full_scale);
+    }
+    let raw = raw_value as i32;
+    let bp_mapped = breakpoint_percent.min(100) as i32 * full_scale / 100;
+    // Dry segment when the reading is on the dry side of the breakpoint,
+    // whichever way the polarity runs
+    if (raw - bp) * (dry - bp) >= 0 {
+        (dry - raw) * bp_mapped / (dry - bp)
+    } else {
+        bp_mapped + (bp - raw) * (full_scale - bp_mapped) / (bp - wet)
+    }
+}
+
+/// Convert raw ADC reading to moisture percentage
+pub fn raw_to_moisture_percent(raw_value: u16, cal: &Calibration) -> u8 {
+    raw_to_moisture_unclamped(raw_value, cal).clamp(0, 100) as u8
+}
+
+/// Convert raw ADC reading to tenths of a percent (0..=1000) for trend math
+pub fn raw_to_moisture_tenths(raw_value: u16, cal: &Calibration) -> u16 {
+    cal.correct(map_raw(raw_value, cal, 1000), 1000)
+        .clamp(0, 1000) as u16
+}
+
+/// Raw reading on `to`'s scale and curve that converts to the same moisture
+/// as `raw_value` does on `from`'s, so one probe's readings can stand in for
+/// another's. A degenerate `to` passes the reading through unchanged.
+pub fn rescale_raw(
+    raw_value: u16,
+    from: &Calibration,
+    from_kind: ProbeKind,
+    to: &Calibration,
+    to_kind: ProbeKind,
+) -> u16 {
+    if to.is_inverted() || to.output_gain == 0 {
+        return raw_value;
+    }
+    let tenths = from_kind.moisture_tenths_unclamped(raw_value, from);
+    // Undo `to`'s output correction, then its curve
+    let mapped =
+        (tenths - to.output_offset as i32 * 10) * UNITY_GAIN as i32 / to.output_gain as i32;
+    to_kind.raw_for_mapped(mapped, to).clamp(0, u16::MAX as i32) as u16
+}
+
+/// Uncertainty in tenths of a percent for a reading whose conversions had
+/// standard deviation `spread` (raw counts): the spread scaled by the
+/// mapping slope. A degenerate calibration is a step, so it is fully uncertain.
+pub fn uncertainty_tenths(spread: u16, cal: &Calibration) -> u16 {
+    if cal.is_inverted() {
+        return 1000;
+    }
+    let span = cal.dry.abs_diff(cal.wet) as u32;
+    // The output gain steepens or flattens the slope too
+    let tenths = (spread as u32 * cal.output_gain as u32 + span / 2) / span;
+    tenths.min(1000) as u16
+}
+
+/// What to do with readings that fall outside the calibration range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClampPolicy {
+    /// Clamp to 0..=100 without comment
+    #[default]
+    Silent,
+    /// Clamp to 0..=100 and count how often it happens
+    Count,
+    /// Report up to `max_percent` beyond either end (e.g. -5 / 105) so drift is visible
+    Overshoot { max_percent: u8 },
+}
+
+/// Raw-to-percent conversion applying a [`ClampPolicy`]
+#[derive(Debug, Clone, Default)]
+pub struct MoistureConverter {
+    policy: ClampPolicy,
+    clip_count: u32,
+    clamped: bool,
+}
+
+impl MoistureConverter {
+    pub fn new(policy: ClampPolicy) -> Self {
+        Self {
+            policy,
+            clip_count: 0,
+            clamped: false,
+        }
+    }
+
+    /// Convert `raw` on `kind`'s curve; the result only leaves 0..=100 under
+    /// [`ClampPolicy::Overshoot`]
+    pub fn convert(&mut self, raw_value: u16, cal: &Calibration, kind: ProbeKind) -> i16 {
+        let tenths = kind.moisture_tenths_unclamped(raw_value, cal);
+        let (lo, hi) = match self.policy {
+            ClampPolicy::Silent | ClampPolicy::Count => (0, 100),
+            ClampPolicy::Overshoot { max_percent } => {
+                (-(max_percent as i32), 100 + max_percent as i32)
+            }
+        };
+        self.clamped = !(0..=1000).contains(&tenths);
+        if self.policy == ClampPolicy::Count && self.clamped {
+            self.clip_count = self.clip_count.saturating_add(1);
+        }
+        (tenths / 10).clamp(lo, hi) as i16
+    }
+
+    /// The last reading converted fell outside the calibration range
+    pub fn clamped(&self) -> bool {
+        self.clamped
+    }
+
+    /// Readings clamped so far under [`ClampPolicy::Count`]
+    pub fn clip_count(&self) -> u32 {
+        self.clip_count
+    }
+}
+
+/// Final clamp of a possibly overshooting percentage for status and display
+pub fn clamp_percent(percent: i16) -> u8 {
+    percent.clamp(0, 100) as u8
+}
+
+/// Get soil condition description and LED state
+pub fn get_soil_condition(moisture_percent: u8) -> (&'static str, bool) {
+    if moisture_percent < MOISTURE_LOW {
+        ("DRY - Need Water!", true) // LED on for dry soil
+    } else if moisture_percent > MOISTURE_HIGH {
+        ("WET - Too Much Water!", false) // LED off for wet soil
+    } else {
+        ("OPTIMAL", false) // LED off for optimal conditions
+    }
+}
+
+/// Soil condition shown on the console and status LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoilCondition {
+    Dry,
+    Optimal,
+    Wet,
+}
+
+impl SoilCondition {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SoilCondition::Dry => "DRY - Need Water!",
+            SoilCondition::Optimal => "OPTIMAL",
+            SoilCondition::Wet => "WET - Too Much Water!",
+        }
+    }
+
+    /// LED is lit only for dry soil
+    pub fn led_on(&self) -> bool {
+        *self == SoilCondition::Dry
+    }
+}
+
+/// Debounced [`get_soil_condition`] with hysteresis on both boundaries.
+///
+/// DRY engages below `low` and clears once moisture reaches `low + hysteresis`;
+/// WET engages above `high` and clears once moisture drops below
+/// `high - hysteresis`, so readings hovering on a threshold don't flap.
+#[derive(Debug, Clone)]
+pub struct ConditionTracker {
+    low: u8,
+    high: u8,
+    hysteresis: u8,
+    current: Option<SoilCondition>,
+}
+
+impl ConditionTracker {
+    pub fn new(low: u8, high: u8, hysteresis: u8) -> Self {
+        Self {
+            low,
+            high,
+            hysteresis,
+            current: None,
+        }
+    }
+
+    /// Feed the latest moisture and get the condition to display
+    pub fn update(&mut self, moisture_percent: u8) -> SoilCondition {
+        let holds = match self.current {
+            Some(SoilCondition::Dry) => moisture_percent < self.low.saturating_add(self.hysteresis),
+            Some(SoilCondition::Wet) => {
+                moisture_percent >= self.high.saturating_sub(self.hysteresis)
+            }
+            _ => false,
+        };
+        let next = if holds {
+            self.current.unwrap_or(SoilCondition::Optimal)
+        } else if moisture_percent < self.low {
+            SoilCondition::Dry
+        } else if moisture_percent > self.high {
+            SoilCondition::Wet
+        } else {
+            SoilCondition::Optimal
+        };
+        self.current = Some(next);
+        next
+    }
+}
+
+impl Default for ConditionTracker {
+    fn default() -> Self {
+        Self::new(MOISTURE_LOW, MOISTURE_HIGH, MOISTURE_HYSTERESIS)
+    }
+}
+
+/// What one raw value converts to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Converted {
+    pub moisture_percent: u8,
+}
+
+/// Reuses the last conversion while the raw value repeats, which is most
+/// cycles once the soil has settled.
+///
+/// Entries are keyed on the calibration and probe kind as well, so a
+/// recalibration never serves a stale percentage.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionCache {
+    last: Option<(u16, Calibration, ProbeKind, Converted)>,
+    hits: u32,
+}
+
+impl ConversionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Convert `raw_value`, or return the cached result for the same inputs
+    pub fn convert(&mut self, raw_value: u16, cal: &Calibration, kind: ProbeKind) -> &Converted {
+        let fresh = match &self.last {
+            Some((raw, c, k, _)) => *raw == raw_value && c == cal && *k == kind,
+            None => false,
+        };
+        if fresh {
+            self.hits = self.hits.saturating_add(1);
+        } else {
+            let converted = Converted {
+                moisture_percent: kind.moisture_percent(raw_value, cal),
+            };
+            self.last = Some((raw_value, *cal, kind, converted));
+        }
+        // Just filled above if it was empty or stale
+        &self.last.as_ref().expect("cache entry").3
+    }
+
+    /// Drop the cached entry
+    pub fn invalidate(&mut self) {
+        self.last = None;
+    }
+
+    /// Conversions served from the cache so far
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+}
+
+/// Moisture range considered comfortable enough that nothing needs doing.
+///
+/// Independent of the pump thresholds: inside the band the pump is held off
+/// and the status LED stays dark, whatever the thresholds would do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComfortBand {
+    low: u8,
+    high: u8,
+}
+
+impl ComfortBand {
+    /// Inclusive band from `low` to `high` percent
+    pub fn new(low: u8, high: u8) -> Self {
+        Self {
+            low: low.min(high),
+            high: low.max(high),
+        }
+    }
+
+    pub fn contains(&self, moisture_percent: u8) -> bool {
+        (self.low..=self.high).contains(&moisture_percent)
+    }
+}
+
+/// Blends reported moisture from what was last shown to a new calibration
+/// over a number of cycles, so a runtime recalibration does not show as a
+/// step. Only for display: control should use the new calibration at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalibrationTransition {
+    to: Calibration,
+    /// Reported minus new-calibration moisture when the change was made,
+    /// in percentage points; shrinks to zero over the blend
+    offset: i32,
+    cycles: u32,
+    elapsed: u32,
+}
+
+impl CalibrationTransition {
+    /// Start from `reported`, the moisture last shown for `raw`, and be fully
+    /// on `to` after `cycles` conversions (at least one). A change made
+    /// mid-blend passes the blended value, so it continues from there.
+    pub fn new(reported: u8, raw: u16, to: Calibration, kind: ProbeKind, cycles: u32) -> Self {
+        Self {
+            to,
+            offset: i32::from(reported) - i32::from(kind.moisture_percent(raw, &to)),
+            cycles: cycles.max(1),
+            elapsed: 0,
+        }
+    }
+
+    pub fn target(&self) -> Calibration {
+        self.to
+    }
+
+    /// Reported moisture has reached the new calibration
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.cycles
+    }
+
+    /// Moisture for `raw` one cycle further into the blend
+    pub fn convert(&mut self, raw: u16, kind: ProbeKind) -> u8 {
+        self.elapsed = (self.elapsed + 1).min(self.cycles);
+        let new = i32::from(kind.moisture_percent(raw, &self.to));
+        let old = new + self.offset;
+        let weight = self.elapsed as i32;
+        (old + (new - old) * weight / self.cycles as i32).clamp(0, 100) as u8
+    }
+}
+
+/// Second scale expressing sensor moisture as percent of field capacity:
+/// 0 at the wilting point, 100 at field capacity. Saturated soil reads
+/// above 100; soil drier than the wilting point reads 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldCapacityScale {
+    wilting_point: u8,
+    field_capacity: u8,
+}
+
+impl FieldCapacityScale {
+    /// Reference sensor percentages measured at each point
+    pub fn new(wilting_point: u8, field_capacity: u8) -> Result<Self> {
+        ensure!(
+            wilting_point < field_capacity,
+            "wilting point ({wilting_point}%) must be below field capacity ({field_capacity}%)"
+        );
+        Ok(Self {
+            wilting_point,
+            field_capacity,
+        })
+    }
+
+    /// `moisture_percent` on the field-capacity scale, rounded to nearest
+    pub fn percent_of_capacity(&self, moisture_percent: u8) -> u8 {
+        let span = u32::from(self.field_capacity - self.wilting_point);
+        let above = u32::from(moisture_percent.saturating_sub(self.wilting_point));
+        ((above * 100 + span / 2) / span).min(u32::from(u8::MAX)) as u8
+    }
+}
+
+// Unit tests are host-only; they are not built for the Xtensa target used in CI clippy.
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        clamp_percent, get_soil_condition, raw_to_moisture_percent, raw_to_moisture_tenths,
+        rescale_raw, uncertainty_tenths, Calibration, CalibrationTransition, ClampPolicy,
+        ComfortBand, ConditionTracker, ConversionCache, FieldCapacityScale, MoistureConverter,
+        Polarity, ProbeKind, SoilCondition, DRY_SOIL, MOISTURE_HIGH, MOISTURE_LOW, WET_SOIL,
+    };
+
+    #[test]
+    fn uncertainty_scales_spread_by_mapping_slope() {
+        // 1500 counts span 100%, so 15 counts are 1.0%
+        let cal = Calibration::new(3000, 1500);
+        assert_eq!(uncertainty_tenths(0, &cal), 0);
+        assert_eq!(uncertainty_tenths(15, &cal), 10);
+        assert_eq!(uncertainty_tenths(150, &cal), 100);
+        // A narrower calibration makes the same spread count for more
+        assert_eq!(uncertainty_tenths(15, &Calibration::new(2250, 1500)), 20);
+        // Wet-high probes use the magnitude of the slope
+        let wet_high = Calibration::new(1500, 3000).with_polarity(Polarity::WetHigh);
+        assert_eq!(uncertainty_tenths(15, &wet_high), 10);
+        assert_eq!(uncertainty_tenths(5, &Calibration::new(2000, 2000)), 1000);
+    }
+
+    #[test]
+    fn maps_raw_values_to_expected_percentages() {
+        let cal = Calibration::default();
+        assert_eq!(raw_to_moisture_percent(DRY_SOIL + 50, &cal), 0);
+        assert_eq!(
+            raw_to_moisture_percent(WET_SOIL.saturating_sub(50), &cal),
+            100
+        );
+        // Midpoint between DRY_SOIL and WET_SOIL should be ~50%
+        let mid = WET_SOIL + ((DRY_SOIL - WET_SOIL) / 2);
+        assert_eq!(raw_to_moisture_percent(mid, &cal), 50);
+    }
+
+    #[test]
+    fn tenths_agree_with_whole_percent() {
+        let cal = Calibration::default();
+        for raw in [
+            WET_SOIL - 10,
+            WET_SOIL,
+            1337,
+            2100,
+            2555,
+            2999,
+            DRY_SOIL,
+            DRY_SOIL + 10,
+        ] {
+            let tenths = raw_to_moisture_tenths(raw, &cal);
+            let percent = raw_to_moisture_percent(raw, &cal) as u16;
+            // Both truncate, so the tenths value only adds the missing fraction
+            assert!(
+                (percent * 10..percent * 10 + 10).contains(&tenths),
+                "raw {raw}: {tenths} tenths vs {percent}%"
+            );
+        }
+        assert_eq!(raw_to_moisture_tenths(2100, &cal), 500);
+        assert_eq!(raw_to_moisture_tenths(1337, &cal), 923);
+    }
+
+    #[test]
+    fn soil_condition_matches_thresholds() {
+        let (label, led) = get_soil_condition(MOISTURE_LOW.saturating_sub(1));
+        assert_eq!(label, "DRY - Need Water!");
+        assert!(led);
+
+        let (label, led) = get_soil_condition(MOISTURE_HIGH.saturating_add(1));
+        assert_eq!(label, "WET - Too Much Water!");
+        assert!(!led);
+
+        let (label, led) = get_soil_condition((MOISTURE_LOW + MOISTURE_HIGH) / 2);
+        assert_eq!(label, "OPTIMAL");
+        assert!(!led);
+    }
+
+    #[test]
+    fn wet_warning_does_not_flap_on_the_boundary() {
+        use SoilCondition::{Optimal, Wet};
+
+        let mut tracker = ConditionTracker::default();
+        // 75% is HIGH; release point is 72%
+        let seen: Vec<_> = [74, 76, 75, 74, 76, 73, 72, 71, 74, 75, 76]
+            .iter()
+            .map(|&m| tracker.update(m))
+            .collect();
+        assert_eq!(
+            seen,
+            vec![Optimal, Wet, Wet, Wet, Wet, Wet, Wet, Optimal, Optimal, Optimal, Wet]
+        );
+    }
+
+    #[test]
+    fn dry_warning_has_matching_hysteresis() {
+        use SoilCondition::{Dry, Optimal};
+
+        let mut tracker = ConditionTracker::default();
+        let seen: Vec<_> = [25, 24, 25, 27, 28, 26, 24]
+            .iter()
+            .map(|&m| tracker.update(m))
+            .collect();
+        assert_eq!(seen, vec![Optimal, Dry, Dry, Dry, Optimal, Optimal, Dry]);
+        assert!(Dry.led_on() && !Optimal.led_on());
+        // First reading matches the stateless classification
+        assert_eq!(
+            ConditionTracker::default().update(90).label(),
+            get_soil_condition(90).0
+        );
+    }
+
+    #[test]
+    fn valid_range_defaults_to_global_fault_bounds() {
+        use crate::fault::{FAULT_RAW_MAX, FAULT_RAW_MIN};
+
+        let cal = Calibration::default();
+        assert_eq!(cal.valid_range(), (FAULT_RAW_MIN, FAULT_RAW_MAX));
+
+        let tight = cal.with_valid_range(1000, 3200);
+        assert_eq!(tight.valid_range(), (1000, 3200));
+    }
+
+    #[test]
+    fn calibration_round_trips_through_bytes() {
+        for cal in [
+            Calibration::default(),
+            Calibration::new(2900, 1100).with_valid_range(900, 3100),
+            Calibration::new(1100, 2900).with_polarity(Polarity::WetHigh),
+            Calibration::default().with_output_correction(-5, 1050),
+        ] {
+            assert_eq!(Calibration::from_bytes(&cal.to_bytes()).unwrap(), cal);
+        }
+        assert!(Calibration::from_bytes(&[1, 2, 3]).is_err());
+
+        // Version 1 blobs predate polarity and stay dry-high
+        let v1 = [1, 0xb8, 0x0b, 0xb0, 0x04, 0, 0, 0, 0, 0, 0];
+        assert_eq!(
+            Calibration::from_bytes(&v1).unwrap(),
+            Calibration::default()
+        );
+        // Version 2 blobs predate the output correction
+        let v2 = [2, 0xb8, 0x0b, 0xb0, 0x04, 0, 0, 0, 0, 0, 0, 1];
+        assert_eq!(
+            Calibration::from_bytes(&v2).unwrap(),
+            Calibration::default().with_polarity(Polarity::WetHigh)
+        );
+    }
+
+    #[test]
+    fn output_correction_adjusts_final_percentage_and_clamps() {
+        let mid = WET_SOIL + ((DRY_SOIL - WET_SOIL) / 2);
+        // Reads 5% high against the gravimetric reference
+        let offset = Calibration::default().with_output_correction(-5, 1000);
+        assert_eq!(raw_to_moisture_percent(mid, &offset), 45);
+        assert_eq!(raw_to_moisture_tenths(mid, &offset), 450);
+        assert_eq!(raw_to_moisture_percent(DRY_SOIL, &offset), 0);
+
+        let gain = Calibration::default().with_output_correction(0, 1200);
+        assert_eq!(raw_to_moisture_percent(mid, &gain), 60);
+        assert_eq!(raw_to_moisture_percent(WET_SOIL, &gain), 100);
+
+        // Gain applies before the offset
+        let both = Calibration::default().with_output_correction(10, 500);
+        assert_eq!(raw_to_moisture_percent(mid, &both), 35);
+        assert_eq!(raw_to_moisture_percent(DRY_SOIL, &both), 10);
+        assert_eq!(
+            ProbeKind::Resistive.moisture_percent(WET_SOIL, &both),
+            60,
+            "resistive probes are corrected after their curve"
+        );
+    }
+
+    #[test]
+    fn wet_high_probe_maps_rising_raw_to_wetter() {
+        let cal = Calibration::new(1200, 3000).with_polarity(Polarity::WetHigh);
+        assert!(!cal.is_inverted());
+        assert_eq!(raw_to_moisture_percent(1200, &cal), 0);
+        assert_eq!(raw_to_moisture_percent(2100, &cal), 50);
+        assert_eq!(raw_to_moisture_percent(3000, &cal), 100);
+        assert_eq!(raw_to_moisture_percent(3300, &cal), 100);
+
+        // Dry-high points under a wet-high polarity are degenerate
+        assert!(Calibration::new(3000, 1200)
+            .with_polarity(Polarity::WetHigh)
+            .is_inverted());
+    }
+
+    // DRY_SOIL + 90 is 5% beyond the dry end, WET_SOIL - 180 is 10% beyond the wet end
+    const TOO_DRY: u16 = DRY_SOIL + 90;
+    const TOO_WET: u16 = WET_SOIL - 180;
+
+    #[test]
+    fn silent_policy_clamps_without_counting() {
+        let cal = Calibration::default();
+        let mut conv = MoistureConverter::new(ClampPolicy::Silent);
+        assert_eq!(conv.convert(TOO_DRY, &cal, ProbeKind::Capacitive), 0);
+        assert!(conv.clamped());
+        assert_eq!(conv.convert(TOO_WET, &cal, ProbeKind::Capacitive), 100);
+        assert_eq!(conv.clip_count(), 0);
+    }
+
+    #[test]
+    fn count_policy_clamps_and_counts() {
+        let cal = Calibration::default();
+        let mut conv = MoistureConverter::new(ClampPolicy::Count);
+        assert_eq!(conv.convert(TOO_DRY, &cal, ProbeKind::Capacitive), 0);
+        assert_eq!(conv.convert(TOO_WET, &cal, ProbeKind::Capacitive), 100);
+        assert_eq!(conv.convert(2100, &cal, ProbeKind::Capacitive), 50);
+        assert!(!conv.clamped());
+        assert_eq!(conv.clip_count(), 2);
+    }
+
+    #[test]
+    fn overshoot_policy_reports_beyond_range_up_to_limit() {
+        let cal = Calibration::default();
+        let mut conv = MoistureConverter::new(ClampPolicy::Overshoot { max_percent: 5 });
+        let capacitive = ProbeKind::Capacitive;
+        assert_eq!(conv.convert(TOO_DRY, &cal, capacitive), -5);
+        // 10% past the wet end is limited to 105
+        assert_eq!(conv.convert(TOO_WET, &cal, capacitive), 105);
+        assert_eq!(clamp_percent(conv.convert(TOO_WET, &cal, capacitive)), 100);
+        assert_eq!(clamp_percent(conv.convert(TOO_DRY, &cal, capacitive)), 0);
+        // Clipping follows the probe's own curve
+        let resistive = ProbeKind::Resistive;
+        assert!(conv.convert(TOO_WET, &cal, resistive) > 100 && conv.clamped());
+    }
+
+    #[test]
+    fn calibration_change_blends_over_cycles() {
+        let old = Calibration::default();
+        // Re-calibrated wet point moves the same raw value from 50% to 60%
+        let new = Calibration::new(3000, 1500);
+        let raw = 2100;
+        let reported = ProbeKind::Capacitive.moisture_percent(raw, &old);
+        let mut transition =
+            CalibrationTransition::new(reported, raw, new, ProbeKind::Capacitive, 5);
+        let reported: Vec<u8> = (0..7)
+            .map(|_| transition.convert(raw, ProbeKind::Capacitive))
+            .collect();
+        assert_eq!(reported, vec![52, 54, 56, 58, 60, 60, 60]);
+        assert!(transition.is_done());
+        assert_eq!(transition.target(), new);
+
+        // Moving drier steps down just as evenly
+        let mut back = CalibrationTransition::new(60, raw, old, ProbeKind::Capacitive, 4);
+        let reported: Vec<u8> = (0..4)
+            .map(|_| back.convert(raw, ProbeKind::Capacitive))
+            .collect();
+        assert_eq!(reported, vec![58, 55, 53, 50]);
+
+        // Re-calibrated again half way: carries on from the 55% shown
+        let mut back = CalibrationTransition::new(60, raw, old, ProbeKind::Capacitive, 4);
+        back.convert(raw, ProbeKind::Capacitive);
+        let shown = back.convert(raw, ProbeKind::Capacitive);
+        let mut again = CalibrationTransition::new(shown, raw, new, ProbeKind::Capacitive, 5);
+        let reported: Vec<u8> = (0..5)
+            .map(|_| again.convert(raw, ProbeKind::Capacitive))
+            .collect();
+        assert_eq!(reported, vec![56, 57, 58, 59, 60]);
+    }
+
+    #[test]
+    fn field_capacity_scale_maps_reference_points() {
+        let scale = FieldCapacityScale::new(15, 45).unwrap();
+        assert_eq!(scale.percent_of_capacity(15), 0);
+        assert_eq!(scale.percent_of_capacity(30), 50);
+        assert_eq!(scale.percent_of_capacity(45), 100);
+        // 22% is 7/30 of the way up, 23.3% of capacity
+        assert_eq!(scale.percent_of_capacity(22), 23);
+        // Drier than wilting point bottoms out; saturated soil exceeds 100
+        assert_eq!(scale.percent_of_capacity(5), 0);
+        assert_eq!(scale.percent_of_capacity(60), 150);
+        assert_eq!(
+            FieldCapacityScale::new(0, 1)
+                .unwrap()
+                .percent_of_capacity(100),
+            255
+        );
+
+        assert!(FieldCapacityScale::new(45, 15).is_err());
+        assert!(FieldCapacityScale::new(30, 30).is_err());
+    }
+
+    #[test]
+    fn comfort_band_is_inclusive_and_order_independent() {
+        let band = ComfortBand::new(60, 40);
+        assert_eq!(band, ComfortBand::new(40, 60));
+        assert!(band.contains(40) && band.contains(60));
+        assert!(!band.contains(39) && !band.contains(61));
+    }
+
+    #[test]
+    fn probe_kind_selects_conversion_and_bounds() {
+        let cal = Calibration::default();
+        // Half way between the calibration points
+        assert_eq!(ProbeKind::Capacitive.moisture_percent(2100, &cal), 50);
+        assert_eq!(ProbeKind::Resistive.moisture_percent(2100, &cal), 25);
+        // Both agree at the calibration points themselves
+        for kind in [ProbeKind::Capacitive, ProbeKind::Resistive] {
+            assert_eq!(kind.moisture_percent(DRY_SOIL, &cal), 0);
+            assert_eq!(kind.moisture_percent(WET_SOIL, &cal), 100);
+        }
+
+        let resistive = ProbeKind::Resistive.default_calibration();
+        assert_eq!(ProbeKind::Resistive.moisture_percent(2700, &resistive), 25);
+        assert_eq!(resistive.valid_range_for(ProbeKind::Resistive).1, 4095);
+        assert_eq!(cal.valid_range(), (200, 4000));
+        // Explicit bounds win over the probe kind's
+        let bounded = cal.with_valid_range(500, 3500);
+        assert_eq!(bounded.valid_range_for(ProbeKind::Resistive), (500, 3500));
+    }
+
+    #[test]
+    fn dual_linear_maps_each_segment_and_meets_at_breakpoint() {
+        let cal = Calibration::default();
+        let kind = ProbeKind::DualLinear {
+            breakpoint_raw: 2400,
+            breakpoint_percent: 20,
+        };
+        // Continuous across the breakpoint
+        assert_eq!(kind.moisture_percent(2400, &cal), 20);
+        for raw in [2399, 2401] {
+            assert!(kind.moisture_percent(raw, &cal).abs_diff(20) <= 1);
+        }
+        // 600 raw counts cover the first 20%, the remaining 1200 the other 80%
+        assert_eq!(kind.moisture_percent(2700, &cal), 10);
+        assert_eq!(kind.moisture_percent(1800, &cal), 60);
+        assert_eq!(kind.moisture_percent(DRY_SOIL, &cal), 0);
+        assert_eq!(kind.moisture_percent(WET_SOIL, &cal), 100);
+        assert_eq!(kind.moisture_percent(3500, &cal), 0);
+        assert_eq!(kind.moisture_percent(800, &cal), 100);
+
+        let wet_high = Calibration::new(1000, 3000).with_polarity(Polarity::WetHigh);
+        let kind = ProbeKind::DualLinear {
+            breakpoint_raw: 1500,
+            breakpoint_percent: 30,
+        };
+        assert_eq!(kind.moisture_percent(1250, &wet_high), 15);
+        assert_eq!(kind.moisture_percent(1500, &wet_high), 30);
+        assert_eq!(kind.moisture_percent(2250, &wet_high), 65);
+
+        // A breakpoint outside the calibration range is just the straight line
+        let outside = ProbeKind::DualLinear {
+            breakpoint_raw: 3500,
+            breakpoint_percent: 20,
+        };
+        assert_eq!(outside.moisture_percent(2100, &cal), 50);
+    }
+
+    #[test]
+    fn rescaling_onto_the_same_curve_returns_the_reading() {
+        let cal = Calibration::new(3000, 1200);
+        let kinds = [
+            ProbeKind::Capacitive,
+            ProbeKind::Resistive,
+            ProbeKind::DualLinear {
+                breakpoint_raw: 2400,
+                breakpoint_percent: 20,
+            },
+        ];
+        for kind in kinds {
+            for raw in (1300..3000).step_by(100) {
+                // Flat stretches of a curve leave some counts per tenth
+                let back = rescale_raw(raw, &cal, kind, &cal, kind);
+                let tenths = |raw| kind.moisture_tenths_unclamped(raw, &cal);
+                assert!(
+                    tenths(back).abs_diff(tenths(raw)) <= 1,
+                    "{kind:?}: {raw} -> {back}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn clipping_and_uncertainty_follow_the_probe_curve() {
+        let spread = 24;
+        let capacitive = Calibration::new(3900, 1500);
+        assert_eq!(
+            ProbeKind::Capacitive.uncertainty_tenths(2700, spread, &capacitive),
+            10
+        );
+        assert_eq!(
+            ProbeKind::Capacitive.uncertainty_tenths(1500, spread, &capacitive),
+            10
+        );
+
+        // The resistive curve is flat when dry and twice as steep as the line when wet
+        let resistive = ProbeKind::Resistive;
+        let cal = resistive.default_calibration();
+        assert_eq!(resistive.uncertainty_tenths(3900, spread, &cal), 1);
+        assert_eq!(resistive.uncertainty_tenths(2700, spread, &cal), 10);
+        assert_eq!(resistive.uncertainty_tenths(1500, spread, &cal), 20);
+        // Just past either point is clipped, even where the curve is flat
+        let tenths = |raw| resistive.moisture_tenths_unclamped(raw, &cal);
+        assert!(tenths(3910) < 0 && tenths(1490) > 1000);
+        assert_eq!((tenths(3900), tenths(1500)), (0, 1000));
+
+        let dual = ProbeKind::DualLinear {
+            breakpoint_raw: 2400,
+            breakpoint_percent: 20,
+        };
+        let cal = Calibration::default();
+        assert_eq!(dual.uncertainty_tenths(2700, 30, &cal), 10);
+        assert_eq!(dual.uncertainty_tenths(1800, 30, &cal), 20);
+        assert_eq!(dual.moisture_tenths_unclamped(2400, &cal), 200);
+        assert!(dual.moisture_tenths_unclamped(1100, &cal) > 1000);
+        assert!((0..=1000).contains(&dual.moisture_tenths_unclamped(1250, &cal)));
+    }
+
+    #[test]
+    fn conversion_cache_hits_until_calibration_changes() {
+        let mut cache = ConversionCache::new();
+        let cal = Calibration::default();
+        let first = cache.convert(2100, &cal, ProbeKind::Capacitive).clone();
+        assert_eq!(first.moisture_percent, 50);
+        assert_eq!(cache.hits(), 0);
+
+        assert_eq!(cache.convert(2100, &cal, ProbeKind::Capacitive), &first);
+        assert_eq!(cache.hits(), 1);
+
+        // Recalibrating changes the result for the same raw value
+        let recalibrated = Calibration::new(3000, 2000);
+        let converted = cache.convert(2100, &recalibrated, ProbeKind::Capacitive);
+        assert_eq!(converted.moisture_percent, 90);
+        assert_eq!(cache.hits(), 1);
+
+        cache.convert(2150, &recalibrated, ProbeKind::Capacitive);
+        cache.invalidate();
+        cache.convert(2150, &recalibrated, ProbeKind::Capacitive);
+        assert_eq!(cache.hits(), 1);
+    }
+}
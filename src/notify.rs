@@ -0,0 +1,145 @@
+//! One-shot notifications when moisture crosses a configured level.
+
+/// Which way the reading has to move through the level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Fires when moisture drops below the level
+    Falling,
+    /// Fires when moisture rises to or above the level
+    Rising,
+}
+
+/// Handle for removing a subscription
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u32);
+
+struct Subscription {
+    id: SubscriptionId,
+    level: u8,
+    direction: Direction,
+    callback: Box<dyn FnMut(u8) + Send>,
+    /// Side of the level seen last time; `None` until the first reading
+    below: Option<bool>,
+}
+
+/// Calls subscribers once per crossing rather than on every reading past the level
+///
+/// A subscription re-arms only after the reading has gone back across the
+/// level the other way. The first reading after subscribing just records which
+/// side of the level it is on, so nothing fires for a value that was already
+/// past the level.
+#[derive(Default)]
+pub struct CrossingNotifier {
+    subscriptions: Vec<Subscription>,
+    next_id: u32,
+}
+
+impl CrossingNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `callback` with the reading each time moisture crosses `level` in `direction`
+    pub fn subscribe(
+        &mut self,
+        level: u8,
+        direction: Direction,
+        callback: impl FnMut(u8) + Send + 'static,
+    ) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.subscriptions.push(Subscription {
+            id,
+            level,
+            direction,
+            callback: Box::new(callback),
+            below: None,
+        });
+        id
+    }
+
+    /// Remove a subscription; returns `false` if it was already gone
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id != id);
+        self.subscriptions.len() != before
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    /// Feed the latest moisture and fire any subscriptions it crossed
+    pub fn update(&mut self, moisture_percent: u8) {
+        for sub in &mut self.subscriptions {
+            let below = moisture_percent < sub.level;
+            let crossed = match (sub.below, sub.direction) {
+                (Some(false), Direction::Falling) => below,
+                (Some(true), Direction::Rising) => !below,
+                _ => false,
+            };
+            sub.below = Some(below);
+            if crossed {
+                (sub.callback)(moisture_percent);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{CrossingNotifier, Direction};
+    use std::sync::{Arc, Mutex};
+
+    fn recorder() -> (Arc<Mutex<Vec<u8>>>, impl FnMut(u8) + Send + 'static) {
+        let fired = Arc::new(Mutex::new(Vec::new()));
+        let sink = fired.clone();
+        (fired, move |m| sink.lock().unwrap().push(m))
+    }
+
+    #[test]
+    fn fires_once_per_crossing_and_rearms_after_recrossing() {
+        let mut notifier = CrossingNotifier::new();
+        let (fired, callback) = recorder();
+        notifier.subscribe(30, Direction::Falling, callback);
+
+        for m in [40, 35, 29, 25, 20, 28] {
+            notifier.update(m);
+        }
+        assert_eq!(*fired.lock().unwrap(), vec![29]);
+
+        // Back above the level re-arms; the next drop fires again
+        for m in [30, 45, 27] {
+            notifier.update(m);
+        }
+        assert_eq!(*fired.lock().unwrap(), vec![29, 27]);
+    }
+
+    #[test]
+    fn rising_subscription_and_removal() {
+        let mut notifier = CrossingNotifier::new();
+        let (rising, on_rise) = recorder();
+        let (falling, on_fall) = recorder();
+        let rise_id = notifier.subscribe(60, Direction::Rising, on_rise);
+        notifier.subscribe(60, Direction::Falling, on_fall);
+
+        // Starting past the level establishes state without firing
+        notifier.update(70);
+        notifier.update(50);
+        notifier.update(60);
+        assert_eq!(*rising.lock().unwrap(), vec![60]);
+        assert_eq!(*falling.lock().unwrap(), vec![50]);
+
+        assert!(notifier.unsubscribe(rise_id));
+        assert!(!notifier.unsubscribe(rise_id));
+        assert_eq!(notifier.len(), 1);
+        notifier.update(40);
+        notifier.update(80);
+        assert_eq!(*rising.lock().unwrap(), vec![60]);
+        assert_eq!(*falling.lock().unwrap(), vec![50, 40]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Key/value persistence abstraction over ESP-IDF NVS.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+/// Small named blobs that survive reboots and deep sleep
+pub trait KvStore {
+    /// Read `key`, or `None` if it has never been written
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Create or replace `key`
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<()>;
+
+    /// Delete `key`; missing keys are not an error
+    fn remove(&mut self, key: &str) -> Result<()>;
+}
+
+/// In-RAM NVS stand-in for tests
+#[derive(Debug, Default, Clone)]
+pub struct MemoryKv {
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryKv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KvStore for MemoryKv {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        self.entries.insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.entries.remove(key);
+        Ok(())
+    }
+}
+
+/// Largest blob read back from NVS
+#[cfg(target_os = "espidf")]
+const MAX_BLOB_LEN: usize = 512;
+
+/// NVS namespace on the default partition
+#[cfg(target_os = "espidf")]
+pub struct EspKv {
+    nvs: esp_idf_svc::nvs::EspNvs<esp_idf_svc::nvs::NvsDefault>,
+}
+
+#[cfg(target_os = "espidf")]
+impl EspKv {
+    pub fn new(
+        partition: esp_idf_svc::nvs::EspDefaultNvsPartition,
+        namespace: &str,
+    ) -> Result<Self> {
+        Ok(Self {
+            nvs: esp_idf_svc::nvs::EspNvs::new(partition, namespace, true)?,
+        })
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl KvStore for EspKv {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let mut buf = [0u8; MAX_BLOB_LEN];
+        Ok(self.nvs.get_raw(key, &mut buf)?.map(|v| v.to_vec()))
+    }
+
+    fn set(&mut self, key: &str, value: &[u8]) -> Result<()> {
+        self.nvs.set_raw(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.nvs.remove(key)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+//! Per-channel polling cadences so slow-changing signals aren't over-read.
+
+use std::time::Duration;
+
+struct Entry<K> {
+    channel: K,
+    interval: Duration,
+    /// `None` until first polled, which makes a new channel due immediately
+    next_due: Option<Duration>,
+}
+
+/// Tracks when each channel (moisture, battery, EC, ...) is next due
+pub struct PollScheduler<K> {
+    entries: Vec<Entry<K>>,
+}
+
+impl<K: Copy + PartialEq> PollScheduler<K> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Poll `channel` every `interval`; replaces any existing cadence for it
+    pub fn with_channel(mut self, channel: K, interval: Duration) -> Self {
+        self.entries.retain(|e| e.channel != channel);
+        self.entries.push(Entry {
+            channel,
+            interval,
+            next_due: None,
+        });
+        self
+    }
+
+    /// Channels due at `now`, in the order they were added; each returned
+    /// channel is treated as read and rescheduled one interval later.
+    ///
+    /// A channel that fell behind (e.g. the loop stalled) is read once and
+    /// rescheduled from `now` rather than catching up with a burst.
+    pub fn due_channels(&mut self, now: Duration) -> Vec<K> {
+        let mut due = Vec::new();
+        for entry in &mut self.entries {
+            if entry.next_due.map_or(true, |at| now >= at) {
+                let from = match entry.next_due {
+                    Some(at) if now.saturating_sub(at) < entry.interval => at,
+                    _ => now,
+                };
+                entry.next_due = Some(from + entry.interval);
+                due.push(entry.channel);
+            }
+        }
+        due
+    }
+
+    /// Time until the earliest channel is due, for sleeping between ticks
+    pub fn next_due_in(&self, now: Duration) -> Option<Duration> {
+        self.entries
+            .iter()
+            .map(|e| {
+                e.next_due
+                    .map_or(Duration::ZERO, |at| at.saturating_sub(now))
+            })
+            .min()
+    }
+}
+
+impl<K: Copy + PartialEq> Default for PollScheduler<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::PollScheduler;
+    use crate::clock::{Clock, MockClock};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Channel {
+        Moisture,
+        Battery,
+        Ec,
+    }
+
+    fn scheduler() -> PollScheduler<Channel> {
+        PollScheduler::new()
+            .with_channel(Channel::Moisture, Duration::from_secs(2))
+            .with_channel(Channel::Battery, Duration::from_secs(60))
+            .with_channel(Channel::Ec, Duration::from_secs(5 * 60))
+    }
+
+    #[test]
+    fn only_due_channels_are_returned_each_tick() {
+        use Channel::{Battery, Ec, Moisture};
+
+        let clock = MockClock::new();
+        let mut polls = scheduler();
+        assert_eq!(polls.due_channels(clock.now()), vec![Moisture, Battery, Ec]);
+        assert_eq!(polls.due_channels(clock.now()), vec![]);
+
+        let mut reads = (0, 0, 0);
+        for _ in 0..150 {
+            clock.advance(Duration::from_secs(2));
+            for channel in polls.due_channels(clock.now()) {
+                match channel {
+                    Moisture => reads.0 += 1,
+                    Battery => reads.1 += 1,
+                    Ec => reads.2 += 1,
+                }
+            }
+            if clock.now() == Duration::from_secs(60) {
+                assert_eq!(polls.next_due_in(clock.now()), Some(Duration::from_secs(2)));
+            }
+        }
+        // Five simulated minutes
+        assert_eq!(reads, (150, 5, 1));
+    }
+
+    #[test]
+    fn stalled_loop_reads_once_and_reschedules_from_now() {
+        let mut polls = scheduler();
+        polls.due_channels(Duration::ZERO);
+
+        let late = Duration::from_secs(600);
+        assert_eq!(polls.due_channels(late).len(), 3);
+        assert_eq!(polls.due_channels(late + Duration::from_secs(1)), vec![]);
+        assert_eq!(
+            polls.due_channels(late + Duration::from_secs(2)),
+            vec![Channel::Moisture]
+        );
+    }
+
+    #[test]
+    fn readding_a_channel_replaces_its_cadence() {
+        let mut polls = scheduler().with_channel(Channel::Ec, Duration::from_secs(2));
+        polls.due_channels(Duration::ZERO);
+        assert_eq!(
+            polls.due_channels(Duration::from_secs(2)),
+            vec![Channel::Moisture, Channel::Ec]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Switched probe power so the sensor only draws current while being read,
+//! and supply rail monitoring to spot readings biased by voltage sag or a
+//! flat battery.
+
+use crate::alert::Alert;
+use crate::clock::Clock;
+use crate::sensor::SoilSensor;
+use anyhow::Result;
+use std::time::Duration;
+
+/// Default time for a freshly powered probe to settle before sampling
+pub const DEFAULT_SETTLE: Duration = Duration::from_millis(100);
+/// Nominal regulated rail feeding the ADC reference and probe
+pub const NOMINAL_SUPPLY_MV: u16 = 3300;
+/// Supply below which the battery is reported low
+pub const LOW_BATTERY_MV: u16 = 3000;
+/// Recovery above the low threshold needed before the battery counts as fine again
+const LOW_BATTERY_HYSTERESIS_MV: u16 = 100;
+
+/// Switch supplying power to the probe
+pub trait PowerGate {
+    fn power_on(&mut self) -> Result<()>;
+    fn power_off(&mut self) -> Result<()>;
+}
+
+/// Powers the probe up, waits for it to settle, reads, then powers it down
+pub struct PoweredSensor<S, G, C> {
+    sensor: S,
+    gate: G,
+    clock: C,
+    settle: Duration,
+}
+
+impl<S: SoilSensor, G: PowerGate, C: Clock> PoweredSensor<S, G, C> {
+    pub fn new(sensor: S, gate: G, clock: C, settle: Duration) -> Self {
+        Self {
+            sensor,
+            gate,
+            clock,
+            settle,
+        }
+    }
+}
+
+impl<S: SoilSensor, G: PowerGate, C: Clock> SoilSensor for PoweredSensor<S, G, C> {
+    fn read_averaged(&mut self, samples: usize) -> Result<u16> {
+        self.powered(|sensor| sensor.read_averaged(samples))
+    }
+
+    fn read_with_spread(&mut self, samples: usize) -> Result<(u16, Option<u16>)> {
+        self.powered(|sensor| sensor.read_with_spread(samples))
+    }
+}
+
+impl<S: SoilSensor, G: PowerGate, C: Clock> PoweredSensor<S, G, C> {
+    fn powered<T>(&mut self, read: impl FnOnce(&mut S) -> Result<T>) -> Result<T> {
+        self.gate.power_on()?;
+        self.clock.sleep(self.settle);
+        let reading = read(&mut self.sensor);
+        // Always cut power, even if the read failed
+        self.gate.power_off()?;
+        reading
+    }
+}
+
+/// Supply rail (or battery) voltage, e.g. through a divider on a spare ADC channel
+pub trait SupplyMonitor {
+    fn read_millivolts(&mut self) -> Result<u16>;
+}
+
+/// How far the rail may drop before soil readings are treated as biased.
+///
+/// The ESP32 ADC shifts with supply sag, most visibly during pump inrush.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SagThreshold {
+    pub nominal_mv: u16,
+    /// Largest drop below nominal that still counts as a stable rail
+    pub max_sag_mv: u16,
+}
+
+impl SagThreshold {
+    /// Whether a rail reading of `millivolts` is significantly depressed
+    pub fn is_sagging(&self, millivolts: u16) -> bool {
+        self.nominal_mv.saturating_sub(millivolts) > self.max_sag_mv
+    }
+}
+
+impl Default for SagThreshold {
+    /// 3.3 V rail, flagged once it falls more than 150 mV
+    fn default() -> Self {
+        Self {
+            nominal_mv: NOMINAL_SUPPLY_MV,
+            max_sag_mv: 150,
+        }
+    }
+}
+
+/// Raises [`Alert::LowBattery`] once as the supply falls below `threshold_mv`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LowBatteryDetector {
+    threshold_mv: u16,
+    low: bool,
+}
+
+impl Default for LowBatteryDetector {
+    fn default() -> Self {
+        Self::new(LOW_BATTERY_MV)
+    }
+}
+
+impl LowBatteryDetector {
+    pub fn new(threshold_mv: u16) -> Self {
+        Self {
+            threshold_mv,
+            low: false,
+        }
+    }
+
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+
+    /// Feed the latest supply voltage, `None` when there is none to judge;
+    /// returns the alert when the battery goes low
+    pub fn update(&mut self, millivolts: Option<u16>) -> Option<Alert> {
+        let mv = millivolts?;
+        if self.low {
+            self.low = mv < self.threshold_mv.saturating_add(LOW_BATTERY_HYSTERESIS_MV);
+            None
+        } else {
+            self.low = mv < self.threshold_mv;
+            self.low.then_some(Alert::LowBattery)
+        }
+    }
+
+    /// Forget the last state, e.g. once a simulated input ends with no real
+    /// supply reading to follow it
+    pub fn reset(&mut self) {
+        self.low = false;
+    }
+}
+
+/// Probe supply switched by a GPIO output
+#[cfg(target_os = "espidf")]
+pub struct GpioPowerGate<'d> {
+    pin: esp_idf_hal::gpio::PinDriver<
+        'd,
+        esp_idf_hal::gpio::AnyOutputPin,
+        esp_idf_hal::gpio::Output,
+    >,
+}
+
+#[cfg(target_os = "espidf")]
+impl<'d> GpioPowerGate<'d> {
+    pub fn new(
+        pin: esp_idf_hal::gpio::PinDriver<
+            'd,
+            esp_idf_hal::gpio::AnyOutputPin,
+            esp_idf_hal::gpio::Output,
+        >,
+    ) -> Self {
+        Self { pin }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl PowerGate for GpioPowerGate<'_> {
+    fn power_on(&mut self) -> Result<()> {
+        self.pin.set_high()?;
+        Ok(())
+    }
+
+    fn power_off(&mut self) -> Result<()> {
+        self.pin.set_low()?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{LowBatteryDetector, PowerGate, PoweredSensor, SagThreshold};
+    use crate::alert::Alert;
+    use crate::clock::{Clock, MockClock};
+    use crate::sensor::SoilSensor;
+    use anyhow::{anyhow, Result};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        On(Duration),
+        Read(Duration),
+        Off(Duration),
+    }
+
+    type Log = Rc<RefCell<Vec<Event>>>;
+
+    struct StubGate(Log, MockClock);
+
+    impl PowerGate for StubGate {
+        fn power_on(&mut self) -> Result<()> {
+            self.0.borrow_mut().push(Event::On(self.1.now()));
+            Ok(())
+        }
+
+        fn power_off(&mut self) -> Result<()> {
+            self.0.borrow_mut().push(Event::Off(self.1.now()));
+            Ok(())
+        }
+    }
+
+    struct StubSensor(Log, MockClock, bool);
+
+    impl SoilSensor for StubSensor {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            self.0.borrow_mut().push(Event::Read(self.1.now()));
+            if self.2 {
+                Err(anyhow!("adc timeout"))
+            } else {
+                Ok(2100)
+            }
+        }
+    }
+
+    fn powered(fail: bool) -> (PoweredSensor<StubSensor, StubGate, MockClock>, Log) {
+        let log = Log::default();
+        let clock = MockClock::new();
+        let sensor = PoweredSensor::new(
+            StubSensor(log.clone(), clock.clone(), fail),
+            StubGate(log.clone(), clock.clone()),
+            clock,
+            Duration::from_millis(150),
+        );
+        (sensor, log)
+    }
+
+    #[test]
+    fn powers_on_settles_reads_then_powers_off() {
+        let (mut sensor, log) = powered(false);
+        assert_eq!(sensor.read_averaged(5).unwrap(), 2100);
+
+        let settle = Duration::from_millis(150);
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                Event::On(Duration::ZERO),
+                Event::Read(settle),
+                Event::Off(settle)
+            ]
+        );
+    }
+
+    #[test]
+    fn powers_off_after_failed_read() {
+        let (mut sensor, log) = powered(true);
+        assert!(sensor.read_averaged(5).is_err());
+        assert!(matches!(log.borrow().last(), Some(Event::Off(_))));
+    }
+
+    #[test]
+    fn sag_beyond_threshold_is_flagged() {
+        let sag = SagThreshold::default();
+        assert!(!sag.is_sagging(3300));
+        assert!(!sag.is_sagging(3150));
+        assert!(sag.is_sagging(3149));
+        // A rail above nominal is never a sag
+        assert!(!sag.is_sagging(3450));
+    }
+
+    #[test]
+    fn low_battery_alerts_once_and_clears_with_hysteresis() {
+        let mut battery = LowBatteryDetector::new(3000);
+        assert_eq!(battery.update(Some(3200)), None);
+        assert_eq!(battery.update(Some(2950)), Some(Alert::LowBattery));
+        assert_eq!(battery.update(Some(2900)), None);
+        // No reading keeps the last state
+        assert_eq!(battery.update(None), None);
+        assert!(battery.is_low());
+        // Just above the threshold is not enough to clear
+        battery.update(Some(3050));
+        assert!(battery.is_low());
+        battery.update(Some(3150));
+        assert!(!battery.is_low());
+        assert_eq!(battery.update(Some(2900)), Some(Alert::LowBattery));
+    }
+}
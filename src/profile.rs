@@ -0,0 +1,67 @@
+//! Named moisture threshold profiles.
+
+use crate::moisture::{MOISTURE_HIGH, MOISTURE_LOW};
+use anyhow::{bail, ensure, Result};
+
+const PROFILE_FORMAT_VERSION: u8 = 1;
+
+/// Thresholds for a particular plant or bed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    /// Below this the soil is considered dry
+    pub moisture_low: u8,
+    /// Above this the soil is considered wet
+    pub moisture_high: u8,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            moisture_low: MOISTURE_LOW,
+            moisture_high: MOISTURE_HIGH,
+        }
+    }
+}
+
+impl Profile {
+    /// Compact persisted form: version, low, high, name length, name bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.name.as_bytes();
+        let name = &name[..name.len().min(u8::MAX as usize)];
+        let mut out = vec![
+            PROFILE_FORMAT_VERSION,
+            self.moisture_low,
+            self.moisture_high,
+            name.len() as u8,
+        ];
+        out.extend_from_slice(name);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() >= 4,
+            "profile blob too short ({} bytes)",
+            bytes.len()
+        );
+        if bytes[0] != PROFILE_FORMAT_VERSION {
+            bail!("unsupported profile format version {}", bytes[0]);
+        }
+        let name_len = bytes[3] as usize;
+        ensure!(bytes.len() == 4 + name_len, "profile blob length mismatch");
+        let (low, high) = (bytes[1], bytes[2]);
+        ensure!(
+            low < high && high <= 100,
+            "profile thresholds {}..{} out of range",
+            low,
+            high
+        );
+        Ok(Self {
+            name: String::from_utf8(bytes[4..].to_vec())?,
+            moisture_low: bytes[1],
+            moisture_high: bytes[2],
+        })
+    }
+}
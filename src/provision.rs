@@ -0,0 +1,258 @@
+//! First-boot detection and default configuration setup.
+
+use crate::maintenance::ServiceRecord;
+use crate::moisture::Calibration;
+use crate::nvs::KvStore;
+use crate::profile::Profile;
+use crate::pump::{PumpLifetime, PumpState};
+use anyhow::Result;
+use log::{info, warn};
+
+/// NVS key marking the device as set up
+pub const SENTINEL_KEY: &str = "initialized";
+/// NVS key holding the active threshold profile
+pub const PROFILE_KEY: &str = "profile";
+/// NVS key holding the probe calibration
+pub const CALIBRATION_KEY: &str = "calibration";
+/// NVS key holding cumulative pump runtime, kept across reboots and deep sleep
+pub const PUMP_LIFETIME_KEY: &str = "pump_life";
+/// NVS key holding the pump controller's run and cooldown position
+pub const PUMP_STATE_KEY: &str = "pump_state";
+/// NVS key holding the maintenance baseline from the last service
+pub const SERVICE_KEY: &str = "service";
+
+const SENTINEL_VALUE: &[u8] = b"soil-v1";
+
+/// What the sentinel says about this boot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootState {
+    /// No sentinel: brand-new device or erased NVS
+    FirstBoot,
+    /// Sentinel present and valid
+    Initialized,
+    /// Sentinel present but unreadable; treated as first boot to self-heal
+    CorruptSentinel,
+}
+
+/// Inspect the NVS sentinel without changing anything
+pub fn check_boot_state(kv: &dyn KvStore) -> Result<BootState> {
+    Ok(match kv.get(SENTINEL_KEY)? {
+        None => BootState::FirstBoot,
+        Some(v) if v == SENTINEL_VALUE => BootState::Initialized,
+        Some(_) => BootState::CorruptSentinel,
+    })
+}
+
+/// Write the default profile and calibration on first boot.
+///
+/// Returns `true` when initialization ran. The sentinel is written last so
+/// an interrupted setup is retried on the next boot.
+pub fn ensure_initialized(kv: &mut dyn KvStore) -> Result<bool> {
+    match check_boot_state(kv)? {
+        BootState::Initialized => return Ok(false),
+        BootState::CorruptSentinel => warn!("Setup marker is corrupt, restoring defaults"),
+        BootState::FirstBoot => {}
+    }
+
+    info!("Welcome! First boot detected, writing default configuration");
+    kv.set(PROFILE_KEY, &Profile::default().to_bytes())?;
+    kv.set(CALIBRATION_KEY, &Calibration::default().to_bytes())?;
+    kv.set(SENTINEL_KEY, SENTINEL_VALUE)?;
+    info!("Device initialized; run calibration mode to tune DRY/WET points");
+    Ok(true)
+}
+
+/// Decode `key`, falling back to `T::default()` if it is missing or unreadable
+fn load_or_default<T: Default>(kv: &dyn KvStore, key: &str, decode: fn(&[u8]) -> Result<T>) -> T {
+    match kv.get(key) {
+        Ok(Some(bytes)) => decode(&bytes).unwrap_or_else(|e| {
+            warn!("Stored {} unreadable ({}), using defaults", key, e);
+            T::default()
+        }),
+        Ok(None) => T::default(),
+        Err(e) => {
+            warn!("Failed to read {} ({}), using defaults", key, e);
+            T::default()
+        }
+    }
+}
+
+/// Stored calibration, or the built-in default if missing or unreadable
+pub fn load_calibration(kv: &dyn KvStore) -> Calibration {
+    load_or_default(kv, CALIBRATION_KEY, Calibration::from_bytes)
+}
+
+pub fn save_calibration(kv: &mut dyn KvStore, calibration: &Calibration) -> Result<()> {
+    kv.set(CALIBRATION_KEY, &calibration.to_bytes())
+}
+
+/// Stored threshold profile, or the built-in default if missing or unreadable
+pub fn load_profile(kv: &dyn KvStore) -> Profile {
+    load_or_default(kv, PROFILE_KEY, Profile::from_bytes)
+}
+
+/// Stored pump totals, or zero if missing or unreadable
+pub fn load_pump_lifetime(kv: &dyn KvStore) -> PumpLifetime {
+    load_or_default(kv, PUMP_LIFETIME_KEY, PumpLifetime::from_bytes)
+}
+
+pub fn save_pump_lifetime(kv: &mut dyn KvStore, lifetime: &PumpLifetime) -> Result<()> {
+    kv.set(PUMP_LIFETIME_KEY, &lifetime.to_bytes())
+}
+
+/// Stored pump controller state, or idle if missing or unreadable
+pub fn load_pump_state(kv: &dyn KvStore) -> PumpState {
+    load_or_default(kv, PUMP_STATE_KEY, PumpState::from_bytes)
+}
+
+pub fn save_pump_state(kv: &mut dyn KvStore, state: &PumpState) -> Result<()> {
+    kv.set(PUMP_STATE_KEY, &state.to_bytes())
+}
+
+/// Stored service baseline, or "serviced at first boot" if missing or unreadable
+pub fn load_service_record(kv: &dyn KvStore) -> ServiceRecord {
+    load_or_default(kv, SERVICE_KEY, ServiceRecord::from_bytes)
+}
+
+pub fn save_service_record(kv: &mut dyn KvStore, record: &ServiceRecord) -> Result<()> {
+    kv.set(SERVICE_KEY, &record.to_bytes())
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        check_boot_state, ensure_initialized, load_calibration, load_pump_lifetime,
+        load_pump_state, save_pump_lifetime, save_pump_state, BootState, CALIBRATION_KEY,
+        PROFILE_KEY, PUMP_LIFETIME_KEY, PUMP_STATE_KEY, SENTINEL_KEY,
+    };
+    use crate::clock::MockClock;
+    use crate::moisture::Calibration;
+    use crate::nvs::{KvStore, MemoryKv};
+    use crate::profile::Profile;
+    use crate::pump::{
+        PumpAction, PumpConfig, PumpController, PumpLifetime, PumpState, RuntimeMeter,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn fresh_device_gets_defaults_and_sentinel() {
+        let mut kv = MemoryKv::new();
+        assert_eq!(check_boot_state(&kv).unwrap(), BootState::FirstBoot);
+        assert!(ensure_initialized(&mut kv).unwrap());
+
+        assert_eq!(check_boot_state(&kv).unwrap(), BootState::Initialized);
+        let profile = kv.get(PROFILE_KEY).unwrap().unwrap();
+        assert_eq!(Profile::from_bytes(&profile).unwrap(), Profile::default());
+        let cal = kv.get(CALIBRATION_KEY).unwrap().unwrap();
+        assert_eq!(
+            Calibration::from_bytes(&cal).unwrap(),
+            Calibration::default()
+        );
+    }
+
+    #[test]
+    fn initialized_device_is_left_alone() {
+        let mut kv = MemoryKv::new();
+        ensure_initialized(&mut kv).unwrap();
+        let custom = Calibration::new(2800, 1300);
+        kv.set(CALIBRATION_KEY, &custom.to_bytes()).unwrap();
+
+        assert!(!ensure_initialized(&mut kv).unwrap());
+        assert_eq!(load_calibration(&kv), custom);
+    }
+
+    #[test]
+    fn corrupt_sentinel_is_treated_as_first_boot() {
+        let mut kv = MemoryKv::new();
+        kv.set(SENTINEL_KEY, b"\xff\x00garbage").unwrap();
+        assert_eq!(check_boot_state(&kv).unwrap(), BootState::CorruptSentinel);
+
+        assert!(ensure_initialized(&mut kv).unwrap());
+        assert_eq!(check_boot_state(&kv).unwrap(), BootState::Initialized);
+    }
+
+    #[test]
+    fn pump_lifetime_accumulates_across_reboots() {
+        let mut kv = MemoryKv::new();
+        assert_eq!(load_pump_lifetime(&kv), PumpLifetime::default());
+
+        let secs = Duration::from_secs;
+        let mut meter = RuntimeMeter::new(load_pump_lifetime(&kv));
+        meter.record(secs(10), PumpAction::Activate);
+        // Stopped early (e.g. paused) after 25s of a longer commanded run
+        assert!(meter.record(secs(35), PumpAction::Deactivate));
+        save_pump_lifetime(&mut kv, &meter.lifetime()).unwrap();
+
+        // Simulated reboot: session clock restarts, totals come back from NVS
+        let mut meter = RuntimeMeter::new(load_pump_lifetime(&kv));
+        assert!(!meter.record(secs(2), PumpAction::Deactivate));
+        meter.record(secs(5), PumpAction::Activate);
+        meter.record(secs(6), PumpAction::Activate);
+        assert!(meter.record(secs(65), PumpAction::Deactivate));
+        save_pump_lifetime(&mut kv, &meter.lifetime()).unwrap();
+
+        let lifetime = load_pump_lifetime(&kv);
+        assert_eq!(
+            lifetime,
+            PumpLifetime {
+                activations: 2,
+                runtime: secs(85)
+            }
+        );
+        assert_eq!(lifetime.to_string(), "0h01m over 2 runs");
+
+        kv.set(PUMP_LIFETIME_KEY, b"\x01\x02").unwrap();
+        assert_eq!(load_pump_lifetime(&kv), PumpLifetime::default());
+    }
+
+    fn controller(clock: &MockClock) -> PumpController<MockClock> {
+        let config = PumpConfig {
+            start_below: 30,
+            stop_at: 50,
+            min_run: Duration::from_secs(10),
+            max_run: Duration::from_secs(300),
+            cooldown: Duration::from_secs(120),
+        };
+        PumpController::new(config, clock.clone())
+    }
+
+    #[test]
+    fn pump_controller_resumes_across_reboots() {
+        let secs = Duration::from_secs;
+        let mut kv = MemoryKv::new();
+        assert_eq!(load_pump_state(&kv), PumpState::default());
+
+        // Watering, and part way up through the deadband
+        let clock = MockClock::new();
+        let mut pump = controller(&clock);
+        assert_eq!(pump.update(25), Some(PumpAction::Activate));
+        clock.advance(secs(40));
+        assert_eq!(pump.update(40), None);
+        save_pump_state(&mut kv, &pump.state()).unwrap();
+
+        // Simulated reboot: the clock restarts and the run picks up where it was
+        let clock = MockClock::new();
+        let mut pump = controller(&clock);
+        assert_eq!(
+            pump.restore(load_pump_state(&kv)),
+            Some(PumpAction::Activate)
+        );
+        assert!(pump.is_running());
+        assert_eq!(pump.update(45), None, "still inside the deadband");
+        clock.advance(secs(20));
+        assert_eq!(pump.update(50), Some(PumpAction::Deactivate));
+        clock.advance(secs(30));
+        save_pump_state(&mut kv, &pump.state()).unwrap();
+
+        // Rebooting during the cooldown does not start a run straight away
+        let clock = MockClock::new();
+        let mut pump = controller(&clock);
+        assert_eq!(pump.restore(load_pump_state(&kv)), None);
+        assert_eq!(pump.update(20), None);
+        clock.advance(secs(90));
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+
+        kv.set(PUMP_STATE_KEY, b"\x02").unwrap();
+        assert_eq!(load_pump_state(&kv), PumpState::default());
+    }
+}
@@ -0,0 +1,1254 @@
+//! Pump control decisions and actuation bookkeeping.
+
+use crate::alert::Alert;
+use crate::clock::{Clock, MockClock};
+use crate::led::{fade, play, Led, LED_FULL, LED_OFF};
+use crate::moisture::{MOISTURE_HIGH, MOISTURE_LOW};
+use crate::schedule::{Schedule, WindowInstance};
+use anyhow::{bail, ensure, Result};
+use log::{error, warn};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+const LIFETIME_FORMAT_VERSION: u8 = 1;
+const LIFETIME_BLOB_LEN: usize = 13;
+const STATE_FORMAT_VERSION: u8 = 1;
+const STATE_BLOB_LEN: usize = 10;
+
+/// Command issued to the pump relay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PumpAction {
+    Activate,
+    Deactivate,
+}
+
+/// Thresholds and timing limits for automatic watering
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PumpConfig {
+    /// Start watering when moisture drops below this
+    pub start_below: u8,
+    /// Stop watering once moisture reaches this
+    pub stop_at: u8,
+    /// Once started, keep running at least this long so water can reach the probe
+    pub min_run: Duration,
+    /// Hard cap on a single run
+    pub max_run: Duration,
+    /// Minimum off time between runs
+    pub cooldown: Duration,
+}
+
+impl Default for PumpConfig {
+    fn default() -> Self {
+        Self {
+            start_below: MOISTURE_LOW,
+            stop_at: (MOISTURE_LOW + MOISTURE_HIGH) / 2,
+            min_run: Duration::from_secs(5),
+            max_run: Duration::from_secs(60),
+            cooldown: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+impl PumpConfig {
+    /// Thresholds for a probe `depth_cm` deep. Down to the reference depth
+    /// the band is unchanged; deeper soil changes more slowly, so the band
+    /// narrows about its midpoint in proportion to depth.
+    pub fn scaled_for_depth(self, depth_cm: u8, scaling: &DepthScaling) -> Self {
+        let width = self.stop_at.saturating_sub(self.start_below) as u32;
+        if depth_cm <= scaling.reference_cm || width == 0 {
+            return self;
+        }
+        let scaled = (width * scaling.reference_cm as u32 / depth_cm as u32)
+            .max((scaling.min_band as u32).min(width));
+        let mid = (self.start_below as u32 + self.stop_at as u32) / 2;
+        let start_below = mid - scaled / 2;
+        Self {
+            start_below: start_below as u8,
+            stop_at: (start_below + scaled) as u8,
+            ..self
+        }
+    }
+}
+
+/// How the watering band follows probe insertion depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthScaling {
+    /// Depth the configured thresholds were chosen for
+    pub reference_cm: u8,
+    /// Narrowest band, in moisture points, however deep the probe
+    pub min_band: u8,
+}
+
+impl Default for DepthScaling {
+    fn default() -> Self {
+        Self {
+            reference_cm: 10,
+            min_band: 4,
+        }
+    }
+}
+
+/// Absolute moisture limits that override every other part of the decision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guardrails {
+    /// Above this the pump is stopped at once and never started, whatever
+    /// the thresholds, minimum run or activation rule say
+    pub flood_above: u8,
+    /// Below this watering may start outside the schedule's windows and
+    /// regardless of the activation rule; cooldown and lockouts still apply
+    pub emergency_below: u8,
+}
+
+impl Default for Guardrails {
+    fn default() -> Self {
+        Self {
+            flood_above: 90,
+            emergency_below: 10,
+        }
+    }
+}
+
+/// Which guardrail a reading is past
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guardrail {
+    Flood,
+    Emergency,
+}
+
+/// Confirms the pump actually runs, e.g. via a current sense resistor
+pub trait PumpFeedback {
+    /// Whether pump current is flowing right now
+    fn current_detected(&mut self) -> Result<bool>;
+}
+
+struct FeedbackCheck {
+    source: Box<dyn PumpFeedback + Send>,
+    timeout: Duration,
+    /// Set while an activation is waiting to be confirmed
+    pending_since: Option<Duration>,
+}
+
+/// Two-stage start: a dry reading arms, a second one within `window` fires
+struct Confirmation {
+    window: Duration,
+    armed_at: Option<Duration>,
+}
+
+/// Where the controller is in its watering cycle, as elapsed times so it
+/// can be carried across a reboot that restarts the clock
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PumpState {
+    /// How long the current run has lasted; `Some` while watering up
+    /// through the deadband towards `stop_at`
+    pub running_for: Option<Duration>,
+    /// Time since the last run ended, for the cooldown
+    pub stopped_for: Option<Duration>,
+}
+
+impl PumpState {
+    /// Persisted form: version, presence flags (bit 0 running, bit 1 stopped),
+    /// then each duration in ms as u32 LE (saturating at about 49 days)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![
+            STATE_FORMAT_VERSION,
+            self.running_for.is_some() as u8 | (self.stopped_for.is_some() as u8) << 1,
+        ];
+        for elapsed in [self.running_for, self.stopped_for] {
+            let ms = elapsed
+                .unwrap_or_default()
+                .as_millis()
+                .min(u32::MAX as u128) as u32;
+            out.extend_from_slice(&ms.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == STATE_BLOB_LEN,
+            "pump state blob is {} bytes, expected {}",
+            bytes.len(),
+            STATE_BLOB_LEN
+        );
+        if bytes[0] != STATE_FORMAT_VERSION {
+            bail!("unsupported pump state format version {}", bytes[0]);
+        }
+        let elapsed = |bit: u8, at: usize| {
+            let ms = u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+            (bytes[1] & bit != 0).then(|| Duration::from_millis(ms as u64))
+        };
+        Ok(Self {
+            running_for: elapsed(1, 2),
+            stopped_for: elapsed(2, 6),
+        })
+    }
+}
+
+/// Decides when the pump runs based on moisture and elapsed time
+pub struct PumpController<C> {
+    config: PumpConfig,
+    clock: C,
+    /// Run start and last stop on the [`run_clock`](Self::run_clock)
+    running_since: Option<Duration>,
+    last_stop: Option<Duration>,
+    /// Run and cooldown time carried over from before a reboot
+    carried: Duration,
+    feedback: Option<FeedbackCheck>,
+    failed: bool,
+    /// The lockout comes only from [`simulate_failure`](Self::simulate_failure)
+    failure_simulated: bool,
+    alert: Option<Alert>,
+    schedule: Option<Schedule>,
+    /// Window the most recent run started in
+    last_session: Option<WindowInstance>,
+    paused: bool,
+    /// Held off by safe mode rather than the operator
+    locked_out: bool,
+    confirmation: Option<Confirmation>,
+    guardrails: Option<Guardrails>,
+}
+
+impl<C: Clock> PumpController<C> {
+    pub fn new(config: PumpConfig, clock: C) -> Self {
+        Self {
+            config,
+            clock,
+            running_since: None,
+            last_stop: None,
+            carried: Duration::ZERO,
+            feedback: None,
+            failed: false,
+            failure_simulated: false,
+            alert: None,
+            schedule: None,
+            last_session: None,
+            paused: false,
+            locked_out: false,
+            confirmation: None,
+            guardrails: None,
+        }
+    }
+
+    /// Hard flood and emergency limits checked on top of all other logic
+    pub fn with_guardrails(mut self, guardrails: Guardrails) -> Self {
+        self.guardrails = Some(guardrails);
+        self
+    }
+
+    /// Guardrail `moisture_percent` is past, if any
+    pub fn guardrail(&self, moisture_percent: u8) -> Option<Guardrail> {
+        let g = self.guardrails?;
+        if moisture_percent > g.flood_above {
+            Some(Guardrail::Flood)
+        } else if moisture_percent < g.emergency_below {
+            Some(Guardrail::Emergency)
+        } else {
+            None
+        }
+    }
+
+    /// Only start watering inside the schedule's windows, keeping its dwell
+    /// between sessions in different windows
+    pub fn with_schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = Some(schedule);
+        self
+    }
+
+    /// Only start after two dry readings no more than `window` apart, so a
+    /// lone spurious low reading never actuates
+    pub fn with_confirmation(mut self, window: Duration) -> Self {
+        self.confirmation = Some(Confirmation {
+            window,
+            armed_at: None,
+        });
+        self
+    }
+
+    /// Waiting for a confirming dry reading
+    pub fn is_armed(&self) -> bool {
+        matches!(&self.confirmation, Some(c) if c.armed_at.is_some())
+    }
+
+    /// Require current to be sensed within `timeout` of every activation
+    pub fn with_feedback(
+        mut self,
+        source: impl PumpFeedback + Send + 'static,
+        timeout: Duration,
+    ) -> Self {
+        self.feedback = Some(FeedbackCheck {
+            source: Box::new(source),
+            timeout,
+            pending_since: None,
+        });
+        self
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running_since.is_some()
+    }
+
+    pub fn config(&self) -> PumpConfig {
+        self.config
+    }
+
+    /// Pump was commanded on but never confirmed; watering stays locked out
+    /// until [`clear_failure`](Self::clear_failure)
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Re-enable watering after the pump has been inspected
+    pub fn clear_failure(&mut self) {
+        self.failed = false;
+        self.failure_simulated = false;
+    }
+
+    /// Undo [`simulate_failure`](Self::simulate_failure), leaving a real
+    /// failure locked out
+    pub fn clear_simulated_failure(&mut self) {
+        if self.failure_simulated {
+            self.clear_failure();
+        }
+    }
+
+    /// Suspend automatic control for maintenance; returns
+    /// [`PumpAction::Deactivate`] if the pump was running
+    pub fn pause(&mut self) -> Option<PumpAction> {
+        self.paused = true;
+        self.stop_now()
+    }
+
+    /// Hand control back to the moisture thresholds
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Lock watering out independently of [`pause`](Self::pause), e.g. while
+    /// the probe is dead; returns [`PumpAction::Deactivate`] if the pump was running
+    pub fn lock_out(&mut self) -> Option<PumpAction> {
+        self.locked_out = true;
+        self.stop_now()
+    }
+
+    pub fn release_lockout(&mut self) {
+        self.locked_out = false;
+    }
+
+    pub fn is_locked_out(&self) -> bool {
+        self.locked_out
+    }
+
+    /// Switch off without pausing or locking out, e.g. once moisture is
+    /// inside the comfort band; returns [`PumpAction::Deactivate`] if it was running
+    pub fn stop(&mut self) -> Option<PumpAction> {
+        self.stop_now()
+    }
+
+    /// Stop a run that has reached `max_run` without looking at moisture, for
+    /// cycles whose reading is not trusted to decide anything else
+    pub fn enforce_max_run(&mut self) -> Option<PumpAction> {
+        let since = self.running_since?;
+        if self.run_clock().saturating_sub(since) < self.config.max_run {
+            return None;
+        }
+        self.stop_now()
+    }
+
+    /// Act as if an activation went unconfirmed, for field tests of the
+    /// alert chain; returns [`PumpAction::Deactivate`] if the pump was running
+    pub fn simulate_failure(&mut self) -> Option<PumpAction> {
+        let action = self.stop_now();
+        let already_failed = self.failed;
+        self.fail();
+        self.failure_simulated = !already_failed;
+        action
+    }
+
+    fn fail(&mut self) {
+        error!("Pump commanded on but no current sensed, locking out watering");
+        self.failed = true;
+        self.failure_simulated = false;
+        self.alert = Some(Alert::PumpFailure);
+    }
+
+    /// Stop any run in progress, abandoning a pending feedback check
+    fn stop_now(&mut self) -> Option<PumpAction> {
+        if let Some(confirmation) = &mut self.confirmation {
+            confirmation.armed_at = None;
+        }
+        if let Some(feedback) = &mut self.feedback {
+            feedback.pending_since = None;
+        }
+        self.running_since.take().map(|_| {
+            self.last_stop = Some(self.run_clock());
+            PumpAction::Deactivate
+        })
+    }
+
+    /// Alert raised since the last call, if any
+    pub fn take_alert(&mut self) -> Option<Alert> {
+        self.alert.take()
+    }
+
+    /// Time base for run starts and stops: the clock, running ahead by the
+    /// carried-over time so restored instants stay representable after a
+    /// reboot restarts it
+    fn run_clock(&self) -> Duration {
+        self.clock.now() + self.carried
+    }
+
+    /// Run and cooldown position, for persisting across reboots
+    pub fn state(&self) -> PumpState {
+        let now = self.run_clock();
+        PumpState {
+            running_for: self.running_since.map(|since| now.saturating_sub(since)),
+            stopped_for: self.last_stop.map(|stop| now.saturating_sub(stop)),
+        }
+    }
+
+    /// Resume from a persisted [`state`](Self::state); time spent rebooting is
+    /// not counted. Returns [`PumpAction::Activate`] when a run was in
+    /// progress, so the caller switches the pump back on.
+    pub fn restore(&mut self, state: PumpState) -> Option<PumpAction> {
+        self.carried = state.running_for.max(state.stopped_for).unwrap_or_default();
+        let now = self.run_clock();
+        self.running_since = state.running_for.map(|ran| now - ran);
+        self.last_stop = state.stopped_for.map(|ago| now - ago);
+        self.running_since.map(|_| PumpAction::Activate)
+    }
+
+    /// Feed the latest moisture; returns an action when the pump should change state
+    pub fn update(&mut self, moisture_percent: u8) -> Option<PumpAction> {
+        let now = self.clock.now();
+        if self.failed || self.paused || self.locked_out {
+            return None;
+        }
+        if self.check_feedback(now) {
+            return Some(PumpAction::Deactivate);
+        }
+        let guardrail = self.guardrail(moisture_percent);
+        let run_now = self.run_clock();
+        match self.running_since {
+            Some(since) => {
+                let ran = run_now.saturating_sub(since);
+                let satisfied =
+                    ran >= self.config.min_run && moisture_percent >= self.config.stop_at;
+                let flooding = guardrail == Some(Guardrail::Flood);
+                if flooding {
+                    warn!(
+                        "Moisture {}% above flood limit, stopping pump",
+                        moisture_percent
+                    );
+                }
+                if satisfied || flooding || ran >= self.config.max_run {
+                    return self.stop_now();
+                }
+                None
+            }
+            None => {
+                let cooling_down = matches!(
+                    self.last_stop,
+                    Some(stop) if run_now.saturating_sub(stop) < self.config.cooldown
+                );
+                let emergency = guardrail == Some(Guardrail::Emergency);
+                let dry = moisture_percent < self.config.start_below || emergency;
+                if dry
+                    && guardrail != Some(Guardrail::Flood)
+                    && !cooling_down
+                    && (self.schedule_allows() || emergency)
+                    && self.confirmed(now, true)
+                {
+                    if emergency && !self.schedule_allows() {
+                        warn!(
+                            "Moisture {}% below emergency limit, watering outside schedule",
+                            moisture_percent
+                        );
+                    }
+                    self.running_since = Some(run_now);
+                    // Window occurrences mean nothing before the clock is synced
+                    self.last_session = self
+                        .schedule
+                        .as_ref()
+                        .zip(self.clock.local_time())
+                        .and_then(|(s, local)| s.window_at(local));
+                    if let Some(feedback) = &mut self.feedback {
+                        feedback.pending_since = Some(now);
+                    }
+                    return Some(PumpAction::Activate);
+                }
+                if !dry {
+                    self.confirmed(now, false);
+                }
+                None
+            }
+        }
+    }
+
+    /// Advance the arm/confirm state with a reading that is (`dry`) or isn't
+    /// asking to water; true when watering may start. Always true without
+    /// [`with_confirmation`](Self::with_confirmation).
+    fn confirmed(&mut self, now: Duration, dry: bool) -> bool {
+        let Some(confirmation) = &mut self.confirmation else {
+            return true;
+        };
+        if !dry {
+            // A recovering reading disarms
+            confirmation.armed_at = None;
+            return false;
+        }
+        match confirmation.armed_at {
+            Some(at) if now.saturating_sub(at) <= confirmation.window => {
+                confirmation.armed_at = None;
+                true
+            }
+            // First dry reading, or the previous one is too old to count
+            _ => {
+                confirmation.armed_at = Some(now);
+                false
+            }
+        }
+    }
+
+    /// Whether the schedule (if any) permits starting a run now
+    fn schedule_allows(&self) -> bool {
+        let Some(schedule) = self.schedule.as_ref().filter(|s| !s.is_unrestricted()) else {
+            return true;
+        };
+        let Some(local) = self.clock.local_time() else {
+            return schedule.in_window(Duration::ZERO, false);
+        };
+        let Some(window) = schedule.window_at(local) else {
+            return false;
+        };
+        // Anti-siphon dwell: don't open a new window's session right after the last one
+        let new_session = self.last_session != Some(window);
+        let too_soon = matches!(
+            self.last_stop,
+            Some(stop) if self.run_clock().saturating_sub(stop) < schedule.min_dwell
+        );
+        !(new_session && too_soon)
+    }
+
+    /// Returns `true` when an unconfirmed activation has timed out and the
+    /// pump must be switched off
+    fn check_feedback(&mut self, now: Duration) -> bool {
+        let Some(feedback) = &mut self.feedback else {
+            return false;
+        };
+        let Some(since) = feedback.pending_since else {
+            return false;
+        };
+
+        let detected = feedback.source.current_detected().unwrap_or_else(|e| {
+            warn!("Pump current sense failed: {:?}", e);
+            false
+        });
+        if detected {
+            feedback.pending_since = None;
+            return false;
+        }
+        if now.saturating_sub(since) < feedback.timeout {
+            return false;
+        }
+
+        feedback.pending_since = None;
+        self.running_since = None;
+        self.last_stop = Some(now + self.carried);
+        self.fail();
+        true
+    }
+}
+
+/// Re-run the production controller over recorded `(timestamp, moisture)`
+/// inputs and return every action it takes, for reproducing field behavior
+pub fn replay_pump(inputs: &[(Duration, u8)], config: PumpConfig) -> Vec<(Duration, PumpAction)> {
+    let clock = MockClock::new();
+    let mut controller = PumpController::new(config, clock.clone());
+    inputs
+        .iter()
+        .filter_map(|&(at, moisture)| {
+            clock.set(at);
+            controller.update(moisture).map(|action| (at, action))
+        })
+        .collect()
+}
+
+/// Bounded log of recent pump actions plus lifetime totals for the session
+#[derive(Debug)]
+pub struct PumpAudit {
+    entries: VecDeque<(Duration, PumpAction)>,
+    capacity: usize,
+    activations: u32,
+}
+
+impl PumpAudit {
+    /// Keep at most `capacity` recent entries; totals are never truncated
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            activations: 0,
+        }
+    }
+
+    /// Record `action` issued at time `at`
+    pub fn record(&mut self, at: Duration, action: PumpAction) {
+        if action == PumpAction::Activate {
+            self.activations = self.activations.saturating_add(1);
+        }
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((at, action));
+    }
+
+    /// Number of activations this session
+    pub fn activations(&self) -> u32 {
+        self.activations
+    }
+
+    /// Most recent actions, oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &(Duration, PumpAction)> {
+        self.entries.iter()
+    }
+}
+
+/// Cumulative pump usage across reboots, for service planning
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PumpLifetime {
+    /// Completed runs
+    pub activations: u32,
+    /// Time the pump actually ran
+    pub runtime: Duration,
+}
+
+impl PumpLifetime {
+    /// Persisted form: version, activations u32, runtime in ms u64 (little endian)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![LIFETIME_FORMAT_VERSION];
+        out.extend_from_slice(&self.activations.to_le_bytes());
+        out.extend_from_slice(&(self.runtime.as_millis() as u64).to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == LIFETIME_BLOB_LEN,
+            "pump lifetime blob is {} bytes, expected {}",
+            bytes.len(),
+            LIFETIME_BLOB_LEN
+        );
+        if bytes[0] != LIFETIME_FORMAT_VERSION {
+            bail!("unsupported pump lifetime format version {}", bytes[0]);
+        }
+        let mut ms = [0u8; 8];
+        ms.copy_from_slice(&bytes[5..13]);
+        Ok(Self {
+            activations: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]),
+            runtime: Duration::from_millis(u64::from_le_bytes(ms)),
+        })
+    }
+}
+
+impl fmt::Display for PumpLifetime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.runtime.as_secs() / 60;
+        write!(
+            f,
+            "{}h{:02}m over {} runs",
+            minutes / 60,
+            minutes % 60,
+            self.activations
+        )
+    }
+}
+
+/// Adds each completed run to a [`PumpLifetime`], timed from the relay
+/// actually switching on to it switching off
+#[derive(Debug, Default)]
+pub struct RuntimeMeter {
+    lifetime: PumpLifetime,
+    running_since: Option<Duration>,
+}
+
+impl RuntimeMeter {
+    /// Continue accumulating on top of previously persisted totals
+    pub fn new(lifetime: PumpLifetime) -> Self {
+        Self {
+            lifetime,
+            running_since: None,
+        }
+    }
+
+    /// Feed an applied pump action; returns `true` when a run completed and
+    /// the totals should be persisted
+    pub fn record(&mut self, at: Duration, action: PumpAction) -> bool {
+        match action {
+            PumpAction::Activate => {
+                self.running_since.get_or_insert(at);
+                false
+            }
+            PumpAction::Deactivate => {
+                let Some(since) = self.running_since.take() else {
+                    return false;
+                };
+                self.lifetime.activations = self.lifetime.activations.saturating_add(1);
+                self.lifetime.runtime += at.saturating_sub(since);
+                true
+            }
+        }
+    }
+
+    pub fn lifetime(&self) -> PumpLifetime {
+        self.lifetime
+    }
+}
+
+/// What happens to readings while [`PumpReadingGate`] holds them back
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GateMode {
+    /// Read and report as usual, but leave the readings out of control
+    #[default]
+    Discard,
+    /// Skip reading during the settle delay; runs are still read so their
+    /// stop conditions apply
+    Pause,
+}
+
+/// Holds readings out of start decisions and dry alerts while the pump runs
+/// and for a settle period after it stops: pump noise biases the ADC, and the
+/// water takes a while to spread to the probe. Stopping a run never waits for
+/// the gate.
+#[derive(Debug, Clone)]
+pub struct PumpReadingGate {
+    mode: GateMode,
+    settle: Duration,
+    running: bool,
+    stopped_at: Option<Duration>,
+}
+
+impl PumpReadingGate {
+    pub fn new(mode: GateMode, settle: Duration) -> Self {
+        Self {
+            mode,
+            settle,
+            running: false,
+            stopped_at: None,
+        }
+    }
+
+    pub fn mode(&self) -> GateMode {
+        self.mode
+    }
+
+    /// Feed an applied pump action
+    pub fn record(&mut self, at: Duration, action: PumpAction) {
+        self.running = action == PumpAction::Activate;
+        if action == PumpAction::Deactivate {
+            self.stopped_at = Some(at);
+        }
+    }
+
+    /// Whether a reading taken at `now` must stay out of start decisions
+    pub fn is_gated(&self, now: Duration) -> bool {
+        self.running
+            || self
+                .stopped_at
+                .is_some_and(|at| now.saturating_sub(at) < self.settle)
+    }
+}
+
+/// How the pump output is switched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PumpDrive {
+    /// Plain relay: straight to full on or off
+    #[default]
+    Relay,
+    /// PWM driver: ramp duty over `ramp` in `steps` equal steps to limit inrush
+    SoftStart { ramp: Duration, steps: u32 },
+}
+
+/// Applies [`PumpAction`]s to a PWM-capable or on/off output
+pub struct PumpOutput<O, C> {
+    output: O,
+    clock: C,
+    drive: PumpDrive,
+}
+
+impl<O: Led, C: Clock> PumpOutput<O, C> {
+    pub fn new(output: O, clock: C, drive: PumpDrive) -> Self {
+        Self {
+            output,
+            clock,
+            drive,
+        }
+    }
+
+    /// Switch the output; a soft-start ramp blocks for its duration
+    pub fn apply(&mut self, action: PumpAction) -> Result<()> {
+        let (from, to) = match action {
+            PumpAction::Activate => (LED_OFF, LED_FULL),
+            PumpAction::Deactivate => (LED_FULL, LED_OFF),
+        };
+        match self.drive {
+            PumpDrive::Relay => self.output.set_brightness(to),
+            PumpDrive::SoftStart { ramp, steps } => {
+                play(&mut self.output, &fade(from, to, ramp, steps), &self.clock)
+            }
+        }
+    }
+
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        replay_pump, DepthScaling, GateMode, Guardrail, Guardrails, PumpAction, PumpAudit,
+        PumpConfig, PumpController, PumpDrive, PumpFeedback, PumpOutput, PumpReadingGate,
+    };
+    use crate::alert::Alert;
+    use crate::clock::Clock;
+    use crate::clock::MockClock;
+    use crate::led::RecordingLed;
+    use crate::schedule::{Schedule, UnsyncedPolicy, Window};
+    use anyhow::Result;
+    use std::time::Duration;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    fn config() -> PumpConfig {
+        PumpConfig {
+            start_below: 30,
+            stop_at: 50,
+            min_run: secs(10),
+            max_run: secs(60),
+            cooldown: secs(120),
+        }
+    }
+
+    #[test]
+    fn replay_reproduces_action_sequence() {
+        let inputs = [
+            (secs(0), 40),
+            (secs(2), 28),   // dry -> start
+            (secs(4), 55),   // wet already, but min-run not met
+            (secs(12), 55),  // min-run met -> stop
+            (secs(20), 20),  // dry again, still cooling down
+            (secs(132), 20), // cooldown over -> start
+            (secs(200), 22), // never recovers -> max-run stop
+            (secs(202), 22),
+        ];
+        assert_eq!(
+            replay_pump(&inputs, config()),
+            vec![
+                (secs(2), PumpAction::Activate),
+                (secs(12), PumpAction::Deactivate),
+                (secs(132), PumpAction::Activate),
+                (secs(200), PumpAction::Deactivate),
+            ]
+        );
+    }
+
+    #[test]
+    fn replay_is_deterministic() {
+        let inputs: Vec<_> = (0..500u64)
+            .map(|i| (secs(i * 7), (i * 37 % 100) as u8))
+            .collect();
+        assert_eq!(
+            replay_pump(&inputs, config()),
+            replay_pump(&inputs, config())
+        );
+    }
+
+    #[test]
+    fn audit_keeps_recent_entries_and_full_count() {
+        let mut audit = PumpAudit::new(2);
+        for i in 0..3 {
+            audit.record(Duration::from_secs(i * 10), PumpAction::Activate);
+        }
+        audit.record(Duration::from_secs(40), PumpAction::Deactivate);
+
+        assert_eq!(audit.activations(), 3);
+        let entries: Vec<_> = audit.entries().copied().collect();
+        assert_eq!(
+            entries,
+            vec![
+                (Duration::from_secs(20), PumpAction::Activate),
+                (Duration::from_secs(40), PumpAction::Deactivate),
+            ]
+        );
+    }
+
+    struct Sense(bool);
+
+    impl PumpFeedback for Sense {
+        fn current_detected(&mut self) -> Result<bool> {
+            Ok(self.0)
+        }
+    }
+
+    fn with_sense(current: bool) -> (PumpController<MockClock>, MockClock) {
+        let clock = MockClock::new();
+        let controller =
+            PumpController::new(config(), clock.clone()).with_feedback(Sense(current), secs(3));
+        (controller, clock)
+    }
+
+    #[test]
+    fn confirmed_activation_raises_no_alert() {
+        let (mut pump, clock) = with_sense(true);
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+        clock.advance(secs(5));
+        assert_eq!(pump.update(20), None);
+        assert!(pump.is_running());
+        assert!(!pump.has_failed());
+        assert_eq!(pump.take_alert(), None);
+    }
+
+    #[test]
+    fn missing_current_raises_pump_failure_and_locks_out() {
+        let (mut pump, clock) = with_sense(false);
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+        clock.advance(secs(1));
+        assert_eq!(pump.update(20), None); // still within the timeout
+        clock.advance(secs(2));
+        assert_eq!(pump.update(20), Some(PumpAction::Deactivate));
+        assert_eq!(pump.take_alert(), Some(Alert::PumpFailure));
+        assert!(pump.has_failed());
+
+        // Locked out even after the cooldown
+        clock.advance(secs(600));
+        assert_eq!(pump.update(20), None);
+        pump.clear_failure();
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn run_ending_before_feedback_timeout_leaves_no_stale_check() {
+        let clock = MockClock::new();
+        let mut pump =
+            PumpController::new(config(), clock.clone()).with_feedback(Sense(false), secs(30));
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+        clock.advance(secs(15));
+        assert_eq!(pump.update(60), Some(PumpAction::Deactivate));
+
+        // Off and correctly drawing no current, well past the timeout
+        for _ in 0..5 {
+            clock.advance(secs(30));
+            assert_eq!(pump.update(40), None);
+        }
+        assert!(!pump.has_failed());
+        assert_eq!(pump.take_alert(), None);
+    }
+
+    #[test]
+    fn clearing_a_simulated_failure_keeps_a_real_one() {
+        let (mut pump, clock) = with_sense(false);
+        pump.simulate_failure();
+        assert_eq!(pump.take_alert(), Some(Alert::PumpFailure));
+        pump.clear_simulated_failure();
+        assert!(!pump.has_failed());
+
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+        clock.advance(secs(5));
+        assert_eq!(pump.update(20), Some(PumpAction::Deactivate));
+        assert!(pump.has_failed());
+        // Simulating on top of the real failure must not let it be undone
+        pump.simulate_failure();
+        pump.clear_simulated_failure();
+        assert!(pump.has_failed());
+    }
+
+    fn minutes(m: u64) -> Duration {
+        secs(m * 60)
+    }
+
+    fn scheduled(windows: Vec<Window>) -> (PumpController<MockClock>, MockClock) {
+        let clock = MockClock::new();
+        let schedule = Schedule::new(windows, minutes(120));
+        let controller = PumpController::new(config(), clock.clone()).with_schedule(schedule);
+        (controller, clock)
+    }
+
+    #[test]
+    fn dwell_suppresses_activation_in_adjacent_window() {
+        let (mut pump, clock) = scheduled(vec![
+            Window::new(minutes(360), minutes(420)),
+            Window::new(minutes(420), minutes(480)),
+        ]);
+
+        // Outside any window nothing starts
+        clock.set(minutes(300));
+        assert_eq!(pump.update(10), None);
+
+        clock.set(minutes(410));
+        assert_eq!(pump.update(10), Some(PumpAction::Activate));
+        clock.advance(minutes(1)); // max-run
+        assert_eq!(pump.update(10), Some(PumpAction::Deactivate));
+
+        // The second window opens well past the cooldown but inside the dwell
+        clock.set(minutes(420));
+        assert_eq!(pump.update(10), None);
+        clock.set(minutes(470));
+        assert_eq!(pump.update(10), None);
+    }
+
+    #[test]
+    fn dwell_allows_repeat_runs_within_a_window_and_later_windows() {
+        let (mut pump, clock) = scheduled(vec![
+            Window::new(minutes(360), minutes(480)),
+            Window::new(minutes(600), minutes(660)),
+        ]);
+
+        clock.set(minutes(360));
+        assert_eq!(pump.update(10), Some(PumpAction::Activate));
+        clock.advance(minutes(1));
+        assert_eq!(pump.update(10), Some(PumpAction::Deactivate));
+
+        // Same window: only the regular cooldown applies
+        clock.advance(config().cooldown);
+        assert_eq!(pump.update(10), Some(PumpAction::Activate));
+        clock.advance(minutes(1));
+        assert_eq!(pump.update(10), Some(PumpAction::Deactivate));
+
+        // Next window opens after the dwell has elapsed
+        clock.set(minutes(600));
+        assert_eq!(pump.update(10), Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn unsynced_clock_blocks_or_ignores_schedule_by_policy() {
+        let windows = vec![Window::new(minutes(360), minutes(420))];
+
+        // Blocking: nothing starts, even at what looks like window time
+        let (mut pump, clock) = scheduled(windows.clone());
+        clock.set_synced(false);
+        clock.set(minutes(380));
+        assert_eq!(pump.update(10), None);
+        // Once synced the window applies as usual
+        clock.set_synced(true);
+        assert_eq!(pump.update(10), Some(PumpAction::Activate));
+
+        let clock = MockClock::new();
+        let schedule = Schedule::new(windows, minutes(120))
+            .with_unsynced_policy(UnsyncedPolicy::IgnoreSchedule);
+        let mut pump = PumpController::new(config(), clock.clone()).with_schedule(schedule);
+        clock.set_synced(false);
+        clock.set(minutes(600));
+        assert_eq!(pump.update(10), Some(PumpAction::Activate));
+        clock.advance(minutes(1));
+        assert_eq!(pump.update(10), Some(PumpAction::Deactivate));
+
+        clock.set_synced(true);
+        clock.advance(config().cooldown);
+        assert_eq!(pump.update(10), None);
+    }
+
+    #[test]
+    fn flood_guardrail_overrides_thresholds_and_min_run() {
+        let clock = MockClock::new();
+        // Misconfigured thresholds that would water very wet soil
+        let wet_config = PumpConfig {
+            start_below: 98,
+            stop_at: 100,
+            ..config()
+        };
+        let mut pump = PumpController::new(wet_config, clock.clone()).with_guardrails(Guardrails {
+            flood_above: 85,
+            emergency_below: 10,
+        });
+        assert_eq!(pump.guardrail(90), Some(Guardrail::Flood));
+        assert_eq!(pump.update(90), None);
+
+        assert_eq!(pump.update(80), Some(PumpAction::Activate));
+        // Well inside min_run, but flooding wins
+        clock.advance(secs(1));
+        assert_eq!(pump.update(86), Some(PumpAction::Deactivate));
+    }
+
+    #[test]
+    fn emergency_guardrail_waters_outside_schedule() {
+        let (pump, clock) = scheduled(vec![Window::new(minutes(360), minutes(420))]);
+        let mut pump = pump.with_guardrails(Guardrails::default());
+        clock.set(minutes(600));
+        // Dry but not critically dry: the schedule holds it back
+        assert_eq!(pump.update(20), None);
+        assert_eq!(pump.guardrail(5), Some(Guardrail::Emergency));
+        assert_eq!(pump.update(5), Some(PumpAction::Activate));
+        clock.advance(minutes(1));
+        assert_eq!(pump.update(5), Some(PumpAction::Deactivate));
+
+        // Cooldown still applies to emergency runs
+        clock.advance(secs(10));
+        assert_eq!(pump.update(5), None);
+        // Lockouts do too
+        clock.advance(config().cooldown);
+        pump.lock_out();
+        assert_eq!(pump.update(5), None);
+    }
+
+    #[test]
+    fn max_run_holds_without_a_trusted_reading() {
+        let clock = MockClock::new();
+        let mut pump = PumpController::new(config(), clock.clone());
+        assert_eq!(pump.enforce_max_run(), None);
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+        clock.advance(secs(59));
+        assert_eq!(pump.enforce_max_run(), None);
+        clock.advance(secs(1));
+        assert_eq!(pump.enforce_max_run(), Some(PumpAction::Deactivate));
+        assert!(!pump.is_running());
+    }
+
+    #[test]
+    fn deeper_probes_get_a_tighter_band() {
+        let base = PumpConfig {
+            start_below: 30,
+            stop_at: 50,
+            ..config()
+        };
+        let scaling = DepthScaling::default();
+        let band = |depth| {
+            let scaled = base.scaled_for_depth(depth, &scaling);
+            (scaled.start_below, scaled.stop_at)
+        };
+        assert_eq!(band(5), (30, 50));
+        assert_eq!(band(10), (30, 50));
+        assert_eq!(band(20), (35, 45));
+        assert_eq!(band(40), (38, 43));
+        assert_eq!(band(200), (38, 42), "never narrower than the minimum band");
+        assert_eq!(base.scaled_for_depth(20, &scaling).max_run, base.max_run);
+    }
+
+    #[test]
+    fn reading_gate_covers_run_and_settle_delay() {
+        let mut gate = PumpReadingGate::new(GateMode::Discard, secs(30));
+        assert!(!gate.is_gated(secs(0)));
+        gate.record(secs(10), PumpAction::Activate);
+        assert!(gate.is_gated(secs(100)));
+        gate.record(secs(100), PumpAction::Deactivate);
+        assert!(gate.is_gated(secs(129)));
+        assert!(!gate.is_gated(secs(130)));
+    }
+
+    #[test]
+    fn paused_controller_never_actuates() {
+        let clock = MockClock::new();
+        let mut pump = PumpController::new(config(), clock.clone());
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+
+        // Pausing mid-run switches the pump off
+        assert_eq!(pump.pause(), Some(PumpAction::Deactivate));
+        assert!(pump.is_paused() && !pump.is_running());
+        for _ in 0..10 {
+            clock.advance(secs(60));
+            assert_eq!(pump.update(5), None);
+        }
+        assert_eq!(pump.pause(), None);
+
+        pump.resume();
+        assert_eq!(pump.update(5), Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn lockout_survives_operator_resume() {
+        let clock = MockClock::new();
+        let mut pump = PumpController::new(config(), clock.clone());
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+        assert_eq!(pump.lock_out(), Some(PumpAction::Deactivate));
+
+        pump.resume();
+        clock.advance(secs(600));
+        assert_eq!(pump.update(5), None);
+
+        pump.release_lockout();
+        assert_eq!(pump.update(5), Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn confirmation_requires_second_dry_reading_in_window() {
+        let clock = MockClock::new();
+        let mut pump = PumpController::new(config(), clock.clone()).with_confirmation(secs(30));
+        assert_eq!(pump.update(20), None);
+        assert!(pump.is_armed());
+        clock.advance(secs(10));
+        assert_eq!(pump.update(22), Some(PumpAction::Activate));
+        assert!(!pump.is_armed());
+    }
+
+    #[test]
+    fn lone_low_reading_is_rejected() {
+        let clock = MockClock::new();
+        let mut pump = PumpController::new(config(), clock.clone()).with_confirmation(secs(30));
+        assert_eq!(pump.update(5), None);
+        // Next dry reading comes too late; it only re-arms
+        clock.advance(secs(31));
+        assert_eq!(pump.update(20), None);
+        assert!(pump.is_armed());
+        clock.advance(secs(5));
+        assert_eq!(pump.update(20), Some(PumpAction::Activate));
+    }
+
+    #[test]
+    fn recovering_reading_disarms() {
+        let clock = MockClock::new();
+        let mut pump = PumpController::new(config(), clock.clone()).with_confirmation(secs(30));
+        assert_eq!(pump.update(20), None);
+        clock.advance(secs(5));
+        assert_eq!(pump.update(40), None);
+        assert!(!pump.is_armed());
+        clock.advance(secs(5));
+        assert_eq!(pump.update(20), None);
+        assert!(pump.is_armed());
+    }
+
+    #[test]
+    fn soft_start_ramps_duty_monotonically_over_the_ramp() {
+        let clock = MockClock::new();
+        let drive = PumpDrive::SoftStart {
+            ramp: secs(2),
+            steps: 8,
+        };
+        let mut pump = PumpOutput::new(RecordingLed::new(clock.clone()), clock.clone(), drive);
+
+        pump.apply(PumpAction::Activate).unwrap();
+        assert_eq!(clock.now(), secs(2));
+        let up = pump.output().timeline().to_vec();
+        assert_eq!(up.len(), 8);
+        assert!(up.windows(2).all(|w| w[0].1 < w[1].1 && w[0].0 < w[1].0));
+        assert!(up[0].1 < 64);
+        assert_eq!(up.last(), Some(&(Duration::from_millis(1750), 255)));
+
+        pump.apply(PumpAction::Deactivate).unwrap();
+        assert_eq!(clock.now(), secs(4));
+        let down = &pump.output().timeline()[8..];
+        assert!(down.windows(2).all(|w| w[0].1 > w[1].1));
+        assert_eq!(down.last().map(|&(_, level)| level), Some(0));
+    }
+
+    #[test]
+    fn relay_switches_instantly() {
+        let clock = MockClock::new();
+        let mut pump = PumpOutput::new(
+            RecordingLed::new(clock.clone()),
+            clock.clone(),
+            PumpDrive::Relay,
+        );
+        pump.apply(PumpAction::Activate).unwrap();
+        pump.apply(PumpAction::Deactivate).unwrap();
+        assert_eq!(pump.output().timeline(), &[(secs(0), 255), (secs(0), 0)]);
+    }
+}
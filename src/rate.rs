@@ -0,0 +1,138 @@
+//! Rate of change of moisture over time.
+
+use crate::fault::SensorFault;
+use std::time::Duration;
+
+const MS_PER_HOUR: i64 = 60 * 60 * 1000;
+
+/// Moisture change in thousandths of a percent per hour, integer only.
+///
+/// Matches [`rate_per_hour`] to within 1 milli-percent/hour (truncation).
+/// Returns `None` when no time has passed.
+pub fn rate_milli_per_hour(previous: u8, current: u8, elapsed: Duration) -> Option<i32> {
+    let ms = elapsed.as_millis() as i64;
+    if ms == 0 {
+        return None;
+    }
+    let delta_milli = (current as i64 - previous as i64) * 1000;
+    // A tiny elapsed time can push the rate past i32; saturate rather than wrap
+    let rate = delta_milli * MS_PER_HOUR / ms;
+    Some(i32::try_from(rate).unwrap_or(if rate < 0 { i32::MIN } else { i32::MAX }))
+}
+
+/// Soil texture, which bounds how quickly moisture can really fall
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoilType {
+    /// Drains and dries fast
+    Sand,
+    #[default]
+    Loam,
+    /// Holds water and dries slowly
+    Clay,
+}
+
+impl SoilType {
+    /// Fastest plausible drying, in thousandths of a percent per hour
+    pub fn max_drying_milli_per_hour(&self) -> i32 {
+        match self {
+            SoilType::Sand => 5_000,
+            SoilType::Loam => 2_000,
+            SoilType::Clay => 1_000,
+        }
+    }
+}
+
+/// Rate between two readings, rejecting drops too fast for `soil` to be real
+/// drying (e.g. a probe pulled out of the ground). Rises are never flagged
+/// since watering can add moisture quickly.
+pub fn check_drying_rate(
+    previous: u8,
+    current: u8,
+    elapsed: Duration,
+    soil: SoilType,
+) -> Result<Option<i32>, SensorFault> {
+    let Some(rate) = rate_milli_per_hour(previous, current, elapsed) else {
+        return Ok(None);
+    };
+    let limit = soil.max_drying_milli_per_hour();
+    if rate.saturating_neg() > limit {
+        return Err(SensorFault::ImplausibleDrying {
+            rate_milli_per_hour: rate,
+            limit,
+        });
+    }
+    Ok(Some(rate))
+}
+
+/// Moisture change in percent per hour
+pub fn rate_per_hour(previous: u8, current: u8, elapsed: Duration) -> Option<f32> {
+    let hours = elapsed.as_secs_f32() / 3600.0;
+    if hours <= 0.0 {
+        return None;
+    }
+    Some((current as f32 - previous as f32) / hours)
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{check_drying_rate, rate_milli_per_hour, SoilType};
+    use crate::fault::SensorFault;
+    use std::time::Duration;
+
+    #[test]
+    fn same_drop_is_plausible_for_sand_but_not_clay() {
+        let hour = Duration::from_secs(60 * 60);
+        assert_eq!(
+            check_drying_rate(40, 37, hour, SoilType::Sand),
+            Ok(Some(-3_000))
+        );
+        assert_eq!(
+            check_drying_rate(40, 37, hour, SoilType::Clay),
+            Err(SensorFault::ImplausibleDrying {
+                rate_milli_per_hour: -3_000,
+                limit: 1_000
+            })
+        );
+        // Watering-speed rises are fine even for clay
+        assert_eq!(
+            check_drying_rate(30, 70, hour, SoilType::Clay),
+            Ok(Some(40_000))
+        );
+        assert_eq!(
+            check_drying_rate(40, 20, Duration::ZERO, SoilType::Clay),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn integer_rate_scales_to_per_hour() {
+        let half_hour = Duration::from_secs(30 * 60);
+        assert_eq!(rate_milli_per_hour(60, 55, half_hour), Some(-10_000));
+        assert_eq!(rate_milli_per_hour(40, 40, half_hour), Some(0));
+        assert_eq!(rate_milli_per_hour(40, 50, Duration::ZERO), None);
+        // 100 points in a millisecond overflows i32 and saturates
+        let ms = Duration::from_millis(1);
+        assert_eq!(rate_milli_per_hour(0, 100, ms), Some(i32::MAX));
+        assert_eq!(rate_milli_per_hour(100, 0, ms), Some(i32::MIN));
+    }
+
+    #[test]
+    fn integer_rate_matches_float_within_one_milli() {
+        use super::rate_per_hour;
+
+        for (prev, curr, secs) in [
+            (60u8, 55u8, 1800u64),
+            (10, 90, 7),
+            (50, 49, 3601),
+            (0, 100, 86_400),
+        ] {
+            let elapsed = Duration::from_secs(secs);
+            let fixed = rate_milli_per_hour(prev, curr, elapsed).unwrap() as f32;
+            let float = rate_per_hour(prev, curr, elapsed).unwrap() * 1000.0;
+            assert!(
+                (fixed - float).abs() <= 1.0,
+                "fixed {fixed} vs float {float}"
+            );
+        }
+    }
+}
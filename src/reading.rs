@@ -0,0 +1,385 @@
+//! A single processed soil measurement as handed to sinks, and manual
+//! annotations placed on the same timeline.
+
+use crate::boot::BootReason;
+use crate::rewet::RewetCause;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::ops::BitOr;
+use std::time::Duration;
+
+/// Boolean state of a reading packed into one word, so compact telemetry
+/// (the binary frame, JSON) carries it cheaply. Bits 0..=5 are read through
+/// the accessors of the same name on [`Reading`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ReadingFlags(u16);
+
+impl ReadingFlags {
+    pub const PUMP_ON: ReadingFlags = ReadingFlags(1 << 0);
+    pub const FAULT: ReadingFlags = ReadingFlags(1 << 1);
+    pub const LOW_BATTERY: ReadingFlags = ReadingFlags(1 << 2);
+    pub const CONTROL_PAUSED: ReadingFlags = ReadingFlags(1 << 3);
+    pub const SUPPLY_SAG: ReadingFlags = ReadingFlags(1 << 4);
+    pub const SAFE_MODE: ReadingFlags = ReadingFlags(1 << 5);
+    /// Raw value beyond a calibration point, so the percentage was clamped
+    pub const CLIPPED: ReadingFlags = ReadingFlags(1 << 6);
+    /// Raw baseline has shifted away from where the calibration settled
+    pub const RECALIBRATION_RECOMMENDED: ReadingFlags = ReadingFlags(1 << 7);
+    /// Taken before the probe settled after boot
+    pub const WARMING_UP: ReadingFlags = ReadingFlags(1 << 8);
+    /// Every assigned bit
+    pub const ALL: ReadingFlags = ReadingFlags(0x01ff);
+
+    pub const fn empty() -> Self {
+        ReadingFlags(0)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// `None` if any unassigned bit is set
+    pub fn from_bits(bits: u16) -> Option<Self> {
+        (bits & !Self::ALL.0 == 0).then_some(ReadingFlags(bits))
+    }
+
+    /// Every bit of `other` is set
+    pub fn contains(self, other: ReadingFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn set(&mut self, other: ReadingFlags, on: bool) {
+        if on {
+            self.0 |= other.0;
+        } else {
+            self.0 &= !other.0;
+        }
+    }
+}
+
+impl BitOr for ReadingFlags {
+    type Output = ReadingFlags;
+
+    fn bitor(self, rhs: ReadingFlags) -> ReadingFlags {
+        ReadingFlags(self.0 | rhs.0)
+    }
+}
+
+/// One processed measurement
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reading {
+    /// Time since boot when the reading was taken
+    pub timestamp: Duration,
+    /// Time as emitted to backends, per the configured
+    /// [`TimestampConfig`](crate::timestamp::TimestampConfig); whole seconds
+    /// of `timestamp` unless set
+    #[serde(default)]
+    pub emitted_at: Timestamp,
+    /// Raw (averaged) ADC value
+    pub raw: u16,
+    /// Moisture after calibration, 0..=100
+    pub moisture_percent: u8,
+    /// Soil electrical conductivity in uS/cm, when the probe reports it
+    pub ec_us_cm: Option<u16>,
+    /// Reset reason, carried only by the first reading after boot
+    pub boot_reason: Option<BootReason>,
+    /// Watering zone the probe belongs to; 0 for single-zone installs
+    #[serde(default)]
+    pub zone: u8,
+    /// Probe insertion depth below the surface, when configured
+    #[serde(default)]
+    pub depth_cm: Option<u8>,
+    /// Supply rail sampled alongside the soil channel, when monitored
+    #[serde(default)]
+    pub supply_mv: Option<u16>,
+    /// Set on the reading that completed a significant moisture rise
+    #[serde(default)]
+    pub rewet: Option<RewetCause>,
+    /// Moisture as percent of field capacity, when reference points are configured
+    #[serde(default)]
+    pub field_capacity_percent: Option<u8>,
+    /// Temperature in tenths of °C, from a [`TemperatureChannel`](crate::temperature::TemperatureChannel)
+    #[serde(default)]
+    pub temperature_tenths_c: Option<i16>,
+    /// +/- uncertainty of `moisture_percent` in tenths of a percent, from the
+    /// spread of the conversions behind the reading
+    #[serde(default)]
+    pub uncertainty_tenths: Option<u16>,
+    /// All boolean state, set by the `with_*` builders
+    #[serde(default)]
+    pub flags: ReadingFlags,
+}
+
+impl Reading {
+    pub fn new(timestamp: Duration, raw: u16, moisture_percent: u8) -> Self {
+        Self {
+            timestamp,
+            emitted_at: Timestamp::seconds(timestamp.as_secs()),
+            raw,
+            moisture_percent,
+            ec_us_cm: None,
+            boot_reason: None,
+            zone: 0,
+            depth_cm: None,
+            supply_mv: None,
+            rewet: None,
+            field_capacity_percent: None,
+            temperature_tenths_c: None,
+            uncertainty_tenths: None,
+            flags: ReadingFlags::empty(),
+        }
+    }
+
+    /// Set or clear `flag`
+    pub fn with_flag(mut self, flag: ReadingFlags, on: bool) -> Self {
+        self.flags.set(flag, on);
+        self
+    }
+
+    /// Pump was running when the reading was taken
+    pub fn pump_on(&self) -> bool {
+        self.flags.contains(ReadingFlags::PUMP_ON)
+    }
+
+    /// A sensor fault was flagged for this reading
+    pub fn fault(&self) -> bool {
+        self.flags.contains(ReadingFlags::FAULT)
+    }
+
+    pub fn low_battery(&self) -> bool {
+        self.flags.contains(ReadingFlags::LOW_BATTERY)
+    }
+
+    /// Taken while automatic pump control was paused
+    pub fn control_paused(&self) -> bool {
+        self.flags.contains(ReadingFlags::CONTROL_PAUSED)
+    }
+
+    /// Rail was depressed (e.g. pump inrush), so the ADC value may be biased
+    pub fn supply_sag(&self) -> bool {
+        self.flags.contains(ReadingFlags::SUPPLY_SAG)
+    }
+
+    /// Taken while the probe was considered dead and watering locked out
+    pub fn safe_mode(&self) -> bool {
+        self.flags.contains(ReadingFlags::SAFE_MODE)
+    }
+
+    /// Emit `at` instead of the seconds since boot
+    pub fn with_emitted_at(mut self, at: Timestamp) -> Self {
+        self.emitted_at = at;
+        self
+    }
+
+    /// Attach an EC measurement
+    pub fn with_ec(mut self, ec_us_cm: u16) -> Self {
+        self.ec_us_cm = Some(ec_us_cm);
+        self
+    }
+
+    /// Tag the first reading of a session with the reset reason
+    pub fn with_boot_reason(mut self, reason: BootReason) -> Self {
+        self.boot_reason = Some(reason);
+        self
+    }
+
+    /// Mark the reading as taken with pump control paused
+    pub fn with_control_paused(self, paused: bool) -> Self {
+        self.with_flag(ReadingFlags::CONTROL_PAUSED, paused)
+    }
+
+    pub fn with_zone(mut self, zone: u8) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    /// Record how deep the probe sits
+    pub fn with_depth(mut self, depth_cm: u8) -> Self {
+        self.depth_cm = Some(depth_cm);
+        self
+    }
+
+    pub fn with_pump_on(self, pump_on: bool) -> Self {
+        self.with_flag(ReadingFlags::PUMP_ON, pump_on)
+    }
+
+    /// Flag the reading as suspect
+    pub fn with_fault(self, fault: bool) -> Self {
+        self.with_flag(ReadingFlags::FAULT, fault)
+    }
+
+    pub fn with_low_battery(self, low_battery: bool) -> Self {
+        self.with_flag(ReadingFlags::LOW_BATTERY, low_battery)
+    }
+
+    /// Mark the reading as taken in safe mode
+    pub fn with_safe_mode(self, safe_mode: bool) -> Self {
+        self.with_flag(ReadingFlags::SAFE_MODE, safe_mode)
+    }
+
+    /// Tag the reading with what caused the moisture rise it completes
+    pub fn with_rewet(mut self, cause: RewetCause) -> Self {
+        self.rewet = Some(cause);
+        self
+    }
+
+    /// Attach the moisture expressed on the field-capacity scale
+    pub fn with_field_capacity(mut self, percent: u8) -> Self {
+        self.field_capacity_percent = Some(percent);
+        self
+    }
+
+    /// Attach a temperature in tenths of °C
+    pub fn with_temperature(mut self, tenths_c: i16) -> Self {
+        self.temperature_tenths_c = Some(tenths_c);
+        self
+    }
+
+    /// Attach the +/- moisture uncertainty in tenths of a percent
+    pub fn with_uncertainty(mut self, tenths: u16) -> Self {
+        self.uncertainty_tenths = Some(tenths);
+        self
+    }
+
+    /// Record the rail voltage and whether it was sagging
+    pub fn with_supply(mut self, millivolts: u16, sagging: bool) -> Self {
+        self.supply_mv = Some(millivolts);
+        self.with_flag(ReadingFlags::SUPPLY_SAG, sagging)
+    }
+
+    /// Confidence in the reading, 0-100. Suspect readings score zero;
+    /// clipping, warm-up, a sagging supply and conversion uncertainty each
+    /// take points off.
+    pub fn quality(&self) -> u8 {
+        if self.fault() {
+            return 0;
+        }
+        let penalties = [
+            (ReadingFlags::CLIPPED, 30),
+            (ReadingFlags::WARMING_UP, 40),
+            (ReadingFlags::SUPPLY_SAG, 30),
+        ];
+        let flagged: u16 = penalties
+            .iter()
+            .filter(|(flag, _)| self.flags.contains(*flag))
+            .map(|(_, points)| points)
+            .sum();
+        // Two points per percent of uncertainty, at most half the score
+        let uncertain = self.uncertainty_tenths.map_or(0, |u| (u / 5).min(50));
+        100u16.saturating_sub(flagged + uncertain) as u8
+    }
+}
+
+/// Longest annotation text kept, in bytes
+pub const ANNOTATION_MAX_LEN: usize = 64;
+
+/// Free-text note entered by the operator ("added fertilizer", "repotted")
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Seconds since boot
+    pub timestamp_s: u32,
+    /// Time sinks emit, stamped like the readings around it
+    pub emitted_at: Timestamp,
+    text: String,
+}
+
+impl Annotation {
+    /// Line breaks and other control characters become spaces; text past
+    /// [`ANNOTATION_MAX_LEN`] bytes is cut at a character boundary
+    pub fn new(timestamp: Duration, text: &str) -> Self {
+        let mut clean = String::with_capacity(text.len().min(ANNOTATION_MAX_LEN));
+        for c in text.trim().chars() {
+            let c = if c.is_control() { ' ' } else { c };
+            if clean.len() + c.len_utf8() > ANNOTATION_MAX_LEN {
+                break;
+            }
+            clean.push(c);
+        }
+        Self {
+            timestamp_s: timestamp.as_secs() as u32,
+            emitted_at: Timestamp::seconds(timestamp.as_secs()),
+            text: clean,
+        }
+    }
+
+    /// Emit `at` instead of the seconds since boot
+    pub fn with_emitted_at(mut self, at: Timestamp) -> Self {
+        self.emitted_at = at;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Reading, ReadingFlags};
+    use std::time::Duration;
+
+    fn reading() -> Reading {
+        Reading::new(Duration::from_secs(60), 2100, 50)
+    }
+
+    #[test]
+    fn builders_set_matching_flag_bits() {
+        assert_eq!(reading().flags, ReadingFlags::empty());
+
+        let watering = reading().with_pump_on(true).with_low_battery(true);
+        assert_eq!(watering.flags.bits(), 0b101);
+
+        let dead_probe = reading()
+            .with_fault(true)
+            .with_safe_mode(true)
+            .with_flag(ReadingFlags::CLIPPED, true);
+        assert_eq!(
+            dead_probe.flags,
+            ReadingFlags::FAULT | ReadingFlags::SAFE_MODE | ReadingFlags::CLIPPED
+        );
+
+        let fresh_boot = reading()
+            .with_supply(3100, true)
+            .with_flag(ReadingFlags::WARMING_UP, true)
+            .with_flag(ReadingFlags::RECALIBRATION_RECOMMENDED, true);
+        assert_eq!(fresh_boot.flags.bits(), 0x190);
+
+        // Clearing a flag clears its field too
+        let stopped = watering.with_flag(ReadingFlags::PUMP_ON, false);
+        assert!(!stopped.pump_on() && stopped.low_battery());
+        assert_eq!(stopped.flags, ReadingFlags::LOW_BATTERY);
+    }
+
+    #[test]
+    fn quality_drops_with_each_doubt() {
+        assert_eq!(reading().quality(), 100);
+        assert_eq!(reading().with_uncertainty(25).quality(), 95);
+        assert_eq!(reading().with_uncertainty(900).quality(), 50);
+        assert_eq!(
+            reading()
+                .with_flag(ReadingFlags::CLIPPED, true)
+                .with_flag(ReadingFlags::WARMING_UP, true)
+                .quality(),
+            30
+        );
+        assert_eq!(
+            reading()
+                .with_supply(2900, true)
+                .with_flag(ReadingFlags::CLIPPED, true)
+                .with_flag(ReadingFlags::WARMING_UP, true)
+                .quality(),
+            0
+        );
+        assert_eq!(reading().with_fault(true).quality(), 0);
+    }
+
+    #[test]
+    fn unassigned_bits_are_rejected() {
+        assert_eq!(
+            ReadingFlags::from_bits(0x0141),
+            Some(ReadingFlags::PUMP_ON | ReadingFlags::CLIPPED | ReadingFlags::WARMING_UP)
+        );
+        assert_eq!(ReadingFlags::from_bits(0x0200), None);
+    }
+}
@@ -0,0 +1,140 @@
+//! Telling rain apart from pump watering when moisture rises sharply.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Likely source of a significant moisture rise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RewetCause {
+    /// No pump run preceded the rise within the window
+    RainDetected,
+    /// The pump started within the window before the rise
+    PumpRewet,
+}
+
+impl RewetCause {
+    /// Stable machine-readable name
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RewetCause::RainDetected => "rain_detected",
+            RewetCause::PumpRewet => "pump_rewet",
+        }
+    }
+}
+
+impl fmt::Display for RewetCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RewetCause::RainDetected => write!(f, "rain detected (no recent pump run)"),
+            RewetCause::PumpRewet => write!(f, "rewetted by pump"),
+        }
+    }
+}
+
+/// What counts as a rise and how long a pump start is held responsible for one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewetConfig {
+    /// Rise in percentage points above the lowest recent reading
+    pub min_rise: u8,
+    /// A pump start this long before the rise (or less) explains it
+    pub pump_window: Duration,
+}
+
+impl Default for RewetConfig {
+    fn default() -> Self {
+        Self {
+            min_rise: 10,
+            pump_window: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// Classifies moisture rises against recent pump starts.
+///
+/// Rises are measured from the driest reading since the last classified
+/// rise, so soaking spread over several readings still counts.
+#[derive(Debug, Clone, Default)]
+pub struct RewetDetector {
+    config: RewetConfig,
+    trough: Option<u8>,
+    last_pump_start: Option<Duration>,
+}
+
+impl RewetDetector {
+    pub fn new(config: RewetConfig) -> Self {
+        Self {
+            config,
+            trough: None,
+            last_pump_start: None,
+        }
+    }
+
+    /// Note that the pump was switched on at `at`
+    pub fn record_pump_start(&mut self, at: Duration) {
+        self.last_pump_start = Some(at);
+    }
+
+    /// Feed a reading; returns the cause once moisture has risen by at least
+    /// `min_rise`
+    pub fn update(&mut self, at: Duration, moisture_percent: u8) -> Option<RewetCause> {
+        let trough = self
+            .trough
+            .map_or(moisture_percent, |t| t.min(moisture_percent));
+        if moisture_percent - trough < self.config.min_rise {
+            self.trough = Some(trough);
+            return None;
+        }
+        self.trough = Some(moisture_percent);
+        let pumped = self
+            .last_pump_start
+            .is_some_and(|start| at.saturating_sub(start) <= self.config.pump_window);
+        Some(if pumped {
+            RewetCause::PumpRewet
+        } else {
+            RewetCause::RainDetected
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{RewetCause, RewetConfig, RewetDetector};
+    use std::time::Duration;
+
+    fn mins(m: u64) -> Duration {
+        Duration::from_secs(m * 60)
+    }
+
+    fn detector() -> RewetDetector {
+        RewetDetector::new(RewetConfig {
+            min_rise: 8,
+            pump_window: mins(20),
+        })
+    }
+
+    #[test]
+    fn rise_after_pumping_is_pump_rewet() {
+        let mut rewet = detector();
+        assert_eq!(rewet.update(mins(0), 22), None);
+        rewet.record_pump_start(mins(1));
+        assert_eq!(rewet.update(mins(5), 26), None);
+        assert_eq!(rewet.update(mins(10), 35), Some(RewetCause::PumpRewet));
+        // The new level is the baseline for the next rise
+        assert_eq!(rewet.update(mins(15), 38), None);
+    }
+
+    #[test]
+    fn rise_without_pumping_is_rain() {
+        let mut rewet = detector();
+        assert_eq!(rewet.update(mins(0), 40), None);
+        // Drying lowers the baseline
+        assert_eq!(rewet.update(mins(30), 30), None);
+        assert_eq!(rewet.update(mins(60), 41), Some(RewetCause::RainDetected));
+
+        // A pump start longer ago than the window no longer explains a rise
+        rewet.record_pump_start(mins(61));
+        assert_eq!(rewet.update(mins(90), 35), None);
+        assert_eq!(rewet.update(mins(120), 45), Some(RewetCause::RainDetected));
+    }
+}
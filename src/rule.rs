@@ -0,0 +1,145 @@
+//! Composable pump activation rules, e.g. "dry AND in a window AND NOT
+//! reservoir empty AND NOT recent rain".
+//!
+//! Rules deserialize from config as externally tagged JSON:
+//! `{"and": [{"moisture_below": 30}, "in_window", {"not": "recent_rain"}]}`.
+
+use serde::{Deserialize, Serialize};
+use std::ops::Not;
+
+/// What a rule is evaluated against each cycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RuleContext {
+    pub moisture_percent: u8,
+    /// A schedule window is open (always true without a schedule)
+    pub in_window: bool,
+    pub reservoir_empty: bool,
+    /// Rain was detected within the lookback period
+    pub recent_rain: bool,
+}
+
+/// Predicate tree over a [`RuleContext`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition {
+    MoistureBelow(u8),
+    MoistureAbove(u8),
+    InWindow,
+    ReservoirEmpty,
+    RecentRain,
+    /// Every clause holds; true when empty
+    And(Vec<Condition>),
+    /// At least one clause holds; false when empty
+    Or(Vec<Condition>),
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn and(self, other: Condition) -> Condition {
+        match self {
+            Condition::And(mut clauses) => {
+                clauses.push(other);
+                Condition::And(clauses)
+            }
+            first => Condition::And(vec![first, other]),
+        }
+    }
+
+    pub fn or(self, other: Condition) -> Condition {
+        match self {
+            Condition::Or(mut clauses) => {
+                clauses.push(other);
+                Condition::Or(clauses)
+            }
+            first => Condition::Or(vec![first, other]),
+        }
+    }
+
+    pub fn evaluate(&self, ctx: &RuleContext) -> bool {
+        match self {
+            Condition::MoistureBelow(level) => ctx.moisture_percent < *level,
+            Condition::MoistureAbove(level) => ctx.moisture_percent > *level,
+            Condition::InWindow => ctx.in_window,
+            Condition::ReservoirEmpty => ctx.reservoir_empty,
+            Condition::RecentRain => ctx.recent_rain,
+            Condition::And(clauses) => clauses.iter().all(|c| c.evaluate(ctx)),
+            Condition::Or(clauses) => clauses.iter().any(|c| c.evaluate(ctx)),
+            Condition::Not(inner) => !inner.evaluate(ctx),
+        }
+    }
+}
+
+impl Not for Condition {
+    type Output = Condition;
+
+    fn not(self) -> Condition {
+        Condition::Not(Box::new(self))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Condition, RuleContext};
+
+    fn watering_rule() -> Condition {
+        Condition::MoistureBelow(30)
+            .and(Condition::InWindow)
+            .and(!Condition::ReservoirEmpty)
+            .and(!Condition::RecentRain)
+    }
+
+    fn ready() -> RuleContext {
+        RuleContext {
+            moisture_percent: 20,
+            in_window: true,
+            reservoir_empty: false,
+            recent_rain: false,
+        }
+    }
+
+    #[test]
+    fn composite_rule_needs_every_clause() {
+        let rule = watering_rule();
+        assert!(rule.evaluate(&ready()));
+
+        for blocked in [
+            RuleContext {
+                moisture_percent: 30,
+                ..ready()
+            },
+            RuleContext {
+                in_window: false,
+                ..ready()
+            },
+            RuleContext {
+                reservoir_empty: true,
+                ..ready()
+            },
+            RuleContext {
+                recent_rain: true,
+                ..ready()
+            },
+        ] {
+            assert!(!rule.evaluate(&blocked), "{blocked:?}");
+        }
+
+        // Very dry soil may override the window
+        let urgent = Condition::MoistureBelow(10).or(Condition::InWindow);
+        let outside = RuleContext {
+            in_window: false,
+            moisture_percent: 5,
+            ..ready()
+        };
+        assert!(urgent.evaluate(&outside));
+        assert!(Condition::And(Vec::new()).evaluate(&outside));
+        assert!(!Condition::Or(Vec::new()).evaluate(&outside));
+    }
+
+    #[test]
+    fn rule_loads_from_config_json() {
+        let json = r#"{"and":[{"moisture_below":30},"in_window",{"not":"reservoir_empty"},{"not":"recent_rain"}]}"#;
+        let rule: Condition = serde_json::from_str(json).unwrap();
+        assert_eq!(rule, watering_rule());
+        assert_eq!(serde_json::to_string(&rule).unwrap(), json);
+    }
+}
@@ -0,0 +1,217 @@
+//! Per-reading ADC sample count, optionally tuned to the observed noise.
+
+use crate::sensor::isqrt;
+use log::info;
+use std::collections::VecDeque;
+
+/// How many ADC conversions are averaged into each reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplingConfig {
+    pub samples: usize,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self { samples: 5 }
+    }
+}
+
+/// Limits and spread thresholds for [`SamplingTuner`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TunerConfig {
+    /// Never average fewer conversions than this
+    pub floor: usize,
+    /// Never average more conversions than this
+    pub cap: usize,
+    /// Readings judged together before adjusting
+    pub window: usize,
+    /// Noise of the averaged reading (standard deviation, raw counts) above
+    /// which samples double
+    pub noisy_spread: u16,
+    /// Noise of the averaged reading below which samples halve
+    pub clean_spread: u16,
+}
+
+impl Default for TunerConfig {
+    fn default() -> Self {
+        Self {
+            floor: 2,
+            cap: 32,
+            window: 8,
+            noisy_spread: 20,
+            clean_spread: 5,
+        }
+    }
+}
+
+/// Doubles the sample count while readings are noisy and halves it while
+/// they are clean, trading accuracy against ADC-on time.
+///
+/// Noise is judged from the spread of the conversions within each reading,
+/// scaled down by the count averaged, so soil drying or wetting between
+/// readings is not mistaken for noise. Sources that report no spread fall
+/// back to the scatter of the readings about their trend.
+#[derive(Debug, Clone)]
+pub struct SamplingTuner {
+    config: TunerConfig,
+    /// Raw value and within-reading spread of the readings in this window
+    recent: VecDeque<(u16, Option<u16>)>,
+}
+
+impl SamplingTuner {
+    pub fn new(config: TunerConfig) -> Self {
+        Self {
+            recent: VecDeque::with_capacity(config.window),
+            config,
+        }
+    }
+
+    /// Record one reading's raw value and the standard deviation of its
+    /// conversions, if known; once a full window has been seen, adjust
+    /// `sampling` and return the new count if it changed
+    pub fn observe(
+        &mut self,
+        raw: u16,
+        spread: Option<u16>,
+        sampling: &mut SamplingConfig,
+    ) -> Option<usize> {
+        self.recent.push_back((raw, spread));
+        if self.recent.len() < self.config.window.max(3) {
+            return None;
+        }
+        let current = sampling.samples;
+        let variance = self.reading_variance(current);
+        self.recent.clear();
+
+        let (noisy, clean) = (
+            self.config.noisy_spread as u64,
+            self.config.clean_spread as u64,
+        );
+        let next = if variance > noisy * noisy {
+            current.saturating_mul(2)
+        } else if variance < clean * clean {
+            current / 2
+        } else {
+            current
+        }
+        .clamp(self.config.floor, self.config.cap.max(self.config.floor));
+        if next == current {
+            return None;
+        }
+        info!(
+            "Reading noise ~{} counts over {} readings, averaging {} samples (was {})",
+            isqrt(variance),
+            self.config.window,
+            next,
+            current
+        );
+        sampling.samples = next;
+        Some(next)
+    }
+
+    /// Variance of an averaged reading over the window: the mean
+    /// within-reading variance over `samples` when every reading reported a
+    /// spread, else half the variance of successive differences, which
+    /// cancels a steady trend
+    fn reading_variance(&self, samples: usize) -> u64 {
+        let n = self.recent.len() as u64;
+        if self.recent.iter().all(|(_, spread)| spread.is_some()) {
+            let total: u64 = self
+                .recent
+                .iter()
+                .map(|&(_, spread)| (spread.unwrap_or(0) as u64).pow(2))
+                .sum();
+            return total / n / samples.max(1) as u64;
+        }
+        let diffs: Vec<i64> = self
+            .recent
+            .iter()
+            .zip(self.recent.iter().skip(1))
+            .map(|(&(a, _), &(b, _))| b as i64 - a as i64)
+            .collect();
+        let m = diffs.len() as i64;
+        // Rounded to nearest, integer only
+        let mean = (2 * diffs.iter().sum::<i64>() + m).div_euclid(2 * m);
+        let scatter: u64 = diffs.iter().map(|&d| (d - mean).pow(2) as u64).sum();
+        scatter / (2 * (m as u64 - 1))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{SamplingConfig, SamplingTuner, TunerConfig};
+
+    fn tuner() -> SamplingTuner {
+        SamplingTuner::new(TunerConfig {
+            floor: 2,
+            cap: 16,
+            window: 4,
+            noisy_spread: 50,
+            clean_spread: 10,
+        })
+    }
+
+    #[test]
+    fn noisy_readings_raise_samples_up_to_cap() {
+        let mut tuner = tuner();
+        let mut sampling = SamplingConfig { samples: 4 };
+        let noisy = [2000, 2100, 1950, 2080];
+        let changes: Vec<Option<usize>> = noisy
+            .repeat(4)
+            .into_iter()
+            .map(|raw| tuner.observe(raw, None, &mut sampling))
+            .filter(Option::is_some)
+            .collect();
+        assert_eq!(changes, vec![Some(8), Some(16)]);
+        assert_eq!(sampling.samples, 16);
+    }
+
+    #[test]
+    fn clean_readings_lower_samples_to_floor() {
+        let mut tuner = tuner();
+        let mut sampling = SamplingConfig { samples: 16 };
+        for raw in [2000, 2003, 2001, 2004].repeat(5) {
+            tuner.observe(raw, None, &mut sampling);
+        }
+        assert_eq!(sampling.samples, 2);
+
+        // Moderate spread leaves the count alone
+        let mut sampling = SamplingConfig { samples: 8 };
+        for raw in [2000, 2030, 2010, 2020] {
+            assert_eq!(tuner.observe(raw, None, &mut sampling), None);
+        }
+        assert_eq!(sampling.samples, 8);
+    }
+
+    #[test]
+    fn drying_trend_is_not_noise() {
+        // Steady drift between readings with quiet conversions within each
+        let drying = [2000, 2040, 2080, 2120];
+        let mut tuner = tuner();
+        let mut sampling = SamplingConfig { samples: 4 };
+        for raw in drying {
+            tuner.observe(raw, Some(4), &mut sampling);
+        }
+        assert_eq!(sampling.samples, 2);
+
+        // The trend is cancelled without spreads too
+        let mut sampling = SamplingConfig { samples: 4 };
+        for raw in drying {
+            tuner.observe(raw, None, &mut sampling);
+        }
+        assert_eq!(sampling.samples, 2);
+    }
+
+    #[test]
+    fn within_reading_noise_is_scaled_by_the_count_averaged() {
+        // Spread 160 per conversion averages to 80 over 4 samples and 57
+        // over 8, both noisy, and to 40 over 16, which is not
+        let mut tuner = tuner();
+        let mut sampling = SamplingConfig { samples: 4 };
+        let changes: Vec<usize> = (0..12)
+            .filter_map(|_| tuner.observe(2000, Some(160), &mut sampling))
+            .collect();
+        assert_eq!(changes, vec![8, 16]);
+        assert_eq!(sampling.samples, 16);
+    }
+}
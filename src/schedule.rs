@@ -0,0 +1,184 @@
+//! Daily watering windows and the dwell enforced between them.
+
+use std::time::Duration;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Daily period in which watering may start, as offsets from midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    pub start: Duration,
+    /// Exclusive; a window with `end <= start` runs past midnight
+    pub end: Duration,
+}
+
+impl Window {
+    pub fn new(start: Duration, end: Duration) -> Self {
+        Self { start, end }
+    }
+
+    fn contains(&self, time_of_day: Duration) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&time_of_day)
+        } else {
+            time_of_day >= self.start || time_of_day < self.end
+        }
+    }
+}
+
+/// Identifies one occurrence of a window, so sessions in different windows
+/// (or the same window on different days) can be told apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowInstance {
+    /// Day the occurrence started on, counted from clock zero
+    pub day: u64,
+    /// Index into [`Schedule::windows`]
+    pub index: usize,
+}
+
+/// What a schedule with windows does while the clock is not synced
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnsyncedPolicy {
+    /// No automatic watering until the time is known
+    #[default]
+    Block,
+    /// Water on moisture alone, as if no windows were configured
+    IgnoreSchedule,
+}
+
+/// When automatic watering is allowed to start
+///
+/// Clock time is taken as time since local midnight of day zero. An empty
+/// window list places no restriction on when watering starts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    pub windows: Vec<Window>,
+    /// Minimum time from the end of one watering session to the start of a
+    /// session in another window; unlike the pump cooldown this only applies
+    /// across window boundaries
+    pub min_dwell: Duration,
+    pub unsynced: UnsyncedPolicy,
+}
+
+impl Schedule {
+    pub fn new(windows: Vec<Window>, min_dwell: Duration) -> Self {
+        Self {
+            windows,
+            min_dwell,
+            unsynced: UnsyncedPolicy::default(),
+        }
+    }
+
+    pub fn with_unsynced_policy(mut self, policy: UnsyncedPolicy) -> Self {
+        self.unsynced = policy;
+        self
+    }
+
+    /// No windows configured, so watering may start at any time
+    pub fn is_unrestricted(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// Time of day at which the next window opens after `now`
+    pub fn next_start(&self, now: Duration) -> Option<Duration> {
+        let time_of_day = time_of_day(now);
+        self.windows
+            .iter()
+            .min_by_key(|w| (w.start + DAY - time_of_day).as_secs() % DAY.as_secs())
+            .map(|w| w.start)
+    }
+
+    /// Whether watering may start at `now` as far as the windows go; the
+    /// windows are only consulted when `synced`
+    pub fn in_window(&self, now: Duration, synced: bool) -> bool {
+        if self.is_unrestricted() {
+            return true;
+        }
+        if !synced {
+            return self.unsynced == UnsyncedPolicy::IgnoreSchedule;
+        }
+        self.window_at(now).is_some()
+    }
+
+    /// Window occurrence covering `now`, if any
+    pub fn window_at(&self, now: Duration) -> Option<WindowInstance> {
+        let day = now.as_secs() / DAY.as_secs();
+        let time_of_day = time_of_day(now);
+        self.windows
+            .iter()
+            .position(|w| w.contains(time_of_day))
+            .map(|index| {
+                let w = &self.windows[index];
+                // The part of an overnight window after midnight belongs to the previous day
+                let wrapped = w.start >= w.end && time_of_day < w.end;
+                WindowInstance {
+                    day: if wrapped { day.saturating_sub(1) } else { day },
+                    index,
+                }
+            })
+    }
+}
+
+/// Offset of `now` from the preceding midnight
+fn time_of_day(now: Duration) -> Duration {
+    let day = now.as_secs() / DAY.as_secs();
+    now - DAY * day as u32
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Schedule, UnsyncedPolicy, Window, WindowInstance};
+    use std::time::Duration;
+
+    fn hours(h: u64) -> Duration {
+        Duration::from_secs(h * 60 * 60)
+    }
+
+    #[test]
+    fn finds_window_occurrences_including_overnight() {
+        let schedule = Schedule::new(
+            vec![
+                Window::new(hours(6), hours(8)),
+                Window::new(hours(22), hours(2)),
+            ],
+            hours(1),
+        );
+        assert_eq!(
+            schedule.window_at(hours(7)),
+            Some(WindowInstance { day: 0, index: 0 })
+        );
+        assert_eq!(schedule.window_at(hours(8)), None);
+        assert_eq!(
+            schedule.window_at(hours(23)),
+            Some(WindowInstance { day: 0, index: 1 })
+        );
+        assert_eq!(
+            schedule.window_at(hours(25)),
+            Some(WindowInstance { day: 0, index: 1 })
+        );
+        assert_eq!(
+            schedule.window_at(hours(24 + 6)),
+            Some(WindowInstance { day: 1, index: 0 })
+        );
+    }
+
+    #[test]
+    fn unsynced_clock_follows_the_fallback_policy() {
+        let schedule = Schedule::new(vec![Window::new(hours(6), hours(8))], hours(1));
+        // Default blocks, even at a time that looks like it is in the window
+        assert!(!schedule.in_window(hours(7), false));
+        assert!(!schedule.in_window(hours(12), false));
+
+        let lenient = schedule
+            .clone()
+            .with_unsynced_policy(UnsyncedPolicy::IgnoreSchedule);
+        assert!(lenient.in_window(hours(12), false));
+
+        // Once synced both go by the windows
+        for s in [&schedule, &lenient] {
+            assert!(s.in_window(hours(7), true));
+            assert!(!s.in_window(hours(12), true));
+        }
+        assert!(Schedule::new(Vec::new(), hours(1)).in_window(hours(12), false));
+    }
+}
@@ -0,0 +1,326 @@
+//! Reading logs on a removable SD card for offline deployments, one file per
+//! day.
+//!
+//! The card is mounted as FAT through the VFS (see `mount_sd_card`), after
+//! which the logs are plain appended files. File names are 8.3 (`d00123.csv`,
+//! by day of the emitted timestamp the rows carry) since FATFS is usually built without
+//! long names. A missing or full card never stops the control loop: failed
+//! writes are logged once per outage and the readings dropped.
+
+use crate::codec::{Codec, JsonCodec};
+use crate::reading::Reading;
+use crate::sink::ReadingSink;
+use crate::timestamp::Timestamp;
+use anyhow::{anyhow, Context, Result};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const CSV_HEADER: &str = "timestamp,raw,moisture,pump,fault\n";
+
+/// Directory of append-only log files
+pub trait LogDirectory {
+    /// Append `data` to `name`, creating it if needed
+    fn append(&mut self, name: &str, data: &[u8]) -> Result<()>;
+
+    fn exists(&self, name: &str) -> bool;
+}
+
+/// Log files under a mounted VFS path, e.g. `/sdcard`
+pub struct FsLogDirectory {
+    root: PathBuf,
+}
+
+impl FsLogDirectory {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl LogDirectory for FsLogDirectory {
+    fn append(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.root.join(name);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(data))
+            .with_context(|| format!("appending to {}", path.display()))
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        fs::metadata(self.root.join(name)).is_ok()
+    }
+}
+
+/// In-RAM log directory for tests
+#[derive(Debug, Default)]
+pub struct MemoryLogDirectory {
+    files: HashMap<String, Vec<u8>>,
+    /// When set, every append fails as if the card were missing or full
+    pub fail_writes: bool,
+}
+
+impl MemoryLogDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Contents of `name`, if it was ever written
+    pub fn file(&self, name: &str) -> Option<&str> {
+        self.files
+            .get(name)
+            .map(|data| std::str::from_utf8(data).unwrap_or_default())
+    }
+
+    /// Names of every file, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.files.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl LogDirectory for MemoryLogDirectory {
+    fn append(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        if self.fail_writes {
+            return Err(anyhow!("simulated SD card write failure for {}", name));
+        }
+        self.files
+            .entry(name.to_string())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.files.contains_key(name)
+    }
+}
+
+/// Layout of the daily log files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Header plus one row of the main fields per reading
+    #[default]
+    Csv,
+    /// One JSON-encoded [`Reading`] per line, every field included
+    JsonLines,
+}
+
+impl LogFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Csv => "csv",
+            LogFormat::JsonLines => "jsn",
+        }
+    }
+}
+
+/// Appends every reading to the day's file in a [`LogDirectory`]
+pub struct SdCardSink<D> {
+    dir: D,
+    format: LogFormat,
+    /// Day whose file has been started this boot
+    day: Option<u64>,
+    /// Readings dropped since the card last accepted a write
+    dropped: u32,
+}
+
+impl<D: LogDirectory> SdCardSink<D> {
+    pub fn new(dir: D, format: LogFormat) -> Self {
+        Self {
+            dir,
+            format,
+            day: None,
+            dropped: 0,
+        }
+    }
+
+    /// File readings emitted at `at` go to
+    pub fn file_name(&self, at: Timestamp) -> String {
+        format!("d{:05}.{}", day_of(at), self.format.extension())
+    }
+
+    pub fn dir(&self) -> &D {
+        &self.dir
+    }
+
+    pub fn dir_mut(&mut self) -> &mut D {
+        &mut self.dir
+    }
+
+    /// Readings lost to the current card outage
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    fn line(&self, reading: &Reading) -> Result<Vec<u8>> {
+        Ok(match self.format {
+            LogFormat::Csv => format!(
+                "{},{},{},{},{}\n",
+                reading.emitted_at,
+                reading.raw,
+                reading.moisture_percent,
+                reading.pump_on() as u8,
+                reading.fault() as u8
+            )
+            .into_bytes(),
+            LogFormat::JsonLines => {
+                let mut line = JsonCodec.encode(reading)?;
+                line.push(b'\n');
+                line
+            }
+        })
+    }
+
+    fn write(&mut self, reading: &Reading) -> Result<()> {
+        // Same clock as the rows, so files roll over at the anchored midnight
+        let day = day_of(reading.emitted_at);
+        let name = self.file_name(reading.emitted_at);
+        let mut data = Vec::new();
+        if self.format == LogFormat::Csv && !self.dir.exists(&name) {
+            data.extend_from_slice(CSV_HEADER.as_bytes());
+        }
+        data.extend(self.line(reading)?);
+        self.dir.append(&name, &data)?;
+        if self.day != Some(day) {
+            info!("SD card log now {}", name);
+            self.day = Some(day);
+        }
+        Ok(())
+    }
+}
+
+fn day_of(at: Timestamp) -> u64 {
+    at.as_secs() / DAY.as_secs()
+}
+
+impl<D: LogDirectory> ReadingSink for SdCardSink<D> {
+    /// Never fails: a missing or full card only costs the log
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        match self.write(reading) {
+            Ok(()) => {
+                if self.dropped > 0 {
+                    info!(
+                        "SD card writable again, {} readings were not logged",
+                        self.dropped
+                    );
+                    self.dropped = 0;
+                }
+            }
+            Err(e) => {
+                if self.dropped == 0 {
+                    warn!("SD card write failed, logging suspended: {:?}", e);
+                }
+                self.dropped = self.dropped.saturating_add(1);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Most files FATFS keeps open at once; the sink only ever needs one
+#[cfg(target_os = "espidf")]
+const SD_MAX_OPEN_FILES: usize = 4;
+
+/// Mount an SD card's FAT filesystem at `mount_point` (e.g. `/sdcard`) for
+/// an [`FsLogDirectory`] there. `fatfs` wraps the card driver, for SPI wiring
+/// an `SdCardDriver::new_spi`. The card stays mounted until the returned
+/// handle is dropped.
+#[cfg(target_os = "espidf")]
+pub fn mount_sd_card<T>(
+    fatfs: esp_idf_svc::fs::fatfs::Fatfs<T>,
+    mount_point: &str,
+) -> Result<esp_idf_svc::io::vfs::MountedFatfs<T>> {
+    Ok(esp_idf_svc::io::vfs::MountedFatfs::mount(
+        fatfs,
+        mount_point,
+        SD_MAX_OPEN_FILES,
+    )?)
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{LogFormat, MemoryLogDirectory, SdCardSink};
+    use crate::codec::{Codec, JsonCodec};
+    use crate::reading::Reading;
+    use crate::sink::ReadingSink;
+    use crate::timestamp::Timestamp;
+    use std::time::Duration;
+
+    fn at(day: u64, hour: u64) -> Duration {
+        Duration::from_secs(day * 86_400 + hour * 3600)
+    }
+
+    #[test]
+    fn rotates_to_a_new_file_each_day() {
+        let mut sink = SdCardSink::new(MemoryLogDirectory::new(), LogFormat::Csv);
+        sink.emit(&Reading::new(at(3, 22), 2100, 50)).unwrap();
+        sink.emit(&Reading::new(at(3, 23), 2200, 45).with_pump_on(true))
+            .unwrap();
+        sink.emit(&Reading::new(at(4, 0), 2300, 40)).unwrap();
+
+        let dir = sink.dir();
+        assert_eq!(dir.names(), vec!["d00003.csv", "d00004.csv"]);
+        assert_eq!(
+            dir.file("d00003.csv").unwrap(),
+            "timestamp,raw,moisture,pump,fault\n\
+             338400,2100,50,0,0\n\
+             342000,2200,45,1,0\n"
+        );
+        assert_eq!(
+            dir.file("d00004.csv").unwrap(),
+            "timestamp,raw,moisture,pump,fault\n345600,2300,40,0,0\n"
+        );
+
+        // After a reboot the day's file is continued without a second header
+        let mut rebooted = SdCardSink::new(sink.dir, LogFormat::Csv);
+        rebooted.emit(&Reading::new(at(4, 1), 2310, 40)).unwrap();
+        assert_eq!(
+            rebooted.dir().file("d00004.csv").unwrap().lines().count(),
+            3
+        );
+    }
+
+    #[test]
+    fn rotates_on_the_emitted_time() {
+        let mut sink = SdCardSink::new(MemoryLogDirectory::new(), LogFormat::Csv);
+        // Ten minutes after boot, but past midnight on the anchored clock
+        let reading = Reading::new(Duration::from_secs(600), 2100, 50)
+            .with_emitted_at(Timestamp::seconds(at(19_700, 0).as_secs()));
+        sink.emit(&reading).unwrap();
+        assert_eq!(sink.dir().names(), vec!["d19700.csv"]);
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let mut sink = SdCardSink::new(MemoryLogDirectory::new(), LogFormat::JsonLines);
+        let reading = Reading::new(at(0, 1), 2100, 50).with_ec(180);
+        sink.emit(&reading).unwrap();
+        let file = sink.dir().file("d00000.jsn").unwrap();
+        let line = file.strip_suffix('\n').unwrap();
+        assert_eq!(JsonCodec.decode(line.as_bytes()).unwrap(), reading);
+    }
+
+    #[test]
+    fn missing_or_full_card_drops_readings_and_continues() {
+        let mut sink = SdCardSink::new(MemoryLogDirectory::new(), LogFormat::Csv);
+        sink.dir_mut().fail_writes = true;
+        for hour in 0..3 {
+            assert!(sink.emit(&Reading::new(at(1, hour), 2100, 50)).is_ok());
+        }
+        assert_eq!(sink.dropped(), 3);
+        assert!(sink.dir().names().is_empty());
+
+        // Card reinserted
+        sink.dir_mut().fail_writes = false;
+        sink.emit(&Reading::new(at(1, 3), 2100, 50)).unwrap();
+        assert_eq!(sink.dropped(), 0);
+        assert_eq!(sink.dir().file("d00001.csv").unwrap().lines().count(), 2);
+    }
+}
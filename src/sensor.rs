@@ -0,0 +1,777 @@
+//! Simulated soil moisture sensor used by the reference application and tests.
+
+use crate::clock::{Clock, SystemClock};
+use crate::history::HistoryEntry;
+use anyhow::{anyhow, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// Slow probe degradation applied on top of the simulated soil condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgingProfile {
+    /// Raw counts the baseline shifts per simulated day (positive = reads drier)
+    pub drift_per_day: i32,
+    /// Extra peak noise amplitude (raw counts) gained per simulated day
+    pub noise_growth_per_day: u32,
+}
+
+impl AgingProfile {
+    /// Baseline shift after `age` of simulated time
+    fn drift_at(&self, age: Duration) -> i64 {
+        self.drift_per_day as i64 * age.as_millis() as i64 / MS_PER_DAY as i64
+    }
+
+    /// Peak noise amplitude after `age` of simulated time
+    fn noise_amplitude_at(&self, age: Duration) -> u64 {
+        self.noise_growth_per_day as u64 * age.as_millis() as u64 / MS_PER_DAY
+    }
+}
+
+struct Aging {
+    profile: AgingProfile,
+    installed_at: Duration,
+}
+
+impl Aging {
+    /// Combined drift and noise offset at simulated time `now`
+    fn offset(&self, now: Duration) -> i64 {
+        let age = now.saturating_sub(self.installed_at);
+        let amplitude = self.profile.noise_amplitude_at(age);
+
+        // Deterministic pseudo-noise derived from the simulated timestamp
+        let noise = if amplitude == 0 {
+            0
+        } else {
+            let mut hasher = DefaultHasher::new();
+            now.as_millis().hash(&mut hasher);
+            (hasher.finish() % (2 * amplitude + 1)) as i64 - amplitude as i64
+        };
+
+        self.profile.drift_at(age) + noise
+    }
+}
+
+/// Simple soil water balance driven by the simulated pump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoilDynamics {
+    /// Raw counts the reading rises (dries out) per simulated hour
+    pub dry_down_per_hour: u32,
+    /// Raw counts per second of pumping once the water has reached the probe
+    pub wetting_per_sec: u32,
+    /// Time constant of the delay between pumping and the probe seeing the water;
+    /// zero registers it instantly
+    pub lag: Duration,
+}
+
+struct Dynamics {
+    config: SoilDynamics,
+    pump_on: bool,
+    /// Simulated time integrated up to
+    sim_at: Duration,
+    /// Water pumped but not yet seen by the probe, in milli-counts
+    in_transit: i64,
+    /// Net wetting applied to the baseline so far, in milli-counts
+    wetness: i64,
+}
+
+impl Dynamics {
+    /// Advance the water balance to `now` in whole-second steps
+    fn integrate(&mut self, now: Duration) {
+        let dry_per_sec = self.config.dry_down_per_hour as i64 * 1000 / 3600;
+        let lag_ms = self.config.lag.as_millis() as i64;
+        while now.saturating_sub(self.sim_at) >= Duration::from_secs(1) {
+            if self.pump_on {
+                self.in_transit += self.config.wetting_per_sec as i64 * 1000;
+            }
+            // First-order lag: a 1/tau share of the water in transit arrives each second
+            let arrived = if lag_ms <= 1000 {
+                self.in_transit
+            } else {
+                self.in_transit * 1000 / lag_ms
+            };
+            self.in_transit -= arrived;
+            self.wetness += arrived - dry_per_sec;
+            self.sim_at += Duration::from_secs(1);
+        }
+    }
+}
+
+/// Two-layer water balance: pumped water lands in the surface layer, which
+/// loses it quickly to evaporation and seepage into the deep layer, which
+/// dries slowly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoilLayers {
+    /// Raw counts the surface layer rises (dries out) per simulated hour
+    pub surface_dry_per_hour: u32,
+    /// Raw counts the deep layer rises per simulated hour
+    pub deep_dry_per_hour: u32,
+    /// Raw counts per second of pumping added to the surface layer
+    pub wetting_per_sec: u32,
+    /// Time constant of seepage evening out the two layers; zero disables it
+    pub percolation: Duration,
+}
+
+/// Layer a layered mock probe reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProbeDepth {
+    #[default]
+    Surface,
+    Deep,
+}
+
+struct Layers {
+    config: SoilLayers,
+    depth: ProbeDepth,
+    pump_on: bool,
+    sim_at: Duration,
+    /// Wetting of each layer, in milli-counts
+    surface: i64,
+    deep: i64,
+}
+
+impl Layers {
+    /// Advance both layers to `now` in whole-second steps
+    fn integrate(&mut self, now: Duration) {
+        let surface_dry = self.config.surface_dry_per_hour as i64 * 1000 / 3600;
+        let deep_dry = self.config.deep_dry_per_hour as i64 * 1000 / 3600;
+        let tau_ms = self.config.percolation.as_millis() as i64;
+        while now.saturating_sub(self.sim_at) >= Duration::from_secs(1) {
+            if self.pump_on {
+                self.surface += self.config.wetting_per_sec as i64 * 1000;
+            }
+            // Only a wetter surface seeps down; a dry crust does not pull water up
+            if tau_ms > 0 && self.surface > self.deep {
+                // Half the share each way, so the difference decays with tau
+                let flow = (self.surface - self.deep) * 1000 / tau_ms.max(1000) / 2;
+                self.surface -= flow;
+                self.deep += flow;
+            }
+            self.surface -= surface_dry;
+            self.deep -= deep_dry;
+            self.sim_at += Duration::from_secs(1);
+        }
+    }
+
+    fn wetness(&self) -> i64 {
+        match self.depth {
+            ProbeDepth::Surface => self.surface,
+            ProbeDepth::Deep => self.deep,
+        }
+    }
+}
+
+/// Source of raw soil moisture readings
+pub trait SoilSensor {
+    /// Read the raw ADC value averaged over `samples` conversions
+    fn read_averaged(&mut self, samples: usize) -> Result<u16>;
+
+    /// Like [`read_averaged`](Self::read_averaged), plus the standard
+    /// deviation of the conversions when the source can report it
+    fn read_with_spread(&mut self, samples: usize) -> Result<(u16, Option<u16>)> {
+        Ok((self.read_averaged(samples)?, None))
+    }
+}
+
+/// Rounded mean and standard deviation of raw conversions, for sources
+/// implementing [`SoilSensor::read_with_spread`]; `None` without samples
+pub fn sample_spread(samples: &[u16]) -> Option<(u16, u16)> {
+    if samples.is_empty() {
+        return None;
+    }
+    let n = samples.len() as u64;
+    let sum: u64 = samples.iter().map(|&s| s as u64).sum();
+    let mean = (sum + n / 2) / n;
+    let variance = samples
+        .iter()
+        .map(|&s| (s as i64 - mean as i64).pow(2) as u64)
+        .sum::<u64>()
+        / n;
+    Some((mean as u16, isqrt(variance) as u16))
+}
+
+/// Integer square root, rounded down
+pub(crate) fn isqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+    let mut x = value;
+    let mut y = x.div_ceil(2);
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
+
+/// Simulated soil moisture sensor for demonstration
+///
+/// All time-dependent behavior is taken from `clock`, so tests can use a
+/// [`MockClock`](crate::clock::MockClock) to step through simulated time.
+pub struct MockSoilSensor<C: Clock = SystemClock> {
+    // Simulate sensor drift over time
+    base_value: u16,
+    clock: C,
+    last_reading: Duration,
+    aging: Option<Aging>,
+    dynamics: Option<Dynamics>,
+    layers: Option<Layers>,
+}
+
+impl MockSoilSensor {
+    pub fn new() -> Self {
+        Self::with_clock(SystemClock::new())
+    }
+}
+
+impl<C: Clock> MockSoilSensor<C> {
+    /// Simulated sensor driven by `clock`
+    pub fn with_clock(clock: C) -> Self {
+        let last_reading = clock.now();
+        Self {
+            base_value: 2400, // Simulated sensor baseline
+            clock,
+            last_reading,
+            aging: None,
+            dynamics: None,
+            layers: None,
+        }
+    }
+
+    /// Age the simulated probe according to `profile`, starting now
+    pub fn with_aging(mut self, profile: AgingProfile) -> Self {
+        self.aging = Some(Aging {
+            profile,
+            installed_at: self.clock.now(),
+        });
+        self
+    }
+
+    /// Let the simulated soil dry out over time and respond to [`set_pump`](Self::set_pump)
+    pub fn with_dynamics(mut self, config: SoilDynamics) -> Self {
+        self.dynamics = Some(Dynamics {
+            config,
+            pump_on: false,
+            sim_at: self.clock.now(),
+            in_transit: 0,
+            wetness: 0,
+        });
+        self
+    }
+
+    /// Model the soil as a fast-drying surface over a slow-drying deep
+    /// layer, with the probe reading the layer at `depth`
+    pub fn with_layers(mut self, config: SoilLayers, depth: ProbeDepth) -> Self {
+        self.layers = Some(Layers {
+            config,
+            depth,
+            pump_on: false,
+            sim_at: self.clock.now(),
+            surface: 0,
+            deep: 0,
+        });
+        self
+    }
+
+    /// Move the probe to another layer; has no effect without [`with_layers`](Self::with_layers)
+    pub fn set_probe_depth(&mut self, depth: ProbeDepth) {
+        if let Some(layers) = &mut self.layers {
+            layers.depth = depth;
+        }
+    }
+
+    /// Switch the simulated pump; has no effect without
+    /// [`with_dynamics`](Self::with_dynamics) or [`with_layers`](Self::with_layers)
+    pub fn set_pump(&mut self, on: bool) {
+        let now = self.clock.now();
+        if let Some(dynamics) = &mut self.dynamics {
+            dynamics.integrate(now);
+            dynamics.pump_on = on;
+        }
+        if let Some(layers) = &mut self.layers {
+            layers.integrate(now);
+            layers.pump_on = on;
+        }
+    }
+
+    /// Simulate different soil conditions
+    pub fn set_soil_condition(&mut self, condition: &str) {
+        self.base_value = match condition {
+            "dry" => 2800,     // Dry soil simulation
+            "optimal" => 2000, // Optimal moisture
+            "wet" => 1400,     // Wet soil simulation
+            _ => 2400,         // Default
+        };
+    }
+}
+
+impl<C: Clock> SoilSensor for MockSoilSensor<C> {
+    /// Simulate reading from ADC with realistic sensor behavior
+    fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+        // Simulate time-based sensor variations
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_reading).as_secs();
+
+        // Add some realistic noise and drift
+        let noise = (elapsed as u16 % 200).wrapping_sub(100); // +/-100 noise
+        let mut reading = self.base_value.wrapping_add(noise);
+
+        if let Some(aging) = &self.aging {
+            reading = (reading as i64 + aging.offset(now)).clamp(0, u16::MAX as i64) as u16;
+        }
+
+        if let Some(dynamics) = &mut self.dynamics {
+            dynamics.integrate(now);
+            let wetness = dynamics.wetness / 1000;
+            reading = (reading as i64 - wetness).clamp(0, u16::MAX as i64) as u16;
+        }
+
+        if let Some(layers) = &mut self.layers {
+            layers.integrate(now);
+            let wetness = layers.wetness() / 1000;
+            reading = (reading as i64 - wetness).clamp(0, u16::MAX as i64) as u16;
+        }
+
+        self.last_reading = now;
+        Ok(reading)
+    }
+}
+
+impl Default for MockSoilSensor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Shape of a [`WaveformSource`] curve over one period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    /// `offset + amplitude * sin(2 pi t / period)`; with `integer-only` the
+    /// sine is Bhaskara's rational approximation, within 0.2% of `amplitude`
+    Sine,
+    /// Rises linearly from `offset - amplitude` to `offset + amplitude`, then jumps back
+    Sawtooth,
+    /// Grows without repeating by `amplitude` every `period`
+    Ramp,
+}
+
+/// Noise-free readings from an analytic function of time, for predictable
+/// demo curves and tests
+pub struct WaveformSource<C> {
+    waveform: Waveform,
+    amplitude: u16,
+    period: Duration,
+    offset: u16,
+    clock: C,
+    start: Duration,
+}
+
+impl<C: Clock> WaveformSource<C> {
+    /// Time zero of the curve is now; a zero `period` is treated as 1 ms
+    pub fn new(
+        waveform: Waveform,
+        amplitude: u16,
+        period: Duration,
+        offset: u16,
+        clock: C,
+    ) -> Self {
+        let start = clock.now();
+        Self {
+            waveform,
+            amplitude,
+            period: period.max(Duration::from_millis(1)),
+            offset,
+            clock,
+            start,
+        }
+    }
+
+    /// Raw value at `t` after time zero, clamped to the `u16` range
+    pub fn value_at(&self, t: Duration) -> u16 {
+        let t_ms = t.as_millis() as i64;
+        let period_ms = self.period.as_millis() as i64;
+        let amplitude = self.amplitude as i64;
+        let delta = match self.waveform {
+            #[cfg(not(feature = "integer-only"))]
+            Waveform::Sine => {
+                let phase = (t_ms % period_ms) as f64 / period_ms as f64;
+                (amplitude as f64 * (phase * std::f64::consts::TAU).sin()).round() as i64
+            }
+            #[cfg(feature = "integer-only")]
+            Waveform::Sine => integer_sine(amplitude, t_ms % period_ms, period_ms),
+            Waveform::Sawtooth => -amplitude + 2 * amplitude * (t_ms % period_ms) / period_ms,
+            Waveform::Ramp => amplitude * t_ms / period_ms,
+        };
+        (self.offset as i64 + delta).clamp(0, u16::MAX as i64) as u16
+    }
+}
+
+/// `amplitude * sin(2 pi at / period)` for `at` in `[0, period)`, rounded,
+/// by Bhaskara's `sin(pi h) ~= 16h(1-h) / (5 - 4h(1-h))` on each half period
+#[cfg(feature = "integer-only")]
+fn integer_sine(amplitude: i64, at: i64, period: i64) -> i64 {
+    let (twice, period) = (2 * at as i128, period as i128);
+    let (sign, along) = if twice < period {
+        (1, twice)
+    } else {
+        (-1, twice - period)
+    };
+    let product = along * (period - along);
+    let numerator = 16 * amplitude as i128 * product;
+    let denominator = 5 * period * period - 4 * product;
+    sign * ((numerator + denominator / 2) / denominator) as i64
+}
+
+impl<C: Clock> SoilSensor for WaveformSource<C> {
+    fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+        Ok(self.value_at(self.clock.now().saturating_sub(self.start)))
+    }
+}
+
+/// Serves a recorded dataset as if it were happening now, so a demo runs
+/// the whole live pipeline on real soil behaviour. Recorded time is paced
+/// by the clock times `speed`; after the last entry the recording loops,
+/// one recorded interval later.
+pub struct ReplaySensor<C> {
+    /// Raw values keyed by their offset from the first entry, in ms
+    recording: Vec<(u64, u16)>,
+    /// Recorded ms after which playback starts over
+    loop_ms: u64,
+    speed: u32,
+    clock: C,
+    start: Duration,
+}
+
+impl<C: Clock> ReplaySensor<C> {
+    /// Playback starts now at real-time speed; entries are sorted by timestamp
+    pub fn new(recording: impl IntoIterator<Item = HistoryEntry>, clock: C) -> Self {
+        let mut entries: Vec<HistoryEntry> = recording.into_iter().collect();
+        entries.sort_by_key(|e| e.timestamp_s);
+        let first = entries.first().map_or(0, |e| e.timestamp_s);
+        let recording: Vec<(u64, u16)> = entries
+            .iter()
+            .map(|e| ((e.timestamp_s - first) as u64 * 1000, e.raw))
+            .collect();
+        let last_gap = match recording.as_slice() {
+            [.., (before, _), (last, _)] => last - before,
+            _ => 0,
+        };
+        let loop_ms = recording.last().map_or(0, |(last, _)| *last) + last_gap.max(1);
+        let start = clock.now();
+        Self {
+            recording,
+            loop_ms,
+            speed: 1,
+            clock,
+            start,
+        }
+    }
+
+    /// Play `speed` recorded seconds per clock second; zero is treated as 1
+    pub fn with_speed(mut self, speed: u32) -> Self {
+        self.speed = speed.max(1);
+        self
+    }
+
+    /// Recorded value current `elapsed` after playback started
+    pub fn value_at(&self, elapsed: Duration) -> Option<u16> {
+        let at = elapsed.as_millis() as u64 * self.speed as u64 % self.loop_ms;
+        let next = self.recording.partition_point(|(offset, _)| *offset <= at);
+        next.checked_sub(1).map(|i| self.recording[i].1)
+    }
+}
+
+impl<C: Clock> SoilSensor for ReplaySensor<C> {
+    fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+        self.value_at(self.clock.now().saturating_sub(self.start))
+            .ok_or_else(|| anyhow!("replay recording is empty"))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        sample_spread, AgingProfile, MockSoilSensor, ProbeDepth, ReplaySensor, SoilDynamics,
+        SoilLayers, SoilSensor, Waveform, WaveformSource,
+    };
+    use crate::clock::MockClock;
+    use crate::drift::{DriftDetector, DriftStatus};
+    use crate::history::HistoryEntry;
+    use crate::moisture::{raw_to_moisture_percent, Calibration, MOISTURE_HIGH};
+    use crate::pump::{PumpAction, PumpConfig, PumpController};
+    use std::time::Duration;
+
+    fn recording() -> Vec<HistoryEntry> {
+        // Watered between the second and third minute
+        [(1000, 2800), (1060, 2790), (1120, 1900), (1180, 2000)]
+            .into_iter()
+            .map(|(timestamp_s, raw)| HistoryEntry {
+                timestamp_s,
+                raw,
+                moisture_percent: 0,
+                temperature_tenths_c: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_serves_recording_in_order_and_loops() {
+        let clock = MockClock::new();
+        clock.set(Duration::from_secs(500));
+        let mut replay = ReplaySensor::new(recording(), clock.clone());
+        let mut served = Vec::new();
+        for _ in 0..10 {
+            served.push(replay.read_averaged(1).unwrap());
+            clock.advance(Duration::from_secs(30));
+        }
+        assert_eq!(
+            served,
+            vec![2800, 2800, 2790, 2790, 1900, 1900, 2000, 2000, 2800, 2800]
+        );
+    }
+
+    #[test]
+    fn replay_speed_multiplies_recorded_time() {
+        let clock = MockClock::new();
+        let mut replay = ReplaySensor::new(recording(), clock.clone()).with_speed(60);
+        let mut served = Vec::new();
+        for _ in 0..6 {
+            served.push(replay.read_averaged(1).unwrap());
+            clock.advance(Duration::from_secs(1));
+        }
+        assert_eq!(served, vec![2800, 2790, 1900, 2000, 2800, 2790]);
+
+        let mut empty = ReplaySensor::new(Vec::new(), clock);
+        assert!(empty.read_averaged(1).is_err());
+    }
+
+    #[test]
+    fn spread_is_standard_deviation_of_conversions() {
+        assert_eq!(sample_spread(&[]), None);
+        assert_eq!(sample_spread(&[2000; 8]), Some((2000, 0)));
+        assert_eq!(sample_spread(&[1990, 2010, 1990, 2010]), Some((2000, 10)));
+        assert_eq!(sample_spread(&[1900, 2100, 1900, 2100]), Some((2000, 100)));
+    }
+
+    #[test]
+    fn aging_shifts_baseline_over_simulated_days() {
+        let clock = MockClock::new();
+        let profile = AgingProfile {
+            drift_per_day: 10,
+            noise_growth_per_day: 0,
+        };
+        let mut aged = MockSoilSensor::with_clock(clock.clone()).with_aging(profile);
+        let mut fresh = MockSoilSensor::with_clock(clock.clone());
+        aged.set_soil_condition("optimal");
+        fresh.set_soil_condition("optimal");
+
+        assert_eq!(
+            aged.read_averaged(5).unwrap(),
+            fresh.read_averaged(5).unwrap()
+        );
+        clock.advance(Duration::from_secs(30 * 24 * 60 * 60));
+        assert_eq!(
+            aged.read_averaged(5).unwrap(),
+            fresh.read_averaged(5).unwrap() + 300
+        );
+    }
+
+    #[test]
+    fn drift_detector_recommends_recalibration_after_weeks_of_aging() {
+        let clock = MockClock::new();
+        let profile = AgingProfile {
+            drift_per_day: 15,
+            noise_growth_per_day: 4,
+        };
+        let mut sensor = MockSoilSensor::with_clock(clock.clone()).with_aging(profile);
+        sensor.set_soil_condition("optimal");
+
+        let reference = sensor.read_averaged(5).unwrap();
+        let mut detector = DriftDetector::new(reference, 150, 24);
+
+        // Hourly readings across six simulated weeks
+        let mut recommended_on_day = None;
+        for hour in 1..=6 * 7 * 24 {
+            clock.advance(Duration::from_secs(60 * 60));
+            let status = detector.update(sensor.read_averaged(5).unwrap());
+            if matches!(status, DriftStatus::RecalibrationRecommended { .. }) {
+                recommended_on_day = Some(hour / 24);
+                break;
+            }
+        }
+
+        let day = recommended_on_day.expect("drift detector never recommended recalibration");
+        assert!(day >= 7, "recommended too early (day {day})");
+    }
+
+    #[test]
+    fn noise_follows_advanced_clock() {
+        let clock = MockClock::new();
+        let mut sensor = MockSoilSensor::with_clock(clock.clone());
+        sensor.set_soil_condition("optimal");
+
+        // No time between reads: the noise term sits at its -100 floor
+        assert_eq!(sensor.read_averaged(5).unwrap(), 1900);
+
+        // 50s since the last read lands halfway through the noise cycle
+        clock.advance(Duration::from_secs(50));
+        assert_eq!(sensor.read_averaged(5).unwrap(), 1950);
+
+        clock.advance(Duration::from_secs(150));
+        assert_eq!(sensor.read_averaged(5).unwrap(), 2050);
+    }
+
+    /// Surface then deep reading at the same instant, so they share the noise term
+    fn read_both(sensor: &mut MockSoilSensor<MockClock>) -> (u16, u16) {
+        sensor.set_probe_depth(ProbeDepth::Surface);
+        let surface = sensor.read_averaged(5).unwrap();
+        sensor.set_probe_depth(ProbeDepth::Deep);
+        (surface, sensor.read_averaged(5).unwrap())
+    }
+
+    #[test]
+    fn surface_layer_dries_faster_than_deep_after_watering() {
+        let clock = MockClock::new();
+        let mut sensor = MockSoilSensor::with_clock(clock.clone()).with_layers(
+            SoilLayers {
+                surface_dry_per_hour: 600,
+                deep_dry_per_hour: 60,
+                wetting_per_sec: 20,
+                percolation: Duration::from_secs(600),
+            },
+            ProbeDepth::Surface,
+        );
+        sensor.set_soil_condition("dry");
+        let (dry_surface, dry_deep) = read_both(&mut sensor);
+        assert_eq!(dry_surface, dry_deep);
+
+        sensor.set_pump(true);
+        clock.advance(Duration::from_secs(60));
+        sensor.set_pump(false);
+        let (surface, deep) = read_both(&mut sensor);
+        assert!(
+            surface < deep,
+            "surface {surface} should be wetter than deep {deep}"
+        );
+
+        // Let seepage even the layers out, then watch each dry for two hours
+        clock.advance(Duration::from_secs(30 * 60));
+        let (surface_before, deep_before) = read_both(&mut sensor);
+        clock.advance(Duration::from_secs(2 * 60 * 60));
+        let (surface_after, deep_after) = read_both(&mut sensor);
+        let surface_dried = surface_after as i32 - surface_before as i32;
+        let deep_dried = deep_after as i32 - deep_before as i32;
+        assert!(
+            surface_dried > 4 * deep_dried,
+            "surface dried {surface_dried}, deep {deep_dried}"
+        );
+        assert!(surface_after > deep_after, "deep layer stays wetter");
+    }
+
+    /// Run the controller against a lagging dry probe for ten minutes and
+    /// return the peak moisture it produced
+    fn peak_moisture_with_lag(config: PumpConfig) -> u8 {
+        let clock = MockClock::new();
+        let mut sensor = MockSoilSensor::with_clock(clock.clone()).with_dynamics(SoilDynamics {
+            dry_down_per_hour: 0,
+            wetting_per_sec: 20,
+            lag: Duration::from_secs(30),
+        });
+        sensor.set_soil_condition("dry");
+        let mut pump = PumpController::new(config, clock.clone());
+        let cal = Calibration::default();
+
+        let mut peak = 0;
+        for _ in 0..600 {
+            clock.advance(Duration::from_secs(1));
+            let moisture = raw_to_moisture_percent(sensor.read_averaged(5).unwrap(), &cal);
+            peak = peak.max(moisture);
+            match pump.update(moisture) {
+                Some(PumpAction::Activate) => sensor.set_pump(true),
+                Some(PumpAction::Deactivate) => sensor.set_pump(false),
+                None => {}
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn lag_makes_naive_controller_overwater() {
+        // Runs until the probe reads wet enough, by which time more water is still on its way
+        let naive = PumpConfig {
+            start_below: 25,
+            stop_at: 50,
+            min_run: Duration::ZERO,
+            max_run: Duration::from_secs(120),
+            cooldown: Duration::from_secs(10),
+        };
+        let peak = peak_moisture_with_lag(naive);
+        assert!(peak > MOISTURE_HIGH, "naive controller peaked at {peak}%");
+
+        // A fixed dose followed by a soak long enough for the probe to catch up
+        let dosed = PumpConfig {
+            min_run: Duration::from_secs(25),
+            max_run: Duration::from_secs(25),
+            cooldown: Duration::from_secs(180),
+            ..naive
+        };
+        let peak = peak_moisture_with_lag(dosed);
+        assert!(
+            (naive.start_below..=MOISTURE_HIGH).contains(&peak),
+            "dosed controller peaked at {peak}%"
+        );
+    }
+
+    fn sample(waveform: Waveform, at_secs: &[u64]) -> Vec<u16> {
+        let clock = MockClock::new();
+        clock.advance(Duration::from_secs(5)); // curve starts at construction
+        let mut source =
+            WaveformSource::new(waveform, 400, Duration::from_secs(100), 2000, clock.clone());
+        at_secs
+            .iter()
+            .map(|&t| {
+                clock.set(Duration::from_secs(5 + t));
+                source.read_averaged(1).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sine_hits_peaks_at_quarter_periods() {
+        assert_eq!(
+            sample(Waveform::Sine, &[0, 25, 50, 75, 100, 125]),
+            vec![2000, 2400, 2000, 1600, 2000, 2400]
+        );
+        // sin(36 degrees) = sin(144 degrees) ~= 0.588
+        assert_eq!(sample(Waveform::Sine, &[10, 40]), vec![2235, 2235]);
+    }
+
+    #[test]
+    fn sawtooth_rises_then_resets() {
+        assert_eq!(
+            sample(Waveform::Sawtooth, &[0, 25, 50, 99, 100, 150]),
+            vec![1600, 1800, 2000, 2392, 1600, 2000]
+        );
+    }
+
+    #[test]
+    fn ramp_grows_linearly_and_clamps() {
+        assert_eq!(
+            sample(Waveform::Ramp, &[0, 50, 100, 250]),
+            vec![2000, 2200, 2400, 3000]
+        );
+        let clock = MockClock::new();
+        let flat = WaveformSource::new(Waveform::Ramp, 0, Duration::ZERO, 10, clock);
+        assert_eq!(flat.value_at(Duration::from_secs(1)), 10);
+        let big = WaveformSource::new(
+            Waveform::Ramp,
+            u16::MAX,
+            Duration::from_secs(1),
+            1,
+            MockClock::new(),
+        );
+        assert_eq!(big.value_at(Duration::from_secs(2)), u16::MAX);
+    }
+}
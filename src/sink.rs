@@ -0,0 +1,621 @@
+//! Destinations for processed readings.
+
+use crate::alert::Tenths;
+use crate::codec::Codec;
+use crate::moisture::{ConditionTracker, SoilCondition};
+use crate::reading::{Annotation, Reading};
+use crate::storage::FlashStore;
+use anyhow::{bail, ensure, Result};
+use log::info;
+use std::fmt::Write;
+
+/// Consumer of processed readings (console, flash, network, ...)
+pub trait ReadingSink {
+    fn emit(&mut self, reading: &Reading) -> Result<()>;
+
+    /// Record a manual note; sinks without a place for notes ignore it
+    fn annotate(&mut self, _annotation: &Annotation) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// When [`ConsoleSink`] may skip a row that would repeat the last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coalescing {
+    /// Moisture difference (percentage points) from the last printed row
+    /// still treated as the same row
+    pub tolerance: u8,
+    /// Skipped rows between "still ..." summary lines
+    pub summary_every: u32,
+}
+
+impl Default for Coalescing {
+    fn default() -> Self {
+        Self {
+            tolerance: 1,
+            summary_every: 120,
+        }
+    }
+}
+
+/// Logs readings as rows of the serial console table
+#[derive(Debug, Default)]
+pub struct ConsoleSink {
+    condition: ConditionTracker,
+    coalescing: Option<Coalescing>,
+    /// Condition and moisture of the last printed row
+    last_row: Option<(SoilCondition, u8)>,
+    /// Rows skipped since then
+    skipped: u32,
+    /// Last formatted row and the raw value, moisture and condition it shows,
+    /// reused while they repeat
+    formatted: Option<((u16, u8, SoilCondition), String)>,
+    /// Rows served from `formatted`
+    reused: u32,
+}
+
+impl ConsoleSink {
+    /// Skip rows matching the last printed one, with a periodic summary instead
+    pub fn with_coalescing(mut self, coalescing: Coalescing) -> Self {
+        self.coalescing = Some(coalescing);
+        self
+    }
+
+    /// Rows reused from the previous one rather than formatted again
+    pub fn reused_rows(&self) -> u32 {
+        self.reused
+    }
+
+    /// Print the table header
+    pub fn header(&self) {
+        info!("Raw Value | Moisture % | Status");
+        info!("----------|------------|--------");
+    }
+
+    /// Line to log for `reading`, or `None` while coalescing a run
+    fn row(&mut self, reading: &Reading) -> Option<String> {
+        let condition = self.condition.update(reading.moisture_percent);
+        let moisture = reading.moisture_percent;
+        if let (Some(coalescing), Some((last_condition, last_moisture))) =
+            (self.coalescing, self.last_row)
+        {
+            let same = last_condition == condition
+                && last_moisture.abs_diff(moisture) <= coalescing.tolerance
+                && reading.boot_reason.is_none();
+            if same {
+                self.skipped += 1;
+                let every = coalescing.summary_every.max(1);
+                return (self.skipped % every == 0)
+                    .then(|| format!("still {} ({} readings)", condition.label(), self.skipped));
+            }
+        }
+        self.last_row = Some((condition, moisture));
+        self.skipped = 0;
+        let key = (reading.raw, moisture, condition);
+        match &self.formatted {
+            Some((last, line)) if *last == key => {
+                self.reused = self.reused.saturating_add(1);
+                Some(line.clone())
+            }
+            _ => {
+                let led_status = if condition.led_on() { "ON" } else { "OFF" };
+                let line = format!(
+                    "{:9} | {:8}% | {} (LED: {})",
+                    reading.raw,
+                    moisture,
+                    condition.label(),
+                    led_status
+                );
+                self.formatted = Some((key, line.clone()));
+                Some(line)
+            }
+        }
+    }
+}
+
+impl ReadingSink for ConsoleSink {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        if let Some(line) = self.row(reading) {
+            info!("{}", line);
+        }
+        if let Some(reason) = reading.boot_reason {
+            info!("     -> Boot reason: {}", reason);
+        }
+        Ok(())
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        info!("     -> Note: {}", annotation.text());
+        Ok(())
+    }
+}
+
+/// Logs each reading as one logfmt line (`key=value ...`) for Loki and
+/// similar aggregators
+#[derive(Debug, Default)]
+pub struct LogfmtSink {
+    condition: ConditionTracker,
+}
+
+impl ReadingSink for LogfmtSink {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        let condition = self.condition.update(reading.moisture_percent);
+        info!("{}", render_logfmt(reading, condition));
+        Ok(())
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        info!(
+            "ts={} note={}",
+            annotation.emitted_at,
+            logfmt_value(annotation.text())
+        );
+        Ok(())
+    }
+}
+
+/// Render `reading` as logfmt; optional fields are only present when set
+pub fn render_logfmt(reading: &Reading, condition: SoilCondition) -> String {
+    let on_off = |on: bool| if on { "on" } else { "off" };
+
+    let mut out = String::new();
+    // Writing to a String cannot fail
+    let _ = write!(
+        out,
+        "ts={} raw={} moisture={} status={} pump={}",
+        reading.emitted_at,
+        reading.raw,
+        reading.moisture_percent,
+        logfmt_value(condition.label()),
+        on_off(reading.pump_on())
+    );
+    if let Some(ec) = reading.ec_us_cm {
+        let _ = write!(out, " ec_us_cm={ec}");
+    }
+    if let Some(fc) = reading.field_capacity_percent {
+        let _ = write!(out, " fc_percent={fc}");
+    }
+    if let Some(depth) = reading.depth_cm {
+        let _ = write!(out, " depth_cm={depth}");
+    }
+    if let Some(u) = reading.uncertainty_tenths {
+        let _ = write!(
+            out,
+            " uncertainty_percent={}",
+            Tenths(u.min(i16::MAX as u16) as i16)
+        );
+    }
+    if let Some(t) = reading.temperature_tenths_c {
+        let _ = write!(out, " temp_c={}", Tenths(t));
+    }
+    if let Some(mv) = reading.supply_mv {
+        let _ = write!(out, " supply_mv={mv}");
+    }
+    for (key, set) in [
+        ("paused", reading.control_paused()),
+        ("fault", reading.fault()),
+        ("supply_sag", reading.supply_sag()),
+        ("safe_mode", reading.safe_mode()),
+        ("low_battery", reading.low_battery()),
+    ] {
+        if set {
+            let _ = write!(out, " {key}=true");
+        }
+    }
+    if let Some(cause) = reading.rewet {
+        let _ = write!(out, " rewet={}", cause.kind());
+    }
+    if let Some(reason) = reading.boot_reason {
+        let _ = write!(out, " boot_reason={}", logfmt_value(&reason.to_string()));
+    }
+    out
+}
+
+/// Quote values that would otherwise split the line or be misparsed
+fn logfmt_value(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '=' || c == '"' || c == '\\');
+    if !needs_quotes {
+        return value.to_string();
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Collects readings in memory, for tests and buffering
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    pub readings: Vec<Reading>,
+    pub annotations: Vec<Annotation>,
+}
+
+impl ReadingSink for MemorySink {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        self.readings.push(reading.clone());
+        Ok(())
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        self.annotations.push(annotation.clone());
+        Ok(())
+    }
+}
+
+/// Feeds every reading and note to both sinks; the second still gets them
+/// when the first fails
+pub struct Tee<A, B>(pub A, pub B);
+
+impl<A: ReadingSink, B: ReadingSink> ReadingSink for Tee<A, B> {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        let first = self.0.emit(reading);
+        self.1.emit(reading)?;
+        first
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        let first = self.0.annotate(annotation);
+        self.1.annotate(annotation)?;
+        first
+    }
+}
+
+/// Size at which [`FlashLogSink`] rotates its file by default
+pub const FLASH_LOG_MAX_BYTES: usize = 64 * 1024;
+
+/// Appends encoded readings to a flash file as `u16` length-prefixed records.
+///
+/// A record that would take the file past the size cap rotates it first:
+/// the full file becomes `<file>.1`, replacing the previous generation, and
+/// logging starts over in an empty file.
+pub struct FlashLogSink<F, C> {
+    flash: F,
+    file: String,
+    codec: C,
+    max_bytes: usize,
+    /// Current file size, read from flash on the first emit
+    len: Option<usize>,
+}
+
+impl<F: FlashStore, C: Codec> FlashLogSink<F, C> {
+    pub fn new(flash: F, file: impl Into<String>, codec: C) -> Self {
+        Self {
+            flash,
+            file: file.into(),
+            codec,
+            max_bytes: FLASH_LOG_MAX_BYTES,
+            len: None,
+        }
+    }
+
+    /// Rotate once the file would grow past `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    fn rotated_file(&self) -> String {
+        format!("{}.1", self.file)
+    }
+
+    /// Decode every reading logged so far, the rotated generation first
+    pub fn read_back(&self) -> Result<Vec<Reading>> {
+        let mut readings = Vec::new();
+        for file in [self.rotated_file(), self.file.clone()] {
+            let data = self.flash.read_file(&file)?.unwrap_or_default();
+            let mut rest = data.as_slice();
+            while !rest.is_empty() {
+                if rest.len() < 2 {
+                    bail!("truncated record header in {}", file);
+                }
+                let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+                ensure!(rest.len() >= 2 + len, "truncated record in {}", file);
+                readings.push(self.codec.decode(&rest[2..2 + len])?);
+                rest = &rest[2 + len..];
+            }
+        }
+        Ok(readings)
+    }
+
+    /// Move the full file aside and start an empty one
+    fn rotate(&mut self) -> Result<()> {
+        let data = self.flash.read_file(&self.file)?.unwrap_or_default();
+        self.flash.write_file(&self.rotated_file(), &data)?;
+        self.flash.write_file(&self.file, &[])?;
+        self.len = Some(0);
+        Ok(())
+    }
+}
+
+impl<F: FlashStore, C: Codec> ReadingSink for FlashLogSink<F, C> {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        let record = self.codec.encode(reading)?;
+        ensure!(
+            record.len() <= u16::MAX as usize,
+            "encoded reading too large"
+        );
+        let len = match self.len {
+            Some(len) => len,
+            None => self
+                .flash
+                .read_file(&self.file)?
+                .map_or(0, |data| data.len()),
+        };
+        self.len = Some(len);
+        let mut data = Vec::with_capacity(2 + record.len());
+        data.extend_from_slice(&(record.len() as u16).to_le_bytes());
+        data.extend_from_slice(&record);
+        if len > 0 && len + data.len() > self.max_bytes {
+            self.rotate()?;
+        }
+        self.flash.append_file(&self.file, &data)?;
+        self.len = self.len.map(|len| len + data.len());
+        Ok(())
+    }
+}
+
+/// Message transport such as an MQTT client
+pub trait Publisher {
+    fn publish(&mut self, topic: &str, content_type: &str, payload: &[u8]) -> Result<()>;
+}
+
+/// Publishes each reading, encoded with `codec`, to a fixed topic
+pub struct NetworkSink<P, C> {
+    publisher: P,
+    topic: String,
+    codec: C,
+}
+
+impl<P: Publisher, C: Codec> NetworkSink<P, C> {
+    pub fn new(publisher: P, topic: impl Into<String>, codec: C) -> Self {
+        Self {
+            publisher,
+            topic: topic.into(),
+            codec,
+        }
+    }
+
+    pub fn publisher(&self) -> &P {
+        &self.publisher
+    }
+}
+
+impl<P: Publisher, C: Codec> ReadingSink for NetworkSink<P, C> {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        let payload = self.codec.encode(reading)?;
+        self.publisher
+            .publish(&self.topic, self.codec.content_type(), &payload)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        logfmt_value, render_logfmt, Coalescing, ConsoleSink, FlashLogSink, MemorySink,
+        NetworkSink, Publisher, ReadingSink, Tee,
+    };
+    use crate::boot::BootReason;
+    use crate::codec::Codec;
+    use crate::moisture::SoilCondition;
+    use crate::reading::Reading;
+    use crate::rewet::RewetCause;
+    use crate::storage::{FlashStore, MemoryFlash};
+    use anyhow::{ensure, Result};
+    use std::time::Duration;
+
+    /// Fixed-width test encoding, so the framing is tested independently of serde
+    struct RawCodec;
+
+    impl Codec for RawCodec {
+        fn content_type(&self) -> &'static str {
+            "application/x-test"
+        }
+
+        fn encode(&self, reading: &Reading) -> Result<Vec<u8>> {
+            let mut out = (reading.timestamp.as_secs() as u16).to_le_bytes().to_vec();
+            out.extend_from_slice(&reading.raw.to_le_bytes());
+            out.push(reading.moisture_percent);
+            Ok(out)
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<Reading> {
+            ensure!(bytes.len() == 5, "bad length");
+            let word = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+            Ok(Reading::new(
+                Duration::from_secs(word(0) as u64),
+                word(2),
+                bytes[4],
+            ))
+        }
+    }
+
+    fn readings() -> Vec<Reading> {
+        (0..3)
+            .map(|i| Reading::new(Duration::from_secs(i * 2), 2000 + i as u16, 40 + i as u8))
+            .collect()
+    }
+
+    #[test]
+    fn flash_log_appends_and_reads_back() {
+        let mut sink = FlashLogSink::new(MemoryFlash::new(), "readings.log", RawCodec);
+        for reading in readings() {
+            sink.emit(&reading).unwrap();
+        }
+        assert_eq!(sink.read_back().unwrap(), readings());
+    }
+
+    #[test]
+    fn flash_log_rotates_at_the_size_cap() {
+        let record = RawCodec.encode(&readings()[0]).unwrap().len() + 2;
+        let mut sink = FlashLogSink::new(MemoryFlash::new(), "readings.log", RawCodec)
+            .with_max_bytes(record * 2);
+        for reading in readings() {
+            sink.emit(&reading).unwrap();
+        }
+        // Two records fit, so the third rotates them aside
+        fn size_of(sink: &FlashLogSink<MemoryFlash, RawCodec>, file: &str) -> usize {
+            sink.flash
+                .read_file(file)
+                .unwrap()
+                .map_or(0, |data| data.len())
+        }
+        assert_eq!(size_of(&sink, "readings.log"), record);
+        assert_eq!(size_of(&sink, "readings.log.1"), record * 2);
+        assert_eq!(sink.read_back().unwrap(), readings());
+
+        // An existing file's size is picked up after a restart
+        let mut sink =
+            FlashLogSink::new(sink.flash, "readings.log", RawCodec).with_max_bytes(record * 2);
+        sink.emit(&readings()[0]).unwrap();
+        assert_eq!(size_of(&sink, "readings.log"), record * 2);
+        // The oldest generation is dropped on the next rotation
+        sink.emit(&readings()[1]).unwrap();
+        let read_back = sink.read_back().unwrap();
+        assert_eq!(
+            read_back,
+            [
+                readings()[2].clone(),
+                readings()[0].clone(),
+                readings()[1].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn flash_log_rejects_truncated_file() {
+        let mut flash = MemoryFlash::new();
+        flash.write_file("readings.log", &[5, 0, 1, 2]).unwrap();
+        let sink = FlashLogSink::new(flash, "readings.log", RawCodec);
+        assert!(sink.read_back().is_err());
+    }
+
+    #[derive(Default)]
+    struct Outbox(Vec<(String, String, Vec<u8>)>);
+
+    impl Publisher for Outbox {
+        fn publish(&mut self, topic: &str, content_type: &str, payload: &[u8]) -> Result<()> {
+            self.0.push((
+                topic.to_string(),
+                content_type.to_string(),
+                payload.to_vec(),
+            ));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn network_sink_publishes_encoded_readings() {
+        let mut sink = NetworkSink::new(Outbox::default(), "soil/readings", RawCodec);
+        let reading = &readings()[1];
+        sink.emit(reading).unwrap();
+
+        let (topic, content_type, payload) = &sink.publisher().0[0];
+        assert_eq!(topic, "soil/readings");
+        assert_eq!(content_type, "application/x-test");
+        assert_eq!(RawCodec.decode(payload).unwrap(), *reading);
+    }
+
+    fn rows(sink: &mut ConsoleSink, moistures: &[u8]) -> Vec<Option<String>> {
+        moistures
+            .iter()
+            .enumerate()
+            .map(|(i, &m)| sink.row(&Reading::new(Duration::from_secs(i as u64), 2100, m)))
+            .collect()
+    }
+
+    #[test]
+    fn console_prints_every_row_by_default() {
+        let mut sink = ConsoleSink::default();
+        assert!(rows(&mut sink, &[50, 50, 50]).iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn console_coalesces_identical_rows_with_periodic_summary() {
+        let mut sink = ConsoleSink::default().with_coalescing(Coalescing {
+            tolerance: 1,
+            summary_every: 3,
+        });
+        let out = rows(&mut sink, &[50, 51, 50, 49, 50, 50, 50, 55]);
+        assert_eq!(
+            out[0].as_deref(),
+            Some("     2100 |       50% | OPTIMAL (LED: OFF)")
+        );
+        assert_eq!(out[1..3], [None, None]);
+        assert_eq!(out[3].as_deref(), Some("still OPTIMAL (3 readings)"));
+        assert_eq!(out[4..6], [None, None]);
+        assert_eq!(out[6].as_deref(), Some("still OPTIMAL (6 readings)"));
+        // A real change prints again and starts a new run
+        assert!(out[7].as_deref().unwrap().contains("55%"));
+        assert_eq!(rows(&mut sink, &[55]), [None]);
+    }
+
+    #[test]
+    fn console_reuses_the_row_while_the_reading_repeats() {
+        let mut sink = ConsoleSink::default();
+        let out = rows(&mut sink, &[50, 50, 51]);
+        assert_eq!(out[0], out[1]);
+        assert_eq!(sink.reused_rows(), 1);
+        assert!(out[2].as_deref().unwrap().contains("51%"));
+    }
+
+    #[test]
+    fn logfmt_quotes_values_with_spaces() {
+        let reading = Reading::new(Duration::from_secs(42), 2100, 45);
+        assert_eq!(
+            render_logfmt(&reading, SoilCondition::Optimal),
+            "ts=42 raw=2100 moisture=45 status=OPTIMAL pump=off"
+        );
+
+        let reading = Reading::new(Duration::from_secs(7), 2950, 12)
+            .with_pump_on(true)
+            .with_ec(180)
+            .with_depth(20)
+            .with_temperature(-15)
+            .with_fault(true)
+            .with_rewet(RewetCause::PumpRewet)
+            .with_boot_reason(BootReason::Brownout);
+        assert_eq!(
+            render_logfmt(&reading, SoilCondition::Dry),
+            "ts=7 raw=2950 moisture=12 status=\"DRY - Need Water!\" pump=on \
+             ec_us_cm=180 depth_cm=20 temp_c=-1.5 fault=true rewet=pump_rewet boot_reason=brownout"
+        );
+    }
+
+    #[test]
+    fn logfmt_escapes_quotes_and_backslashes() {
+        assert_eq!(logfmt_value("plain"), "plain");
+        assert_eq!(logfmt_value(""), "\"\"");
+        assert_eq!(logfmt_value("a=b"), "\"a=b\"");
+        assert_eq!(logfmt_value(r#"say "hi"\"#), r#""say \"hi\"\\""#);
+    }
+
+    #[test]
+    fn tee_feeds_second_sink_when_first_fails() {
+        struct Failing;
+        impl ReadingSink for Failing {
+            fn emit(&mut self, _reading: &Reading) -> Result<()> {
+                ensure!(false, "link down");
+                Ok(())
+            }
+        }
+
+        let mut tee = Tee(Failing, MemorySink::default());
+        assert!(tee
+            .emit(&Reading::new(Duration::from_secs(1), 2100, 45))
+            .is_err());
+        assert_eq!(tee.1.readings.len(), 1);
+    }
+}
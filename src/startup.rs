@@ -0,0 +1,56 @@
+//! Power-on blink sequence shared by the demo and firmware entry points.
+
+use crate::clock::Clock;
+use crate::led::Led;
+use anyhow::Result;
+use log::info;
+use std::time::Duration;
+
+pub const STARTUP_BLINKS: usize = 3;
+pub const STARTUP_BLINK_ON: Duration = Duration::from_millis(200);
+pub const STARTUP_BLINK_OFF: Duration = Duration::from_millis(200);
+
+/// Blink the status LED to show the board is alive, then announce readiness
+pub fn startup_sequence(led: &mut dyn Led, clock: &dyn Clock) -> Result<()> {
+    info!("Performing startup sequence...");
+    for i in 0..STARTUP_BLINKS {
+        info!("LED ON (blink {})", i + 1);
+        led.set_on(true)?;
+        clock.sleep(STARTUP_BLINK_ON);
+        info!("LED OFF");
+        led.set_on(false)?;
+        clock.sleep(STARTUP_BLINK_OFF);
+    }
+
+    info!("System ready! Starting measurements...");
+    Ok(())
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::startup_sequence;
+    use crate::clock::{Clock, MockClock};
+    use crate::led::{RecordingLed, LED_FULL, LED_OFF};
+    use std::time::Duration;
+
+    #[test]
+    fn blinks_three_times_with_expected_timing() {
+        let clock = MockClock::new();
+        let mut led = RecordingLed::new(clock.clone());
+        startup_sequence(&mut led, &clock).unwrap();
+
+        let ms = Duration::from_millis;
+        assert_eq!(
+            led.timeline(),
+            &[
+                (ms(0), LED_FULL),
+                (ms(200), LED_OFF),
+                (ms(400), LED_FULL),
+                (ms(600), LED_OFF),
+                (ms(800), LED_FULL),
+                (ms(1000), LED_OFF),
+            ]
+        );
+        assert_eq!(clock.now(), ms(1200));
+    }
+}
@@ -0,0 +1,115 @@
+//! Running session statistics over moisture readings.
+
+use anyhow::{bail, ensure, Result};
+
+const STATS_FORMAT_VERSION: u8 = 1;
+const STATS_BLOB_LEN: usize = 17;
+
+/// Min/max/mean moisture over the current session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    count: u32,
+    min: Option<u8>,
+    max: Option<u8>,
+    sum: u64,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include one moisture percentage in the statistics
+    pub fn record(&mut self, moisture_percent: u8) {
+        self.count = self.count.saturating_add(1);
+        self.sum += moisture_percent as u64;
+        self.min = Some(
+            self.min
+                .map_or(moisture_percent, |m| m.min(moisture_percent)),
+        );
+        self.max = Some(
+            self.max
+                .map_or(moisture_percent, |m| m.max(moisture_percent)),
+        );
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<u8> {
+        self.min
+    }
+
+    pub fn max(&self) -> Option<u8> {
+        self.max
+    }
+
+    /// Integer mean, or `None` before the first reading
+    pub fn mean(&self) -> Option<u8> {
+        (self.count > 0).then(|| (self.sum / self.count as u64) as u8)
+    }
+
+    /// Persisted form: version, count, min and max as presence byte plus
+    /// value, then the sum (little endian)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![STATS_FORMAT_VERSION];
+        out.extend_from_slice(&self.count.to_le_bytes());
+        for bound in [self.min, self.max] {
+            out.push(bound.is_some() as u8);
+            out.push(bound.unwrap_or(0));
+        }
+        out.extend_from_slice(&self.sum.to_le_bytes());
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(
+            bytes.len() == STATS_BLOB_LEN,
+            "stats blob is {} bytes, expected {}",
+            bytes.len(),
+            STATS_BLOB_LEN
+        );
+        if bytes[0] != STATS_FORMAT_VERSION {
+            bail!("unsupported stats format version {}", bytes[0]);
+        }
+        let bound = |i: usize| (bytes[i] != 0).then_some(bytes[i + 1]);
+        let mut sum = [0u8; 8];
+        sum.copy_from_slice(&bytes[9..17]);
+        Ok(Self {
+            count: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]),
+            min: bound(5),
+            max: bound(7),
+            sum: u64::from_le_bytes(sum),
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::Stats;
+
+    #[test]
+    fn tracks_min_max_mean() {
+        let mut stats = Stats::new();
+        assert_eq!(stats.mean(), None);
+        for m in [40, 10, 70] {
+            stats.record(m);
+        }
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.min(), Some(10));
+        assert_eq!(stats.max(), Some(70));
+        assert_eq!(stats.mean(), Some(40));
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut stats = Stats::new();
+        assert_eq!(Stats::from_bytes(&stats.to_bytes()).unwrap(), stats);
+        for m in [40, 10, 70] {
+            stats.record(m);
+        }
+        assert_eq!(Stats::from_bytes(&stats.to_bytes()).unwrap(), stats);
+        assert!(Stats::from_bytes(&stats.to_bytes()[..10]).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! One-line status summary for the serial console or a local display.
+
+use crate::clock::Clock;
+use crate::pump::PumpLifetime;
+use crate::schedule::Schedule;
+use std::fmt;
+use std::time::Duration;
+
+/// When watering is next allowed to start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NextWindow {
+    /// A window is open right now
+    Now,
+    /// The next window opens at this time of day
+    At(Duration),
+    /// No windows configured, so watering is never held back
+    Always,
+}
+
+impl fmt::Display for NextWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NextWindow::Now => write!(f, "now"),
+            NextWindow::At(time_of_day) => {
+                let minutes = time_of_day.as_secs() / 60;
+                write!(f, "{:02}:{:02}", minutes / 60, minutes % 60)
+            }
+            NextWindow::Always => write!(f, "always"),
+        }
+    }
+}
+
+/// Time left until the reading due `interval` after `last_reading`
+pub fn next_reading_in(clock: &dyn Clock, last_reading: Duration, interval: Duration) -> Duration {
+    (last_reading + interval).saturating_sub(clock.now())
+}
+
+/// When the schedule next lets watering start
+pub fn next_watering_window(clock: &dyn Clock, schedule: &Schedule) -> NextWindow {
+    let now = clock.now();
+    if schedule.is_unrestricted() {
+        return NextWindow::Always;
+    }
+    if schedule.window_at(now).is_some() {
+        return NextWindow::Now;
+    }
+    schedule
+        .next_start(now)
+        .map_or(NextWindow::Always, NextWindow::At)
+}
+
+/// Upcoming events shown alongside each reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    pub next_reading_in: Duration,
+    pub next_window: NextWindow,
+    /// Automatic pump control suspended for maintenance
+    pub paused: bool,
+    /// Raw readings pinned at ADC full scale this session
+    pub adc_saturations: u32,
+    /// Readings clamped to the calibration range this session, if counted
+    pub clipped_readings: u32,
+    /// Probe considered dead; watering locked out
+    pub safe_mode: bool,
+    /// Totals across every boot, for pump service planning
+    pub pump_lifetime: PumpLifetime,
+    /// Service interval exceeded
+    pub maintenance_due: bool,
+}
+
+impl Status {
+    pub fn new(
+        clock: &dyn Clock,
+        last_reading: Duration,
+        interval: Duration,
+        schedule: &Schedule,
+    ) -> Self {
+        Self {
+            next_reading_in: next_reading_in(clock, last_reading, interval),
+            next_window: next_watering_window(clock, schedule),
+            paused: false,
+            adc_saturations: 0,
+            clipped_readings: 0,
+            safe_mode: false,
+            pump_lifetime: PumpLifetime::default(),
+            maintenance_due: false,
+        }
+    }
+
+    /// Flag that pump control is paused
+    pub fn with_paused(mut self, paused: bool) -> Self {
+        self.paused = paused;
+        self
+    }
+
+    /// Flag that the dead-probe safe mode is active
+    pub fn with_safe_mode(mut self, safe_mode: bool) -> Self {
+        self.safe_mode = safe_mode;
+        self
+    }
+
+    /// Report how many readings hit ADC full scale
+    pub fn with_adc_saturations(mut self, count: u32) -> Self {
+        self.adc_saturations = count;
+        self
+    }
+
+    /// Report how many readings were clamped to the calibration range
+    pub fn with_clipped_readings(mut self, count: u32) -> Self {
+        self.clipped_readings = count;
+        self
+    }
+
+    /// Flag that the pump is due for service
+    pub fn with_maintenance_due(mut self, due: bool) -> Self {
+        self.maintenance_due = due;
+        self
+    }
+
+    /// Report lifetime pump usage
+    pub fn with_pump_lifetime(mut self, lifetime: PumpLifetime) -> Self {
+        self.pump_lifetime = lifetime;
+        self
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Round up so "0s" only shows once the reading is actually due
+        let secs = (self.next_reading_in.as_millis() as u64).div_ceil(1000);
+        write!(f, "next reading in {secs}s, next watering window ")?;
+        match self.next_window {
+            NextWindow::At(_) => write!(f, "at {}", self.next_window)?,
+            other => write!(f, "{other}")?,
+        }
+        if self.paused {
+            write!(f, " [control paused]")?;
+        }
+        if self.safe_mode {
+            write!(f, " [SAFE MODE: probe dead]")?;
+        }
+        if self.maintenance_due {
+            write!(f, " [maintenance due]")?;
+        }
+        if self.adc_saturations > 0 {
+            write!(f, " [{} ADC saturations]", self.adc_saturations)?;
+        }
+        if self.clipped_readings > 0 {
+            write!(f, " [{} readings clipped]", self.clipped_readings)?;
+        }
+        if self.pump_lifetime.activations > 0 {
+            write!(f, " [pump {}]", self.pump_lifetime)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{next_watering_window, NextWindow, Status};
+    use crate::clock::MockClock;
+    use crate::pump::PumpLifetime;
+    use crate::schedule::{Schedule, Window};
+    use std::time::Duration;
+
+    fn hm(h: u64, m: u64) -> Duration {
+        Duration::from_secs((h * 60 + m) * 60)
+    }
+
+    fn schedule() -> Schedule {
+        Schedule::new(
+            vec![
+                Window::new(hm(6, 30), hm(7, 30)),
+                Window::new(hm(18, 0), hm(19, 0)),
+            ],
+            Duration::ZERO,
+        )
+    }
+
+    #[test]
+    fn before_window_shows_next_start() {
+        let clock = MockClock::new();
+        clock.set(hm(5, 0));
+        let status = Status::new(
+            &clock,
+            hm(5, 0) - Duration::from_millis(500),
+            Duration::from_secs(2),
+            &schedule(),
+        );
+        assert_eq!(
+            status.to_string(),
+            "next reading in 2s, next watering window at 06:30"
+        );
+
+        // After the last window of the day, wraps to tomorrow's first
+        clock.set(hm(20, 0));
+        assert_eq!(
+            next_watering_window(&clock, &schedule()),
+            NextWindow::At(hm(6, 30))
+        );
+        clock.set(hm(24 + 12, 0));
+        assert_eq!(
+            next_watering_window(&clock, &schedule()),
+            NextWindow::At(hm(18, 0))
+        );
+    }
+
+    #[test]
+    fn inside_window_shows_now() {
+        let clock = MockClock::new();
+        clock.set(hm(18, 15));
+        let status = Status::new(&clock, hm(18, 15), Duration::from_secs(2), &schedule());
+        assert_eq!(status.next_window, NextWindow::Now);
+        assert_eq!(
+            status.to_string(),
+            "next reading in 2s, next watering window now"
+        );
+        assert_eq!(
+            status.with_paused(true).to_string(),
+            "next reading in 2s, next watering window now [control paused]"
+        );
+    }
+
+    #[test]
+    fn no_windows_shows_always() {
+        let clock = MockClock::new();
+        let unrestricted = Schedule::new(Vec::new(), Duration::ZERO);
+        let status = Status::new(
+            &clock,
+            Duration::ZERO,
+            Duration::from_secs(2),
+            &unrestricted,
+        );
+        assert_eq!(status.next_window, NextWindow::Always);
+        assert!(status.to_string().ends_with("window always"));
+
+        // An overdue reading never goes negative
+        clock.set(Duration::from_secs(10));
+        let status = Status::new(
+            &clock,
+            Duration::ZERO,
+            Duration::from_secs(2),
+            &unrestricted,
+        );
+        assert_eq!(status.next_reading_in, Duration::ZERO);
+    }
+
+    #[test]
+    fn saturations_shown_only_when_present() {
+        let clock = MockClock::new();
+        let status = Status::new(&clock, Duration::ZERO, Duration::from_secs(2), &schedule());
+        assert!(!status.to_string().contains("ADC"));
+        assert!(status
+            .with_adc_saturations(3)
+            .to_string()
+            .ends_with(" [3 ADC saturations]"));
+    }
+
+    #[test]
+    fn pump_lifetime_shown_once_it_has_run() {
+        let clock = MockClock::new();
+        let status = Status::new(&clock, Duration::ZERO, Duration::from_secs(2), &schedule());
+        assert!(!status.to_string().contains("pump"));
+        let lifetime = PumpLifetime {
+            activations: 12,
+            runtime: Duration::from_secs(2 * 3600 + 5 * 60),
+        };
+        assert!(status
+            .with_pump_lifetime(lifetime)
+            .to_string()
+            .ends_with(" [pump 2h05m over 12 runs]"));
+    }
+}
@@ -0,0 +1,100 @@
+//! Minimal flash file abstraction so persistence logic can be tested on the host.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+/// Named-file storage on a flash partition
+pub trait FlashStore {
+    /// Create or replace `name` with `data`
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()>;
+
+    /// Read `name`, or `None` if it does not exist
+    fn read_file(&self, name: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Append `data` to `name`, creating it if needed. The default reads and
+    /// rewrites the whole file; stores that can append in place override it.
+    fn append_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let mut contents = self.read_file(name)?.unwrap_or_default();
+        contents.extend_from_slice(data);
+        self.write_file(name, &contents)
+    }
+}
+
+/// Flash files reached through the VFS, e.g. a SPIFFS/FAT partition mounted at `/spiffs`
+pub struct FsFlash {
+    root: PathBuf,
+}
+
+impl FsFlash {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl FlashStore for FsFlash {
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.root.join(name);
+        fs::write(&path, data).with_context(|| format!("writing {}", path.display()))
+    }
+
+    fn read_file(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.root.join(name);
+        match fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow!("reading {}: {}", path.display(), e)),
+        }
+    }
+
+    fn append_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let path = self.root.join(name);
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(data))
+            .with_context(|| format!("appending to {}", path.display()))
+    }
+}
+
+/// In-RAM flash stand-in for tests
+#[derive(Debug, Default)]
+pub struct MemoryFlash {
+    files: HashMap<String, Vec<u8>>,
+    /// When set, every write fails as if the partition were full or unmounted
+    pub fail_writes: bool,
+}
+
+impl MemoryFlash {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl FlashStore for MemoryFlash {
+    fn write_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        if self.fail_writes {
+            return Err(anyhow!("simulated flash write failure for {}", name));
+        }
+        self.files.insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn read_file(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.files.get(name).cloned())
+    }
+
+    fn append_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        if self.fail_writes {
+            return Err(anyhow!("simulated flash write failure for {}", name));
+        }
+        self.files
+            .entry(name.to_string())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+}
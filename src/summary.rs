@@ -0,0 +1,99 @@
+//! End-of-session summary persisted on graceful shutdown.
+
+use crate::pump::PumpAudit;
+use crate::stats::Stats;
+use crate::storage::FlashStore;
+use anyhow::{Context, Result};
+use log::info;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Flash file the summary is written to
+pub const SESSION_SUMMARY_FILE: &str = "session_summary.txt";
+
+/// Render the session summary as `key=value` lines
+pub fn render_session_summary(
+    stats: &Stats,
+    audit: &PumpAudit,
+    duration: Duration,
+    alerts: u32,
+) -> String {
+    let fmt_opt = |v: Option<u8>| v.map_or_else(|| "n/a".to_string(), |v| v.to_string());
+
+    let mut out = String::new();
+    // Writing to a String cannot fail
+    let _ = writeln!(out, "duration_s={}", duration.as_secs());
+    let _ = writeln!(out, "readings={}", stats.count());
+    let _ = writeln!(out, "moisture_min={}", fmt_opt(stats.min()));
+    let _ = writeln!(out, "moisture_max={}", fmt_opt(stats.max()));
+    let _ = writeln!(out, "moisture_mean={}", fmt_opt(stats.mean()));
+    let _ = writeln!(out, "pump_activations={}", audit.activations());
+    let _ = writeln!(out, "alerts={}", alerts);
+    out
+}
+
+/// Log the session summary and persist it to flash.
+///
+/// The summary is always logged first so it is not lost if the write fails.
+pub fn write_session_summary(
+    stats: &Stats,
+    audit: &PumpAudit,
+    duration: Duration,
+    alerts: u32,
+    flash: &mut dyn FlashStore,
+) -> Result<()> {
+    let summary = render_session_summary(stats, audit, duration, alerts);
+    info!("Session summary:");
+    for line in summary.lines() {
+        info!("  {}", line);
+    }
+
+    flash
+        .write_file(SESSION_SUMMARY_FILE, summary.as_bytes())
+        .context("persisting session summary")
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{write_session_summary, SESSION_SUMMARY_FILE};
+    use crate::pump::{PumpAction, PumpAudit};
+    use crate::stats::Stats;
+    use crate::storage::{FlashStore, MemoryFlash};
+    use std::time::Duration;
+
+    fn session() -> (Stats, PumpAudit) {
+        let mut stats = Stats::new();
+        for m in [20, 50, 80] {
+            stats.record(m);
+        }
+        let mut audit = PumpAudit::new(8);
+        audit.record(Duration::from_secs(2), PumpAction::Activate);
+        audit.record(Duration::from_secs(6), PumpAction::Deactivate);
+        (stats, audit)
+    }
+
+    #[test]
+    fn writes_summary_with_session_state() {
+        let (stats, audit) = session();
+        let mut flash = MemoryFlash::new();
+        write_session_summary(&stats, &audit, Duration::from_secs(40), 1, &mut flash).unwrap();
+
+        let written = flash.read_file(SESSION_SUMMARY_FILE).unwrap().unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "duration_s=40\nreadings=3\nmoisture_min=20\nmoisture_max=80\n\
+             moisture_mean=50\npump_activations=1\nalerts=1\n"
+        );
+    }
+
+    #[test]
+    fn reports_flash_failure() {
+        let (stats, audit) = session();
+        let mut flash = MemoryFlash::new();
+        flash.fail_writes = true;
+        assert!(
+            write_session_summary(&stats, &audit, Duration::from_secs(40), 0, &mut flash).is_err()
+        );
+        assert!(flash.read_file(SESSION_SUMMARY_FILE).unwrap().is_none());
+    }
+}
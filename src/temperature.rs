@@ -0,0 +1,390 @@
+//! Temperature-only channel for probes that report no moisture, and
+//! temperature compensation of the moisture channel.
+//!
+//! Temperatures are tenths of a degree Celsius so the conversion stays in
+//! integer math. Frost and heat alerts fire once per excursion and re-arm
+//! once the temperature is back past the threshold by the hysteresis.
+//!
+//! [`CompensatedSensor`] corrects raw moisture readings for soil temperature;
+//! a [`TemperatureFallback`] decides what happens when the temperature read
+//! fails, so moisture keeps reporting either way.
+
+use crate::alert::Alert;
+use crate::sensor::SoilSensor;
+use anyhow::Result;
+use log::warn;
+
+/// Two-point linear calibration from raw counts to tenths of °C
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureCalibration {
+    pub raw_low: u16,
+    pub tenths_low: i16,
+    pub raw_high: u16,
+    pub tenths_high: i16,
+}
+
+impl Default for TemperatureCalibration {
+    /// TMP36-style sensor on the 12-bit, 3.3 V ADC: 500 mV at 0 °C, 10 mV/°C
+    fn default() -> Self {
+        Self {
+            raw_low: 620,
+            tenths_low: 0,
+            raw_high: 1241,
+            tenths_high: 500,
+        }
+    }
+}
+
+impl TemperatureCalibration {
+    /// Convert a raw reading to tenths of °C, rounded to nearest
+    pub fn to_tenths_c(&self, raw: u16) -> i16 {
+        let dx = self.raw_high as i64 - self.raw_low as i64;
+        if dx == 0 {
+            return self.tenths_low;
+        }
+        let dy = self.tenths_high as i64 - self.tenths_low as i64;
+        let num = (raw as i64 - self.raw_low as i64) * dy;
+        // Round half away from zero, whatever the signs
+        let offset = (2 * num + num.signum() * dx.abs()) / (2 * dx);
+        (self.tenths_low as i64 + offset).clamp(i16::MIN as i64, i16::MAX as i64) as i16
+    }
+}
+
+/// Temperatures that trigger alerts, in tenths of °C
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureThresholds {
+    /// Frost alert below this
+    pub frost_below: i16,
+    /// Heat alert above this
+    pub heat_above: i16,
+    /// Distance back past a threshold before its alert can fire again
+    pub hysteresis: i16,
+}
+
+impl Default for TemperatureThresholds {
+    fn default() -> Self {
+        Self {
+            frost_below: 20,
+            heat_above: 350,
+            hysteresis: 10,
+        }
+    }
+}
+
+/// Converted temperature plus any alert raised by it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemperatureSample {
+    pub tenths_c: i16,
+    pub alert: Option<Alert>,
+}
+
+/// Temperature conversion and frost/heat alerting, independent of the
+/// moisture path
+#[derive(Debug, Clone, Default)]
+pub struct TemperatureChannel {
+    calibration: TemperatureCalibration,
+    thresholds: TemperatureThresholds,
+    frost: bool,
+    heat: bool,
+}
+
+impl TemperatureChannel {
+    pub fn new(calibration: TemperatureCalibration, thresholds: TemperatureThresholds) -> Self {
+        Self {
+            calibration,
+            thresholds,
+            frost: false,
+            heat: false,
+        }
+    }
+
+    /// Convert `raw` and check it against the frost and heat thresholds
+    pub fn update(&mut self, raw: u16) -> TemperatureSample {
+        let tenths_c = self.calibration.to_tenths_c(raw);
+        let t = &self.thresholds;
+        let mut alert = None;
+        if tenths_c < t.frost_below {
+            if !self.frost {
+                alert = Some(Alert::Frost { tenths_c });
+            }
+            self.frost = true;
+        } else if tenths_c >= t.frost_below.saturating_add(t.hysteresis) {
+            self.frost = false;
+        }
+        if tenths_c > t.heat_above {
+            if !self.heat {
+                alert = Some(Alert::Heat { tenths_c });
+            }
+            self.heat = true;
+        } else if tenths_c <= t.heat_above.saturating_sub(t.hysteresis) {
+            self.heat = false;
+        }
+        TemperatureSample { tenths_c, alert }
+    }
+
+    /// Above the heat threshold and not yet back below it by the hysteresis
+    pub fn is_hot(&self) -> bool {
+        self.heat
+    }
+}
+
+/// Soil temperature for compensating the moisture channel
+pub trait TemperatureSource {
+    fn read_tenths_c(&mut self) -> Result<i16>;
+}
+
+/// Linear temperature drift of the raw moisture reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemperatureCompensation {
+    /// Temperature the moisture calibration was captured at
+    pub reference_tenths_c: i16,
+    /// Raw counts the probe shifts per °C above the reference
+    pub counts_per_degree: i16,
+}
+
+impl Default for TemperatureCompensation {
+    fn default() -> Self {
+        Self {
+            reference_tenths_c: 200,
+            counts_per_degree: 4,
+        }
+    }
+}
+
+impl TemperatureCompensation {
+    /// Raw value as it would have read at the reference temperature
+    pub fn apply(&self, raw: u16, tenths_c: i16) -> u16 {
+        let delta = tenths_c as i32 - self.reference_tenths_c as i32;
+        let shift = delta * self.counts_per_degree as i32 / 10;
+        (raw as i32 - shift).clamp(0, u16::MAX as i32) as u16
+    }
+}
+
+/// Temperature to compensate with when the source fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TemperatureFallback {
+    /// Last temperature read successfully; uncompensated until there is one
+    #[default]
+    LastKnown,
+    /// A fixed temperature, e.g. the site's seasonal average
+    Default { tenths_c: i16 },
+    /// Report the raw value uncompensated
+    Skip,
+}
+
+/// Moisture sensor whose readings are corrected to the reference temperature
+pub struct CompensatedSensor<S, T> {
+    sensor: S,
+    temperature: T,
+    compensation: TemperatureCompensation,
+    fallback: TemperatureFallback,
+    last_known: Option<i16>,
+    /// Temperature the latest reading was compensated with
+    applied: Option<i16>,
+}
+
+impl<S: SoilSensor, T: TemperatureSource> CompensatedSensor<S, T> {
+    pub fn new(sensor: S, temperature: T, compensation: TemperatureCompensation) -> Self {
+        Self {
+            sensor,
+            temperature,
+            compensation,
+            fallback: TemperatureFallback::default(),
+            last_known: None,
+            applied: None,
+        }
+    }
+
+    pub fn with_fallback(mut self, fallback: TemperatureFallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Temperature the latest reading was compensated with; `None` if it
+    /// went out uncompensated
+    pub fn applied_tenths_c(&self) -> Option<i16> {
+        self.applied
+    }
+
+    fn compensate(&mut self, raw: u16) -> u16 {
+        self.applied = match self.temperature.read_tenths_c() {
+            Ok(tenths_c) => {
+                self.last_known = Some(tenths_c);
+                Some(tenths_c)
+            }
+            Err(e) => {
+                warn!(
+                    "Temperature read failed, using {:?}: {:?}",
+                    self.fallback, e
+                );
+                match self.fallback {
+                    TemperatureFallback::LastKnown => self.last_known,
+                    TemperatureFallback::Default { tenths_c } => Some(tenths_c),
+                    TemperatureFallback::Skip => None,
+                }
+            }
+        };
+        match self.applied {
+            Some(tenths_c) => self.compensation.apply(raw, tenths_c),
+            None => raw,
+        }
+    }
+}
+
+impl<S: SoilSensor, T: TemperatureSource> SoilSensor for CompensatedSensor<S, T> {
+    fn read_averaged(&mut self, samples: usize) -> Result<u16> {
+        let raw = self.sensor.read_averaged(samples)?;
+        Ok(self.compensate(raw))
+    }
+
+    fn read_with_spread(&mut self, samples: usize) -> Result<(u16, Option<u16>)> {
+        let (raw, spread) = self.sensor.read_with_spread(samples)?;
+        Ok((self.compensate(raw), spread))
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{
+        CompensatedSensor, TemperatureCalibration, TemperatureChannel, TemperatureCompensation,
+        TemperatureFallback, TemperatureSource, TemperatureThresholds,
+    };
+    use crate::alert::{Alert, Severity};
+    use crate::sensor::SoilSensor;
+    use anyhow::{anyhow, Result};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn converts_with_two_point_calibration() {
+        let cal = TemperatureCalibration::default();
+        assert_eq!(cal.to_tenths_c(620), 0);
+        assert_eq!(cal.to_tenths_c(1241), 500);
+        assert_eq!(cal.to_tenths_c(931), 250); // 750 mV
+        assert_eq!(cal.to_tenths_c(497), -99); // 400 mV, below freezing
+
+        let flat = TemperatureCalibration {
+            raw_high: 620,
+            ..cal
+        };
+        assert_eq!(flat.to_tenths_c(3000), 0);
+    }
+
+    /// Raw count for `tenths` °C under the default calibration
+    fn raw_at(tenths: i16) -> u16 {
+        (620 + (tenths as i32 * 621 + 250) / 500) as u16
+    }
+
+    #[test]
+    fn frost_alert_fires_once_until_it_warms_up() {
+        let mut ch = TemperatureChannel::new(
+            TemperatureCalibration::default(),
+            TemperatureThresholds::default(),
+        );
+        assert_eq!(ch.update(raw_at(50)).alert, None);
+        let sample = ch.update(raw_at(10));
+        assert_eq!(sample.alert, Some(Alert::Frost { tenths_c: 10 }));
+        assert_eq!(sample.alert.unwrap().to_string(), "frost risk at 1.0°C");
+        // Hovering around the threshold does not re-fire
+        assert_eq!(ch.update(raw_at(25)).alert, None);
+        assert_eq!(ch.update(raw_at(-30)).alert, None);
+        // Back above threshold + hysteresis re-arms it
+        assert_eq!(ch.update(raw_at(40)).alert, None);
+        assert!(ch.update(raw_at(0)).alert.is_some());
+    }
+
+    #[test]
+    fn heat_alert_fires_above_threshold() {
+        let mut ch = TemperatureChannel::new(
+            TemperatureCalibration::default(),
+            TemperatureThresholds {
+                heat_above: 300,
+                ..TemperatureThresholds::default()
+            },
+        );
+        assert_eq!(ch.update(raw_at(290)).alert, None);
+        let alert = ch.update(raw_at(320)).alert.unwrap();
+        assert_eq!(alert, Alert::Heat { tenths_c: 320 });
+        assert_eq!(alert.severity(), Severity::Warning);
+        assert_eq!(ch.update(raw_at(295)).alert, None);
+        assert_eq!(ch.update(raw_at(310)).alert, None);
+    }
+
+    struct Fixed(u16);
+
+    impl SoilSensor for Fixed {
+        fn read_averaged(&mut self, _samples: usize) -> Result<u16> {
+            Ok(self.0)
+        }
+    }
+
+    /// Thermometer reporting whatever the test last set; `None` fails the read
+    struct Thermometer(Rc<Cell<Option<i16>>>);
+
+    impl TemperatureSource for Thermometer {
+        fn read_tenths_c(&mut self) -> Result<i16> {
+            self.0.get().ok_or_else(|| anyhow!("1-Wire CRC error"))
+        }
+    }
+
+    /// Raw readings at 30 °C, then with the thermometer failing
+    fn readings(fallback: TemperatureFallback) -> Vec<(u16, Option<i16>)> {
+        let temperature = Rc::new(Cell::new(Some(300)));
+        let mut sensor = CompensatedSensor::new(
+            Fixed(2000),
+            Thermometer(temperature.clone()),
+            TemperatureCompensation::default(),
+        )
+        .with_fallback(fallback);
+        let mut read = || {
+            let raw = sensor.read_averaged(4).unwrap();
+            (raw, sensor.applied_tenths_c())
+        };
+        let warm = read();
+        temperature.set(None);
+        vec![warm, read(), read()]
+    }
+
+    #[test]
+    fn compensates_to_reference_temperature() {
+        let compensation = TemperatureCompensation::default();
+        assert_eq!(compensation.apply(2000, 200), 2000);
+        // 10 °C warmer reads 40 counts high
+        assert_eq!(compensation.apply(2040, 300), 2000);
+        assert_eq!(compensation.apply(1960, 100), 2000);
+    }
+
+    #[test]
+    fn failed_read_falls_back_to_last_known_temperature() {
+        assert_eq!(
+            readings(TemperatureFallback::LastKnown),
+            vec![(1960, Some(300)); 3]
+        );
+
+        // Nothing known yet: uncompensated rather than failing
+        let mut sensor = CompensatedSensor::new(
+            Fixed(2000),
+            Thermometer(Rc::new(Cell::new(None))),
+            TemperatureCompensation::default(),
+        );
+        assert_eq!(sensor.read_averaged(4).unwrap(), 2000);
+        assert_eq!(sensor.applied_tenths_c(), None);
+    }
+
+    #[test]
+    fn failed_read_falls_back_to_configured_default() {
+        let fallback = TemperatureFallback::Default { tenths_c: 150 };
+        assert_eq!(
+            readings(fallback),
+            vec![(1960, Some(300)), (2020, Some(150)), (2020, Some(150))]
+        );
+    }
+
+    #[test]
+    fn failed_read_can_skip_compensation() {
+        assert_eq!(
+            readings(TemperatureFallback::Skip),
+            vec![(1960, Some(300)), (2000, None), (2000, None)]
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Unit and epoch of the timestamps readings are emitted with.
+//!
+//! Backends disagree on seconds vs milliseconds and on the epoch, so each
+//! reading's [`Timestamp`] is computed once, when it is taken, and every sink
+//! and codec emits that value unchanged.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimestampPrecision {
+    #[default]
+    Seconds,
+    Millis,
+}
+
+/// What emitted timestamps count from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Epoch {
+    /// The device clock's own zero: boot, or day zero once synced
+    #[default]
+    Device,
+    /// 1970-01-01 UTC
+    Unix,
+    /// Any other instant, given as its Unix time (946684800 s for 2000-01-01)
+    Custom(Duration),
+}
+
+/// Reading time as emitted, in [`precision`](Self::precision) units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Timestamp {
+    pub value: u64,
+    pub precision: TimestampPrecision,
+}
+
+impl Timestamp {
+    pub fn seconds(value: u64) -> Self {
+        Self {
+            value,
+            precision: TimestampPrecision::Seconds,
+        }
+    }
+
+    /// Whole seconds, for fixed-width fields that only carry seconds
+    pub fn as_secs(&self) -> u64 {
+        match self.precision {
+            TimestampPrecision::Seconds => self.value,
+            TimestampPrecision::Millis => self.value / 1000,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+/// How reading timestamps are emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimestampConfig {
+    pub precision: TimestampPrecision,
+    pub epoch: Epoch,
+    /// Unix time at which the device clock read zero, known once a time
+    /// source has answered; until then every epoch falls back to the device clock
+    pub clock_zero_unix: Option<Duration>,
+}
+
+impl TimestampConfig {
+    pub fn new(precision: TimestampPrecision, epoch: Epoch) -> Self {
+        Self {
+            precision,
+            epoch,
+            clock_zero_unix: None,
+        }
+    }
+
+    /// Record that the device clock read `clock_now` at Unix time `unix_now`
+    pub fn anchor(&mut self, clock_now: Duration, unix_now: Duration) {
+        self.clock_zero_unix = Some(unix_now.saturating_sub(clock_now));
+    }
+
+    /// Timestamp for device clock time `at`; instants before a custom epoch
+    /// saturate to zero
+    pub fn stamp(&self, at: Duration) -> Timestamp {
+        let since_epoch = match (self.epoch, self.clock_zero_unix) {
+            (Epoch::Unix, Some(zero)) => zero + at,
+            (Epoch::Custom(epoch), Some(zero)) => (zero + at).saturating_sub(epoch),
+            _ => at,
+        };
+        let value = match self.precision {
+            TimestampPrecision::Seconds => since_epoch.as_secs(),
+            TimestampPrecision::Millis => since_epoch.as_millis() as u64,
+        };
+        Timestamp {
+            value,
+            precision: self.precision,
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Epoch, Timestamp, TimestampConfig, TimestampPrecision};
+    use std::time::Duration;
+
+    /// 2023-11-14 22:13:20.250 UTC, reached 90.25 s after boot
+    const UNIX_NOW: Duration = Duration::from_millis(1_700_000_000_250);
+    const CLOCK_NOW: Duration = Duration::from_millis(90_250);
+    const Y2K: Duration = Duration::from_secs(946_684_800);
+
+    fn stamp(precision: TimestampPrecision, epoch: Epoch) -> u64 {
+        let mut config = TimestampConfig::new(precision, epoch);
+        config.anchor(CLOCK_NOW, UNIX_NOW);
+        config.stamp(CLOCK_NOW).value
+    }
+
+    #[test]
+    fn stamps_fixed_instant_in_each_precision_and_epoch() {
+        use TimestampPrecision::{Millis, Seconds};
+        assert_eq!(stamp(Seconds, Epoch::Device), 90);
+        assert_eq!(stamp(Millis, Epoch::Device), 90_250);
+        assert_eq!(stamp(Seconds, Epoch::Unix), 1_700_000_000);
+        assert_eq!(stamp(Millis, Epoch::Unix), 1_700_000_000_250);
+        assert_eq!(stamp(Seconds, Epoch::Custom(Y2K)), 753_315_200);
+        assert_eq!(stamp(Millis, Epoch::Custom(Y2K)), 753_315_200_250);
+    }
+
+    #[test]
+    fn unanchored_clock_stamps_device_time() {
+        let config = TimestampConfig::new(TimestampPrecision::Millis, Epoch::Unix);
+        assert_eq!(config.stamp(CLOCK_NOW).value, 90_250);
+        assert_eq!(config.stamp(CLOCK_NOW).as_secs(), 90);
+        assert_eq!(
+            TimestampConfig::default().stamp(CLOCK_NOW),
+            Timestamp::seconds(90)
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Self-profiling of the control loop, for budgeting the reading interval.
+//!
+//! [`CycleTimer`] measures each cycle (read, filter, convert, decide, emit)
+//! on the injectable clock and logs min/max/mean once per reporting window.
+
+use crate::clock::Clock;
+use log::info;
+use std::fmt;
+use std::time::Duration;
+
+/// Cycle durations over one reporting window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CycleStats {
+    pub cycles: u32,
+    pub min: Duration,
+    pub max: Duration,
+    pub mean: Duration,
+}
+
+impl fmt::Display for CycleStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {} ms, max {} ms, mean {} ms over {} cycles",
+            self.min.as_millis(),
+            self.max.as_millis(),
+            self.mean.as_millis(),
+            self.cycles
+        )
+    }
+}
+
+/// Times loop bodies and reports every `report_every` cycles
+pub struct CycleTimer<C> {
+    clock: C,
+    report_every: u32,
+    cycles: u32,
+    total: Duration,
+    min: Duration,
+    max: Duration,
+}
+
+impl<C: Clock> CycleTimer<C> {
+    pub fn new(clock: C, report_every: u32) -> Self {
+        Self {
+            clock,
+            report_every: report_every.max(1),
+            cycles: 0,
+            total: Duration::ZERO,
+            min: Duration::MAX,
+            max: Duration::ZERO,
+        }
+    }
+
+    /// Run `body` and record how long it took
+    pub fn time<T>(&mut self, body: impl FnOnce() -> T) -> T {
+        let start = self.clock.now();
+        let out = body();
+        self.record(self.clock.now().saturating_sub(start));
+        out
+    }
+
+    /// Record one cycle measured elsewhere; at the end of a window the stats
+    /// are logged, returned and reset
+    pub fn record(&mut self, elapsed: Duration) -> Option<CycleStats> {
+        self.cycles += 1;
+        self.total += elapsed;
+        self.min = self.min.min(elapsed);
+        self.max = self.max.max(elapsed);
+        if self.cycles < self.report_every {
+            return None;
+        }
+        let stats = self.stats();
+        if let Some(stats) = stats {
+            info!("Cycle time: {}", stats);
+        }
+        self.cycles = 0;
+        self.total = Duration::ZERO;
+        self.min = Duration::MAX;
+        self.max = Duration::ZERO;
+        stats
+    }
+
+    /// Stats of the current window so far; `None` before the first cycle
+    pub fn stats(&self) -> Option<CycleStats> {
+        (self.cycles > 0).then(|| CycleStats {
+            cycles: self.cycles,
+            min: self.min,
+            max: self.max,
+            mean: self.total / self.cycles,
+        })
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{CycleStats, CycleTimer};
+    use crate::clock::{Clock, MockClock};
+    use std::time::Duration;
+
+    #[test]
+    fn reports_min_max_mean_per_window() {
+        let clock = MockClock::new();
+        let mut timer = CycleTimer::new(clock.clone(), 4);
+        assert_eq!(timer.stats(), None);
+
+        for ms in [12, 40, 20] {
+            let out = timer.time(|| {
+                clock.sleep(Duration::from_millis(ms));
+                ms
+            });
+            assert_eq!(out, ms);
+        }
+        assert_eq!(timer.stats().unwrap().cycles, 3);
+
+        let stats = timer.record(Duration::from_millis(8)).unwrap();
+        assert_eq!(
+            stats,
+            CycleStats {
+                cycles: 4,
+                min: Duration::from_millis(8),
+                max: Duration::from_millis(40),
+                mean: Duration::from_millis(20),
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "min 8 ms, max 40 ms, mean 20 ms over 4 cycles"
+        );
+
+        // The next window starts from scratch
+        assert_eq!(timer.stats(), None);
+        timer.time(|| clock.sleep(Duration::from_millis(5)));
+        assert_eq!(timer.stats().unwrap().max, Duration::from_millis(5));
+    }
+}
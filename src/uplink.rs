@@ -0,0 +1,262 @@
+//! Network work (MQTT, HTTP, SNTP) isolated from the sensing loop.
+//!
+//! The sensing loop hands readings and annotations to an [`UplinkSender`], which never
+//! blocks: when the queue is full the oldest reading is dropped to make room.
+//! A dedicated task drains the queue into a (possibly slow) network sink. On
+//! ESP-IDF std threads are FreeRTOS tasks, so [`spawn_uplink`] is the
+//! dedicated task.
+
+use crate::reading::{Annotation, Reading};
+use crate::sink::ReadingSink;
+use anyhow::{Context, Result};
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Stack for the uplink task; TLS handshakes need more than the default
+pub const UPLINK_STACK_SIZE: usize = 8 * 1024;
+
+/// What travels from sensing to the uplink task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UplinkItem {
+    Reading(Reading),
+    Annotation(Annotation),
+}
+
+struct Queue {
+    items: VecDeque<UplinkItem>,
+    capacity: usize,
+    dropped: u32,
+    /// The sender is gone; the receiver drains what is left and stops
+    closed: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        // A panicking peer leaves the queue itself consistent
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Bounded queue between sensing and the uplink task; holds at least one item
+pub fn uplink_channel(capacity: usize) -> (UplinkSender, UplinkReceiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue {
+            items: VecDeque::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+            dropped: 0,
+            closed: false,
+        }),
+        ready: Condvar::new(),
+    });
+    (
+        UplinkSender {
+            shared: shared.clone(),
+        },
+        UplinkReceiver { shared },
+    )
+}
+
+/// Sensing side of [`uplink_channel`]
+pub struct UplinkSender {
+    shared: Arc<Shared>,
+}
+
+impl UplinkSender {
+    /// Queue `item` without blocking; returns `false` if the oldest queued
+    /// item had to be dropped to make room
+    pub fn send(&self, item: UplinkItem) -> bool {
+        let mut queue = self.shared.lock();
+        let room = queue.items.len() < queue.capacity;
+        if !room {
+            queue.items.pop_front();
+            queue.dropped = queue.dropped.saturating_add(1);
+        }
+        queue.items.push_back(item);
+        drop(queue);
+        self.shared.ready.notify_one();
+        room
+    }
+
+    /// Items dropped so far because the uplink fell behind
+    pub fn dropped(&self) -> u32 {
+        self.shared.lock().dropped
+    }
+}
+
+impl ReadingSink for UplinkSender {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        self.send(UplinkItem::Reading(reading.clone()));
+        Ok(())
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        self.send(UplinkItem::Annotation(annotation.clone()));
+        Ok(())
+    }
+}
+
+impl Drop for UplinkSender {
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.ready.notify_all();
+    }
+}
+
+/// Uplink side of [`uplink_channel`]
+pub struct UplinkReceiver {
+    shared: Arc<Shared>,
+}
+
+impl UplinkReceiver {
+    /// Oldest queued item, waiting for one; `None` once the sender is
+    /// gone and the queue is empty
+    pub fn recv(&self) -> Option<UplinkItem> {
+        let mut queue = self.shared.lock();
+        loop {
+            if let Some(item) = queue.items.pop_front() {
+                return Some(item);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self
+                .shared
+                .ready
+                .wait(queue)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Like [`recv`](Self::recv) but gives up after `timeout`, e.g. to run
+    /// periodic SNTP syncs between readings
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<UplinkItem> {
+        let queue = self.shared.lock();
+        let (mut queue, _) = self
+            .shared
+            .ready
+            .wait_timeout_while(queue, timeout, |q| q.items.is_empty() && !q.closed)
+            .unwrap_or_else(|e| e.into_inner());
+        queue.items.pop_front()
+    }
+
+    /// Items currently waiting
+    pub fn len(&self) -> usize {
+        self.shared.lock().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Run `sink` on its own task, fed from `receiver` until the sender is
+/// dropped; delivery errors are logged and the reading discarded
+pub fn spawn_uplink(
+    receiver: UplinkReceiver,
+    mut sink: impl ReadingSink + Send + 'static,
+) -> Result<JoinHandle<()>> {
+    std::thread::Builder::new()
+        .name("uplink".into())
+        .stack_size(UPLINK_STACK_SIZE)
+        .spawn(move || {
+            while let Some(item) = receiver.recv() {
+                let delivered = match &item {
+                    UplinkItem::Reading(reading) => sink.emit(reading),
+                    UplinkItem::Annotation(annotation) => sink.annotate(annotation),
+                };
+                if let Err(e) = delivered {
+                    warn!("Uplink delivery failed: {:?}", e);
+                }
+            }
+        })
+        .context("spawning uplink task")
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{spawn_uplink, uplink_channel, UplinkItem};
+    use crate::reading::Reading;
+    use crate::sink::ReadingSink;
+    use anyhow::Result;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::time::Duration;
+
+    fn reading(i: u64) -> Reading {
+        Reading::new(Duration::from_secs(i), 2000, i as u8)
+    }
+
+    fn item(i: u64) -> UplinkItem {
+        UplinkItem::Reading(reading(i))
+    }
+
+    /// Uplink that reports each reading it takes, then waits for a
+    /// go-ahead before taking the next, like a stalled network
+    struct GatedSink {
+        taken: Sender<u64>,
+        go: Receiver<()>,
+    }
+
+    impl ReadingSink for GatedSink {
+        fn emit(&mut self, reading: &Reading) -> Result<()> {
+            self.taken.send(reading.timestamp.as_secs())?;
+            self.go.recv()?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn full_queue_drops_oldest() {
+        let (tx, rx) = uplink_channel(3);
+        for i in 0..3 {
+            assert!(tx.send(item(i)));
+        }
+        assert!(!tx.send(item(3)));
+        assert!(!tx.send(item(4)));
+        assert_eq!(tx.dropped(), 2);
+        assert_eq!(rx.len(), 3);
+        drop(tx);
+
+        let kept: Vec<UplinkItem> = std::iter::from_fn(|| rx.recv()).collect();
+        assert_eq!(kept, vec![item(2), item(3), item(4)]);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(1)), None);
+    }
+
+    #[test]
+    fn slow_uplink_never_blocks_sensing() {
+        let (taken_tx, taken) = mpsc::channel();
+        let (go_tx, go_rx) = mpsc::channel();
+        let (mut uplink, rx) = uplink_channel(2);
+        let task = spawn_uplink(
+            rx,
+            GatedSink {
+                taken: taken_tx,
+                go: go_rx,
+            },
+        )
+        .unwrap();
+
+        // Reading 0 is taken by the task, which then stalls on the network
+        uplink.emit(&reading(0)).unwrap();
+        assert_eq!(taken.recv().unwrap(), 0);
+        // Sensing carries on; only the newest two of the backlog survive
+        for i in 1..=5 {
+            uplink.emit(&reading(i)).unwrap();
+        }
+        assert_eq!(uplink.dropped(), 3);
+
+        for _ in 0..3 {
+            go_tx.send(()).unwrap();
+        }
+        drop(uplink);
+        task.join().unwrap();
+        assert_eq!(taken.try_iter().collect::<Vec<_>>(), vec![4, 5]);
+    }
+}
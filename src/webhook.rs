@@ -0,0 +1,441 @@
+//! Alert delivery to an HTTP webhook (Slack, Discord, IFTTT, ...).
+
+use crate::alert::{Alert, AlertOutput};
+use crate::clock::Clock;
+use crate::storage::FlashStore;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Serialize;
+use std::time::Duration;
+
+const JSON: &str = "application/json";
+
+/// Minimal HTTP client used to deliver alerts
+pub trait HttpClient {
+    /// POST `body`; non-2xx responses are errors
+    fn post(&mut self, url: &str, content_type: &str, body: &[u8]) -> Result<()>;
+}
+
+#[derive(Serialize)]
+struct AlertPayload<'a> {
+    alert: &'a str,
+    severity: &'a str,
+    message: String,
+    uptime_s: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ec_us_cm: Option<u16>,
+}
+
+/// JSON body posted for `alert` raised at uptime `at`
+pub fn alert_payload(alert: &Alert, at: Duration) -> Result<Vec<u8>> {
+    let payload = AlertPayload {
+        alert: alert.kind(),
+        severity: alert.severity().as_str(),
+        message: alert.to_string(),
+        uptime_s: at.as_secs(),
+        ec_us_cm: match alert {
+            Alert::Fertilize { ec_us_cm } => Some(*ec_us_cm),
+            _ => None,
+        },
+    };
+    serde_json::to_vec(&payload).context("encoding alert payload")
+}
+
+/// What happened to an alert handed to [`AlertSink::send`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// Identical alert already sent within the dedup window
+    Suppressed,
+    /// Raised during the startup grace period; sent once it ends unless
+    /// [`resolved`](AlertSink::resolve) first
+    Held,
+    /// POST failed; payload saved for [`AlertSink::retry_queued`]
+    Queued,
+    /// POST failed and there is no retry queue
+    Dropped,
+}
+
+struct RetryQueue {
+    flash: Box<dyn FlashStore + Send>,
+    file: String,
+}
+
+impl RetryQueue {
+    /// Queued payloads, one JSON document per line
+    fn load(&self) -> Result<Vec<Vec<u8>>> {
+        let data = self.flash.read_file(&self.file)?.unwrap_or_default();
+        Ok(data
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_vec())
+            .collect())
+    }
+
+    fn store(&mut self, payloads: &[Vec<u8>]) -> Result<()> {
+        let mut data = Vec::new();
+        for payload in payloads {
+            data.extend_from_slice(payload);
+            data.push(b'\n');
+        }
+        self.flash.write_file(&self.file, &data)
+    }
+}
+
+/// Posts alerts as JSON to a webhook, suppressing repeats and optionally
+/// queueing failed deliveries on flash
+pub struct AlertSink<H, C> {
+    client: H,
+    clock: C,
+    url: String,
+    dedup_window: Duration,
+    /// Last time each distinct alert was sent
+    recent: Vec<(Alert, Duration)>,
+    retry: Option<RetryQueue>,
+    /// Alerts before this time are held rather than posted
+    grace_until: Duration,
+    /// Raised during grace, with when, waiting for it to end
+    held: Vec<(Alert, Duration)>,
+}
+
+impl<H: HttpClient, C: Clock> AlertSink<H, C> {
+    /// Identical alerts within `dedup_window` of the last one are not re-sent
+    pub fn new(client: H, clock: C, url: impl Into<String>, dedup_window: Duration) -> Self {
+        Self {
+            client,
+            clock,
+            url: url.into(),
+            dedup_window,
+            recent: Vec::new(),
+            retry: None,
+            grace_until: Duration::ZERO,
+            held: Vec::new(),
+        }
+    }
+
+    /// Keep failed payloads in `file` until [`retry_queued`](Self::retry_queued) delivers them
+    pub fn with_retry_queue(
+        mut self,
+        flash: impl FlashStore + Send + 'static,
+        file: impl Into<String>,
+    ) -> Self {
+        self.retry = Some(RetryQueue {
+            flash: Box::new(flash),
+            file: file.into(),
+        });
+        self
+    }
+
+    /// Hold alerts for `grace` from now while filters warm up after boot;
+    /// held alerts still active once it ends are sent by the next
+    /// [`send`](Self::send) or [`flush_held`](Self::flush_held)
+    pub fn with_startup_grace(mut self, grace: Duration) -> Self {
+        self.grace_until = self.clock.now() + grace;
+        self
+    }
+
+    pub fn client(&self) -> &H {
+        &self.client
+    }
+
+    /// The condition behind alerts of `alert`'s kind has cleared, so any
+    /// still held are dropped
+    pub fn resolve(&mut self, alert: &Alert) {
+        self.held.retain(|(held, _)| held.kind() != alert.kind());
+    }
+
+    /// Send the alerts held during startup grace once it has ended; returns
+    /// how many went out
+    pub fn flush_held(&mut self) -> usize {
+        if self.clock.now() < self.grace_until {
+            return 0;
+        }
+        std::mem::take(&mut self.held)
+            .into_iter()
+            .filter(|(alert, at)| self.deliver(alert, *at) == Delivery::Sent)
+            .count()
+    }
+
+    /// Deliver `alert` unless an identical one went out recently; failures
+    /// are logged rather than returned
+    pub fn send(&mut self, alert: &Alert) -> Delivery {
+        let now = self.clock.now();
+        if now < self.grace_until {
+            info!("Holding alert during startup grace: {}", alert);
+            if !self.held.iter().any(|(held, _)| held == alert) {
+                self.held.push((alert.clone(), now));
+            }
+            return Delivery::Held;
+        }
+        self.flush_held();
+        self.deliver(alert, now)
+    }
+
+    /// Post `alert`, raised at `at`, subject to the dedup window
+    fn deliver(&mut self, alert: &Alert, at: Duration) -> Delivery {
+        let now = self.clock.now();
+        let window = self.dedup_window;
+        self.recent
+            .retain(|(_, sent)| now.saturating_sub(*sent) < window);
+        if self.recent.iter().any(|(a, _)| a == alert) {
+            info!("Suppressing repeated alert: {}", alert);
+            return Delivery::Suppressed;
+        }
+
+        let payload = match alert_payload(alert, at) {
+            Ok(payload) => payload,
+            Err(e) => {
+                warn!("Dropping alert {}: {:?}", alert, e);
+                return Delivery::Dropped;
+            }
+        };
+        match self.client.post(&self.url, JSON, &payload) {
+            Ok(()) => {
+                // Only a delivered alert holds back its repeats
+                self.recent.push((alert.clone(), now));
+                Delivery::Sent
+            }
+            Err(e) => {
+                warn!("Webhook POST failed: {:?}", e);
+                self.enqueue(payload)
+            }
+        }
+    }
+
+    fn enqueue(&mut self, payload: Vec<u8>) -> Delivery {
+        let Some(queue) = &mut self.retry else {
+            return Delivery::Dropped;
+        };
+        let result = queue.load().and_then(|mut pending| {
+            pending.push(payload);
+            queue.store(&pending)
+        });
+        match result {
+            Ok(()) => Delivery::Queued,
+            Err(e) => {
+                warn!("Could not queue alert for retry: {:?}", e);
+                Delivery::Dropped
+            }
+        }
+    }
+
+    /// Re-post queued payloads in order, stopping at the first failure;
+    /// returns how many were delivered
+    pub fn retry_queued(&mut self) -> Result<usize> {
+        let Some(queue) = &mut self.retry else {
+            return Ok(0);
+        };
+        let pending = queue.load()?;
+        let mut delivered = 0;
+        for payload in &pending {
+            if let Err(e) = self.client.post(&self.url, JSON, payload) {
+                warn!("Webhook retry failed: {:?}", e);
+                break;
+            }
+            delivered += 1;
+        }
+        if delivered > 0 {
+            queue.store(&pending[delivered..])?;
+        }
+        Ok(delivered)
+    }
+}
+
+impl<H: HttpClient, C: Clock> AlertOutput for AlertSink<H, C> {
+    fn deliver(&mut self, alert: &Alert) {
+        self.send(alert);
+    }
+
+    fn tick(&mut self) {
+        self.flush_held();
+        if let Err(e) = self.retry_queued() {
+            warn!("Webhook retry queue unreadable: {:?}", e);
+        }
+    }
+}
+
+/// ESP-IDF HTTP(S) client; HTTPS uses the bundled CA certificates
+#[cfg(target_os = "espidf")]
+pub struct EspHttpClient {
+    connection: esp_idf_svc::http::client::EspHttpConnection,
+}
+
+#[cfg(target_os = "espidf")]
+impl EspHttpClient {
+    pub fn new() -> Result<Self> {
+        let config = esp_idf_svc::http::client::Configuration {
+            timeout: Some(Duration::from_secs(10)),
+            crt_bundle_attach: Some(esp_idf_sys::esp_crt_bundle_attach),
+            ..Default::default()
+        };
+        Ok(Self {
+            connection: esp_idf_svc::http::client::EspHttpConnection::new(&config)?,
+        })
+    }
+}
+
+#[cfg(target_os = "espidf")]
+impl HttpClient for EspHttpClient {
+    fn post(&mut self, url: &str, content_type: &str, body: &[u8]) -> Result<()> {
+        let length = body.len().to_string();
+        let headers = [("Content-Type", content_type), ("Content-Length", &length)];
+        self.connection
+            .initiate_request(esp_idf_svc::http::Method::Post, url, &headers)?;
+        let mut written = 0;
+        while written < body.len() {
+            written += self.connection.write(&body[written..])?;
+        }
+        self.connection.initiate_response()?;
+        let status = self.connection.status();
+        anyhow::ensure!(
+            (200..300).contains(&status),
+            "webhook returned HTTP {status}"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{alert_payload, AlertSink, Delivery, HttpClient};
+    use crate::alert::Alert;
+    use crate::clock::MockClock;
+    use crate::storage::MemoryFlash;
+    use anyhow::{bail, Result};
+    use std::time::Duration;
+
+    /// Records posted bodies; fails every post while `down` is set
+    #[derive(Default)]
+    struct Recorder {
+        bodies: Vec<Vec<u8>>,
+        down: bool,
+    }
+
+    impl HttpClient for Recorder {
+        fn post(&mut self, _url: &str, content_type: &str, body: &[u8]) -> Result<()> {
+            assert_eq!(content_type, "application/json");
+            if self.down {
+                bail!("connection refused");
+            }
+            self.bodies.push(body.to_vec());
+            Ok(())
+        }
+    }
+
+    fn webhook(clock: &MockClock) -> AlertSink<Recorder, MockClock> {
+        AlertSink::new(
+            Recorder::default(),
+            clock.clone(),
+            "https://hooks.example/soil",
+            Duration::from_secs(600),
+        )
+    }
+
+    #[test]
+    fn payload_carries_kind_message_and_details() {
+        let json =
+            alert_payload(&Alert::Fertilize { ec_us_cm: 150 }, Duration::from_secs(90)).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"alert":"fertilize","severity":"info","message":"low fertility (EC 150 uS/cm), consider fertilizing","uptime_s":90,"ec_us_cm":150}"#
+        );
+        let json = alert_payload(&Alert::PumpFailure, Duration::ZERO).unwrap();
+        assert!(!String::from_utf8(json).unwrap().contains("ec_us_cm"));
+    }
+
+    #[test]
+    fn repeated_alert_is_suppressed_within_window() {
+        let clock = MockClock::new();
+        let mut sink = webhook(&clock);
+
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Sent);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Suppressed);
+        // A different alert is not a repeat
+        assert_eq!(
+            sink.send(&Alert::Fertilize { ec_us_cm: 150 }),
+            Delivery::Sent
+        );
+
+        clock.advance(Duration::from_secs(600));
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Sent);
+        assert_eq!(sink.client().bodies.len(), 3);
+    }
+
+    #[test]
+    fn alerts_are_held_during_startup_grace() {
+        let clock = MockClock::new();
+        let mut sink = webhook(&clock).with_startup_grace(Duration::from_secs(120));
+
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Held);
+        clock.advance(Duration::from_secs(119));
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Held);
+        assert!(sink.client().bodies.is_empty());
+
+        // The held alert goes out as soon as grace ends; a repeat is then a duplicate
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Suppressed);
+        assert_eq!(sink.client().bodies.len(), 1);
+    }
+
+    #[test]
+    fn held_alerts_are_delivered_after_grace_unless_resolved() {
+        let clock = MockClock::new();
+        let mut sink = webhook(&clock).with_startup_grace(Duration::from_secs(120));
+        let dry = Alert::SoilDry {
+            moisture_percent: 12,
+        };
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Held);
+        assert_eq!(sink.send(&dry), Delivery::Held);
+        // Edge-triggered: nothing is raised again, but the dry spell ends
+        clock.advance(Duration::from_secs(60));
+        sink.resolve(&Alert::SoilDry {
+            moisture_percent: 30,
+        });
+        assert_eq!(sink.flush_held(), 0);
+
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sink.flush_held(), 1);
+        assert_eq!(sink.flush_held(), 0);
+        // Stamped with when it was raised, not when grace ended
+        assert_eq!(
+            sink.client().bodies,
+            vec![alert_payload(&Alert::PumpFailure, Duration::ZERO).unwrap()]
+        );
+    }
+
+    #[test]
+    fn failed_posts_queue_and_retry_in_order() {
+        let clock = MockClock::new();
+        let mut sink = webhook(&clock).with_retry_queue(MemoryFlash::new(), "alerts.q");
+        sink.client.down = true;
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Queued);
+        assert_eq!(
+            sink.send(&Alert::Fertilize { ec_us_cm: 150 }),
+            Delivery::Queued
+        );
+        assert_eq!(sink.retry_queued().unwrap(), 0);
+
+        sink.client.down = false;
+        assert_eq!(sink.retry_queued().unwrap(), 2);
+        assert_eq!(sink.retry_queued().unwrap(), 0);
+        assert_eq!(sink.client().bodies.len(), 2);
+
+        let mut unqueued = webhook(&clock);
+        unqueued.client.down = true;
+        assert_eq!(unqueued.send(&Alert::PumpFailure), Delivery::Dropped);
+    }
+
+    #[test]
+    fn failed_post_does_not_suppress_the_next_attempt() {
+        let clock = MockClock::new();
+        let mut sink = webhook(&clock);
+        sink.client.down = true;
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Dropped);
+
+        sink.client.down = false;
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Sent);
+        assert_eq!(sink.send(&Alert::PumpFailure), Delivery::Suppressed);
+    }
+}
@@ -0,0 +1,179 @@
+//! Time-window aggregation for low-rate telemetry.
+
+use crate::clock::Clock;
+use crate::reading::{Annotation, Reading};
+use crate::sink::ReadingSink;
+use anyhow::Result;
+use std::time::Duration;
+
+/// How the readings in one window collapse into a single reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    /// Driest reading in the window
+    Min,
+    /// Wettest reading in the window
+    Max,
+    /// Mean raw value and moisture; everything else, time and flags
+    /// included, from the last reading
+    Mean,
+    /// Most recent reading
+    Last,
+}
+
+impl Reduction {
+    fn reduce(self, readings: &[Reading]) -> Option<Reading> {
+        let last = readings.last()?;
+        Some(match self {
+            Reduction::Min => readings.iter().min_by_key(|r| r.moisture_percent)?.clone(),
+            Reduction::Max => readings.iter().max_by_key(|r| r.moisture_percent)?.clone(),
+            Reduction::Last => last.clone(),
+            Reduction::Mean => {
+                let n = readings.len() as u32;
+                let raw: u32 = readings.iter().map(|r| r.raw as u32).sum();
+                let moisture: u32 = readings.iter().map(|r| r.moisture_percent as u32).sum();
+                Reading {
+                    raw: (raw / n) as u16,
+                    moisture_percent: (moisture / n) as u8,
+                    ..last.clone()
+                }
+            }
+        })
+    }
+}
+
+/// Sink stage that buffers readings for `window` and forwards one reduced reading
+pub struct WindowReducer<S, C> {
+    inner: S,
+    clock: C,
+    window: Duration,
+    reduction: Reduction,
+    window_start: Option<Duration>,
+    pending: Vec<Reading>,
+}
+
+impl<S: ReadingSink, C: Clock> WindowReducer<S, C> {
+    pub fn new(inner: S, clock: C, window: Duration, reduction: Reduction) -> Self {
+        Self {
+            inner,
+            clock,
+            window,
+            reduction,
+            window_start: None,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Forward whatever is buffered now, e.g. before shutdown
+    pub fn flush(&mut self) -> Result<()> {
+        self.window_start = None;
+        let reduced = self.reduction.reduce(&self.pending);
+        self.pending.clear();
+        match reduced {
+            Some(reading) => self.inner.emit(&reading),
+            None => Ok(()),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: ReadingSink, C: Clock> ReadingSink for WindowReducer<S, C> {
+    fn emit(&mut self, reading: &Reading) -> Result<()> {
+        let now = self.clock.now();
+        let start = *self.window_start.get_or_insert(now);
+        self.pending.push(reading.clone());
+        if now.saturating_sub(start) >= self.window {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn annotate(&mut self, annotation: &Annotation) -> Result<()> {
+        self.inner.annotate(annotation)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Reduction, WindowReducer};
+    use crate::clock::{Clock, MockClock};
+    use crate::reading::Reading;
+    use crate::sink::{MemorySink, ReadingSink};
+    use std::time::Duration;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    /// One reading per minute for an hour-long window plus one more
+    fn reduce(reduction: Reduction) -> Vec<Reading> {
+        let clock = MockClock::new();
+        let mut stage =
+            WindowReducer::new(MemorySink::default(), clock.clone(), 60 * MINUTE, reduction);
+        let moisture = [40u8, 35, 20, 55, 45];
+        for i in 0..=60u32 {
+            let m = moisture[i as usize % moisture.len()];
+            let reading = Reading::new(clock.now(), 3000 - m as u16 * 18, m);
+            stage.emit(&reading).unwrap();
+            clock.advance(MINUTE);
+        }
+        stage.inner().readings.clone()
+    }
+
+    #[test]
+    fn min_reports_driest_moment() {
+        let out = reduce(Reduction::Min);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].moisture_percent, 20);
+        assert_eq!(out[0].raw, 3000 - 20 * 18);
+    }
+
+    #[test]
+    fn max_reports_wettest_moment() {
+        let out = reduce(Reduction::Max);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].moisture_percent, 55);
+    }
+
+    #[test]
+    fn mean_averages_window() {
+        let out = reduce(Reduction::Mean);
+        assert_eq!(out.len(), 1);
+        // 61 readings: 12 full cycles (mean 39) plus a trailing 40
+        assert_eq!(out[0].moisture_percent, ((39 * 60 + 40) / 61) as u8);
+        assert_eq!(out[0].timestamp, 60 * MINUTE);
+    }
+
+    #[test]
+    fn mean_keeps_the_last_reading_fields() {
+        let clock = MockClock::new();
+        let mut stage = WindowReducer::new(
+            MemorySink::default(),
+            clock.clone(),
+            MINUTE,
+            Reduction::Mean,
+        );
+        stage.emit(&Reading::new(clock.now(), 2000, 50)).unwrap();
+        clock.advance(MINUTE);
+        let last = Reading::new(clock.now(), 2200, 40)
+            .with_zone(3)
+            .with_pump_on(true)
+            .with_depth(15);
+        stage.emit(&last).unwrap();
+
+        let out = &stage.inner().readings[0];
+        assert_eq!((out.raw, out.moisture_percent), (2100, 45));
+        assert_eq!(out.zone, 3);
+        assert!(out.pump_on());
+        assert_eq!(out.depth_cm, Some(15));
+        assert_eq!(out.emitted_at, last.emitted_at);
+    }
+
+    #[test]
+    fn last_reports_latest_reading() {
+        let out = reduce(Reduction::Last);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].timestamp, 60 * MINUTE);
+        assert_eq!(out[0].moisture_percent, 40);
+    }
+}
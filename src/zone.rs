@@ -0,0 +1,250 @@
+//! One pump or valve per watering zone, with safeties shared across zones.
+//!
+//! Each zone keeps its own [`PumpController`] (thresholds, run limits,
+//! cooldown, feedback check); a lockout such as a dead probe, an empty
+//! reservoir or one zone's pump failing stops every zone at once.
+
+use crate::alert::Alert;
+use crate::clock::Clock;
+use crate::pump::{PumpAction, PumpController};
+use log::warn;
+
+/// Why every zone is held off; each is released on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lockout {
+    /// The probe is dead
+    SafeMode,
+    /// A pump was commanded on but never confirmed
+    PumpFailure,
+}
+
+/// Pump controllers keyed by [`Reading::zone`](crate::reading::Reading::zone)
+pub struct ZoneController<C> {
+    zones: Vec<(u8, PumpController<C>)>,
+    lockouts: Vec<Lockout>,
+}
+
+impl<C: Clock> Default for ZoneController<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Clock> ZoneController<C> {
+    pub fn new() -> Self {
+        Self {
+            zones: Vec::new(),
+            lockouts: Vec::new(),
+        }
+    }
+
+    /// Drive `zone` with `pump`, replacing any controller it already had;
+    /// a zone added during a lockout starts locked out too
+    pub fn with_zone(mut self, zone: u8, mut pump: PumpController<C>) -> Self {
+        if self.is_locked_out() {
+            pump.lock_out();
+        }
+        self.zones.retain(|(z, _)| *z != zone);
+        self.zones.push((zone, pump));
+        self
+    }
+
+    /// Configured zones in the order they were added
+    pub fn zones(&self) -> impl Iterator<Item = u8> + '_ {
+        self.zones.iter().map(|(zone, _)| *zone)
+    }
+
+    pub fn pump(&self, zone: u8) -> Option<&PumpController<C>> {
+        self.zones.iter().find(|(z, _)| *z == zone).map(|(_, p)| p)
+    }
+
+    pub fn pump_mut(&mut self, zone: u8) -> Option<&mut PumpController<C>> {
+        self.zones
+            .iter_mut()
+            .find(|(z, _)| *z == zone)
+            .map(|(_, p)| p)
+    }
+
+    pub fn is_running(&self, zone: u8) -> bool {
+        self.pump(zone).is_some_and(|pump| pump.is_running())
+    }
+
+    /// Feed `zone`'s latest moisture; returns every switch it caused,
+    /// including other zones stopped because this zone's pump failed.
+    /// Readings for unknown zones are ignored.
+    pub fn update(&mut self, zone: u8, moisture_percent: u8) -> Vec<(u8, PumpAction)> {
+        let Some(pump) = self.pump_mut(zone) else {
+            warn!("Reading for zone {} which has no pump", zone);
+            return Vec::new();
+        };
+        let was_failed = pump.has_failed();
+        let mut actions: Vec<_> = pump
+            .update(moisture_percent)
+            .map(|action| (zone, action))
+            .into_iter()
+            .collect();
+        if !was_failed && pump.has_failed() {
+            warn!("Zone {} pump failed, locking out every zone", zone);
+            actions.extend(self.lock_out(Lockout::PumpFailure));
+        }
+        actions
+    }
+
+    /// Alerts raised by any zone's pump since the last call
+    pub fn take_alerts(&mut self) -> Vec<Alert> {
+        self.zones
+            .iter_mut()
+            .filter_map(|(_, pump)| pump.take_alert())
+            .collect()
+    }
+
+    /// Any zone's pump has failed and not been cleared
+    pub fn has_failed(&self) -> bool {
+        self.zones.iter().any(|(_, pump)| pump.has_failed())
+    }
+
+    /// Stop and lock out every zone for `reason`; returns the zones that were
+    /// switched off
+    pub fn lock_out(&mut self, reason: Lockout) -> Vec<(u8, PumpAction)> {
+        if !self.lockouts.contains(&reason) {
+            self.lockouts.push(reason);
+        }
+        self.zones
+            .iter_mut()
+            .filter_map(|(zone, pump)| pump.lock_out().map(|action| (*zone, action)))
+            .collect()
+    }
+
+    /// Drop `reason`; the zones run again once no other lockout remains
+    pub fn release_lockout(&mut self, reason: Lockout) {
+        self.lockouts.retain(|&held| held != reason);
+        if self.is_locked_out() {
+            return;
+        }
+        for (_, pump) in &mut self.zones {
+            pump.release_lockout();
+        }
+    }
+
+    pub fn is_locked_out(&self) -> bool {
+        !self.lockouts.is_empty()
+    }
+
+    pub fn is_locked_out_for(&self, reason: Lockout) -> bool {
+        self.lockouts.contains(&reason)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "xtensa")))]
+mod tests {
+    use super::{Lockout, ZoneController};
+    use crate::alert::Alert;
+    use crate::clock::MockClock;
+    use crate::pump::{PumpAction, PumpConfig, PumpController, PumpFeedback};
+    use anyhow::Result;
+    use std::time::Duration;
+
+    /// Current sense on a pump that never starts
+    struct NoCurrent;
+
+    impl PumpFeedback for NoCurrent {
+        fn current_detected(&mut self) -> Result<bool> {
+            Ok(false)
+        }
+    }
+
+    fn controller(clock: &MockClock) -> ZoneController<MockClock> {
+        let bed = PumpConfig {
+            start_below: 30,
+            stop_at: 50,
+            min_run: Duration::from_secs(10),
+            max_run: Duration::from_secs(60),
+            cooldown: Duration::from_secs(120),
+        };
+        // Zone 2 holds thirstier plants
+        let greenhouse = PumpConfig {
+            start_below: 45,
+            stop_at: 60,
+            ..bed
+        };
+        ZoneController::new()
+            .with_zone(1, PumpController::new(bed, clock.clone()))
+            .with_zone(2, PumpController::new(greenhouse, clock.clone()))
+    }
+
+    #[test]
+    fn only_the_dry_zone_waters() {
+        let clock = MockClock::new();
+        let mut zones = controller(&clock);
+        assert_eq!(zones.update(1, 55), vec![]);
+        assert_eq!(zones.update(2, 20), vec![(2, PumpAction::Activate)]);
+        assert!(!zones.is_running(1) && zones.is_running(2));
+
+        // Same moisture, different thresholds per zone
+        clock.advance(Duration::from_secs(15));
+        assert_eq!(zones.update(1, 40), vec![]);
+        assert_eq!(zones.update(2, 40), vec![]);
+        assert_eq!(zones.update(3, 10), vec![]);
+        assert_eq!(zones.zones().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn lockout_stops_every_zone() {
+        let clock = MockClock::new();
+        let mut zones = controller(&clock);
+        assert_eq!(zones.update(1, 10), vec![(1, PumpAction::Activate)]);
+        assert_eq!(zones.update(2, 10), vec![(2, PumpAction::Activate)]);
+
+        assert_eq!(
+            zones.lock_out(Lockout::SafeMode),
+            vec![(1, PumpAction::Deactivate), (2, PumpAction::Deactivate)]
+        );
+        assert!(zones.is_locked_out());
+        clock.advance(Duration::from_secs(300));
+        assert_eq!(zones.update(1, 10), vec![]);
+
+        // A zone added mid-lockout waits for the release like the others
+        let bed = PumpController::new(PumpConfig::default(), clock.clone());
+        let mut zones = zones.with_zone(3, bed);
+        assert_eq!(zones.update(3, 10), vec![]);
+
+        zones.release_lockout(Lockout::SafeMode);
+        assert_eq!(zones.update(1, 10), vec![(1, PumpAction::Activate)]);
+        assert_eq!(zones.update(3, 10), vec![(3, PumpAction::Activate)]);
+    }
+
+    #[test]
+    fn failed_pump_locks_out_every_zone() {
+        let clock = MockClock::new();
+        let zones = controller(&clock);
+        let unpowered = PumpController::new(PumpConfig::default(), clock.clone())
+            .with_feedback(NoCurrent, Duration::from_secs(5));
+        let mut zones = zones.with_zone(3, unpowered);
+        assert_eq!(zones.update(2, 10), vec![(2, PumpAction::Activate)]);
+        assert_eq!(zones.update(3, 10), vec![(3, PumpAction::Activate)]);
+
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(
+            zones.update(3, 10),
+            vec![(3, PumpAction::Deactivate), (2, PumpAction::Deactivate)]
+        );
+        assert!(zones.is_locked_out_for(Lockout::PumpFailure));
+        assert_eq!(zones.take_alerts(), vec![Alert::PumpFailure]);
+        assert_eq!(zones.update(1, 10), vec![]);
+    }
+
+    #[test]
+    fn each_lockout_is_released_on_its_own() {
+        let clock = MockClock::new();
+        let mut zones = controller(&clock);
+        zones.lock_out(Lockout::SafeMode);
+        zones.lock_out(Lockout::PumpFailure);
+
+        zones.release_lockout(Lockout::SafeMode);
+        assert!(zones.is_locked_out());
+        assert_eq!(zones.update(1, 10), vec![]);
+        zones.release_lockout(Lockout::PumpFailure);
+        assert!(!zones.is_locked_out());
+        assert_eq!(zones.update(1, 10), vec![(1, PumpAction::Activate)]);
+    }
+}